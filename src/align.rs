@@ -0,0 +1,62 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Delay in samples of `signal` relative to `reference` (positive if events happen later in
+/// `signal`), found as the peak of their cross-correlation within ±`max_lag` samples.
+pub fn lag(signal: &[f32], reference: &[f32], max_lag: usize) -> isize {
+    let n = (signal.len() + reference.len()).next_power_of_two();
+    let mut planner = FftPlanner::new();
+    let (forward, inverse) = (planner.plan_fft_forward(n), planner.plan_fft_inverse(n));
+    let spectrum = |samples: &[f32]| {
+        let mut buffer: Vec<_> = samples.iter().map(|&s| Complex::new(s, 0.0)).collect();
+        buffer.resize(n, Complex::new(0.0, 0.0));
+        forward.process(&mut buffer);
+        buffer
+    };
+    let mut correlation: Vec<_> = spectrum(signal)
+        .into_iter()
+        .zip(spectrum(reference))
+        .map(|(s, r)| s * r.conj())
+        .collect();
+    inverse.process(&mut correlation);
+    // negative lags wrap around to the end of the buffer
+    let lag_at = |i: usize| {
+        if i > n / 2 {
+            i as isize - n as isize
+        } else {
+            i as isize
+        }
+    };
+    (0..n)
+        .filter(|&i| lag_at(i).unsigned_abs() <= max_lag)
+        .max_by(|&a, &b| correlation[a].re.total_cmp(&correlation[b].re))
+        .map_or(0, lag_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clicks at uneven positions, so the signal only lines up with itself at one lag.
+    fn clicks(len: usize, shift: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; len];
+        for (position, height) in [(100, 1.0), (350, -0.5), (777, 0.8), (1200, 0.3)] {
+            samples[position + shift] = height;
+        }
+        samples
+    }
+
+    #[test]
+    fn the_known_lag_is_found_either_way() {
+        let (reference, delayed) = (clicks(2000, 0), clicks(2000, 37));
+        assert_eq!(lag(&delayed, &reference, 100), 37);
+        assert_eq!(lag(&reference, &delayed, 100), -37);
+        assert_eq!(lag(&reference, &reference, 100), 0);
+    }
+
+    #[test]
+    fn lags_beyond_the_limit_are_not_considered() {
+        let (reference, delayed) = (clicks(2000, 0), clicks(2000, 250));
+        assert_eq!(lag(&delayed, &reference, 300), 250);
+        assert!(lag(&delayed, &reference, 200).abs() <= 200);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::metrics::SpectralFeatures;
+use crate::PlotData;
+
+/// What an analyzer reports for one file: a value per column it contributes, formatted for the
+/// file statistics, or `None` where it doesn't apply, e.g. level statistics of a `.f` spectrum.
+pub type AnalysisResult = Vec<Option<String>>;
+
+/// A per-file computation contributing columns to the file statistics, so a new metric needs no
+/// changes to the GUI: register it with [`Registry::register`].
+pub trait SpectrumAnalyzer: Send + Sync {
+    /// Headers of the columns, one for each value [`Self::analyze`] returns.
+    fn columns(&self) -> Vec<String>;
+
+    fn analyze(&self, plot: &PlotData) -> AnalysisResult;
+}
+
+/// Crest factor, dynamic range, loudness and RMS level of the samples.
+struct Levels;
+
+impl SpectrumAnalyzer for Levels {
+    fn columns(&self) -> Vec<String> {
+        ["Crest factor", "Dynamic range", "Loudness", "RMS"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn analyze(&self, plot: &PlotData) -> AnalysisResult {
+        let Some(stats) = &plot.level_stats else {
+            return vec![None; 4];
+        };
+        vec![
+            Some(format!(
+                "{:.2} ({:.1} dB)",
+                stats.crest_factor,
+                20.0 * stats.crest_factor.log10()
+            )),
+            Some(format!("{:.1} dB", stats.dynamic_range_db)),
+            stats.loudness_lufs.map(|lufs| format!("{:.1} LUFS", lufs)),
+            // samples are in the i16 range
+            Some(format!("{:.1} dBFS", 20.0 * (stats.rms / 32768.0).log10())),
+        ]
+    }
+}
+
+/// Spectral centroid, see [`SpectralFeatures`].
+struct Centroid;
+
+impl SpectrumAnalyzer for Centroid {
+    fn columns(&self) -> Vec<String> {
+        vec!["Centroid".to_string()]
+    }
+
+    fn analyze(&self, plot: &PlotData) -> AnalysisResult {
+        let features = SpectralFeatures::new(&plot.freqs, &plot.amplitudes);
+        vec![features.map(|features| format!("{:.1} Hz", features.centroid))]
+    }
+}
+
+struct Nyquist;
+
+impl SpectrumAnalyzer for Nyquist {
+    fn columns(&self) -> Vec<String> {
+        vec!["Nyquist".to_string()]
+    }
+
+    fn analyze(&self, plot: &PlotData) -> AnalysisResult {
+        // `.f` spectra don't know their sample rate, but end at Nyquist
+        let nyquist = match plot.sample_rate {
+            Some(sample_rate) => Some(sample_rate as f32 / 2.0),
+            None => plot.freqs.last().copied(),
+        };
+        vec![nyquist.map(|f| format!("{:.0} Hz", f))]
+    }
+}
+
+/// The analyzers filling the file statistics, in column order. The default has the built-in
+/// ones.
+pub struct Registry {
+    analyzers: Vec<Box<dyn SpectrumAnalyzer>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Levels);
+        registry.register(Centroid);
+        registry.register(Nyquist);
+        registry
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Registry")
+            .field("columns", &self.columns())
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self {
+            analyzers: Vec::new(),
+        }
+    }
+
+    /// Adds `analyzer`'s columns after those already registered.
+    pub fn register(&mut self, analyzer: impl SpectrumAnalyzer + 'static) {
+        self.analyzers.push(Box::new(analyzer));
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        self.analyzers
+            .iter()
+            .flat_map(|analyzer| analyzer.columns())
+            .collect()
+    }
+
+    /// The values of every analyzer for `plot`, one per column of [`Self::columns`]; an analyzer
+    /// returning too few or too many values is padded or cut to its columns.
+    pub fn analyze(&self, plot: &PlotData) -> AnalysisResult {
+        self.analyzers
+            .iter()
+            .flat_map(|analyzer| {
+                let mut values = analyzer.analyze(plot);
+                values.resize(analyzer.columns().len(), None);
+                values
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reports `values` whatever the plot, under two columns.
+    struct Fixed(Vec<Option<String>>);
+
+    impl SpectrumAnalyzer for Fixed {
+        fn columns(&self) -> Vec<String> {
+            vec!["First".to_string(), "Second".to_string()]
+        }
+
+        fn analyze(&self, _: &PlotData) -> AnalysisResult {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn spectra_without_samples_only_get_the_spectral_columns() {
+        let registry = Registry::default();
+        let columns = [
+            "Crest factor",
+            "Dynamic range",
+            "Loudness",
+            "RMS",
+            "Centroid",
+            "Nyquist",
+        ];
+        assert_eq!(registry.columns(), columns);
+        let plot = PlotData {
+            freqs: vec![0.0, 100.0, 200.0],
+            amplitudes: vec![0.0, 1.0, 0.0],
+            ..Default::default()
+        };
+        let values = registry.analyze(&plot);
+        assert_eq!(values[..4], [None, None, None, None]);
+        assert_eq!(
+            values[4..],
+            [Some("100.0 Hz".to_string()), Some("200 Hz".to_string())]
+        );
+    }
+
+    #[test]
+    fn values_are_padded_or_cut_to_the_columns() {
+        let mut registry = Registry::empty();
+        registry.register(Fixed(vec![Some("a".to_string())]));
+        registry.register(Fixed(vec![
+            Some("b".to_string()),
+            Some("c".to_string()),
+            Some("d".to_string()),
+        ]));
+        let values = registry.analyze(&PlotData::default());
+        let some = |value: &str| Some(value.to_string());
+        assert_eq!(values, [some("a"), None, some("b"), some("c")]);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{spectrum, testgen, AnalysisOptions};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Rate of the synthetic signal; it only matters to decimation and filters.
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Timings of repeated analyses of one signal, with the settings repeated so every JSON line
+/// stands on its own.
+#[derive(Debug, serde::Serialize)]
+pub struct BenchResult {
+    pub samples: usize,
+    pub fft_size: usize,
+    pub window: &'static str,
+    pub precision: &'static str,
+    pub decimation: u32,
+    pub runs: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    /// Input samples per second at the median time.
+    pub samples_per_second: f64,
+}
+
+/// Runs [`spectrum`] `runs` times on `len` samples of a tone in noise, after one untimed run to
+/// warm up caches and the allocator. Planning the FFT is part of every run, as it is when
+/// analysing files.
+pub fn run(len: usize, runs: usize, options: AnalysisOptions) -> Result<BenchResult, String> {
+    let signal = test_signal(len);
+    spectrum(&signal, SAMPLE_RATE, options)?;
+    let mut times = (0..runs.max(1))
+        .map(|_| {
+            let t0 = Instant::now();
+            spectrum(&signal, SAMPLE_RATE, options)?;
+            Ok(t0.elapsed())
+        })
+        .collect::<Result<Vec<Duration>, String>>()?;
+    times.sort();
+    let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+    let percentile = |p: f64| ms(times[((times.len() - 1) as f64 * p).round() as usize]);
+    let median_ms = percentile(0.5);
+    Ok(BenchResult {
+        samples: len,
+        fft_size: options.fft_size(len),
+        window: options.window.name(),
+        precision: options.precision.name(),
+        decimation: options.decimation.factor,
+        runs: times.len(),
+        min_ms: ms(times[0]),
+        median_ms,
+        mean_ms: times.iter().map(|&time| ms(time)).sum::<f64>() / times.len() as f64,
+        p95_ms: percentile(0.95),
+        max_ms: ms(times[times.len() - 1]),
+        samples_per_second: len as f64 / (median_ms / 1000.0),
+    })
+}
+
+/// A 1 kHz tone in white noise, in the i16 range like decoded WAV samples. The noise is seeded,
+/// so every run sees the same signal.
+fn test_signal(len: usize) -> Vec<f32> {
+    let noise = testgen::white_noise(500.0, len, 1);
+    let tone = testgen::sine(1000.0, 10_000.0, SAMPLE_RATE, len);
+    tone.iter()
+        .zip(noise)
+        .map(|(tone, noise)| tone + noise)
+        .collect()
+}
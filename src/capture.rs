@@ -0,0 +1,230 @@
+use crate::wav::Channel;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Blocks of captured samples, or the error that stopped the capture.
+pub type Blocks = Receiver<Result<Vec<f32>, String>>;
+
+/// A sound card input being captured by [`spawn`].
+pub struct Capture {
+    pub blocks: Blocks,
+    /// Name of the device.
+    pub name: String,
+    /// Rate the device captures at, the requested one or its own.
+    pub sample_rate: u32,
+}
+
+/// Names of the inputs of the default host, for `--device`, the default input first.
+pub fn input_devices() -> Result<Vec<String>, String> {
+    let host = cpal::default_host();
+    let default = host
+        .default_input_device()
+        .and_then(|device| device.name().ok());
+    let devices = host.input_devices().map_err(|e| e.to_string())?;
+    let mut names: Vec<String> = devices.filter_map(|device| device.name().ok()).collect();
+    if let Some(i) = names.iter().position(|name| Some(name) == default.as_ref()) {
+        names[..=i].rotate_right(1);
+    }
+    Ok(names)
+}
+
+/// Opens the input named `device`, or the default one, at `sample_rate` if given and at the
+/// device's own rate otherwise, and sends `channel` of what it captures in blocks of
+/// `block_frames` frames, like [`crate::stream::spawn_raw_capture`]. Samples are scaled to the
+/// i16 range like those read from files. [`Channel::Interleaved`], the default, is mixed down to
+/// mono, since the spectrum of interleaved frames would be that of the channels taking turns.
+///
+/// The stream lives on a thread of its own, since it can't move between threads on every
+/// platform, and stops once the receiver is dropped or the device fails.
+pub fn spawn(
+    device: Option<&str>,
+    sample_rate: Option<u32>,
+    channel: Channel,
+    block_frames: usize,
+) -> Result<Capture, String> {
+    let device = device.map(str::to_string);
+    let channel = if channel == Channel::Interleaved {
+        Channel::Mono
+    } else {
+        channel
+    };
+    let (opened_sender, opened) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (sender, blocks) = mpsc::channel();
+        let (stop_sender, stop) = mpsc::channel();
+        let stream = match open(
+            device.as_deref(),
+            sample_rate,
+            channel,
+            block_frames,
+            sender,
+            stop_sender,
+        ) {
+            Ok((stream, name, sample_rate)) => {
+                let _ = opened_sender.send(Ok(Capture {
+                    blocks,
+                    name,
+                    sample_rate,
+                }));
+                stream
+            }
+            Err(e) => {
+                let _ = opened_sender.send(Err(e));
+                return;
+            }
+        };
+        // the callbacks say when to stop, dropping the stream ends the capture
+        let _ = stop.recv();
+        drop(stream);
+    });
+    opened
+        .recv()
+        .map_err(|_| "The capture thread stopped".to_string())?
+}
+
+/// The started stream with the name and sample rate of its device.
+fn open(
+    name: Option<&str>,
+    sample_rate: Option<u32>,
+    channel: Channel,
+    block_frames: usize,
+    sender: Sender<Result<Vec<f32>, String>>,
+    stop: Sender<()>,
+) -> Result<(cpal::Stream, String, u32), String> {
+    let host = cpal::default_host();
+    let device = match name {
+        None => host.default_input_device().ok_or("No input device found")?,
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| e.to_string())?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+            .ok_or_else(|| format!("No input device named '{}', see --device list", name))?,
+    };
+    let name = device.name().map_err(|e| e.to_string())?;
+    let config = stream_config(&device, sample_rate)?;
+    let format = config.sample_format();
+    let config = cpal::StreamConfig::from(config);
+    let blocks = BlockBuffer::new(block_frames.max(1) * config.channels as usize);
+    let stream = match format {
+        SampleFormat::I16 => build::<i16>(&device, &config, channel, blocks, sender, stop),
+        SampleFormat::U16 => build::<u16>(&device, &config, channel, blocks, sender, stop),
+        SampleFormat::I32 => build::<i32>(&device, &config, channel, blocks, sender, stop),
+        SampleFormat::F32 => build::<f32>(&device, &config, channel, blocks, sender, stop),
+        format => {
+            return Err(format!(
+                "{} captures {} samples, which aren't supported",
+                name, format
+            ))
+        }
+    }?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok((stream, name, config.sample_rate.0))
+}
+
+/// The device's default configuration, or the one closest to it capturing at `sample_rate`.
+fn stream_config(
+    device: &cpal::Device,
+    sample_rate: Option<u32>,
+) -> Result<cpal::SupportedStreamConfig, String> {
+    let default = device.default_input_config().map_err(|e| e.to_string())?;
+    let Some(rate) = sample_rate.filter(|&rate| rate != default.sample_rate().0) else {
+        return Ok(default);
+    };
+    let configs = device
+        .supported_input_configs()
+        .map_err(|e| e.to_string())?;
+    configs
+        .filter(|config| (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&rate))
+        .max_by_key(|config| {
+            let same_channels = config.channels() == default.channels();
+            (
+                same_channels,
+                config.sample_format() == default.sample_format(),
+            )
+        })
+        .map(|config| config.with_sample_rate(cpal::SampleRate(rate)))
+        .ok_or_else(|| format!("The input doesn't capture at {} Hz", rate))
+}
+
+fn build<T: SizedSample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channel: Channel,
+    mut blocks: BlockBuffer,
+    sender: Sender<Result<Vec<f32>, String>>,
+    stop: Sender<()>,
+) -> Result<cpal::Stream, String>
+where
+    f32: FromSample<T>,
+{
+    let channels = config.channels;
+    let (error_sender, error_stop) = (sender.clone(), stop.clone());
+    let data = move |data: &[T], _: &cpal::InputCallbackInfo| {
+        for block in blocks.push(
+            data.iter()
+                .map(|&sample| sample.to_sample::<f32>() * 32768.0),
+        ) {
+            if sender.send(channel.extract(&block, channels)).is_err() {
+                let _ = stop.send(());
+            }
+        }
+    };
+    let error = move |e: cpal::StreamError| {
+        let _ = error_sender.send(Err(e.to_string()));
+        let _ = error_stop.send(());
+    };
+    device
+        .build_input_stream(config, data, error, None)
+        .map_err(|e| e.to_string())
+}
+
+/// Collects samples arriving in pieces of any size, like a sound card's callbacks deliver them,
+/// into blocks of `len`.
+struct BlockBuffer {
+    len: usize,
+    pending: Vec<f32>,
+}
+
+impl BlockBuffer {
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            pending: Vec::with_capacity(len),
+        }
+    }
+
+    /// The blocks completed by `samples`; the rest waits for the next call.
+    fn push(&mut self, samples: impl Iterator<Item = f32>) -> Vec<Vec<f32>> {
+        let mut blocks = Vec::new();
+        for sample in samples {
+            self.pending.push(sample);
+            if self.pending.len() == self.len {
+                blocks.push(std::mem::replace(
+                    &mut self.pending,
+                    Vec::with_capacity(self.len),
+                ));
+            }
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_of_any_size_make_whole_blocks() {
+        let mut buffer = BlockBuffer::new(4);
+        let mut next = 0..;
+        let mut piece = |len: usize| buffer.push(next.by_ref().take(len).map(|n| n as f32));
+        assert!(piece(3).is_empty());
+        assert_eq!(piece(2), [vec![0.0, 1.0, 2.0, 3.0]]);
+        assert_eq!(
+            piece(9),
+            [vec![4.0, 5.0, 6.0, 7.0], vec![8.0, 9.0, 10.0, 11.0]]
+        );
+        assert_eq!(piece(2), [vec![12.0, 13.0, 14.0, 15.0]]);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::fft::{mirrored, single_sided_len, Fft, Precision};
+use crate::window::Window;
+use rustfft::FftPlanner;
+
+/// Real cepstrum: the inverse FFT of the log magnitude spectrum. Evenly spaced harmonics are a
+/// periodic ripple across the spectrum, so a pitched sound shows up as a peak at its period.
+#[derive(Debug, Clone, Default)]
+pub struct Cepstrum {
+    /// Quefrency step in seconds, i.e. one sample period of the analysed signal.
+    pub quefrency_step: f32,
+    /// From quefrency 0 up to half the transform, beyond which the cepstrum mirrors.
+    pub values: Vec<f32>,
+}
+
+impl Cepstrum {
+    /// Cepstrum averaged over Hann-windowed frames of `frame_len` samples overlapping by half, or
+    /// one frame padded with zeros if the signal is shorter. Short frames keep the harmonics
+    /// broad; over a long transform they become a train of narrow lines, whose cepstrum has
+    /// almost as high peaks at every multiple of the period. Bins more than 60 dB below the
+    /// loudest are floored so the gaps between harmonics don't dominate the log spectrum.
+    pub fn new(samples: &[f32], sample_rate: u32, frame_len: usize) -> Self {
+        const FLOOR: f32 = 1e-3;
+        if frame_len < 2 || samples.is_empty() {
+            return Self::default();
+        }
+        let fft = Fft::new(frame_len, Precision::F64);
+        let mut log_sum = vec![0.0_f64; single_sided_len(frame_len)];
+        let mut frames = 0;
+        let mut start = 0;
+        loop {
+            let mut frame = samples[start..(start + frame_len).min(samples.len())].to_vec();
+            frame.resize(frame_len, 0.0);
+            let magnitudes = fft.magnitudes(&Window::Hann.apply(&frame));
+            let floor = magnitudes
+                .iter()
+                .fold(0.0_f32, |max, &m| max.max(m))
+                .max(f32::MIN_POSITIVE)
+                * FLOOR;
+            for (sum, &m) in log_sum.iter_mut().zip(&magnitudes) {
+                *sum += (m.max(floor) as f64).ln();
+            }
+            frames += 1;
+            start += frame_len / 2;
+            if start + frame_len > samples.len() {
+                break;
+            }
+        }
+        // the inverse transform of the even, real log spectrum is real
+        let log: Vec<f64> = log_sum.iter().map(|sum| sum / frames as f64).collect();
+        let mut buffer = mirrored(&log);
+        // one sample shorter than the frames for odd lengths, which is close enough
+        let n = buffer.len();
+        FftPlanner::new().plan_fft_inverse(n).process(&mut buffer);
+        Self {
+            quefrency_step: 1.0 / sample_rate as f32,
+            values: buffer[..n / 2]
+                .iter()
+                .map(|c| (c.re / n as f64) as f32)
+                .collect(),
+        }
+    }
+
+    /// Frame length for [`Self::new`] covering three periods of `min_hz`, as a power of two.
+    pub fn frame_len(sample_rate: u32, min_hz: f32) -> usize {
+        ((3.0 * sample_rate as f32 / min_hz.max(1.0)) as usize).next_power_of_two()
+    }
+
+    /// Quefrency in seconds of each value.
+    pub fn quefrencies(&self) -> Vec<f32> {
+        (0..self.values.len())
+            .map(|i| i as f32 * self.quefrency_step)
+            .collect()
+    }
+
+    /// Quefrency of the highest peak between the periods of `max_hz` and `min_hz`, an estimate of
+    /// the pitch period. `None` if the range holds no values.
+    pub fn pitch_period(&self, min_hz: f32, max_hz: f32) -> Option<f32> {
+        if self.quefrency_step <= 0.0 || min_hz <= 0.0 {
+            return None;
+        }
+        let first = ((1.0 / max_hz / self.quefrency_step).ceil() as usize).max(1);
+        let last = (1.0 / min_hz / self.quefrency_step).floor() as usize;
+        let last = last.min(self.values.len().checked_sub(1)?);
+        (first..=last)
+            .max_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+            .map(|i| i as f32 * self.quefrency_step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testgen;
+
+    #[test]
+    fn the_pitch_period_of_a_sawtooth_is_found() {
+        let frame_len = Cepstrum::frame_len(48_000, 50.0);
+        assert_eq!(frame_len, 4096);
+        for freq in [120.0, 200.0, 440.0] {
+            let saw = testgen::sawtooth(freq, 8192.0, 48_000, 48_000);
+            let cepstrum = Cepstrum::new(&saw, 48_000, frame_len);
+            assert_eq!(cepstrum.values.len(), frame_len / 2);
+            let period = cepstrum.pitch_period(50.0, 1000.0).unwrap();
+            // within a sample of the true period
+            assert!(
+                (period - 1.0 / freq).abs() <= cepstrum.quefrency_step,
+                "{} s for {} Hz",
+                period,
+                freq
+            );
+        }
+    }
+
+    #[test]
+    fn nothing_to_analyse_gives_no_pitch() {
+        let cepstrum = Cepstrum::new(&[], 48_000, 4096);
+        assert!(cepstrum.values.is_empty());
+        assert_eq!(cepstrum.pitch_period(50.0, 1000.0), None);
+    }
+}
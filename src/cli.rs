@@ -0,0 +1,337 @@
+use crate::config::Config;
+use ilena_analysis::fft::Precision;
+use ilena_analysis::summary::SummaryFormat;
+use ilena_analysis::wav::{Channel, RawFormat, SampleType};
+use ilena_analysis::AnalysisOptions;
+use std::path::{Path, PathBuf};
+
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} [--sample-type u8|i16|i24|i32|f32] [--precision f32|f64] [--summary | --json-lines] \
+         [--concat] [--memory-limit <MiB>] [--timings] [--threads N] [-v | -q] \
+         [--skip <ms>] [--duration <ms>] [--config <file.ron>] \
+         [--title <text>] [--profile <name>] [--keep-duplicates] [--keep-grids] [--log <file.jsonl>] \
+         [--channel interleaved|mono|left|right|mid|side[,...]] [--raw <rate>,<type>,<channels>[,le|be]] \
+         <folder | file | glob | ->...\n       \
+         {} --live [--device <name>] [--rate <Hz>] [--channel <channel>]\n       \
+         {} --live --raw <rate>,<type>,<channels>[,le|be] [--channel <channel>] < capture\n       \
+         {} --device list\n       \
+         {} --session <file.session | file.ron> [--profile <name>]\n       \
+         {} --verify-fft\n       \
+         {} --bench <samples> [--runs N] [--precision f32|f64]\n       \
+         {} --generate <folder> [--rate <Hz>] [--duration <ms>]",
+        program, program, program, program, program, program, program, program
+    )
+}
+
+/// Command line arguments.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Folders, single files, glob patterns such as `recordings/*_48k.wav`, or `-` for a WAV
+    /// file on stdin.
+    pub inputs: Vec<String>,
+    /// Forces how WAV sample data is interpreted, regardless of the header.
+    pub sample_type: Option<SampleType>,
+    /// FFT precision; overrides the one persisted by the GUI.
+    pub precision: Option<Precision>,
+    /// Print a per-file summary to stdout instead of opening the GUI.
+    pub summary: Option<SummaryFormat>,
+    /// Join all WAV files, in sorted order, into one signal and analyse that.
+    pub concat: bool,
+    /// Budget in bytes for spectra and cached samples, beyond which they're reduced.
+    pub memory_limit: Option<usize>,
+    /// Print per-file load and FFT timings to stdout.
+    pub timings: bool,
+    /// Threads for loading and analysing files; one per core by default.
+    pub threads: Option<usize>,
+    /// `-v` / `-q`: whether progress is logged to stderr, see [`Args::verbose`] and
+    /// [`Args::report`].
+    pub verbosity: Option<bool>,
+    /// Channels to plot from each WAV file, one plot each; `mid,side` for stereo imaging.
+    pub channels: Vec<Channel>,
+    /// Layout of `.raw` / `.pcm` files, which have no header to read it from.
+    pub raw_format: Option<RawFormat>,
+    /// Compare the FFT against a direct DFT on small test signals and exit; takes no inputs.
+    pub verify_fft: bool,
+    /// Time the analysis of a synthetic signal of this many samples and exit; takes no inputs.
+    pub bench: Option<usize>,
+    /// Timed repetitions of every `--bench` configuration.
+    pub runs: Option<usize>,
+    /// Folder to write test signals with known spectra to as WAV files and exit; takes no inputs.
+    pub generate: Option<PathBuf>,
+    /// Sample rate of the `--generate` signals, 48 kHz by default, or of the `--live` input,
+    /// which captures at the device's own rate by default.
+    pub rate: Option<u32>,
+    /// Window title instead of the default one.
+    pub title: Option<String>,
+    /// Leading milliseconds of every file left out of the analysis.
+    pub skip_ms: Option<f32>,
+    /// Milliseconds analysed after the skipped ones, instead of the rest of the file.
+    pub duration_ms: Option<f32>,
+    /// JSON lines file every run appends its inputs, settings and per-file summaries to.
+    pub log: Option<PathBuf>,
+    /// Settings from `--config`, which the other options override.
+    pub config: Option<Config>,
+    /// Show a rolling spectrum of a sound card input instead of loading files, or with
+    /// `raw_format` of headerless PCM piped to stdin, see
+    /// [`ilena_analysis::stream::spawn_raw_capture`].
+    pub live: bool,
+    /// Name of the `--live` input instead of the default one, or `list` to print the names and
+    /// exit.
+    pub device: Option<String>,
+    /// Load both `name.wav` and a `name.f` saved from it instead of only the newer one.
+    pub keep_duplicates: bool,
+    /// Leave `.f` spectra on the grid they were saved with instead of resampling them onto the
+    /// audio's.
+    pub keep_grids: bool,
+    /// Session saved from the GUI to open instead of loading files.
+    pub session: Option<PathBuf>,
+    /// Name of a separate set of persisted GUI settings, so sessions don't share e.g. their
+    /// frequency range.
+    pub profile: Option<String>,
+}
+
+impl Args {
+    /// Whether to log progress to stderr; by default only when opening the GUI, so headless
+    /// output stays clean for scripts.
+    pub fn verbose(&self) -> bool {
+        self.verbosity.unwrap_or(self.summary.is_none())
+    }
+
+    /// Whether headless runs end with a count of processed and failed files on stderr; always
+    /// unless `-q`.
+    pub fn report(&self) -> bool {
+        self.verbosity.unwrap_or(true)
+    }
+
+    /// Analysis settings of the config file, with `--precision`, `--skip` and `--duration`
+    /// applied.
+    pub fn analysis_options(&self) -> AnalysisOptions {
+        let analysis = self
+            .config
+            .as_ref()
+            .map_or_else(AnalysisOptions::default, |config| config.analysis);
+        let fft_len = self.config.as_ref().and_then(|config| config.run.fft_len);
+        analysis
+            .precision(self.precision.unwrap_or(analysis.precision))
+            .length(fft_len)
+            .skip_ms(self.skip_ms.unwrap_or(analysis.skip_ms))
+            .duration_ms(self.duration_ms.or(analysis.duration_ms))
+    }
+
+    /// Whether `--device list` asked for the input devices' names.
+    pub fn lists_devices(&self) -> bool {
+        self.device.as_deref() == Some("list")
+    }
+
+    /// Parses the arguments following the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--sample-type" => {
+                    let value = args.next().ok_or("--sample-type needs a value")?;
+                    parsed.sample_type = Some(SampleType::parse(&value)?);
+                }
+                "--precision" => {
+                    let value = args.next().ok_or("--precision needs a value")?;
+                    parsed.precision = Some(Precision::parse(&value)?);
+                }
+                "--summary" => parsed.summary = Some(SummaryFormat::Tsv),
+                "--json-lines" => parsed.summary = Some(SummaryFormat::JsonLines),
+                "--concat" => parsed.concat = true,
+                "--timings" => parsed.timings = true,
+                "--keep-duplicates" => parsed.keep_duplicates = true,
+                "--keep-grids" => parsed.keep_grids = true,
+                "--live" => parsed.live = true,
+                "--verify-fft" => parsed.verify_fft = true,
+                "--bench" => {
+                    let value = args
+                        .next()
+                        .ok_or("--bench needs a signal length in samples")?;
+                    match value.parse() {
+                        Ok(len) if len > 0 => parsed.bench = Some(len),
+                        _ => {
+                            return Err(format!(
+                                "Invalid signal length '{}', expected samples",
+                                value
+                            ))
+                        }
+                    }
+                }
+                "--runs" => {
+                    let value = args.next().ok_or("--runs needs a value")?;
+                    match value.parse() {
+                        Ok(runs) if runs > 0 => parsed.runs = Some(runs),
+                        _ => {
+                            return Err(format!(
+                                "Invalid run count '{}', expected at least 1",
+                                value
+                            ))
+                        }
+                    }
+                }
+                "--generate" => {
+                    parsed.generate = Some(args.next().ok_or("--generate needs a folder")?.into());
+                }
+                "--rate" => {
+                    let value = args.next().ok_or("--rate needs a value in Hz")?;
+                    match value.parse() {
+                        Ok(rate) if rate > 0 => parsed.rate = Some(rate),
+                        _ => return Err(format!("Invalid sample rate '{}', expected Hz", value)),
+                    }
+                }
+                "--skip" => {
+                    let value = args.next().ok_or("--skip needs a value in ms")?;
+                    match value.parse() {
+                        Ok(ms) if ms >= 0.0 => parsed.skip_ms = Some(ms),
+                        _ => {
+                            return Err(format!("Invalid skip '{}', expected milliseconds", value))
+                        }
+                    }
+                }
+                "--duration" => {
+                    let value = args.next().ok_or("--duration needs a value in ms")?;
+                    match value.parse() {
+                        Ok(ms) if ms > 0.0 => parsed.duration_ms = Some(ms),
+                        _ => {
+                            return Err(format!(
+                                "Invalid duration '{}', expected milliseconds",
+                                value
+                            ))
+                        }
+                    }
+                }
+                "--device" => {
+                    parsed.device = Some(args.next().ok_or("--device needs a name or list")?)
+                }
+                "--log" => parsed.log = Some(args.next().ok_or("--log needs a file")?.into()),
+                "--session" => {
+                    parsed.session = Some(args.next().ok_or("--session needs a file")?.into())
+                }
+                "--config" => {
+                    let value = args.next().ok_or("--config needs a file")?;
+                    parsed.config = Some(Config::load(Path::new(&value))?);
+                }
+                "--title" => parsed.title = Some(args.next().ok_or("--title needs a value")?),
+                "--profile" => {
+                    let value = args.next().ok_or("--profile needs a name")?;
+                    if value.is_empty() {
+                        return Err("--profile needs a non-empty name".to_string());
+                    }
+                    parsed.profile = Some(value);
+                }
+                "--channel" => {
+                    let value = args.next().ok_or("--channel needs a value")?;
+                    parsed.channels = value
+                        .split(',')
+                        .map(Channel::parse)
+                        .collect::<Result<_, _>>()?;
+                }
+                "--raw" => {
+                    let value = args.next().ok_or("--raw needs a value like 48000,i16,2")?;
+                    parsed.raw_format = Some(RawFormat::parse(&value)?);
+                }
+                "-v" | "--verbose" => parsed.verbosity = Some(true),
+                "-q" | "--quiet" => parsed.verbosity = Some(false),
+                "--threads" => {
+                    let value = args.next().ok_or("--threads needs a value")?;
+                    match value.parse() {
+                        Ok(threads) if threads > 0 => parsed.threads = Some(threads),
+                        _ => {
+                            return Err(format!(
+                                "Invalid thread count '{}', expected at least 1",
+                                value
+                            ))
+                        }
+                    }
+                }
+                "--memory-limit" => {
+                    let value = args.next().ok_or("--memory-limit needs a value in MiB")?;
+                    let mib: usize = value
+                        .parse()
+                        .map_err(|_| format!("Invalid memory limit '{}', expected MiB", value))?;
+                    let too_large = || format!("Memory limit of {} MiB is too large", value);
+                    parsed.memory_limit = Some(mib.checked_mul(1 << 20).ok_or_else(too_large)?);
+                }
+                // a lone `-` is stdin
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("Unknown option {}", arg))
+                }
+                _ => parsed.inputs.push(arg),
+            }
+        }
+        if parsed.summary.is_none() {
+            parsed.summary = parsed.config.as_ref().and_then(|config| config.run.format);
+        }
+        if parsed.channels.is_empty() {
+            parsed.channels.push(Channel::Interleaved);
+        }
+        if parsed.live && (!parsed.inputs.is_empty() || parsed.summary.is_some()) {
+            return Err(
+                "--live shows its input in the GUI and takes no inputs or --summary".to_string(),
+            );
+        }
+        if parsed.device.is_some() && !parsed.live && !parsed.lists_devices() {
+            return Err("--device picks the input of --live".to_string());
+        }
+        if parsed.live && parsed.raw_format.is_some() && parsed.device.is_some() {
+            return Err("--live --raw reads stdin and takes no --device".to_string());
+        }
+        let with_others = !parsed.inputs.is_empty() || parsed.summary.is_some() || parsed.live;
+        if parsed.session.is_some() && with_others {
+            return Err("--session opens the saved plots in the GUI and takes no inputs, --summary or --live"
+                .to_string());
+        }
+        let opens_gui = parsed.live || parsed.session.is_some();
+        let headless = parsed.verify_fft
+            || parsed.bench.is_some()
+            || parsed.generate.is_some()
+            || parsed.lists_devices();
+        if parsed.inputs.is_empty() && !headless && !opens_gui {
+            return Err("Missing folder, file or glob pattern".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().chain(&["a.wav"]).map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn memory_limit_in_mib() {
+        assert_eq!(
+            parse(&["--memory-limit", "512"]).unwrap().memory_limit,
+            Some(512 << 20)
+        );
+        let too_large = (usize::MAX >> 19).to_string();
+        assert!(parse(&["--memory-limit", &too_large])
+            .unwrap_err()
+            .contains("too large"));
+        assert!(parse(&["--memory-limit", "-1"]).is_err());
+    }
+
+    #[test]
+    fn live_input_from_a_device_or_stdin() {
+        let parse_live = |args: &[&str]| Args::parse(args.iter().map(|arg| arg.to_string()));
+        let live = parse_live(&["--live", "--device", "USB Audio", "--rate", "96000"]).unwrap();
+        assert_eq!(
+            (live.device.as_deref(), live.rate, live.raw_format),
+            (Some("USB Audio"), Some(96_000), None)
+        );
+        assert!(parse_live(&["--live", "--raw", "48000,i16,1"])
+            .unwrap()
+            .raw_format
+            .is_some());
+        assert!(parse_live(&["--live", "--raw", "48000,i16,1", "--device", "USB Audio"]).is_err());
+        assert!(parse_live(&["--device", "list"]).unwrap().lists_devices());
+        assert!(parse_live(&["--device", "USB Audio"]).is_err());
+        assert!(parse_live(&["--live", "a.wav"]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use egui::Color32;
+
+/// How values are mapped to colors in heatmaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum ColorMap {
+    /// Blue for negative, white at the center, red for positive values.
+    #[default]
+    RedBlue,
+    Viridis,
+    Magma,
+    Grayscale,
+    /// Designed to read the same with red-green color blindness.
+    Cividis,
+}
+
+// Stops sampled from matplotlib's maps at equal spacing, interpolated linearly in between.
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84],
+    [71, 45, 123],
+    [59, 82, 139],
+    [44, 114, 142],
+    [33, 145, 140],
+    [40, 174, 128],
+    [94, 201, 98],
+    [173, 220, 48],
+    [253, 231, 37],
+];
+const MAGMA: [[u8; 3]; 9] = [
+    [0, 0, 4],
+    [28, 16, 68],
+    [79, 18, 123],
+    [129, 37, 129],
+    [181, 54, 122],
+    [229, 80, 100],
+    [251, 135, 97],
+    [254, 194, 135],
+    [252, 253, 191],
+];
+const CIVIDIS: [[u8; 3]; 5] = [
+    [0, 34, 78],
+    [61, 77, 110],
+    [124, 123, 120],
+    [188, 175, 111],
+    [254, 232, 56],
+];
+
+impl ColorMap {
+    pub const ALL: [ColorMap; 5] = [
+        ColorMap::RedBlue,
+        ColorMap::Viridis,
+        ColorMap::Magma,
+        ColorMap::Grayscale,
+        ColorMap::Cividis,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMap::RedBlue => "Red-blue",
+            ColorMap::Viridis => "Viridis",
+            ColorMap::Magma => "Magma",
+            ColorMap::Grayscale => "Grayscale",
+            ColorMap::Cividis => "Cividis",
+        }
+    }
+
+    /// Color at `t`, from 0 (lowest) to 1 (highest); values outside are clamped.
+    pub fn color(self, t: f32) -> Color32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            ColorMap::RedBlue => {
+                let s = 2.0 * t - 1.0;
+                let fade = |c: u8| (255.0 - (255.0 - c as f32) * s.abs()) as u8;
+                if s < 0.0 {
+                    Color32::from_rgb(fade(33), fade(102), fade(172))
+                } else {
+                    Color32::from_rgb(fade(178), fade(24), fade(43))
+                }
+            }
+            ColorMap::Viridis => interpolate(&VIRIDIS, t),
+            ColorMap::Magma => interpolate(&MAGMA, t),
+            ColorMap::Grayscale => {
+                let v = (t * 255.0) as u8;
+                Color32::from_rgb(v, v, v)
+            }
+            ColorMap::Cividis => interpolate(&CIVIDIS, t),
+        }
+    }
+}
+
+fn interpolate(stops: &[[u8; 3]], t: f32) -> Color32 {
+    let position = t * (stops.len() - 1) as f32;
+    let i = (position as usize).min(stops.len() - 2);
+    let frac = position - i as f32;
+    let mix = |channel: usize| {
+        let (from, to) = (stops[i][channel] as f32, stops[i + 1][channel] as f32);
+        (from + (to - from) * frac).round() as u8
+    };
+    Color32::from_rgb(mix(0), mix(1), mix(2))
+}
@@ -0,0 +1,107 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Evenly spaced ripple across a spectrum. A reflection arriving some delay after the direct
+/// sound adds notches every 1 / delay Hz; a pitched sound's harmonics show up the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comb {
+    /// Distance between neighbouring notches (or peaks).
+    pub spacing_hz: f32,
+    /// Delay of the reflection causing it, 1 / spacing.
+    pub delay_ms: f32,
+    /// Autocorrelation of the ripple at the spacing, 1 for a perfectly periodic one.
+    pub strength: f32,
+}
+
+/// Strongest periodic ripple in the bins of `freqs` between `band[0]` and `band[1]` Hz, caused
+/// by a delay between `delay_range_ms[0]` and `delay_range_ms[1]`. The log spectrum is flattened
+/// by subtracting a moving average wider than the slowest ripple, so the overall tilt doesn't
+/// correlate with itself; `None` if the band holds too few bins for the spacing.
+pub fn detect(
+    freqs: &[f32],
+    amplitudes: &[f32],
+    band: [f32; 2],
+    delay_range_ms: [f32; 2],
+) -> Option<Comb> {
+    // silent bins would otherwise dominate the log spectrum as deep fake notches
+    const FLOOR_DB: f32 = -120.0;
+    let first = freqs.partition_point(|&freq| freq < band[0]);
+    let last = freqs.partition_point(|&freq| freq <= band[1]);
+    let bins = freqs.get(first..last)?;
+    let bin_hz = bins.get(1)? - bins[0];
+    if bin_hz <= 0.0 {
+        return None;
+    }
+    // short delays are wide spacings, i.e. long lags
+    let lag = |delay_ms: f32| 1000.0 / delay_ms.max(1e-3) / bin_hz;
+    let min_lag = lag(delay_range_ms[1]).floor().max(2.0) as usize;
+    let max_lag = lag(delay_range_ms[0]).ceil() as usize;
+    if max_lag < min_lag || 4 * max_lag > bins.len() {
+        return None;
+    }
+
+    let amplitudes = &amplitudes[first..last];
+    let peak = amplitudes
+        .iter()
+        .fold(0.0_f32, |max, &a| max.max(a))
+        .max(f32::MIN_POSITIVE);
+    let db: Vec<f32> = amplitudes
+        .iter()
+        .map(|&a| (20.0 * (a / peak).log10()).max(FLOOR_DB))
+        .collect();
+    let ripple = subtract_moving_average(&db, 2 * max_lag + 1);
+
+    let n = (2 * ripple.len()).next_power_of_two();
+    let mut buffer: Vec<Complex<f32>> = ripple.iter().map(|&r| Complex::new(r, 0.0)).collect();
+    buffer.resize(n, Complex::new(0.0, 0.0));
+    let mut planner = FftPlanner::new();
+    planner.plan_fft_forward(n).process(&mut buffer);
+    buffer
+        .iter_mut()
+        .for_each(|c| *c = Complex::new(c.norm_sqr(), 0.0));
+    planner.plan_fft_inverse(n).process(&mut buffer);
+    let zero = buffer[0].re;
+    if zero <= 0.0 {
+        return None;
+    }
+    // unbiased, so long lags with fewer overlapping bins aren't penalized
+    let correlation =
+        |k: usize| buffer[k].re / zero * ripple.len() as f32 / (ripple.len() - k) as f32;
+
+    let best = (min_lag..=max_lag)
+        .filter(|&k| correlation(k) >= correlation(k - 1) && correlation(k) >= correlation(k + 1))
+        .max_by(|&a, &b| correlation(a).total_cmp(&correlation(b)))?;
+    let (before, at, after) = (
+        correlation(best - 1),
+        correlation(best),
+        correlation(best + 1),
+    );
+    let curvature = before - 2.0 * at + after;
+    let offset = if curvature < 0.0 {
+        0.5 * (before - after) / curvature
+    } else {
+        0.0
+    };
+    let spacing_hz = (best as f32 + offset) * bin_hz;
+    Some(Comb {
+        spacing_hz,
+        delay_ms: 1000.0 / spacing_hz,
+        strength: at.clamp(0.0, 1.0),
+    })
+}
+
+/// `values` minus their centered moving average over `width` values, narrower at the ends.
+fn subtract_moving_average(values: &[f32], width: usize) -> Vec<f32> {
+    let half = width / 2;
+    let mut sums = Vec::with_capacity(values.len() + 1);
+    sums.push(0.0_f64);
+    for &value in values {
+        sums.push(sums.last().unwrap() + value as f64);
+    }
+    (0..values.len())
+        .map(|i| {
+            let (start, end) = (i.saturating_sub(half), (i + half + 1).min(values.len()));
+            let mean = (sums[end] - sums[start]) / (end - start) as f64;
+            values[i] - mean as f32
+        })
+        .collect()
+}
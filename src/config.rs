@@ -0,0 +1,75 @@
+use crate::Normalization;
+use ilena_analysis::summary::SummaryFormat;
+use ilena_analysis::AnalysisOptions;
+use ron::extensions::Extensions;
+use std::path::{Path, PathBuf};
+
+/// Settings read from a `--config` file, so a batch run can be reproduced from version control.
+/// Written in RON, with `Some(..)` around optional values left out, for example
+///
+/// ```text
+/// (
+///     analysis: (window: Hann, welch: (segment_len: 8192, hop: 4096)),
+///     run: (fft_len: 65536, min_freq: 20, max_freq: 20000, format: Tsv, output_dir: "results"),
+/// )
+/// ```
+///
+/// Anything missing keeps its default; the command line overrides the file.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub analysis: AnalysisOptions,
+    pub run: RunConfig,
+}
+
+/// What a run does with the spectra besides analysing them.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    /// Samples every file is cut or padded to, so all spectra get the same bins.
+    pub fft_len: Option<usize>,
+    /// Frequency range the GUI shows.
+    pub min_freq: Option<f32>,
+    pub max_freq: Option<f32>,
+    pub normalization: Option<Normalization>,
+    /// Print summaries in this format instead of opening the GUI, like `--summary` or
+    /// `--json-lines`.
+    pub format: Option<SummaryFormat>,
+    /// Folder headless runs write `summary.<format>` to instead of stdout, and the fallback
+    /// without a display its CSVs.
+    pub output_dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(&text)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_documented_example_loads() {
+        let text = "(\n    analysis: (window: Hann, welch: (segment_len: 8192, hop: 4096)),\n    \
+                    run: (fft_len: 65536, min_freq: 20, max_freq: 20000, format: Tsv, output_dir: \"results\"),\n)";
+        let path =
+            std::env::temp_dir().join(format!("ilena_analysis_config_{}.ron", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let config = Config::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+        assert_eq!(
+            config.analysis.welch.map(|welch| welch.segment_len),
+            Some(8192)
+        );
+        assert_eq!(config.run.fft_len, Some(65536));
+        assert_eq!(config.run.output_dir, Some(PathBuf::from("results")));
+    }
+}
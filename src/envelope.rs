@@ -0,0 +1,213 @@
+use egui_plot::PlotPoint;
+use std::collections::VecDeque;
+
+/// Peak-hold envelope: every point is replaced by the highest value within `width` (in x units)
+/// centered on it. `points` must be sorted by x.
+pub fn upper_envelope(points: &[PlotPoint], width: f64) -> Vec<PlotPoint> {
+    let half = width / 2.0;
+    // indices of the candidates for the maximum, with decreasing y
+    let mut candidates: VecDeque<usize> = VecDeque::new();
+    let mut next = 0;
+    points
+        .iter()
+        .map(|point| {
+            while next < points.len() && points[next].x <= point.x + half {
+                while candidates
+                    .back()
+                    .is_some_and(|&j| points[j].y <= points[next].y)
+                {
+                    candidates.pop_back();
+                }
+                candidates.push_back(next);
+                next += 1;
+            }
+            while candidates
+                .front()
+                .is_some_and(|&j| points[j].x < point.x - half)
+            {
+                candidates.pop_front();
+            }
+            let max = candidates.front().map_or(point.y, |&j| points[j].y);
+            PlotPoint::new(point.x, max)
+        })
+        .collect()
+}
+
+/// Fractional-octave smoothing: every point is replaced by the mean y of the points within
+/// 1/`bands` of an octave centered on it, so the window widens with frequency the way acoustic
+/// measurements are usually read. The values are averaged as shown, amplitudes or dB. Negative x
+/// are smoothed like their mirror image and 0 stays as it is. `points` must be sorted by x.
+pub fn octave_smooth(points: &[PlotPoint], bands: f64) -> Vec<PlotPoint> {
+    let ratio = 2.0_f64.powf(0.5 / bands);
+    let mut sums = Vec::with_capacity(points.len() + 1);
+    sums.push(0.0);
+    for point in points {
+        sums.push(sums[sums.len() - 1] + point.y);
+    }
+    // both edges only move right as x grows, also across 0
+    let (mut start, mut end) = (0, 0);
+    points
+        .iter()
+        .map(|point| {
+            let (low, high) = if point.x >= 0.0 {
+                (point.x / ratio, point.x * ratio)
+            } else {
+                (point.x * ratio, point.x / ratio)
+            };
+            while start < points.len() && points[start].x < low {
+                start += 1;
+            }
+            while end < points.len() && points[end].x <= high {
+                end += 1;
+            }
+            // never empty, the point itself is inside its window
+            let mean = (sums[end] - sums[start]) / (end - start) as f64;
+            PlotPoint::new(point.x, mean)
+        })
+        .collect()
+}
+
+/// How [`log_bins`] combines the points falling into one bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Pooling {
+    #[default]
+    Mean,
+    /// The highest point, which keeps peaks at their height and frequency.
+    Max,
+}
+
+impl Pooling {
+    pub const ALL: [Pooling; 2] = [Pooling::Mean, Pooling::Max];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pooling::Mean => "Mean",
+            Pooling::Max => "Max",
+        }
+    }
+}
+
+/// Re-bins `points` onto `bands` logarithmically spaced bins per octave, so the density of the
+/// curve no longer grows with frequency: the points in each bin become one, pooled as given. At
+/// low frequencies, where the linear bins are coarser than the log bins, points are alone in
+/// their bin and stay as they are. Negative x are binned like their mirror image and 0 stays as
+/// it is. `points` must be sorted by x.
+pub fn log_bins(points: &[PlotPoint], bands: f64, pooling: Pooling) -> Vec<PlotPoint> {
+    let bin = |x: f64| (x != 0.0).then(|| (x > 0.0, (x.abs().log2() * bands).floor() as i64));
+    let mut binned = Vec::new();
+    let mut start = 0;
+    while start < points.len() {
+        let key = bin(points[start].x);
+        let mut end = start + 1;
+        while key.is_some() && end < points.len() && bin(points[end].x) == key {
+            end += 1;
+        }
+        let group = &points[start..end];
+        binned.push(match pooling {
+            Pooling::Mean => {
+                let len = group.len() as f64;
+                let (x, y) = group
+                    .iter()
+                    .fold((0.0, 0.0), |(x, y), point| (x + point.x, y + point.y));
+                PlotPoint::new(x / len, y / len)
+            }
+            Pooling::Max => {
+                let higher =
+                    |max: PlotPoint, &point: &PlotPoint| if point.y > max.y { point } else { max };
+                group.iter().fold(group[0], higher)
+            }
+        });
+        start = end;
+    }
+    binned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(xy: &[(f64, f64)]) -> Vec<PlotPoint> {
+        xy.iter().map(|&(x, y)| PlotPoint::new(x, y)).collect()
+    }
+
+    fn ys(points: &[PlotPoint]) -> Vec<f64> {
+        points.iter().map(|point| point.y).collect()
+    }
+
+    #[test]
+    fn peaks_are_held_for_half_the_width_either_side() {
+        let curve = points(&[
+            (0.0, 1.0),
+            (1.0, 5.0),
+            (2.0, 2.0),
+            (3.0, 0.0),
+            (4.0, 3.0),
+            (5.0, 1.0),
+        ]);
+        assert_eq!(
+            ys(&upper_envelope(&curve, 2.0)),
+            [5.0, 5.0, 5.0, 3.0, 3.0, 3.0]
+        );
+        // narrower than the spacing, every point only sees itself
+        assert_eq!(ys(&upper_envelope(&curve, 0.5)), ys(&curve));
+    }
+
+    #[test]
+    fn smoothing_averages_within_a_fraction_of_an_octave() {
+        let curve = points(&[
+            (-200.0, 1.0),
+            (-100.0, 2.0),
+            (0.0, 3.0),
+            (100.0, 4.0),
+            (110.0, 6.0),
+            (120.0, 8.0),
+            (200.0, 10.0),
+        ]);
+        // third octaves reach 12 % either side
+        assert_eq!(
+            ys(&octave_smooth(&curve, 3.0)),
+            [1.0, 2.0, 3.0, 5.0, 6.0, 7.0, 10.0]
+        );
+    }
+
+    #[test]
+    fn log_bins_pool_the_points_of_each_octave() {
+        let curve = points(&[
+            (-3.0, 5.0),
+            (-2.0, 1.0),
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 3.0),
+            (3.0, 2.0),
+            (4.0, 1.0),
+            (5.0, 7.0),
+            (6.0, 2.0),
+            (7.0, 3.0),
+            (8.0, 9.0),
+        ]);
+        let xy = |points: Vec<PlotPoint>| {
+            points
+                .iter()
+                .map(|point| (point.x, point.y))
+                .collect::<Vec<_>>()
+        };
+        let mean = [
+            (-2.5, 3.0),
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (2.5, 2.5),
+            (5.5, 3.25),
+            (8.0, 9.0),
+        ];
+        assert_eq!(xy(log_bins(&curve, 1.0, Pooling::Mean)), mean);
+        let max = [
+            (-3.0, 5.0),
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 3.0),
+            (5.0, 7.0),
+            (8.0, 9.0),
+        ];
+        assert_eq!(xy(log_bins(&curve, 1.0, Pooling::Max)), max);
+    }
+}
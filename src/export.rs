@@ -0,0 +1,784 @@
+use crate::metrics::SpectralFeatures;
+use crate::octave::{BandLevels, Weighting};
+use crate::peaks::{self, Peak};
+use crate::spectrogram::Spectrogram;
+use egui::{Color32, ColorImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Csv, ExportFormat::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Which bins at the edges of a spectrum the spectrum exports keep. Both by default, i.e. all
+/// `N / 2 + 1` bins of a single-sided spectrum of N samples; tools that expect exactly `N / 2`
+/// bins want one of them dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EdgeBins {
+    /// The bin at 0 Hz.
+    pub dc: bool,
+    /// The bin at exactly half the sample rate, which only even FFT lengths have. It's the last
+    /// bin of a single-sided spectrum and the first of a two-sided one.
+    pub nyquist: bool,
+}
+
+impl Default for EdgeBins {
+    fn default() -> Self {
+        Self {
+            dc: true,
+            nyquist: true,
+        }
+    }
+}
+
+impl EdgeBins {
+    /// The bins of a spectrum from FFTs of `fft_size` samples that are kept. A size of 0 means
+    /// unknown, as for `.f` files, and is taken to be the even length of a single-sided spectrum.
+    pub fn apply(self, freqs: &[f32], amplitudes: &[f32], fft_size: usize) -> (Vec<f32>, Vec<f32>) {
+        let resolution = match freqs {
+            [first, second, ..] => second - first,
+            _ => return (freqs.to_vec(), amplitudes.to_vec()),
+        };
+        let fft_size = if fft_size > 0 {
+            fft_size
+        } else {
+            2 * (freqs.len() - 1)
+        };
+        let nyquist = fft_size
+            .is_multiple_of(2)
+            .then_some(resolution * (fft_size / 2) as f32);
+        let near = |freq: f32, target: f32| (freq.abs() - target).abs() < resolution / 2.0;
+        freqs
+            .iter()
+            .zip(amplitudes)
+            .filter(|&(&freq, _)| {
+                let dropped_dc = !self.dc && near(freq, 0.0);
+                let dropped_nyquist =
+                    !self.nyquist && nyquist.is_some_and(|nyquist| near(freq, nyquist));
+                !dropped_dc && !dropped_nyquist
+            })
+            .map(|(&freq, &amp)| (freq, amp))
+            .unzip()
+    }
+}
+
+/// How frequencies are rounded in exports, for cleaner report tables. Only what's written is
+/// rounded, the analysis keeps the exact bin frequencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum FrequencyRounding {
+    #[default]
+    Exact,
+    /// To the nearest Hz.
+    WholeHz,
+    /// To this many significant figures, e.g. 441.357 Hz to 441.4 Hz with 4.
+    Significant(u32),
+}
+
+impl FrequencyRounding {
+    pub fn apply(self, freq: f32) -> f32 {
+        match self {
+            FrequencyRounding::Exact => freq,
+            FrequencyRounding::WholeHz => freq.round(),
+            FrequencyRounding::Significant(_) if freq == 0.0 || !freq.is_finite() => freq,
+            FrequencyRounding::Significant(figures) => {
+                let freq = freq as f64;
+                let scale =
+                    10_f64.powi(figures.max(1) as i32 - 1 - freq.abs().log10().floor() as i32);
+                ((freq * scale).round() / scale) as f32
+            }
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct PeakRow<'a> {
+    file: &'a str,
+    frequency: f32,
+    amplitude: f32,
+    note: String,
+}
+
+/// Writes the peaks of every file, one row per peak.
+pub fn write_peaks(
+    path: &Path,
+    format: ExportFormat,
+    peaks: &[(&str, Vec<Peak>)],
+) -> Result<(), String> {
+    let rows: Vec<PeakRow> = peaks
+        .iter()
+        .flat_map(|(file, peaks)| {
+            peaks.iter().map(move |peak| PeakRow {
+                file,
+                frequency: peak.frequency,
+                amplitude: peak.amplitude,
+                note: peaks::note_name(peak.frequency),
+            })
+        })
+        .collect();
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "file,frequency,amplitude,note").map_err(|e| e.to_string())?;
+            for row in &rows {
+                writeln!(
+                    writer,
+                    "{},{},{},{}",
+                    csv_field(row.file),
+                    row.frequency,
+                    row.amplitude,
+                    row.note
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &rows).map_err(|e| e.to_string())?
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+#[derive(serde::Serialize)]
+struct BandRow<'a> {
+    file: &'a str,
+    weighting: Weighting,
+    #[serde(flatten)]
+    levels: &'a BandLevels,
+}
+
+/// Writes the band levels of every file, one row per band and a last one per file with the
+/// overall level.
+pub fn write_band_levels(
+    path: &Path,
+    format: ExportFormat,
+    weighting: Weighting,
+    levels: &[(&str, BandLevels)],
+) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "file,weighting,center,lower,upper,level_db")
+                .map_err(|e| e.to_string())?;
+            // the letter the weighting is named by, like dB(A)
+            let weighting = format!("{:?}", weighting);
+            for (file, levels) in levels {
+                let file = csv_field(file);
+                for band in &levels.bands {
+                    writeln!(
+                        writer,
+                        "{},{},{},{},{},{}",
+                        file, weighting, band.center, band.lower, band.upper, band.level_db
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+                writeln!(
+                    writer,
+                    "{},{},overall,,,{}",
+                    file, weighting, levels.overall_db
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        ExportFormat::Json => {
+            let rows: Vec<_> = levels
+                .iter()
+                .map(|(file, levels)| BandRow {
+                    file,
+                    weighting,
+                    levels,
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut writer, &rows).map_err(|e| e.to_string())?
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+#[derive(serde::Serialize)]
+struct FeatureRow<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    features: SpectralFeatures,
+}
+
+/// Writes the spectral features of every file, one row per file.
+pub fn write_features(
+    path: &Path,
+    format: ExportFormat,
+    features: &[(&str, SpectralFeatures)],
+) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "file,centroid,bandwidth,rolloff,flatness,flux")
+                .map_err(|e| e.to_string())?;
+            for (file, f) in features {
+                let flux = f.flux.map_or(String::new(), |flux| flux.to_string());
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{}",
+                    csv_field(file),
+                    f.centroid,
+                    f.bandwidth,
+                    f.rolloff,
+                    f.flatness,
+                    flux
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        ExportFormat::Json => {
+            let rows: Vec<_> = features
+                .iter()
+                .map(|&(file, features)| FeatureRow { file, features })
+                .collect();
+            serde_json::to_writer_pretty(&mut writer, &rows).map_err(|e| e.to_string())?
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Writes files in the order ranked as `rank,file,<metric>` rows, leaving the value empty for
+/// files without one.
+pub fn write_ranking(
+    path: &Path,
+    metric: &str,
+    rows: &[(&str, Option<f32>)],
+) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    writeln!(writer, "rank,file,{}", csv_field(metric)).map_err(|e| e.to_string())?;
+    for (rank, (file, value)) in rows.iter().enumerate() {
+        let value = value.map_or(String::new(), |value| value.to_string());
+        writeln!(writer, "{},{},{}", rank + 1, csv_field(file), value)
+            .map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Writes one spectrum as `frequency,amplitude` rows.
+pub fn write_spectrum_csv(path: &Path, freqs: &[f32], amplitudes: &[f32]) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    writeln!(writer, "frequency,amplitude").map_err(|e| e.to_string())?;
+    for (freq, amp) in freqs.iter().zip(amplitudes) {
+        writeln!(writer, "{},{}", freq, amp).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Writes several spectra resampled on `grid` side by side, with a `frequency` column followed
+/// by one column per entry of `columns`. Bins outside a spectrum's range are left empty.
+pub fn write_combined_csv(
+    path: &Path,
+    grid: &[f32],
+    columns: &[(&str, Vec<Option<f32>>)],
+) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    let header: Vec<String> = columns.iter().map(|(name, _)| csv_field(name)).collect();
+    writeln!(writer, "frequency,{}", header.join(",")).map_err(|e| e.to_string())?;
+    for (i, freq) in grid.iter().enumerate() {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|(_, amplitudes)| {
+                let amp = amplitudes.get(i).copied().flatten();
+                amp.map_or(String::new(), |amp| amp.to_string())
+            })
+            .collect();
+        writeln!(writer, "{},{}", freq, row.join(",")).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Writes a spectrogram as a grid with a row per frame: its start time in seconds followed by
+/// one magnitude per bin, under a header of the bin frequencies in Hz.
+pub fn write_spectrogram_csv(path: &Path, spectrogram: &Spectrogram) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    let header: Vec<String> = spectrogram.freqs().iter().map(f32::to_string).collect();
+    writeln!(writer, "time,{}", header.join(",")).map_err(|e| e.to_string())?;
+    for (time, frame) in spectrogram.times().iter().zip(&spectrogram.frames) {
+        let row: Vec<String> = frame.iter().map(f32::to_string).collect();
+        writeln!(writer, "{},{}", time, row.join(",")).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Writes a spectrogram as a numpy `(frames, bins)` float32 array to `path`, with its time and
+/// frequency axes next to it in `<stem>_times.npy` and `<stem>_freqs.npy`.
+pub fn write_spectrogram_npy(path: &Path, spectrogram: &Spectrogram) -> Result<(), String> {
+    let frames = spectrogram.frames.len();
+    let bins = spectrogram.frames.first().map_or(0, Vec::len);
+    write_npy(
+        path,
+        &[frames, bins],
+        spectrogram.frames.iter().flatten().copied(),
+    )?;
+    let stem = path
+        .file_stem()
+        .map_or("spectrogram".into(), |s| s.to_string_lossy());
+    for (axis, values) in [
+        ("times", spectrogram.times()),
+        ("freqs", spectrogram.freqs()),
+    ] {
+        let axis_path = path.with_file_name(format!("{}_{}.npy", stem, axis));
+        write_npy(&axis_path, &[values.len()], values.into_iter())?;
+    }
+    Ok(())
+}
+
+/// Writes a little-endian float32 array in version 1.0 of the `.npy` format, in C order.
+fn write_npy(
+    path: &Path,
+    shape: &[usize],
+    values: impl Iterator<Item = f32>,
+) -> Result<(), String> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+    let dims: Vec<String> = shape.iter().map(usize::to_string).collect();
+    // a 1-tuple needs its trailing comma
+    let shape = if dims.len() == 1 {
+        format!("({},)", dims[0])
+    } else {
+        format!("({})", dims.join(", "))
+    };
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+        shape
+    );
+    // magic, header length and header, padded so the data starts 64-byte aligned
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    writer.write_all(MAGIC).map_err(|e| e.to_string())?;
+    writer
+        .write_all(&(header.len() as u16).to_le_bytes())
+        .map_err(|e| e.to_string())?;
+    writer
+        .write_all(header.as_bytes())
+        .map_err(|e| e.to_string())?;
+    for value in values {
+        writer
+            .write_all(&value.to_le_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Writes `image` as an RGBA PNG tagged with `dpi`, so it prints at the intended size.
+pub fn write_png(path: &Path, image: &ColorImage, dpi: f32) -> Result<(), String> {
+    let [width, height] = image.size;
+    let writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels_per_meter = (dpi / 0.0254).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: pixels_per_meter,
+        yppu: pixels_per_meter,
+        unit: png::Unit::Meter,
+    }));
+    let data: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+        .collect();
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// One line of a [`Figure`], with its points as displayed.
+pub struct Curve<'a> {
+    pub name: &'a str,
+    pub color: Color32,
+    pub points: Vec<[f64; 2]>,
+}
+
+/// What the spectrum view shows, for [`write_figure`].
+pub struct Figure<'a> {
+    pub curves: Vec<Curve<'a>>,
+    pub x_range: [f64; 2],
+    /// `None` to fit the data.
+    pub y_range: Option<[f64; 2]>,
+    /// Empty for no title.
+    pub title: &'a str,
+    pub x_label: &'a str,
+    pub y_label: &'a str,
+}
+
+/// Writes the curves of `figure` as `curve,frequency,amplitude` rows to `<stem>.csv`, and a
+/// script plotting them with matplotlib the way the view does to `path`.
+pub fn write_figure(path: &Path, figure: &Figure) -> Result<(), String> {
+    let csv_path = path.with_extension("csv");
+    let mut writer = BufWriter::new(File::create(&csv_path).map_err(|e| e.to_string())?);
+    writeln!(writer, "curve,frequency,amplitude").map_err(|e| e.to_string())?;
+    for curve in &figure.curves {
+        let name = csv_field(curve.name);
+        for [freq, amp] in &curve.points {
+            writeln!(writer, "{},{},{}", name, freq, amp).map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    // JSON strings and objects of strings are valid Python literals
+    let quote = |text: &str| serde_json::to_string(text).map_err(|e| e.to_string());
+    let colors: serde_json::Map<_, _> = figure
+        .curves
+        .iter()
+        .map(|curve| {
+            let [r, g, b, _] = curve.color.to_srgba_unmultiplied();
+            (
+                curve.name.to_string(),
+                format!("#{:02x}{:02x}{:02x}", r, g, b).into(),
+            )
+        })
+        .collect();
+    let csv_name = csv_path
+        .file_name()
+        .map_or("figure.csv".into(), |name| name.to_string_lossy());
+    let [x_min, x_max] = figure.x_range;
+    let y_limits = match figure.y_range {
+        Some([y_min, y_max]) => format!("ax.set_ylim({}, {})\n", y_min, y_max),
+        None => String::new(),
+    };
+    let title = match figure.title {
+        "" => String::new(),
+        title => format!("ax.set_title({})\n", quote(title)?),
+    };
+    let script = format!(
+        r#"# Plots {csv_name} like the spectrum view it was exported from.
+import csv
+from pathlib import Path
+
+import matplotlib.pyplot as plt
+
+COLORS = {colors}
+
+curves = {{}}
+with open(Path(__file__).with_name({csv_literal}), newline="") as f:
+    for row in csv.DictReader(f):
+        freqs, amps = curves.setdefault(row["curve"], ([], []))
+        freqs.append(float(row["frequency"]))
+        amps.append(float(row["amplitude"]))
+
+fig, ax = plt.subplots(figsize=(10, 6))
+for name, (freqs, amps) in curves.items():
+    ax.plot(freqs, amps, label=name, color=COLORS.get(name), linewidth=1)
+ax.set_xlim({x_min}, {x_max})
+{y_limits}{title}ax.set_xlabel({x_label})
+ax.set_ylabel({y_label})
+ax.grid(True, alpha=0.3)
+ax.legend()
+plt.show()
+"#,
+        colors = serde_json::Value::Object(colors),
+        csv_literal = quote(&csv_name)?,
+        x_label = quote(figure.x_label)?,
+        y_label = quote(figure.y_label)?,
+    );
+    std::fs::write(path, script).map_err(|e| e.to_string())
+}
+
+/// Writes `figure` as a web page plotting it on a canvas, with the curves embedded as JSON so it
+/// opens anywhere without the app or a network connection. Scrolling zooms around the cursor, only
+/// along x with Shift or y with Ctrl, dragging pans, a double click goes back to the exported view
+/// and clicking a legend entry hides its curve.
+pub fn write_html(path: &Path, figure: &Figure) -> Result<(), String> {
+    let curves: Vec<_> = figure
+        .curves
+        .iter()
+        .map(|curve| {
+            let [r, g, b, _] = curve.color.to_srgba_unmultiplied();
+            serde_json::json!({
+                "name": curve.name,
+                "color": format!("#{:02x}{:02x}{:02x}", r, g, b),
+                "points": curve.points,
+            })
+        })
+        .collect();
+    let data = serde_json::json!({
+        "curves": curves,
+        "xRange": figure.x_range,
+        "yRange": figure.y_range,
+        "title": figure.title,
+        "xLabel": figure.x_label,
+        "yLabel": figure.y_label,
+    });
+    // a name containing `</script>` mustn't end the script early
+    let data = data.to_string().replace("</", "<\\/");
+    std::fs::write(path, HTML_PAGE.replace("{figure}", &data)).map_err(|e| e.to_string())
+}
+
+/// Page [`write_html`] writes, with `{figure}` replaced by the data.
+const HTML_PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Spectrum</title>
+<style>
+  body { margin: 0; font: 13px sans-serif; overflow: hidden; }
+  canvas { display: block; width: 100vw; height: 100vh; cursor: crosshair; }
+  #legend {
+    position: absolute; top: 12px; right: 28px;
+    background: #fffe; border: 1px solid #ccc; padding: 4px 8px;
+  }
+  #legend div { cursor: pointer; white-space: nowrap; }
+  #legend .hidden { opacity: 0.35; }
+  #legend span { display: inline-block; width: 18px; height: 3px; margin: 0 6px 3px 0; }
+  #readout { position: absolute; left: 80px; bottom: 4px; color: #555; }
+</style>
+</head>
+<body>
+<canvas id="plot"></canvas>
+<div id="legend"></div>
+<div id="readout"></div>
+<script>
+const FIGURE = {figure};
+const canvas = document.getElementById("plot");
+const ctx = canvas.getContext("2d");
+const margin = { left: 75, right: 20, top: FIGURE.title ? 40 : 20, bottom: 50 };
+const shown = FIGURE.curves.map(() => true);
+let view;
+
+function fitY(x) {
+  let [low, high] = [Infinity, -Infinity];
+  FIGURE.curves.forEach((curve, i) => {
+    if (!shown[i]) return;
+    for (const [px, py] of curve.points) {
+      if (px >= x[0] && px <= x[1]) [low, high] = [Math.min(low, py), Math.max(high, py)];
+    }
+  });
+  if (!(high > low)) return [0, 1];
+  const pad = (high - low) * 0.05;
+  return [low - pad, high + pad];
+}
+
+function reset() {
+  const x = FIGURE.xRange.slice();
+  view = { x, y: FIGURE.yRange ? FIGURE.yRange.slice() : fitY(x) };
+  draw();
+}
+
+function ticks(low, high, count) {
+  const rough = (high - low) / Math.max(count, 1);
+  const magnitude = Math.pow(10, Math.floor(Math.log10(rough)));
+  const step = [1, 2, 5, 10].map((m) => m * magnitude).find((s) => s >= rough);
+  const decimals = Math.min(Math.max(0, -Math.floor(Math.log10(step))), 10);
+  const values = [];
+  for (let t = Math.ceil(low / step) * step; t <= high; t += step) values.push(t);
+  return values.map((t) => [t, t.toFixed(decimals)]);
+}
+
+function plotSize() {
+  return [canvas.clientWidth - margin.left - margin.right, canvas.clientHeight - margin.top - margin.bottom];
+}
+
+function toScreen(x, y) {
+  const [width, height] = plotSize();
+  return [
+    margin.left + ((x - view.x[0]) / (view.x[1] - view.x[0])) * width,
+    margin.top + ((view.y[1] - y) / (view.y[1] - view.y[0])) * height,
+  ];
+}
+
+function toData(event) {
+  const [width, height] = plotSize();
+  const fx = (event.offsetX - margin.left) / width;
+  const fy = (event.offsetY - margin.top) / height;
+  return [view.x[0] + fx * (view.x[1] - view.x[0]), view.y[1] - fy * (view.y[1] - view.y[0])];
+}
+
+function draw() {
+  const ratio = window.devicePixelRatio || 1;
+  canvas.width = canvas.clientWidth * ratio;
+  canvas.height = canvas.clientHeight * ratio;
+  ctx.setTransform(ratio, 0, 0, ratio, 0, 0);
+  const [width, height] = plotSize();
+  const [left, top, bottom] = [margin.left, margin.top, margin.top + height];
+  ctx.clearRect(0, 0, canvas.clientWidth, canvas.clientHeight);
+  ctx.font = "12px sans-serif";
+  ctx.lineWidth = 1;
+  ctx.strokeStyle = "#e4e4e4";
+  ctx.fillStyle = "#333";
+  ctx.textAlign = "center";
+  ctx.textBaseline = "top";
+  for (const [t, text] of ticks(view.x[0], view.x[1], width / 90)) {
+    const [x] = toScreen(t, 0);
+    ctx.beginPath(); ctx.moveTo(x, top); ctx.lineTo(x, bottom); ctx.stroke();
+    ctx.fillText(text, x, bottom + 6);
+  }
+  ctx.textAlign = "right";
+  ctx.textBaseline = "middle";
+  for (const [t, text] of ticks(view.y[0], view.y[1], height / 50)) {
+    const [, y] = toScreen(0, t);
+    ctx.beginPath(); ctx.moveTo(left, y); ctx.lineTo(left + width, y); ctx.stroke();
+    ctx.fillText(text, left - 6, y);
+  }
+  ctx.strokeStyle = "#888";
+  ctx.strokeRect(left, top, width, height);
+  ctx.textAlign = "center";
+  ctx.fillText(FIGURE.xLabel, left + width / 2, bottom + 34);
+  ctx.font = "bold 14px sans-serif";
+  ctx.fillText(FIGURE.title, left + width / 2, top / 2);
+  ctx.font = "12px sans-serif";
+  ctx.save();
+  ctx.translate(14, top + height / 2);
+  ctx.rotate(-Math.PI / 2);
+  ctx.fillText(FIGURE.yLabel, 0, 0);
+  ctx.restore();
+
+  ctx.save();
+  ctx.beginPath();
+  ctx.rect(left, top, width, height);
+  ctx.clip();
+  FIGURE.curves.forEach((curve, i) => {
+    if (!shown[i]) return;
+    ctx.strokeStyle = curve.color;
+    ctx.beginPath();
+    curve.points.forEach(([px, py], j) => {
+      const [x, y] = toScreen(px, py);
+      if (j === 0) ctx.moveTo(x, y); else ctx.lineTo(x, y);
+    });
+    ctx.stroke();
+  });
+  ctx.restore();
+}
+
+const legend = document.getElementById("legend");
+FIGURE.curves.forEach((curve, i) => {
+  const entry = document.createElement("div");
+  const swatch = document.createElement("span");
+  swatch.style.background = curve.color;
+  entry.append(swatch, curve.name);
+  entry.onclick = () => {
+    shown[i] = !shown[i];
+    entry.classList.toggle("hidden", !shown[i]);
+    draw();
+  };
+  legend.append(entry);
+});
+
+canvas.addEventListener("wheel", (event) => {
+  event.preventDefault();
+  const factor = Math.exp(event.deltaY * 0.002);
+  const [x, y] = toData(event);
+  if (!event.ctrlKey) view.x = view.x.map((v) => x + (v - x) * factor);
+  if (!event.shiftKey) view.y = view.y.map((v) => y + (v - y) * factor);
+  draw();
+}, { passive: false });
+
+let drag = null;
+canvas.addEventListener("mousedown", (event) => { drag = [event.offsetX, event.offsetY]; });
+window.addEventListener("mouseup", () => { drag = null; });
+canvas.addEventListener("mousemove", (event) => {
+  const [x, y] = toData(event);
+  document.getElementById("readout").textContent = x.toFixed(2) + " Hz, " + y.toPrecision(4);
+  if (!drag) return;
+  const [width, height] = plotSize();
+  const dx = ((event.offsetX - drag[0]) / width) * (view.x[1] - view.x[0]);
+  const dy = ((event.offsetY - drag[1]) / height) * (view.y[1] - view.y[0]);
+  view.x = view.x.map((v) => v - dx);
+  view.y = view.y.map((v) => v + dy);
+  drag = [event.offsetX, event.offsetY];
+  draw();
+});
+canvas.addEventListener("dblclick", reset);
+window.addEventListener("resize", draw);
+if (FIGURE.title) document.title = FIGURE.title;
+reset();
+</script>
+</body>
+</html>
+"##;
+
+/// A spectrum as tab separated `frequency amplitude` rows, for pasting into spreadsheets.
+pub fn spectrum_tsv(freqs: &[f32], amplitudes: &[f32]) -> String {
+    let mut tsv = String::from("frequency\tamplitude\n");
+    for (freq, amp) in freqs.iter().zip(amplitudes) {
+        tsv.push_str(&format!("{}\t{}\n", freq, amp));
+    }
+    tsv
+}
+
+/// Peaks as tab separated `frequency amplitude note` rows.
+pub fn peaks_tsv(peaks: &[Peak]) -> String {
+    let mut tsv = String::from("frequency\tamplitude\tnote\n");
+    for peak in peaks {
+        tsv.push_str(&format!(
+            "{}\t{}\t{}\n",
+            peak.frequency,
+            peak.amplitude,
+            peaks::note_name(peak.frequency)
+        ));
+    }
+    tsv
+}
+
+/// Quotes a CSV field if it contains a separator, quote or newline.
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("plain name.wav"), "plain name.wav");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn npy_headers_describe_the_array_and_align_the_data() {
+        let path =
+            std::env::temp_dir().join(format!("ilena_analysis_npy_{}.npy", std::process::id()));
+        for (shape, expected) in [(vec![2, 3], "(2, 3)"), (vec![6], "(6,)")] {
+            write_npy(&path, &shape, (0..6).map(|n| n as f32)).unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+            let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            let data_start = 10 + header_len;
+            assert_eq!(data_start % 64, 0);
+            let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+            let dict = format!(
+                "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+                expected
+            );
+            assert_eq!(header.trim_end(), dict);
+            assert!(header.ends_with('\n'));
+            let data: Vec<f32> = bytes[data_start..]
+                .chunks(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            assert_eq!(data, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,340 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::sync::Arc;
+
+/// Floating point type the FFT runs in. Results are always returned as `f32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Precision {
+    #[default]
+    F32,
+    /// Slower, but accumulates far less rounding error over very long transforms.
+    F64,
+}
+
+impl Precision {
+    pub const ALL: [Precision; 2] = [Precision::F32, Precision::F64];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::F32 => "f32",
+            Precision::F64 => "f64",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.name() == name)
+            .ok_or_else(|| format!("Unknown precision '{}', expected f32 or f64", name))
+    }
+}
+
+/// Number of bins from 0 Hz up to Nyquist of a real signal of `len` samples.
+pub fn single_sided_len(len: usize) -> usize {
+    if len == 0 {
+        0
+    } else {
+        len / 2 + 1
+    }
+}
+
+/// Frequency of every bin of a spectrum of `len` samples: 0 Hz up to Nyquist as returned by
+/// [`Fft::magnitudes`], or with `two_sided` from −Fs/2 up to the last bin below +Fs/2 as
+/// returned by [`Fft::two_sided_magnitudes`].
+pub fn bin_freqs(len: usize, sample_rate: u32, two_sided: bool) -> Vec<f32> {
+    let resolution = sample_rate as f32 / len as f32;
+    if two_sided {
+        let shift = (len / 2) as f32;
+        (0..len).map(|i| (i as f32 - shift) * resolution).collect()
+    } else {
+        (0..single_sided_len(len))
+            .map(|i| i as f32 * resolution)
+            .collect()
+    }
+}
+
+/// Group delay in seconds at each of `freqs` from the `phases` in radians there, −dφ/dω. The
+/// phase is unwrapped first, taking every step between neighbouring bins as the one within ±π,
+/// so delays beyond half the length of the transform alias. Central differences are used inside
+/// and one-sided ones at the ends.
+pub fn group_delay(freqs: &[f32], phases: &[f32]) -> Vec<f32> {
+    use std::f64::consts::{PI, TAU};
+    let mut unwrapped: Vec<f64> = Vec::with_capacity(phases.len());
+    for &phase in phases {
+        let phase = phase as f64;
+        let next = match unwrapped.last() {
+            Some(&last) => last + (phase - last + PI).rem_euclid(TAU) - PI,
+            None => phase,
+        };
+        unwrapped.push(next);
+    }
+    let len = freqs.len().min(unwrapped.len());
+    (0..len)
+        .map(|i| {
+            let (before, after) = (i.saturating_sub(1), (i + 1).min(len - 1));
+            let df = (freqs[after] - freqs[before]) as f64;
+            if df == 0.0 {
+                return 0.0;
+            }
+            (-(unwrapped[after] - unwrapped[before]) / (TAU * df)) as f32
+        })
+        .collect()
+}
+
+/// Spectrum of an even-length real signal from its single-sided `bins`: every negative
+/// frequency gets the value of its positive mirror image.
+pub fn mirrored(bins: &[f64]) -> Vec<Complex<f64>> {
+    let n = 2 * bins.len().saturating_sub(1);
+    (0..n)
+        .map(|k| Complex::new(if k < bins.len() { bins[k] } else { bins[n - k] }, 0.0))
+        .collect()
+}
+
+/// Relative difference between the energy of `samples` and that of their spectrum, which
+/// Parseval's theorem says are equal: `Σ x² = (1/N) Σ |X_k|²` over all N bins. `magnitudes` are
+/// single-sided as returned by [`Fft::magnitudes`], so every bin except DC and (for even N)
+/// Nyquist also stands for its mirror image.
+pub fn parseval_error(samples: &[f32], magnitudes: &[f32]) -> f64 {
+    let n = samples.len();
+    let time_energy: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+    let spectrum_energy: f64 = magnitudes
+        .iter()
+        .enumerate()
+        .map(|(k, &m)| {
+            let mirrored = k != 0 && !(n.is_multiple_of(2) && k == n / 2);
+            (m as f64).powi(2) * if mirrored { 2.0 } else { 1.0 }
+        })
+        .sum::<f64>()
+        / n.max(1) as f64;
+    if time_energy == 0.0 {
+        spectrum_energy
+    } else {
+        (spectrum_energy - time_energy).abs() / time_energy
+    }
+}
+
+/// Magnitudes of all bins of `samples` from the direct O(n²) DFT in f64, as a reference that
+/// doesn't share any code with [`Fft`].
+pub fn dft_magnitudes(samples: &[f32]) -> Vec<f32> {
+    let n = samples.len();
+    (0..n)
+        .map(|k| {
+            let (mut re, mut im) = (0.0_f64, 0.0_f64);
+            for (t, &sample) in samples.iter().enumerate() {
+                // reduced modulo n first, since k * t / n loses precision as an angle for large k * t
+                let angle = -2.0 * std::f64::consts::PI * ((k * t) % n) as f64 / n as f64;
+                re += sample as f64 * angle.cos();
+                im += sample as f64 * angle.sin();
+            }
+            re.hypot(im) as f32
+        })
+        .collect()
+}
+
+/// Largest difference between [`Fft::magnitudes`] / [`Fft::two_sided_magnitudes`] and
+/// [`dft_magnitudes`] of `samples`, relative to the largest magnitude. Catches mistakes in how
+/// bins are picked from rustfft's buffer, not only rounding.
+pub fn dft_error(samples: &[f32], precision: Precision) -> f64 {
+    let mut reference = dft_magnitudes(samples);
+    let fft = Fft::new(samples.len(), precision);
+    let single_sided = fft.magnitudes(samples);
+    let scale = reference
+        .iter()
+        .fold(0.0_f32, |m, &a| m.max(a))
+        .max(f32::MIN_POSITIVE) as f64;
+    let error = |fft: &[f32], reference: &[f32]| {
+        if fft.len() != reference.len() {
+            return f64::INFINITY;
+        }
+        fft.iter()
+            .zip(reference)
+            .map(|(&a, &b)| (a as f64 - b as f64).abs())
+            .fold(0.0, f64::max)
+            / scale
+    };
+    let single_error = error(&single_sided, &reference[..single_sided_len(samples.len())]);
+    let shift = reference.len() / 2;
+    reference.rotate_right(shift);
+    single_error.max(error(&fft.two_sided_magnitudes(samples), &reference))
+}
+
+/// Largest transform [`Fft::magnitudes`] checks against Parseval's theorem with the
+/// `parseval-check` feature, to keep debug builds usable on long files.
+#[cfg(feature = "parseval-check")]
+const PARSEVAL_CHECK_MAX_LEN: usize = 1 << 16;
+
+/// Forward FFT of a fixed length, planned once for the selected precision.
+pub enum Fft {
+    F32(Arc<dyn rustfft::Fft<f32>>),
+    F64(Arc<dyn rustfft::Fft<f64>>),
+}
+
+impl Fft {
+    pub fn new(len: usize, precision: Precision) -> Self {
+        match precision {
+            Precision::F32 => Fft::F32(FftPlanner::new().plan_fft_forward(len)),
+            Precision::F64 => Fft::F64(FftPlanner::new().plan_fft_forward(len)),
+        }
+    }
+
+    /// Magnitudes of the non-negative frequency bins of `samples`, which must have the planned
+    /// length: `len / 2 + 1` bins, i.e. up to and including Nyquist for even lengths and up to
+    /// the last bin below it for odd ones.
+    pub fn magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        let magnitudes = self.compute_magnitudes(samples);
+        #[cfg(feature = "parseval-check")]
+        if samples.len() <= PARSEVAL_CHECK_MAX_LEN {
+            let error = parseval_error(samples, &magnitudes);
+            debug_assert!(
+                error < 1e-3,
+                "FFT of {} samples violates Parseval by {}",
+                samples.len(),
+                error
+            );
+        }
+        magnitudes
+    }
+
+    /// Magnitudes of all bins of `samples`, rotated like numpy's `fftshift` so they run from the
+    /// most negative frequency up to the highest positive one, see [`bin_freqs`]. For real input
+    /// both halves mirror each other.
+    pub fn two_sided_magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        let mut magnitudes = self.all_magnitudes(samples);
+        let shift = magnitudes.len() / 2;
+        magnitudes.rotate_right(shift);
+        magnitudes
+    }
+
+    /// Phase in radians, from −π to π, of the same bins as [`Self::magnitudes`].
+    pub fn phases(&self, samples: &[f32]) -> Vec<f32> {
+        let mut phases: Vec<f32> = match self {
+            Fft::F32(fft) => {
+                let mut buffer: Vec<_> = samples.iter().map(|&s| Complex::new(s, 0.0)).collect();
+                fft.process(&mut buffer);
+                buffer.iter().map(|c| c.arg()).collect()
+            }
+            Fft::F64(fft) => {
+                let mut buffer: Vec<_> = samples
+                    .iter()
+                    .map(|&s| Complex::new(s as f64, 0.0))
+                    .collect();
+                fft.process(&mut buffer);
+                buffer.iter().map(|c| c.arg() as f32).collect()
+            }
+        };
+        phases.truncate(single_sided_len(samples.len()));
+        phases
+    }
+
+    fn compute_magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        let mut magnitudes = self.all_magnitudes(samples);
+        magnitudes.truncate(single_sided_len(samples.len()));
+        magnitudes
+    }
+
+    fn all_magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        match self {
+            Fft::F32(fft) => {
+                let mut buffer: Vec<_> = samples.iter().map(|&s| Complex::new(s, 0.0)).collect();
+                fft.process(&mut buffer);
+                buffer.iter().map(|c| c.norm()).collect()
+            }
+            Fft::F64(fft) => {
+                let mut buffer: Vec<_> = samples
+                    .iter()
+                    .map(|&s| Complex::new(s as f64, 0.0))
+                    .collect();
+                fft.process(&mut buffer);
+                buffer.iter().map(|c| c.norm() as f32).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_lengths_stop_at_the_last_bin_below_nyquist() {
+        assert_eq!(single_sided_len(4801), 2401);
+        let freqs = bin_freqs(4801, 48_000, false);
+        assert_eq!(freqs.len(), 2401);
+        assert!((freqs[2400] - 23_995.0).abs() < 0.01, "{}", freqs[2400]);
+        // an even length ends on Nyquist itself
+        assert_eq!(bin_freqs(4800, 48_000, false).last(), Some(&24_000.0));
+        assert_eq!(single_sided_len(0), 0);
+    }
+
+    #[test]
+    fn spectra_keep_the_energy_of_the_signal() {
+        let noise = |len| crate::testgen::white_noise(1000.0, len, 7);
+        for (name, samples) in [
+            ("even", noise(4800)),
+            ("odd", noise(4801)),
+            ("DC only", vec![0.5; 1000]),
+        ] {
+            for precision in Precision::ALL {
+                let magnitudes = Fft::new(samples.len(), precision).magnitudes(&samples);
+                let error = parseval_error(&samples, &magnitudes);
+                assert!(
+                    error < 1e-6,
+                    "{} in {}: off by {:e}",
+                    name,
+                    precision.name(),
+                    error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn two_sided_spectra_of_real_input_are_symmetric() {
+        for len in [4800, 4801] {
+            let samples = crate::testgen::white_noise(1000.0, len, 3);
+            let magnitudes = Fft::new(len, Precision::F64).two_sided_magnitudes(&samples);
+            let freqs = bin_freqs(len, 48_000, true);
+            let zero = len / 2;
+            assert_eq!(freqs[zero], 0.0);
+            for k in 1..=(len - 1) / 2 {
+                assert_eq!(freqs[zero + k], -freqs[zero - k]);
+                let (positive, negative) = (magnitudes[zero + k], magnitudes[zero - k]);
+                assert!(
+                    (positive - negative).abs() <= 1e-6 * positive.max(1.0),
+                    "{} bins of {}",
+                    k,
+                    len
+                );
+            }
+            // the positive half is the single-sided spectrum
+            let single_sided = Fft::new(len, Precision::F64).magnitudes(&samples);
+            assert_eq!(
+                magnitudes[zero..zero + (len - 1) / 2 + 1],
+                single_sided[..(len - 1) / 2 + 1]
+            );
+        }
+    }
+
+    #[test]
+    fn fft_matches_the_direct_dft() {
+        // prime, odd and power of two lengths take different rustfft algorithms
+        for len in [1, 2, 7, 97, 257, 15, 255, 1000, 16, 256, 1024] {
+            let tone = crate::testgen::sine(2291.8, 1000.0, 48_000, len);
+            let noise = crate::testgen::white_noise(50.0, len, len as u32);
+            let samples: Vec<f32> = tone
+                .iter()
+                .zip(noise)
+                .map(|(tone, noise)| tone + noise)
+                .collect();
+            for (precision, tolerance) in [(Precision::F32, 1e-4), (Precision::F64, 1e-6)] {
+                let error = dft_error(&samples, precision);
+                assert!(
+                    error <= tolerance,
+                    "{} samples in {}: off by {:e}",
+                    len,
+                    precision.name(),
+                    error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::borrow::Cow;
+use std::f32::consts::PI;
+
+/// Second order IIR section (RBJ audio EQ cookbook), direct form I.
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    pub fn low_pass(cutoff: f32, sample_rate: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let b1 = 1.0 - cos;
+        Self::normalized(b1 / 2.0, b1, b1 / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    pub fn high_pass(cutoff: f32, sample_rate: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let b1 = 1.0 + cos;
+        Self::normalized(
+            b1 / 2.0,
+            -b1,
+            b1 / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// The two stages of the ITU-R BS.1770 K-weighting pre-filter, a high shelf modelling the head
+/// followed by a high-pass. Derived for any sample rate from the analog prototypes (after Brecht
+/// De Man), which reproduces the tabulated 48 kHz coefficients.
+pub fn k_weighting(sample_rate: f32) -> [Biquad; 2] {
+    let (f0, gain_db, q) = (
+        1_681.974_450_955_532,
+        3.999_843_853_973_347,
+        0.707_175_236_955_419_6,
+    );
+    let k = (std::f64::consts::PI * f0 / sample_rate as f64).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let shelf = Biquad::normalized(
+        (vh + vb * k / q + k * k) as f32,
+        (2.0 * (k * k - vh)) as f32,
+        (vh - vb * k / q + k * k) as f32,
+        (1.0 + k / q + k * k) as f32,
+        (2.0 * (k * k - 1.0)) as f32,
+        (1.0 - k / q + k * k) as f32,
+    );
+    let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+    let k = (std::f64::consts::PI * f0 / sample_rate as f64).tan();
+    let high_pass = Biquad::normalized(
+        1.0,
+        -2.0,
+        1.0,
+        (1.0 + k / q + k * k) as f32,
+        (2.0 * (k * k - 1.0)) as f32,
+        (1.0 - k / q + k * k) as f32,
+    );
+    [shelf, high_pass]
+}
+
+/// Q of each biquad section of an `order`-th order Butterworth filter (`order` is rounded up to
+/// an even number).
+fn butterworth_qs(order: usize) -> Vec<f32> {
+    let sections = order.div_ceil(2).max(1);
+    let n = (sections * 2) as f32;
+    (0..sections)
+        .map(|k| 1.0 / (2.0 * (PI * (2 * k + 1) as f32 / (2.0 * n)).cos()))
+        .collect()
+}
+
+/// Butterworth low-pass of the given order, as a cascade of biquads.
+pub fn low_pass(samples: &[f32], sample_rate: u32, cutoff: f32, order: usize) -> Vec<f32> {
+    let mut sections: Vec<Biquad> = butterworth_qs(order)
+        .into_iter()
+        .map(|q| Biquad::low_pass(cutoff, sample_rate as f32, q))
+        .collect();
+    samples
+        .iter()
+        .map(|&x| sections.iter_mut().fold(x, |x, section| section.process(x)))
+        .collect()
+}
+
+/// Optional high-pass and low-pass run over the signal before the FFT, e.g. against rumble or
+/// hiss. Unlike the displayed frequency range this changes the spectrum itself, and with it the
+/// harmonics and the average.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct PreFilter {
+    /// Cutoff of a second order Butterworth high-pass in Hz; `None` disables it.
+    pub high_pass: Option<f32>,
+    /// Cutoff of a second order Butterworth low-pass in Hz; `None` disables it.
+    pub low_pass: Option<f32>,
+}
+
+impl PreFilter {
+    /// Returns the filtered samples, or `samples` themselves with both filters disabled.
+    pub fn apply(self, samples: &[f32], sample_rate: u32) -> Result<Cow<'_, [f32]>, String> {
+        let nyquist = sample_rate as f32 / 2.0;
+        let mut sections = Vec::new();
+        for (name, cutoff, make) in [
+            (
+                "High-pass",
+                self.high_pass,
+                Biquad::high_pass as fn(f32, f32, f32) -> Biquad,
+            ),
+            ("Low-pass", self.low_pass, Biquad::low_pass),
+        ] {
+            let Some(cutoff) = cutoff else {
+                continue;
+            };
+            if cutoff <= 0.0 || cutoff >= nyquist {
+                return Err(format!(
+                    "{} cutoff {} Hz must be between 0 Hz and Nyquist ({} Hz)",
+                    name, cutoff, nyquist
+                ));
+            }
+            sections.push(make(
+                cutoff,
+                sample_rate as f32,
+                std::f32::consts::FRAC_1_SQRT_2,
+            ));
+        }
+        if sections.is_empty() {
+            return Ok(Cow::Borrowed(samples));
+        }
+        Ok(samples
+            .iter()
+            .map(|&x| sections.iter_mut().fold(x, |x, section| section.process(x)))
+            .collect())
+    }
+}
+
+/// Highest polynomial order [`detrend`] fits; higher ones start following the signal itself.
+pub const MAX_DETREND_ORDER: u32 = 5;
+
+/// `samples` minus their least-squares polynomial of `order`, up to [`MAX_DETREND_ORDER`]: 0
+/// removes only DC, 1 a ramp, higher orders slower drift too. Unlike a high-pass this leaves
+/// the start of the signal alone, so there's no settling transient.
+pub fn detrend(samples: &[f32], order: u32) -> Vec<f32> {
+    let n = samples.len();
+    let terms = (order.min(MAX_DETREND_ORDER) as usize + 1).min(n);
+    if terms == 0 {
+        return Vec::new();
+    }
+    // positions scaled to −1..1 keep the normal equations well conditioned
+    let scale = 2.0 / (n - 1).max(1) as f64;
+    let position = |i: usize| i as f64 * scale - 1.0;
+    let mut moments = vec![0.0_f64; 2 * terms - 1];
+    let mut system = vec![vec![0.0_f64; terms + 1]; terms];
+    for (i, &y) in samples.iter().enumerate() {
+        let x = position(i);
+        let mut power = 1.0;
+        for k in 0..moments.len() {
+            moments[k] += power;
+            if k < terms {
+                system[k][terms] += y as f64 * power;
+            }
+            power *= x;
+        }
+    }
+    for (row, equation) in system.iter_mut().enumerate() {
+        equation[..terms].copy_from_slice(&moments[row..row + terms]);
+    }
+    // Gaussian elimination with partial pivoting, then back substitution
+    for col in 0..terms {
+        let pivot =
+            (col..terms).max_by(|&a, &b| system[a][col].abs().total_cmp(&system[b][col].abs()));
+        system.swap(col, pivot.unwrap_or(col));
+        let lead = system[col][col];
+        if lead == 0.0 {
+            continue;
+        }
+        let (upper, lower) = system.split_at_mut(col + 1);
+        for row in lower {
+            let factor = row[col] / lead;
+            for (value, &above) in row[col..].iter_mut().zip(&upper[col][col..]) {
+                *value -= factor * above;
+            }
+        }
+    }
+    let mut coefficients = vec![0.0_f64; terms];
+    for row in (0..terms).rev() {
+        let known: f64 = (row + 1..terms)
+            .map(|k| system[row][k] * coefficients[k])
+            .sum();
+        let lead = system[row][row];
+        coefficients[row] = if lead == 0.0 {
+            0.0
+        } else {
+            (system[row][terms] - known) / lead
+        };
+    }
+    samples
+        .iter()
+        .enumerate()
+        .map(|(i, &y)| {
+            let x = position(i);
+            let trend = coefficients.iter().rev().fold(0.0, |sum, &c| sum * x + c);
+            (y as f64 - trend) as f32
+        })
+        .collect()
+}
+
+/// Integer downsampling with an anti-aliasing low-pass at 90% of the new Nyquist frequency, so
+/// content above it doesn't fold back into the analysed band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Decimation {
+    /// Keep every `factor`-th sample; 1 disables decimation.
+    pub factor: u32,
+    /// Order of the Butterworth anti-aliasing filter.
+    pub filter_order: usize,
+}
+
+impl Default for Decimation {
+    fn default() -> Self {
+        Self {
+            factor: 1,
+            filter_order: 8,
+        }
+    }
+}
+
+impl Decimation {
+    /// Returns the decimated samples and their sample rate.
+    pub fn apply(self, samples: &[f32], sample_rate: u32) -> Result<(Vec<f32>, u32), String> {
+        if self.factor <= 1 {
+            return Ok((samples.to_vec(), sample_rate));
+        }
+        if !sample_rate.is_multiple_of(self.factor) {
+            return Err(format!(
+                "Decimation factor {} doesn't divide the sample rate {} Hz",
+                self.factor, sample_rate
+            ));
+        }
+        let new_rate = sample_rate / self.factor;
+        let cutoff = 0.9 * new_rate as f32 / 2.0;
+        let filtered = low_pass(samples, sample_rate, cutoff, self.filter_order);
+        let decimated = filtered.into_iter().step_by(self.factor as usize).collect();
+        Ok((decimated, new_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testgen;
+    use crate::window::Window;
+
+    #[test]
+    fn decimation_keeps_tones_above_the_new_nyquist_from_aliasing() {
+        // 9 kHz would fold to 3 kHz at the new rate of 12 kHz
+        let samples = testgen::sines(&[(1000.0, 8192.0), (9000.0, 8192.0)], 48_000, 48_000);
+        let decimation = Decimation {
+            factor: 4,
+            ..Default::default()
+        };
+        let (decimated, rate) = decimation.apply(&samples, 48_000).unwrap();
+        assert_eq!((decimated.len(), rate), (12_000, 12_000));
+        let (freqs, amplitudes) = crate::fourier_analysis(
+            &decimated,
+            rate,
+            &crate::AnalysisOptions::default().window(Window::Hann),
+        );
+        let at = |freq: f32| amplitudes[freqs.iter().position(|&f| f == freq).unwrap()];
+        let alias_db = 20.0 * (at(3000.0) / at(1000.0)).log10();
+        assert!(alias_db < -30.0, "alias at {:.1} dB", alias_db);
+    }
+
+    /// Steady-state gain in dB of `filter` for a sine of `freq` at 48 kHz.
+    fn gain_db(filter: PreFilter, freq: f32) -> f32 {
+        let tone = testgen::sine(freq, 1.0, 48_000, 48_000);
+        let filtered = filter.apply(&tone, 48_000).unwrap();
+        // past the settling transient
+        let settled = |samples: &[f32]| crate::metrics::rms(&samples[24_000..]);
+        20.0 * (settled(&filtered) / settled(&tone)).log10()
+    }
+
+    #[test]
+    fn pre_filters_are_3_db_down_at_the_cutoff_and_12_db_an_octave_past_it() {
+        // a second order Butterworth is 1 / (1 + (f / fc)^4) in power, -12.3 dB an octave away
+        let octave_db = -10.0 * 17.0_f32.log10();
+        let low_pass = PreFilter {
+            low_pass: Some(1000.0),
+            ..Default::default()
+        };
+        let high_pass = PreFilter {
+            high_pass: Some(1000.0),
+            ..Default::default()
+        };
+        for (filter, octave) in [(low_pass, 2000.0), (high_pass, 500.0)] {
+            let (at_cutoff, past) = (gain_db(filter, 1000.0), gain_db(filter, octave));
+            assert!(
+                (at_cutoff + 3.01).abs() < 0.1,
+                "{:?}: {} dB at the cutoff",
+                filter,
+                at_cutoff
+            );
+            assert!(
+                (past - octave_db).abs() < 0.2,
+                "{:?}: {} dB an octave away",
+                filter,
+                past
+            );
+        }
+    }
+
+    #[test]
+    fn detrending_a_tone_on_a_ramp() {
+        // the ramp leaks into the low bins; taking it out has to leave the tone as it is alone
+        let tone = testgen::sine(1000.0, 1.0, 48_000, 4800);
+        let ramped: Vec<f32> = tone
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s + i as f32 / 480.0)
+            .collect();
+        let analyse = |samples: &[f32], order| {
+            let options = crate::AnalysisOptions::default().detrend(order);
+            crate::analyze_samples(samples, 48_000, options).unwrap()
+        };
+        let low_energy_db = |plot_data: &crate::PlotData| {
+            let low = plot_data
+                .freqs
+                .iter()
+                .zip(&plot_data.amplitudes)
+                .filter(|(&freq, _)| freq < 200.0);
+            10.0 * low
+                .map(|(_, amp)| amp * amp)
+                .sum::<f32>()
+                .max(1e-30)
+                .log10()
+        };
+        let (alone, drifting) = (analyse(&tone, None), analyse(&ramped, None));
+        let detrended = analyse(&ramped, Some(1));
+        let reduction = low_energy_db(&drifting) - low_energy_db(&detrended);
+        assert!(reduction > 60.0, "{} dB less below 200 Hz", reduction);
+        // 1 kHz is bin 100 of 10 Hz
+        let tone_error = (detrended.amplitudes[100] / alone.amplitudes[100] - 1.0).abs();
+        assert!(tone_error < 1e-3, "tone off by {}", tone_error);
+    }
+}
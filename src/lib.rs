@@ -0,0 +1,1498 @@
+//! Frequency spectrum analysis of WAV files, used by the `ilena_analysis` viewer.
+
+pub mod align;
+pub mod analyzers;
+pub mod bench;
+#[cfg(all(feature = "live", not(target_arch = "wasm32")))]
+pub mod capture;
+pub mod cepstrum;
+pub mod colormap;
+pub mod comb;
+pub mod envelope;
+pub mod export;
+pub mod fft;
+pub mod filter;
+pub mod mask;
+pub mod memory;
+pub mod metadata;
+pub mod metrics;
+pub mod octave;
+pub mod onsets;
+pub mod parallel;
+pub mod peaks;
+pub mod raster;
+pub mod resynthesis;
+pub mod spectrogram;
+pub mod stream;
+pub mod summary;
+pub mod testgen;
+pub mod tones;
+pub mod wav;
+pub mod welch;
+pub mod window;
+
+use fft::{Fft, Precision};
+use filter::{Decimation, PreFilter};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use metrics::LevelStats;
+use spectrogram::Spectrogram;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wav::{Channel, RawFormat, SampleType};
+use web_time::Instant;
+use welch::Welch;
+use window::Window;
+
+/// Decoded samples above this count aren't kept in memory; changing analysis settings re-reads
+/// those files from disk instead.
+pub const MAX_CACHED_SAMPLES: usize = 1 << 26;
+
+/// What a signal too long for a single FFT is analysed with instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum LimitFallback {
+    /// Welch averaging over half-overlapping segments of the maximum size, so all of the signal
+    /// still counts.
+    #[default]
+    Welch,
+    /// Only the start of the signal, as much as fits.
+    Truncate,
+}
+
+impl LimitFallback {
+    pub const ALL: [LimitFallback; 2] = [LimitFallback::Welch, LimitFallback::Truncate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LimitFallback::Welch => "Welch",
+            LimitFallback::Truncate => "Truncate",
+        }
+    }
+}
+
+/// Bound on the samples fed to one FFT, which needs several times their size in memory for its
+/// plan, buffers and spectrum, so that an accidentally huge input can't exhaust it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SampleLimit {
+    /// In samples after decimation.
+    pub max_fft_size: usize,
+    pub fallback: LimitFallback,
+}
+
+impl Default for SampleLimit {
+    fn default() -> Self {
+        Self {
+            // about 87 s at 48 kHz
+            max_fft_size: 1 << 22,
+            fallback: LimitFallback::default(),
+        }
+    }
+}
+
+/// How samples are turned into a spectrum. Serializable so presets can store it; settings missing
+/// from older presets take their defaults. Built with struct syntax or by chaining on
+/// `AnalysisOptions::default()`, e.g. `.window(Window::Hann).precision(Precision::F64)`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AnalysisOptions {
+    pub window: Window,
+    /// Welch averaging; `None` for a single FFT over the whole signal.
+    pub welch: Option<Welch>,
+    pub decimation: Decimation,
+    /// Filters run before decimating.
+    pub pre_filter: PreFilter,
+    /// Order of the polynomial trend removed before the pre-filter, see [`filter::detrend`];
+    /// `None` keeps the signal as it is.
+    pub detrend: Option<u32>,
+    pub precision: Precision,
+    /// Leave magnitudes attenuated by the window instead of dividing by its coherent gain, which
+    /// makes every window read the same level as rectangular for a tone centered on a bin.
+    pub raw_amplitudes: bool,
+    /// Keep the negative frequencies too, see [`fourier_analysis`].
+    pub two_sided: bool,
+    /// Truncate or zero-pad the signal to this many samples before anything else, so files of
+    /// different lengths and equal sample rates get the same bins. Not serialized, since it's
+    /// usually derived from the files loaded.
+    #[serde(skip)]
+    pub length: Option<usize>,
+    /// Zero-pad signals whose FFT would be shorter than this, so very short files get roughly
+    /// the resolution of the rest instead of a handful of bins. Has no effect with Welch
+    /// averaging, whose FFTs are always a segment long.
+    pub min_fft_size: Option<usize>,
+    /// Leading milliseconds of every signal left out, e.g. silence or a count-in, after its own
+    /// start offset. See [`Self::trimmed_range`].
+    pub skip_ms: f32,
+    /// Only this many milliseconds after `skip_ms` are analysed; `None` for the rest.
+    pub duration_ms: Option<f32>,
+    /// Signals whose single FFT would be longer fall back to Welch averaging or truncation, see
+    /// [`Self::limited`].
+    pub sample_limit: SampleLimit,
+}
+
+impl AnalysisOptions {
+    pub fn window(mut self, window: Window) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn welch(mut self, welch: Option<Welch>) -> Self {
+        self.welch = welch;
+        self
+    }
+
+    pub fn decimation(mut self, decimation: Decimation) -> Self {
+        self.decimation = decimation;
+        self
+    }
+
+    pub fn pre_filter(mut self, pre_filter: PreFilter) -> Self {
+        self.pre_filter = pre_filter;
+        self
+    }
+
+    pub fn detrend(mut self, detrend: Option<u32>) -> Self {
+        self.detrend = detrend;
+        self
+    }
+
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn raw_amplitudes(mut self, raw_amplitudes: bool) -> Self {
+        self.raw_amplitudes = raw_amplitudes;
+        self
+    }
+
+    pub fn two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    pub fn length(mut self, length: Option<usize>) -> Self {
+        self.length = length;
+        self
+    }
+
+    pub fn min_fft_size(mut self, min_fft_size: Option<usize>) -> Self {
+        self.min_fft_size = min_fft_size;
+        self
+    }
+
+    pub fn skip_ms(mut self, skip_ms: f32) -> Self {
+        self.skip_ms = skip_ms;
+        self
+    }
+
+    pub fn duration_ms(mut self, duration_ms: Option<f32>) -> Self {
+        self.duration_ms = duration_ms;
+        self
+    }
+
+    pub fn sample_limit(mut self, sample_limit: SampleLimit) -> Self {
+        self.sample_limit = sample_limit;
+        self
+    }
+
+    /// The options a signal of `len` samples, after [`Self::trimmed_range`], is actually analysed
+    /// with: these, unless a single FFT would be longer than the [`SampleLimit`] allows. Then
+    /// Welch averaging over segments of the maximum size, or only the first samples that fit.
+    pub fn limited(self, len: usize) -> Self {
+        let max = self.sample_limit.max_fft_size.max(1);
+        if self.welch.is_some() || self.unlimited_fft_size(len) <= max {
+            return self;
+        }
+        match self.sample_limit.fallback {
+            LimitFallback::Welch => self.welch(Some(Welch {
+                segment_len: max,
+                hop: (max / 2).max(1),
+            })),
+            LimitFallback::Truncate => self
+                .length(Some(max * self.decimation.factor.max(1) as usize))
+                .min_fft_size(None),
+        }
+    }
+
+    /// Samples of a signal of `len` samples analysed with [`Self::skip_ms`] and
+    /// [`Self::duration_ms`]. A signal no longer than the skip is analysed whole rather than not
+    /// at all.
+    pub fn trimmed_range(&self, len: usize, sample_rate: u32) -> Range<usize> {
+        let to_samples = |ms: f32| (ms.max(0.0) / 1000.0 * sample_rate as f32).round() as usize;
+        let skip = to_samples(self.skip_ms);
+        let start = if skip < len { skip } else { 0 };
+        let end = self
+            .duration_ms
+            .map_or(len, |ms| (start + to_samples(ms).max(1)).min(len));
+        start..end
+    }
+
+    /// Samples a signal is zero-padded to for [`Self::min_fft_size`], before decimation. 0
+    /// without a minimum.
+    pub fn min_input_len(&self) -> usize {
+        match (self.welch, self.min_fft_size) {
+            (None, Some(size)) => size * self.decimation.factor.max(1) as usize,
+            _ => 0,
+        }
+    }
+
+    /// Length of the FFT(s) run on a signal of `len` samples, after [`Self::trimmed_range`].
+    pub fn fft_size(&self, len: usize) -> usize {
+        self.limited(len).unlimited_fft_size(len)
+    }
+
+    fn unlimited_fft_size(&self, len: usize) -> usize {
+        match self.welch {
+            Some(welch) => welch.segment_len,
+            None => {
+                let len = self.length.unwrap_or(len).max(self.min_input_len());
+                len.div_ceil(self.decimation.factor.max(1) as usize)
+            }
+        }
+    }
+
+    /// Samples each FFT windows of a signal of `len` samples, after [`Self::trimmed_range`]: a
+    /// Welch segment, or the decimated signal without the silence padding it to [`Self::fft_size`].
+    pub fn windowed_len(&self, len: usize) -> usize {
+        let options = self.limited(len);
+        match options.welch {
+            Some(welch) => welch.segment_len,
+            None => {
+                let len = options.length.unwrap_or(len).min(len);
+                len.div_ceil(options.decimation.factor.max(1) as usize)
+            }
+        }
+    }
+
+    /// Trims and cuts to `length`, then detrends and runs the pre-filter and decimation, returning
+    /// the samples to transform and their rate. Nothing is padded yet, see [`Self::padded_len`].
+    fn prepare(&self, samples: &[f32], sample_rate: u32) -> Result<(Vec<f32>, u32), String> {
+        let trimmed = &samples[self.trimmed_range(samples.len(), sample_rate)];
+        let trimmed = &trimmed[..self.length.unwrap_or(trimmed.len()).min(trimmed.len())];
+        let detrended = match self.detrend {
+            Some(order) => Cow::Owned(filter::detrend(trimmed, order)),
+            None => Cow::Borrowed(trimmed),
+        };
+        let filtered = self.pre_filter.apply(&detrended, sample_rate)?;
+        self.decimation.apply(&filtered, sample_rate)
+    }
+
+    /// Length `prepared_len` samples from [`Self::prepare`] are padded to with silence: to `length`,
+    /// if set, and further up to [`Self::min_fft_size`], both counted after decimation. A single
+    /// FFT pads after windowing, so the window spans only the real samples and the level doesn't
+    /// depend on the padding. Welch segments have the same bins whatever the length, and silent
+    /// ones would only lower the average, so Welch doesn't pad.
+    fn padded_len(&self, prepared_len: usize) -> usize {
+        if self.welch.is_some() {
+            return prepared_len;
+        }
+        let length = self.length.map_or(0, |length| {
+            length.div_ceil(self.decimation.factor.max(1) as usize)
+        });
+        length.max(self.min_fft_size.unwrap_or(0)).max(prepared_len)
+    }
+
+    /// Amplitude of the sinusoid producing a peak of `magnitude` in FFTs of `windowed_len` real
+    /// samples, see [`Self::windowed_len`] and [`Window::sine_amplitude`]. Zero-padding doesn't
+    /// add to the peak, so it doesn't count.
+    pub fn sine_amplitude(&self, magnitude: f32, windowed_len: usize) -> f32 {
+        if self.raw_amplitudes {
+            self.window.sine_amplitude(magnitude, windowed_len)
+        } else {
+            // already divided by the coherent gain
+            Window::Rectangular.sine_amplitude(magnitude, windowed_len)
+        }
+    }
+}
+
+/// Where the time spent on a file went, to find out why some are slow.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    /// Reading and decoding the file.
+    pub load: Duration,
+    /// Decimation and FFT(s) of the latest analysis.
+    pub fft: Duration,
+    /// Samples analysed, after the start offset and [`AnalysisOptions::trimmed_range`].
+    pub sample_count: usize,
+    pub fft_size: usize,
+}
+
+/// Whether `path` is a spectrum saved by [`write_f`]: `.f`, or `.f.gz` for a compressed one.
+pub fn is_spectrum_file(path: &Path) -> bool {
+    wav::has_extension(path, "f") || is_compressed_spectrum(path)
+}
+
+/// Compression is told by the extension alone: the gzip magic bytes are also a valid start of a
+/// plain `.f`, the little-endian bin count 35615.
+fn is_compressed_spectrum(path: &Path) -> bool {
+    let stem_is_f = path
+        .file_stem()
+        .is_some_and(|stem| wav::has_extension(Path::new(stem), "f"));
+    wav::has_extension(path, "gz") && stem_is_f
+}
+
+/// Start of a `.f` with a header, followed by [`SPECTRUM_VERSION`] and the width of its floats in
+/// bytes. Files from before the header hold `f32` and start with their bin count instead, which
+/// would have to be over a billion to read as these bytes.
+const SPECTRUM_MAGIC: [u8; 4] = *b"ILNF";
+
+/// Layout of a `.f` after its magic bytes.
+const SPECTRUM_VERSION: u8 = 1;
+
+/// Reads a spectrum saved by [`write_f`] with floats of either width, or by a version from before
+/// the header.
+pub fn read_f(file_path: &Path) -> Result<(Vec<f32>, Vec<f32>), String> {
+    let file = BufReader::new(File::open(file_path).map_err(|e| e.to_string())?);
+    if is_compressed_spectrum(file_path) {
+        read_spectrum(GzDecoder::new(file))
+    } else {
+        read_spectrum(file)
+    }
+}
+
+fn read_spectrum(mut reader: impl Read) -> Result<(Vec<f32>, Vec<f32>), String> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).map_err(|e| e.to_string())?;
+    if magic != SPECTRUM_MAGIC {
+        let plot_data: PlotData =
+            bincode::deserialize_from(magic.as_slice().chain(reader)).map_err(|e| e.to_string())?;
+        return Ok((plot_data.freqs, plot_data.amplitudes));
+    }
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).map_err(|e| e.to_string())?;
+    let [version, width] = header;
+    if version != SPECTRUM_VERSION {
+        return Err(format!(
+            "Spectrum format version {} is newer than this build reads",
+            version
+        ));
+    }
+    match width {
+        4 => bincode::deserialize_from(reader).map_err(|e| e.to_string()),
+        8 => {
+            let (freqs, amplitudes): (Vec<f64>, Vec<f64>) =
+                bincode::deserialize_from(reader).map_err(|e| e.to_string())?;
+            let narrow = |values: Vec<f64>| values.into_iter().map(|v| v as f32).collect();
+            Ok((narrow(freqs), narrow(amplitudes)))
+        }
+        _ => Err(format!(
+            "Spectrum has floats of {} bytes, only 4 and 8 are read",
+            width
+        )),
+    }
+}
+
+/// Saves the spectrum of `plot_data` for [`read_f`], gzip-compressed if `file_path` ends in
+/// `.f.gz`.
+pub fn write_f(file_path: &Path, plot_data: &PlotData) -> Result<(), String> {
+    let file = BufWriter::new(File::create(file_path).map_err(|e| e.to_string())?);
+    let result = if is_compressed_spectrum(file_path) {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        write_spectrum(&mut encoder, plot_data, 4).and_then(|_| Ok(encoder.finish()?.flush()?))
+    } else {
+        let mut file = file;
+        write_spectrum(&mut file, plot_data, 4).and_then(|_| Ok(file.flush()?))
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// Writes the header and the frequencies and amplitudes as floats of `width` bytes, 4 or 8.
+fn write_spectrum(writer: &mut impl Write, plot_data: &PlotData, width: u8) -> bincode::Result<()> {
+    writer.write_all(&SPECTRUM_MAGIC)?;
+    writer.write_all(&[SPECTRUM_VERSION, width])?;
+    let (freqs, amplitudes) = (&plot_data.freqs, &plot_data.amplitudes);
+    if width == 8 {
+        let widen = |values: &[f32]| values.iter().map(|&v| v as f64).collect::<Vec<f64>>();
+        bincode::serialize_into(writer, &(widen(freqs), widen(amplitudes)))
+    } else {
+        bincode::serialize_into(writer, &(freqs, amplitudes))
+    }
+}
+
+/// Level of `amp` relative to `reference` in dB. Silent bins are floored so they don't map to ±∞.
+pub fn db_difference(amp: f32, reference: f32) -> f32 {
+    const FLOOR: f32 = 1e-9;
+    20.0 * (amp.max(FLOOR) / reference.max(FLOOR)).log10()
+}
+
+/// Single FFT of samples that are already trimmed, filtered and decimated, with the window and
+/// precision of `options`. Spectrum from 0 Hz up to Nyquist, or with `two_sided` the full spectrum
+/// from −Fs/2 to +Fs/2, which for real signals only mirrors the positive half. The windowed
+/// samples are zero-padded to `length` and `min_fft_size` if shorter; Welch averaging is left to
+/// [`spectrum`].
+pub fn fourier_analysis(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &AnalysisOptions,
+) -> (Vec<f32>, Vec<f32>) {
+    let mut windowed = options.window.apply(samples);
+    windowed.resize(options.padded_len(samples.len()), 0.0);
+    let fft = Fft::new(windowed.len(), options.precision);
+    let two_sided = options.two_sided;
+    let amplitudes = if two_sided {
+        fft.two_sided_magnitudes(&windowed)
+    } else {
+        fft.magnitudes(&windowed)
+    };
+    (
+        fft::bin_freqs(windowed.len(), sample_rate, two_sided),
+        amplitudes,
+    )
+}
+
+/// Single FFT over the whole signal, or Welch averaging when `welch` is set, after filtering and
+/// decimating. Unless `raw_amplitudes` is set, magnitudes are corrected for the window's coherent gain.
+pub fn spectrum(
+    samples: &[f32],
+    sample_rate: u32,
+    options: AnalysisOptions,
+) -> Result<(Vec<f32>, Vec<f32>), String> {
+    let len = options.trimmed_range(samples.len(), sample_rate).len();
+    let options = options.limited(len);
+    let (samples, sample_rate) = options.prepare(samples, sample_rate)?;
+    let (freqs, mut amplitudes) = match options.welch {
+        Some(welch) => welch::welch_analysis(
+            &samples,
+            sample_rate,
+            options.window,
+            welch,
+            options.precision,
+            options.two_sided,
+        )?,
+        None => fourier_analysis(&samples, sample_rate, &options),
+    };
+    if !options.raw_amplitudes {
+        let windowed_len = options
+            .welch
+            .map_or(samples.len(), |welch| welch.segment_len);
+        let gain = options.window.coherent_gain(windowed_len);
+        amplitudes.iter_mut().for_each(|amp| *amp /= gain);
+    }
+    Ok((freqs, amplitudes))
+}
+
+/// The exact samples [`spectrum`] transforms: filtered, decimated and windowed. With Welch averaging each
+/// segment is windowed on its own and the segments are placed back to back.
+pub fn fft_input(
+    samples: &[f32],
+    sample_rate: u32,
+    options: AnalysisOptions,
+) -> Result<(Vec<f32>, u32), String> {
+    let len = options.trimmed_range(samples.len(), sample_rate).len();
+    let options = options.limited(len);
+    let (samples, sample_rate) = options.prepare(samples, sample_rate)?;
+    let windowed = match options.welch {
+        Some(welch) => welch
+            .segments(samples.len())?
+            .into_iter()
+            .flat_map(|segment| options.window.apply(&samples[segment]))
+            .collect(),
+        None => {
+            let mut windowed = options.window.apply(&samples);
+            windowed.resize(options.padded_len(samples.len()), 0.0);
+            windowed
+        }
+    };
+    Ok((windowed, sample_rate))
+}
+
+/// Spectrum and level statistics of samples that are already in memory, e.g. from another
+/// decoder. Samples are expected in the i16 range like those from [`wav::read_wav`]; the result
+/// has no file name and doesn't keep the samples.
+pub fn analyze_samples(
+    samples: &[f32],
+    sample_rate: u32,
+    options: AnalysisOptions,
+) -> Result<PlotData, String> {
+    let t0 = Instant::now();
+    let (freqs, amplitudes) = spectrum(samples, sample_rate, options)?;
+    let analysed = options.trimmed_range(samples.len(), sample_rate).len();
+    let timings = Timings {
+        fft: t0.elapsed(),
+        sample_count: analysed,
+        fft_size: options.fft_size(analysed),
+        ..Default::default()
+    };
+    Ok(PlotData {
+        freqs,
+        amplitudes,
+        level_stats: metrics::level_stats(samples, sample_rate),
+        sample_rate: Some(sample_rate),
+        decoded_len: samples.len(),
+        timings,
+        ..Default::default()
+    })
+}
+
+/// Analyses a whole WAV file already in memory, e.g. piped to stdin or dropped into a browser,
+/// without touching the file system. Its samples are always kept since they can't be read again.
+pub fn load_wav_bytes(
+    bytes: &[u8],
+    name: &str,
+    options: AnalysisOptions,
+    sample_type: Option<SampleType>,
+) -> Result<PlotData, String> {
+    let t0 = Instant::now();
+    let (samples, sample_rate) =
+        wav::read_wav_bytes(bytes, name, Channel::Interleaved, sample_type)?;
+    let load = t0.elapsed();
+    let mut plot_data = analyze_samples(&samples, sample_rate, options)?;
+    plot_data.file_name = name.to_string();
+    plot_data.timings.load = load;
+    plot_data.samples = Some(samples);
+    Ok(plot_data)
+}
+
+pub fn load_wav(
+    file_path: &Path,
+    options: AnalysisOptions,
+    sample_type: Option<SampleType>,
+) -> Result<PlotData, String> {
+    let mut plots = load_wav_channels(file_path, options, sample_type, &[Channel::Interleaved])?;
+    Ok(plots.remove(0))
+}
+
+/// One plot per channel of `channels`, reading the file once. Plots of anything but
+/// [`Channel::Interleaved`] are named after the channel, e.g. `"take1.wav (mid)"`.
+pub fn load_wav_channels(
+    file_path: &Path,
+    options: AnalysisOptions,
+    sample_type: Option<SampleType>,
+    channels: &[Channel],
+) -> Result<Vec<PlotData>, String> {
+    let t0 = Instant::now();
+    let decoded = wav::read_wav_channels(file_path, sample_type)?;
+    analyze_channels(file_path, decoded, t0.elapsed(), options, channels)
+}
+
+/// Like [`load_wav_channels`] for a headerless PCM file laid out as `format`.
+pub fn load_raw_channels(
+    file_path: &Path,
+    options: AnalysisOptions,
+    format: RawFormat,
+    channels: &[Channel],
+) -> Result<Vec<PlotData>, String> {
+    let t0 = Instant::now();
+    let decoded = wav::read_raw(file_path, format)?;
+    let mut plots = analyze_channels(file_path, decoded, t0.elapsed(), options, channels)?;
+    for plot_data in &mut plots {
+        plot_data.raw_format = Some(format);
+    }
+    Ok(plots)
+}
+
+/// One plot per channel of the interleaved samples decoded from `file_path` in `load`.
+fn analyze_channels(
+    file_path: &Path,
+    (interleaved, sample_rate, channel_count): (Vec<f32>, u32, u16),
+    load: Duration,
+    options: AnalysisOptions,
+    channels: &[Channel],
+) -> Result<Vec<PlotData>, String> {
+    channels
+        .iter()
+        .map(|&channel| {
+            let samples = channel.extract(&interleaved, channel_count)?;
+            let mut plot_data = analyze_samples(&samples, sample_rate, options)?;
+            plot_data.file_name = if channel == Channel::Interleaved {
+                file_path.display().to_string()
+            } else {
+                format!("{} ({})", file_path.display(), channel.name())
+            };
+            plot_data.path = Some(file_path.to_path_buf());
+            plot_data.channel = channel;
+            plot_data.timings.load = load;
+            plot_data.samples = (samples.len() <= MAX_CACHED_SAMPLES).then_some(samples);
+            Ok(plot_data)
+        })
+        .collect()
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct PlotData {
+    pub freqs: Vec<f32>,
+    pub amplitudes: Vec<f32>,
+    pub file_name: String,
+    /// Only available for files decoded from audio; `.f` files don't carry the samples.
+    #[serde(skip)]
+    pub level_stats: Option<LevelStats>,
+    /// Decoded samples, kept so analysis settings can change without reading the file again.
+    /// `None` for `.f` files and for files larger than [`MAX_CACHED_SAMPLES`].
+    #[serde(skip)]
+    pub samples: Option<Vec<f32>>,
+    /// `None` for spectra loaded from `.f` files, which can't be re-analysed.
+    #[serde(skip)]
+    pub sample_rate: Option<u32>,
+    /// Rate the file header declares, if `sample_rate` was overridden because it's wrong.
+    #[serde(skip)]
+    pub header_sample_rate: Option<u32>,
+    /// Factor the frequencies are scaled by on top of the sample rate, e.g. to line up the
+    /// fundamental with another file's, see [`Self::set_pitch_scale`].
+    #[serde(skip)]
+    pub pitch_scale: f32,
+    /// Alpha the line is drawn with, from 0 to 1, to fade reference or outlier curves.
+    #[serde(skip)]
+    pub opacity: f32,
+    /// Samples per channel as decoded, before the start offset.
+    #[serde(skip)]
+    pub decoded_len: usize,
+    /// Samples skipped at the start before analysis, to line up recordings with different
+    /// pre-roll.
+    #[serde(skip)]
+    pub offset: usize,
+    #[serde(skip)]
+    pub timings: Timings,
+    /// Command line argument the file was found through, to group files from different folders.
+    #[serde(skip)]
+    pub source: String,
+    /// File the samples are read from. `file_name` is only for display: it's lossy for paths
+    /// that aren't UTF-8 and names the channel for single channels.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    #[serde(skip)]
+    pub channel: Channel,
+    /// How the samples are laid out if the file is headerless PCM rather than WAV.
+    #[serde(skip)]
+    pub raw_format: Option<RawFormat>,
+    /// Window this file is analysed with instead of the one the options give, e.g. a
+    /// rectangular one for a single transient in a batch of steady tones.
+    #[serde(skip)]
+    pub window: Option<Window>,
+    /// Short name given by the user, e.g. "Mic A, 1m", see [`Self::label`].
+    #[serde(skip)]
+    pub label: Option<String>,
+}
+
+impl PlotData {
+    /// A frequency and an amplitude.
+    pub const BYTES_PER_BIN: usize = 2 * std::mem::size_of::<f32>();
+
+    /// Approximate heap usage of the spectrum and cached samples.
+    pub fn memory_bytes(&self) -> usize {
+        self.freqs.len().min(self.amplitudes.len()) * Self::BYTES_PER_BIN
+            + self
+                .samples
+                .as_ref()
+                .map_or(0, |samples| samples.len() * std::mem::size_of::<f32>())
+    }
+
+    /// Reduces the spectrum to at most `max_bins` bins, keeping the loudest bin of each group so
+    /// peaks survive. Returns whether anything changed.
+    pub fn downsample(&mut self, max_bins: usize) -> bool {
+        let len = self.freqs.len().min(self.amplitudes.len());
+        if len <= max_bins || max_bins == 0 {
+            return false;
+        }
+        let group = len.div_ceil(max_bins);
+        let (freqs, amplitudes) = (0..len)
+            .step_by(group)
+            .map(|start| {
+                let end = (start + group).min(len);
+                let loudest = (start..end)
+                    .max_by(|&a, &b| self.amplitudes[a].total_cmp(&self.amplitudes[b]))
+                    .unwrap_or(start);
+                (self.freqs[loudest], self.amplitudes[loudest])
+            })
+            .unzip();
+        self.freqs = freqs;
+        self.amplitudes = amplitudes;
+        true
+    }
+
+    /// Linearly interpolated amplitude at each frequency of the ascending `grid`, `None` outside
+    /// the analysed range.
+    pub fn resample(&self, grid: &[f32]) -> Vec<Option<f32>> {
+        let (freqs, amps) = (&self.freqs, &self.amplitudes);
+        let mut j = 0;
+        grid.iter()
+            .map(|&freq| {
+                // stop on an exact bin, interpolating to it from below would lose small values
+                while j + 1 < freqs.len() && freqs[j + 1] <= freq {
+                    j += 1;
+                }
+                let (&f0, &last) = (freqs.first()?, freqs.last()?);
+                if freq < f0 || freq > last {
+                    return None;
+                }
+                if freq <= freqs[j] || j + 1 == freqs.len() {
+                    return Some(amps[j]);
+                }
+                let t = (freq - freqs[j]) / (freqs[j + 1] - freqs[j]);
+                Some(amps[j] + t * (amps[j + 1] - amps[j]))
+            })
+            .collect()
+    }
+
+    /// Linearly interpolated amplitude at `freq` between the two bins around it, 0 outside the
+    /// analysed range. Use [`Self::resample`] for many frequencies at once.
+    pub fn amplitude_at(&self, freq: f32) -> f32 {
+        let j = self.freqs.partition_point(|&f| f < freq);
+        let (Some(&f1), Some(&a1)) = (self.freqs.get(j), self.amplitudes.get(j)) else {
+            return 0.0;
+        };
+        if f1 == freq {
+            return a1;
+        }
+        if j == 0 {
+            return 0.0;
+        }
+        let (f0, a0) = (self.freqs[j - 1], self.amplitudes[j - 1]);
+        a0 + (freq - f0) / (f1 - f0) * (a1 - a0)
+    }
+
+    /// Decoded samples from the cache, or read again if they weren't cached. `None` for `.f`
+    /// spectra.
+    pub fn decoded_samples(
+        &self,
+        sample_type: Option<SampleType>,
+    ) -> Result<Option<Cow<'_, [f32]>>, String> {
+        if self.sample_rate.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(match &self.samples {
+            Some(samples) => Cow::Borrowed(samples.as_slice()),
+            None => {
+                let (samples, _, channels) = match self.raw_format {
+                    Some(format) => wav::read_raw(self.audio_path(), format)?,
+                    None => wav::read_wav_channels(self.audio_path(), sample_type)?,
+                };
+                Cow::Owned(self.channel.extract(&samples, channels)?)
+            }
+        }))
+    }
+
+    /// Treats the samples as recorded at `rate`, or at the header's rate again for `None`. The
+    /// bins don't depend on the rate, so the frequencies are only rescaled; a pre-filter still
+    /// needs a [`Self::reanalyze`] since its cutoffs are in Hz.
+    pub fn override_sample_rate(&mut self, rate: Option<u32>) {
+        let Some(current) = self.sample_rate else {
+            return;
+        };
+        let header = self.header_sample_rate.unwrap_or(current);
+        let new = rate.unwrap_or(header);
+        self.header_sample_rate = rate.map(|_| header);
+        let scale = new as f32 / current as f32;
+        self.freqs.iter_mut().for_each(|freq| *freq *= scale);
+        self.sample_rate = Some(new);
+    }
+
+    /// Scales the frequencies by `scale` instead of the current [`Self::pitch_scale`]. Kept across
+    /// [`Self::reanalyze`] and [`Self::reload`].
+    pub fn set_pitch_scale(&mut self, scale: f32) {
+        let change = scale / self.pitch_scale;
+        self.freqs.iter_mut().for_each(|freq| *freq *= change);
+        self.pitch_scale = scale;
+    }
+
+    /// Real samples each FFT of the latest analysis with `options` windowed, see
+    /// [`AnalysisOptions::windowed_len`]. Spectra that weren't analysed here, like `.f` files and
+    /// restored sessions, are taken as not zero-padded.
+    pub fn windowed_len(&self, options: AnalysisOptions) -> usize {
+        match self.timings.sample_count {
+            0 => self.timings.fft_size,
+            len => self.options(options).windowed_len(len),
+        }
+    }
+
+    /// The audio file behind this plot.
+    pub fn audio_path(&self) -> &Path {
+        self.path.as_deref().unwrap_or(Path::new(&self.file_name))
+    }
+
+    /// Samples left after the start offset, i.e. analysed unless [`AnalysisOptions::length`] is set.
+    pub fn analysed_len(&self) -> usize {
+        self.decoded_len.saturating_sub(self.offset)
+    }
+
+    /// Name shown in the legend, readouts and exports: the custom label, else the stem of the
+    /// file with the channel if it's a single one. Spectra without a file, like the average, keep
+    /// their `file_name`. Plots are still told apart by `file_name`, which labels needn't be.
+    pub fn label(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        let Some(stem) = self.path.as_deref().and_then(Path::file_stem) else {
+            return self.file_name.clone();
+        };
+        // both extensions of a compressed spectrum
+        let compressed = wav::has_extension(Path::new(stem), "f");
+        let stem = Path::new(stem)
+            .file_stem()
+            .filter(|_| compressed)
+            .unwrap_or(stem);
+        let stem = stem.to_string_lossy();
+        match self.channel {
+            Channel::Interleaved => stem.into_owned(),
+            channel => format!("{} ({})", stem, channel.name()),
+        }
+    }
+
+    /// Why this file isn't transformed whole by a single FFT with `options`, if the
+    /// [`SampleLimit`] makes it fall back.
+    pub fn limit_warning(&self, options: AnalysisOptions) -> Option<String> {
+        let options = self.options(options);
+        let len = options
+            .trimmed_range(self.analysed_len(), self.sample_rate?)
+            .len();
+        if options.limited(len) == options {
+            return None;
+        }
+        let max = options.sample_limit.max_fft_size;
+        Some(match options.sample_limit.fallback {
+            LimitFallback::Welch => format!(
+                "{} is too long for one FFT of at most {} samples, Welch-averaged over segments of {}",
+                self.file_name,
+                max,
+                options.fft_size(len)
+            ),
+            LimitFallback::Truncate => format!(
+                "{} is too long for one FFT of at most {} samples, only its first {} samples are analysed",
+                self.file_name,
+                max,
+                options.limited(len).length.unwrap_or(len)
+            ),
+        })
+    }
+
+    /// Whether the loudest bin, as the amplitude of a sine, or the loudest sample is implausibly
+    /// loud for the input format. Every integer format is decoded into the i16 range, so within
+    /// full scale nothing gets louder than a square wave's fundamental of 4/π of full scale, or
+    /// twice full scale at DC; louder means the samples were decoded or normalized wrong, or a
+    /// float file goes over. Within 0.5 dB of full scale, or samples within 0.1 dB of it, the
+    /// file is likely clipped instead. `None` for `.f` spectra, whose scale isn't known.
+    pub fn overload_warning(&self, options: AnalysisOptions) -> Option<String> {
+        const FULL_SCALE: f32 = 32_768.0;
+        self.sample_rate?;
+        let options = self.options(options);
+        let windowed_len = self.windowed_len(options);
+        let sine = |amp: f32| options.sine_amplitude(amp.abs(), windowed_len) / FULL_SCALE;
+        let (freq, amp) = self
+            .freqs
+            .iter()
+            .zip(&self.amplitudes)
+            .map(|(&freq, &amp)| (freq, sine(amp)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let bin_db = 20.0 * amp.log10();
+        let limit = if freq == 0.0 {
+            2.0
+        } else {
+            4.0 / std::f32::consts::PI
+        };
+        let sample_db = self
+            .level_stats
+            .map(|stats| 20.0 * (stats.peak / FULL_SCALE).log10());
+        // a little tolerance for rounding, a full-scale square wave is still fine
+        if bin_db > 20.0 * limit.log10() + 0.05 || sample_db.is_some_and(|db| db > 0.05) {
+            let samples = sample_db.map_or(String::new(), |db| {
+                format!(", samples peak at {:+.1} dBFS", db)
+            });
+            return Some(format!(
+                "{}: the bin at {:.1} Hz is {:+.1} dB from a full-scale sine, louder than any signal within \
+                 full scale{}; check the sample type and any normalization",
+                self.file_name, freq, bin_db, samples
+            ));
+        }
+        if bin_db > -0.5 || sample_db.is_some_and(|db| db > -0.1) {
+            let peak = sample_db.map_or(String::new(), |db| {
+                format!(", samples peak at {:+.2} dBFS", db)
+            });
+            return Some(format!(
+                "{} has no headroom left, likely clipped: the bin at {:.1} Hz is {:+.1} dB from a full-scale \
+                 sine{}",
+                self.file_name, freq, bin_db, peak
+            ));
+        }
+        None
+    }
+
+    /// The part of `samples` after the start offset.
+    fn after_offset<'a>(&self, samples: &'a [f32]) -> &'a [f32] {
+        &samples[self.offset.min(samples.len())..]
+    }
+
+    /// `options` with this file's [`Self::window`] override.
+    pub fn options(&self, options: AnalysisOptions) -> AnalysisOptions {
+        self.window.map_or(options, |window| options.window(window))
+    }
+
+    /// Re-runs the FFT, reading the file again if its samples weren't cached.
+    pub fn reanalyze(
+        &mut self,
+        options: AnalysisOptions,
+        sample_type: Option<SampleType>,
+    ) -> Result<(), String> {
+        let (Some(samples), Some(sample_rate)) =
+            (self.decoded_samples(sample_type)?, self.sample_rate)
+        else {
+            return Ok(());
+        };
+        let options = self.options(options);
+        let samples = self.after_offset(&samples);
+        let t0 = Instant::now();
+        let (freqs, amplitudes) = spectrum(samples, sample_rate, options)?;
+        let (fft, sample_count) = (
+            t0.elapsed(),
+            options.trimmed_range(samples.len(), sample_rate).len(),
+        );
+        self.timings.fft = fft;
+        self.timings.sample_count = sample_count;
+        self.timings.fft_size = options.fft_size(sample_count);
+        self.freqs = freqs
+            .into_iter()
+            .map(|freq| freq * self.pitch_scale)
+            .collect();
+        self.amplitudes = amplitudes;
+        Ok(())
+    }
+
+    /// [`fft_input`] of the samples after the start offset, reading the file again if they weren't
+    /// cached.
+    pub fn fft_input(
+        &self,
+        options: AnalysisOptions,
+        sample_type: Option<SampleType>,
+    ) -> Result<(Vec<f32>, u32), String> {
+        let (Some(samples), Some(sample_rate)) =
+            (self.decoded_samples(sample_type)?, self.sample_rate)
+        else {
+            return Err(format!("{} has no samples", self.file_name));
+        };
+        fft_input(
+            self.after_offset(&samples),
+            sample_rate,
+            self.options(options),
+        )
+    }
+
+    /// Phase in degrees of each bin of the spectrum, from the FFT of the same input. Welch
+    /// averages magnitudes only, and a two-sided spectrum is rotated, so both are refused.
+    pub fn phases(
+        &self,
+        options: AnalysisOptions,
+        sample_type: Option<SampleType>,
+    ) -> Result<Vec<f32>, String> {
+        if options.welch.is_some() {
+            return Err("Welch averaging keeps no phase".to_string());
+        }
+        let falls_back = self.limit_warning(options).is_some();
+        if falls_back && options.sample_limit.fallback == LimitFallback::Welch {
+            return Err(format!(
+                "{} is too long for one FFT and Welch-averaged instead",
+                self.file_name
+            ));
+        }
+        if options.two_sided {
+            return Err("Phase is only shown for one-sided spectra".to_string());
+        }
+        let (windowed, _) = self.fft_input(options, sample_type)?;
+        let phases = Fft::new(windowed.len(), options.precision).phases(&windowed);
+        Ok(phases.into_iter().map(f32::to_degrees).collect())
+    }
+
+    /// Group delay in seconds of each bin of the spectrum, see [`fft::group_delay`]. Refused in
+    /// the same cases as [`Self::phases`].
+    pub fn group_delays(
+        &self,
+        options: AnalysisOptions,
+        sample_type: Option<SampleType>,
+    ) -> Result<Vec<f32>, String> {
+        let phases = self.phases(options, sample_type)?;
+        let radians: Vec<f32> = phases.into_iter().map(f32::to_radians).collect();
+        Ok(fft::group_delay(&self.freqs, &radians))
+    }
+
+    /// STFT with `segments`, reading the file again if its samples weren't cached.
+    pub fn spectrogram(
+        &self,
+        window: Window,
+        segments: Welch,
+        precision: Precision,
+        sample_type: Option<SampleType>,
+    ) -> Result<Spectrogram, String> {
+        let (Some(samples), Some(sample_rate)) =
+            (self.decoded_samples(sample_type)?, self.sample_rate)
+        else {
+            return Err(format!("{} has no samples", self.file_name));
+        };
+        Spectrogram::new(
+            self.after_offset(&samples),
+            sample_rate,
+            window,
+            segments,
+            precision,
+        )
+    }
+
+    /// Decodes the file again, e.g. after the sample type override changed.
+    pub fn reload(
+        &mut self,
+        options: AnalysisOptions,
+        sample_type: Option<SampleType>,
+    ) -> Result<(), String> {
+        if self.sample_rate.is_some() {
+            let (offset, source, label) = (
+                self.offset,
+                std::mem::take(&mut self.source),
+                self.label.take(),
+            );
+            let overridden = self.header_sample_rate.and(self.sample_rate);
+            let (pitch_scale, opacity, window) = (self.pitch_scale, self.opacity, self.window);
+            let channels = [self.channel];
+            *self = match self.raw_format {
+                Some(format) => load_raw_channels(self.audio_path(), options, format, &channels)?,
+                None => load_wav_channels(self.audio_path(), options, sample_type, &channels)?,
+            }
+            .remove(0);
+            self.source = source;
+            self.label = label;
+            self.offset = offset;
+            self.opacity = opacity;
+            self.window = window;
+            if offset > 0 || overridden.is_some() || window.is_some() {
+                self.override_sample_rate(overridden);
+                self.reanalyze(options, sample_type)?;
+            }
+            self.set_pitch_scale(pitch_scale);
+        }
+        Ok(())
+    }
+}
+
+impl Default for PlotData {
+    fn default() -> Self {
+        Self {
+            freqs: vec![],
+            amplitudes: vec![],
+            file_name: "".to_string(),
+            level_stats: None,
+            samples: None,
+            sample_rate: None,
+            header_sample_rate: None,
+            pitch_scale: 1.0,
+            opacity: 1.0,
+            decoded_len: 0,
+            offset: 0,
+            timings: Timings::default(),
+            source: "".to_string(),
+            path: None,
+            channel: Channel::default(),
+            raw_format: None,
+            window: None,
+            label: None,
+        }
+    }
+}
+
+/// Puts the spectra read from `.f` files on the frequency grid of the first decoded audio file,
+/// so e.g. averaging a folder of cached spectra and fresh recordings doesn't fall back to the
+/// coarsest grid. Spectra covering only part of the audio's range keep that part; ones that
+/// don't overlap it at all, or whose frequencies aren't ascending, are left alone. Returns a
+/// warning for each spectrum changed or left alone.
+pub fn reconcile_grids(plots: &mut [PlotData]) -> Vec<String> {
+    let audio = plots
+        .iter()
+        .find(|plot_data| plot_data.sample_rate.is_some());
+    let Some(grid) = audio.map(|plot_data| plot_data.freqs.clone()) else {
+        return Vec::new();
+    };
+    let (Some(&low), Some(&high)) = (grid.first(), grid.last()) else {
+        return Vec::new();
+    };
+    let step =
+        |freqs: &[f32]| (freqs[freqs.len() - 1] - freqs[0]) / (freqs.len() - 1).max(1) as f32;
+    let mut warnings = Vec::new();
+    for plot_data in plots
+        .iter_mut()
+        .filter(|plot_data| plot_data.sample_rate.is_none())
+    {
+        let freqs = &plot_data.freqs;
+        if freqs.is_empty() || freqs.len() != plot_data.amplitudes.len() {
+            warnings.push(format!(
+                "{} has no matching frequencies and amplitudes",
+                plot_data.file_name
+            ));
+            continue;
+        }
+        // within a thousandth of a bin, to allow for rounding in whatever wrote the file
+        let tolerance = 1e-3 * step(&grid).max(f32::EPSILON);
+        let same = freqs.len() == grid.len()
+            && freqs
+                .iter()
+                .zip(&grid)
+                .all(|(a, b)| (a - b).abs() <= tolerance);
+        if same {
+            continue;
+        }
+        if freqs.windows(2).any(|pair| pair[1] <= pair[0]) {
+            let name = &plot_data.file_name;
+            warnings.push(format!(
+                "{} has frequencies out of order, so it can't be lined up",
+                name
+            ));
+            continue;
+        }
+        let (first, last) = (freqs[0], freqs[freqs.len() - 1]);
+        if last < low || first > high {
+            warnings.push(format!(
+                "{} covers {:.1}-{:.1} Hz, which doesn't overlap the audio's {:.1}-{:.1} Hz",
+                plot_data.file_name, first, last, low, high
+            ));
+            continue;
+        }
+        let (from_step, to_step) = (step(freqs), step(&grid));
+        let mut warning = format!(
+            "Resampled {} from {} bins {:.3} Hz apart onto the audio's {:.3} Hz",
+            plot_data.file_name,
+            freqs.len(),
+            from_step,
+            to_step
+        );
+        if from_step > to_step * 1.5 {
+            warning.push_str(", interpolating its coarser bins");
+        }
+        if first > low || last < high {
+            warning.push_str(&format!(
+                ", only {:.1}-{:.1} Hz",
+                first.max(low),
+                last.min(high)
+            ));
+        }
+        let (freqs, amplitudes): (Vec<f32>, Vec<f32>) = grid
+            .iter()
+            .zip(plot_data.resample(&grid))
+            .filter_map(|(&freq, amp)| Some((freq, amp?)))
+            .unzip();
+        plot_data.freqs = freqs;
+        plot_data.amplitudes = amplitudes;
+        warnings.push(warning);
+    }
+    warnings
+}
+
+/// Whether `actual` matches `expected` within `abs_tol + rel_tol * |expected|` at every bin, for
+/// regression checks of the analysis. Both have to cover the same range to within half a bin;
+/// `expected` is interpolated at `actual`'s frequencies, so their grids may differ. The error
+/// names the bin furthest outside the tolerance.
+pub fn spectra_close(
+    actual: &PlotData,
+    expected: &PlotData,
+    rel_tol: f32,
+    abs_tol: f32,
+) -> Result<(), String> {
+    let (freqs, amplitudes) = (&actual.freqs, &actual.amplitudes);
+    if freqs.len() != amplitudes.len() || expected.freqs.len() != expected.amplitudes.len() {
+        return Err("Frequencies and amplitudes differ in length".to_string());
+    }
+    let (Some(&first), Some(&last)) = (freqs.first(), freqs.last()) else {
+        return Err("No bins to compare".to_string());
+    };
+    let (Some(&expected_first), Some(&expected_last)) =
+        (expected.freqs.first(), expected.freqs.last())
+    else {
+        return Err("No bins expected".to_string());
+    };
+    let half_bin = 0.5 * (last - first) / (freqs.len() - 1).max(1) as f32;
+    if (first - expected_first).abs() > half_bin || (last - expected_last).abs() > half_bin {
+        return Err(format!(
+            "Covers {:.3}-{:.3} Hz instead of {:.3}-{:.3} Hz",
+            first, last, expected_first, expected_last
+        ));
+    }
+    // the ends may lie just outside the expected range, within the half bin allowed
+    let clamped: Vec<f32> = freqs
+        .iter()
+        .map(|freq| freq.clamp(expected_first, expected_last))
+        .collect();
+    let mut worst: Option<(f32, f32, f32, f32)> = None;
+    for ((&freq, &amp), want) in freqs
+        .iter()
+        .zip(amplitudes)
+        .zip(expected.resample(&clamped))
+    {
+        let want = want.unwrap_or(f32::NAN);
+        let excess = (amp - want).abs() - (abs_tol + rel_tol * want.abs());
+        // NaN fails too, and counts as the worst
+        let excess = if excess.is_nan() {
+            f32::INFINITY
+        } else {
+            excess
+        };
+        if excess > 0.0 && worst.is_none_or(|(worst, ..)| excess > worst) {
+            worst = Some((excess, freq, amp, want));
+        }
+    }
+    match worst {
+        None => Ok(()),
+        Some((_, freq, amp, want)) => {
+            Err(format!("At {:.3} Hz: {} instead of {}", freq, amp, want))
+        }
+    }
+}
+
+/// Panics with the error of [`spectra_close`] unless the spectra match, for tests.
+#[track_caller]
+pub fn assert_spectra_close(actual: &PlotData, expected: &PlotData, rel_tol: f32, abs_tol: f32) {
+    if let Err(e) = spectra_close(actual, expected, rel_tol, abs_tol) {
+        panic!("Spectra differ: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loudest bin of `samples` at 48 kHz.
+    fn peak(samples: &[f32], options: AnalysisOptions) -> f32 {
+        let (_, amplitudes) = spectrum(samples, 48_000, options).unwrap();
+        amplitudes.into_iter().fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn windowed_tones_peak_like_rectangular_ones() {
+        // on a bin, where the window's scalloping doesn't come in
+        let tone = testgen::sine(1000.0, 1000.0, 48_000, 4800);
+        let rectangular = peak(&tone, AnalysisOptions::default());
+        for window in Window::ALL {
+            let windowed = peak(&tone, AnalysisOptions::default().window(window));
+            let error = windowed / rectangular - 1.0;
+            assert!(
+                error.abs() < 1e-3,
+                "{:?}: {} vs {}",
+                window,
+                windowed,
+                rectangular
+            );
+        }
+    }
+
+    #[test]
+    fn padding_to_min_fft_size_keeps_the_windowed_level() {
+        let tone = testgen::sine(1000.0, 1000.0, 48_000, 4800);
+        for window in [Window::Rectangular, Window::Hann, Window::FlatTop] {
+            let options = AnalysisOptions::default().window(window);
+            let unpadded = peak(&tone, options);
+            let padded = peak(&tone, options.min_fft_size(Some(48_000)));
+            assert!(
+                (padded / unpadded - 1.0).abs() < 0.01,
+                "{:?}: {} vs {}",
+                window,
+                padded,
+                unpadded
+            );
+        }
+    }
+
+    #[test]
+    fn padding_to_a_common_length_keeps_the_level() {
+        let short = testgen::sine(1000.0, 1000.0, 48_000, 4800);
+        let welch = Some(Welch {
+            segment_len: 2400,
+            hop: 1200,
+        });
+        for options in [
+            AnalysisOptions::default(),
+            AnalysisOptions::default().welch(welch),
+        ] {
+            let options = options.window(Window::Hann);
+            let (native, padded) = (
+                peak(&short, options),
+                peak(&short, options.length(Some(9600))),
+            );
+            assert!(
+                (padded / native - 1.0).abs() < 0.01,
+                "{:?}: {} vs {}",
+                options.welch,
+                padded,
+                native
+            );
+        }
+    }
+
+    #[test]
+    fn sine_amplitude_of_a_padded_tone() {
+        let tone = testgen::sine(1000.0, 1000.0, 48_000, 4800);
+        for window in [Window::Rectangular, Window::Hann] {
+            for raw_amplitudes in [false, true] {
+                let options = AnalysisOptions::default()
+                    .window(window)
+                    .raw_amplitudes(raw_amplitudes);
+                let options = options.min_fft_size(Some(48_000));
+                assert_eq!(options.windowed_len(tone.len()), 4800);
+                let amplitude = options.sine_amplitude(peak(&tone, options), 4800);
+                let context = format!("{:?}, raw amplitudes {}", window, raw_amplitudes);
+                assert!(
+                    (amplitude - 1000.0).abs() < 1.0,
+                    "{}: {}",
+                    context,
+                    amplitude
+                );
+            }
+        }
+    }
+
+    /// Frequency of the loudest of `amplitudes` on `grid`.
+    fn loudest(grid: &[f32], amplitudes: &[Option<f32>]) -> f32 {
+        let amplitude = |i: usize| amplitudes[i].unwrap_or(0.0);
+        grid[(0..grid.len())
+            .max_by(|&a, &b| amplitude(a).total_cmp(&amplitude(b)))
+            .unwrap()]
+    }
+
+    #[test]
+    fn tones_at_mixed_sample_rates_line_up_on_a_common_grid() {
+        let options = AnalysisOptions::default();
+        let cd = analyze_samples(
+            &testgen::sine(1000.0, 8192.0, 44_100, 22_050),
+            44_100,
+            options,
+        )
+        .unwrap();
+        let dat = analyze_samples(
+            &testgen::sine(2500.0, 8192.0, 48_000, 24_000),
+            48_000,
+            options,
+        )
+        .unwrap();
+        // the grid reaching the highest frequency
+        let grid = dat.freqs.clone();
+        assert_eq!(loudest(&grid, &cd.resample(&grid)), 1000.0);
+        assert_eq!(loudest(&grid, &dat.resample(&grid)), 2500.0);
+        // and a spectrum read from a file without its sample rate
+        let saved = PlotData {
+            freqs: cd.freqs.clone(),
+            amplitudes: cd.amplitudes.clone(),
+            ..Default::default()
+        };
+        let mut plots = vec![dat, saved];
+        assert_eq!(reconcile_grids(&mut plots).len(), 1);
+        assert_eq!(plots[1].freqs, grid[..plots[1].freqs.len()]);
+        let amplitudes: Vec<_> = plots[1].amplitudes.iter().copied().map(Some).collect();
+        assert_eq!(loudest(&plots[1].freqs, &amplitudes), 1000.0);
+    }
+
+    #[test]
+    fn single_and_double_precision_analyses_agree() {
+        let tone = testgen::sine(2291.8, 1000.0, 48_000, 48_000);
+        let noise = testgen::white_noise(50.0, 48_000, 1);
+        let samples: Vec<f32> = tone
+            .iter()
+            .zip(noise)
+            .map(|(tone, noise)| tone + noise)
+            .collect();
+        for window in Window::ALL {
+            let options = AnalysisOptions::default().window(window);
+            let single =
+                analyze_samples(&samples, 48_000, options.precision(Precision::F32)).unwrap();
+            let double =
+                analyze_samples(&samples, 48_000, options.precision(Precision::F64)).unwrap();
+            // noise bins are tiny next to the tone, so they only have to match relative to it
+            let peak = double
+                .amplitudes
+                .iter()
+                .fold(0.0, |max: f32, &amp| max.max(amp));
+            assert_spectra_close(&single, &double, 1e-3, 1e-4 * peak);
+        }
+    }
+
+    #[test]
+    fn square_wave_harmonics_measure_as_generated() {
+        // 10 Hz bins, so every harmonic of 1 kHz lands on one
+        let square = testgen::square(1000.0, 1000.0, 48_000, 4800);
+        let plot_data = analyze_samples(&square, 48_000, AnalysisOptions::default()).unwrap();
+        for (freq, amp) in testgen::square_harmonics(1000.0, 1000.0, 48_000) {
+            let bin = (freq / 10.0).round() as usize;
+            let measured =
+                AnalysisOptions::default().sine_amplitude(plot_data.amplitudes[bin], 4800);
+            assert!(
+                (measured / amp - 1.0).abs() < 1e-3,
+                "{} Hz: {} instead of {}",
+                freq,
+                measured,
+                amp
+            );
+        }
+    }
+
+    #[test]
+    fn spectra_read_back_as_written() {
+        let tone = testgen::square(440.0, 8192.0, 48_000, 4800);
+        let plot_data = analyze_samples(&tone, 48_000, AnalysisOptions::default()).unwrap();
+        let dir = std::env::temp_dir().join(format!("ilena_analysis_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["square.f", "square.f.gz"] {
+            let path = dir.join(name);
+            write_f(&path, &plot_data).unwrap();
+            let (freqs, amplitudes) = read_f(&path).unwrap();
+            assert_spectra_close(
+                &PlotData {
+                    freqs,
+                    amplitudes,
+                    ..Default::default()
+                },
+                &plot_data,
+                0.0,
+                0.0,
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spectra_of_both_float_widths_round_trip() {
+        let plot_data = PlotData {
+            freqs: vec![0.0, 10.0, 20.0],
+            amplitudes: vec![1.5, 0.25, 1e-7],
+            file_name: "not saved".to_string(),
+            ..Default::default()
+        };
+        for width in [4, 8] {
+            let mut bytes = Vec::new();
+            write_spectrum(&mut bytes, &plot_data, width).unwrap();
+            assert_eq!(
+                bytes[..6],
+                [b'I', b'L', b'N', b'F', SPECTRUM_VERSION, width]
+            );
+            // two lengths and the floats, nothing else of the plot
+            assert_eq!(bytes.len(), 6 + 2 * 8 + 6 * width as usize);
+            let read = read_spectrum(bytes.as_slice()).unwrap();
+            assert_eq!(
+                read,
+                (plot_data.freqs.clone(), plot_data.amplitudes.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn spectra_with_unknown_float_widths_are_rejected() {
+        let mut bytes = Vec::new();
+        write_spectrum(&mut bytes, &PlotData::default(), 4).unwrap();
+        bytes[5] = 2;
+        assert!(read_spectrum(bytes.as_slice())
+            .unwrap_err()
+            .contains("2 bytes"));
+    }
+
+    #[test]
+    fn amplitude_at_interpolates_between_bins() {
+        let plot_data = PlotData {
+            freqs: vec![0.0, 10.0, 20.0, 30.0],
+            amplitudes: vec![1.0, 3.0, 2.0, 4.0],
+            ..Default::default()
+        };
+        assert_eq!(plot_data.amplitude_at(10.0), 3.0);
+        assert_eq!(plot_data.amplitude_at(0.0), 1.0);
+        assert_eq!(plot_data.amplitude_at(30.0), 4.0);
+        assert_eq!(plot_data.amplitude_at(12.5), 2.75);
+        assert_eq!(plot_data.amplitude_at(25.0), 3.0);
+        assert_eq!(plot_data.amplitude_at(-0.1), 0.0);
+        assert_eq!(plot_data.amplitude_at(30.1), 0.0);
+        assert_eq!(plot_data.amplitude_at(f32::NAN), 0.0);
+        assert_eq!(PlotData::default().amplitude_at(10.0), 0.0);
+    }
+}
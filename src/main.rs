@@ -1,213 +1,6829 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+mod config;
+mod presets;
+mod session;
+
 use eframe::egui;
-use egui_plot::PlotPoint;
-use egui_plot::{Legend, Line, Plot, PlotPoints};
-use hound;
-use rustfft::{num_complex::Complex, FftPlanner};
+use egui::{pos2, Color32, Mesh, Shape, Ui};
+use egui_plot::{
+    AxisHints, GridInput, GridMark, PlotBounds, PlotGeometry, PlotItem, PlotPoint, PlotTransform,
+};
+use egui_plot::{
+    HLine, Legend, Line, LineStyle, Plot, PlotPoints, PlotResponse, Points, Text, VLine,
+};
+use ilena_analysis::analyzers::{AnalysisResult, Registry};
+use ilena_analysis::cepstrum::Cepstrum;
+use ilena_analysis::colormap::ColorMap;
+use ilena_analysis::comb::{self, Comb};
+use ilena_analysis::envelope::Pooling;
+use ilena_analysis::export::{self, EdgeBins, ExportFormat, FrequencyRounding};
+use ilena_analysis::fft::Precision;
+use ilena_analysis::filter::{self, Decimation, PreFilter};
+use ilena_analysis::mask::{self, Mask, Side, Violation};
+use ilena_analysis::metadata::WavMetadata;
+use ilena_analysis::metrics::SpectralFeatures;
+use ilena_analysis::octave::{self, BandFraction, Weighting};
+use ilena_analysis::onsets::{self, Onsets};
+use ilena_analysis::peaks::{self, Fundamental, Peak, PeakBand};
+use ilena_analysis::raster::Rasterizer;
+use ilena_analysis::resynthesis::{self, Phase};
+use ilena_analysis::spectrogram::{DifferenceMap, Spectrogram};
+use ilena_analysis::stream::StreamingSpectrum;
+use ilena_analysis::tones::{self, ExpectedTone};
+use ilena_analysis::wav::{self, Channel, RawFormat, SampleType};
+use ilena_analysis::welch::Welch;
+use ilena_analysis::window::Window;
+use ilena_analysis::{align, db_difference, envelope, load_wav_channels, memory, metrics};
+#[cfg(not(target_arch = "wasm32"))]
+use ilena_analysis::{analyze_samples, fft, reconcile_grids, stream, summary, testgen};
+use ilena_analysis::{
+    is_spectrum_file, load_raw_channels, load_wav_bytes, parallel, read_f, write_f,
+};
+use ilena_analysis::{AnalysisOptions, LimitFallback, PlotData, SampleLimit};
+use presets::Preset;
+use session::{RonSession, Session, SessionFile, SessionPlot};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs::File;
-use std::io::Write;
-use std::ops::AddAssign;
-use std::path::Path;
-
-fn read_wav(file_path: &str) -> Result<(Vec<f32>, u32), String> {
-    let reader = hound::WavReader::open(file_path).map_err(|e| e.to_string())?;
-    let sample_rate = reader.spec().sample_rate;
-    let samples: Vec<f32> = reader
-        .into_samples::<i16>()
-        .filter_map(Result::ok)
-        .map(|s| s as f32)
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+
+/// Default window title, also naming the directory eframe stores its state in.
+#[cfg(not(target_arch = "wasm32"))]
+const APP_NAME: &str = "Frequency Spectrum";
+
+/// Key the GUI state is persisted under: eframe's own for the default profile, so existing
+/// settings keep loading, and a separate one for each `--profile`.
+fn storage_key(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("{}_{}", eframe::APP_KEY, profile),
+        None => eframe::APP_KEY.to_string(),
+    }
+}
+
+/// `--title`, or the default title with the profile appended so sessions can be told apart.
+#[cfg(not(target_arch = "wasm32"))]
+fn window_title(args: &cli::Args) -> String {
+    match (&args.title, &args.profile) {
+        (Some(title), _) => title.clone(),
+        (None, Some(profile)) => format!("{} ({})", APP_NAME, profile),
+        (None, None) => APP_NAME.to_string(),
+    }
+}
+
+/// Files named by the command line input: everything in a folder, a single file, or the matches
+/// of a glob pattern. Subfolders are skipped rather than rejected as unsupported files.
+#[cfg(not(target_arch = "wasm32"))]
+fn input_files(input: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            // follows symlinks, unlike `DirEntry::file_type`
+            if !entry.path().is_dir() {
+                files.push(entry.path());
+            }
+        }
+        return Ok(files);
+    }
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = glob::glob(input)?.collect::<Result<Vec<_>, _>>()?;
+    files.retain(|file| !file.is_dir());
+    if files.is_empty() {
+        return Err(format!("No files match {}", input).into());
+    }
+    Ok(files)
+}
+
+fn is_supported(path: &Path) -> bool {
+    wav::is_wav(path) || wav::is_raw(path) || is_spectrum_file(path)
+}
+
+/// Analyses `channels` of a `.wav` file or of a headerless `.raw` / `.pcm` file laid out as
+/// `raw_format`, or reads the spectrum of a `.f` or `.f.gz` file.
+fn load_file(
+    file_path: &Path,
+    options: AnalysisOptions,
+    sample_type: Option<SampleType>,
+    raw_format: Option<RawFormat>,
+    channels: &[Channel],
+) -> Result<Vec<PlotData>, String> {
+    if wav::is_wav(file_path) {
+        return load_wav_channels(file_path, options, sample_type, channels);
+    }
+    if wav::is_raw(file_path) {
+        let format = raw_format.ok_or_else(|| {
+            let path = file_path.display();
+            format!(
+                "{} has no header, give its layout with --raw <rate>,<type>,<channels>",
+                path
+            )
+        })?;
+        return load_raw_channels(file_path, options, format, channels);
+    }
+    // if file_path.ends_with(".mp3") {
+    //     return read_mp3(file_path);
+    // }
+    let t0 = std::time::Instant::now();
+    let (freqs, amplitudes) = read_f(file_path)?;
+    let mut plot_data = PlotData {
+        freqs,
+        amplitudes,
+        file_name: file_path.display().to_string(),
+        path: Some(file_path.to_path_buf()),
+        ..Default::default()
+    };
+    plot_data.timings.load = t0.elapsed();
+    Ok(vec![plot_data])
+}
+
+/// Drops one of `name.wav` and a `name.f` / `name.f.gz` saved from it, which would plot the same
+/// curve twice: the WAV if the spectrum is at least as new, so it isn't analysed again, otherwise
+/// the stale spectrum. A kept spectrum has whatever settings it was saved with.
+#[cfg(not(target_arch = "wasm32"))]
+fn skip_duplicates(files: &mut Vec<(PathBuf, &str)>, verbose: bool) {
+    let stem = |path: &Path| {
+        let stem = path.with_extension("");
+        // `.f.gz` has two extensions to drop
+        if wav::has_extension(path, "gz") {
+            stem.with_extension("")
+        } else {
+            stem
+        }
+    };
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let mut skipped = HashSet::new();
+    for (spectrum, _) in files.iter().filter(|(path, _)| is_spectrum_file(path)) {
+        let same = |(path, _): &&(PathBuf, &str)| wav::is_wav(path) && stem(path) == stem(spectrum);
+        let Some((audio, _)) = files.iter().find(same) else {
+            continue;
+        };
+        let fresh = matches!((modified(spectrum), modified(audio)), (Some(saved), Some(wav)) if saved >= wav);
+        let (skip, kept) = if fresh {
+            (audio, spectrum)
+        } else {
+            (spectrum, audio)
+        };
+        if verbose {
+            let reason = if fresh {
+                "its spectrum was saved to"
+            } else {
+                "it's older than"
+            };
+            eprintln!("Skipping {}, {} {}", skip.display(), reason, kept.display());
+        }
+        skipped.insert(skip.clone());
+    }
+    files.retain(|(path, _)| !skipped.contains(path));
+}
+
+/// Input argument that reads one WAV file from stdin.
+#[cfg(not(target_arch = "wasm32"))]
+const STDIN: &str = "-";
+
+/// Analyses a WAV file piped to stdin.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_stdin(
+    options: AnalysisOptions,
+    sample_type: Option<SampleType>,
+) -> Result<PlotData, String> {
+    let mut bytes = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read stdin: {}", e))?;
+    if bytes.is_empty() {
+        return Err("No WAV data on stdin".to_string());
+    }
+    load_wav_bytes(&bytes, "<stdin>", options, sample_type)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let args = match cli::Args::parse(args.iter().skip(1).cloned()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", cli::usage(&args[0]));
+            return Ok(());
+        }
+    };
+    if args.verify_fft {
+        return Ok(verify_fft()?);
+    }
+    if let Some(len) = args.bench {
+        return Ok(bench(len, args.runs.unwrap_or(20), args.precision)?);
+    }
+    if let Some(dir) = &args.generate {
+        return Ok(generate(
+            dir,
+            args.rate.unwrap_or(48_000),
+            args.duration_ms.unwrap_or(1000.0),
+        )?);
+    }
+    if args.lists_devices() {
+        return Ok(list_devices()?);
+    }
+    let options = args.analysis_options();
+    let output_dir = args
+        .config
+        .as_ref()
+        .and_then(|config| config.run.output_dir.as_deref());
+    let mut plots = Vec::new();
+    // with the argument each file was found through
+    let mut files: Vec<(PathBuf, &str)> = Vec::new();
+    for input in &args.inputs {
+        if input == STDIN {
+            let mut plot_data = read_stdin(options, args.sample_type)?;
+            plot_data.source = input.clone();
+            plots.push(plot_data);
+            continue;
+        }
+        let mut found = input_files(input)?;
+        found.sort();
+        for path in found {
+            // a file matched by several arguments belongs to the first
+            if !files.iter().any(|(known, _)| *known == path) {
+                files.push((path, input.as_str()));
+            }
+        }
+    }
+    if args.concat {
+        let (file_paths, skipped): (Vec<PathBuf>, Vec<PathBuf>) = std::mem::take(&mut files)
+            .into_iter()
+            .map(|(path, _)| path)
+            .partition(|path| wav::is_wav(path));
+        for path in skipped {
+            eprintln!(
+                "Warning: --concat skips {}, only WAV files are concatenated",
+                path.display()
+            );
+        }
+        let (samples, sample_rate) = wav::read_concatenated(&file_paths, args.sample_type)?;
+        let mut plot_data = analyze_samples(&samples, sample_rate, options)?;
+        let source = args.inputs.join(" ");
+        plot_data.file_name = format!("{} ({} files concatenated)", source, file_paths.len());
+        // kept whatever the size, there's no single file to read them from again
+        plot_data.samples = Some(samples);
+        plot_data.source = source;
+        plots.push(plot_data);
+    }
+    if !args.keep_duplicates {
+        skip_duplicates(&mut files, args.verbose());
+    }
+    let threads = args.threads.unwrap_or_else(parallel::default_threads);
+    // a file that fails to load doesn't lose the others; they're reported at the end, or in the
+    // GUI's error list
+    let started = std::time::Instant::now();
+    let mut failures = drop_unsupported(&mut files);
+    let mut progress = Progress {
+        done: failures.len(),
+        total: failures.len() + files.len(),
+    };
+    // each loaded plot's file, to put them back in order whichever file finished first
+    let first_loaded = plots.len();
+    let mut positions: Vec<usize> = Vec::new();
+    let load = |(path, source): &(PathBuf, &str)| {
+        let (sample_type, raw_format) = (args.sample_type, args.raw_format);
+        let mut plots = load_file(path, options, sample_type, raw_format, &args.channels)?;
+        for plot_data in &mut plots {
+            plot_data.source = source.to_string();
+        }
+        Ok::<_, String>(plots)
+    };
+    parallel::for_each(files.iter(), threads, load, |i, result| {
+        let path = &files[i].0;
+        let step = progress.next();
+        let loaded = match result {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                if args.verbose() {
+                    eprintln!("{} Failed {}: {}", step, path.display(), e);
+                }
+                failures.push((path.display().to_string(), e));
+                return;
+            }
+        };
+        for plot_data in loaded {
+            let warnings = [
+                plot_data.limit_warning(options),
+                plot_data.overload_warning(options),
+            ];
+            for warning in warnings.into_iter().flatten() {
+                eprintln!("Warning: {}", warning);
+            }
+            if args.verbose() {
+                let timings = plot_data.timings;
+                eprintln!(
+                    "{} Loaded {} in {:.1} ms, FFT {:.1} ms",
+                    step,
+                    plot_data.file_name,
+                    timings.load.as_secs_f64() * 1000.0,
+                    timings.fft.as_secs_f64() * 1000.0
+                );
+            }
+            plots.push(plot_data);
+            positions.push(i);
+        }
+        // with every file as it comes in, so a huge folder can't run out of memory before the GUI
+        // opens
+        if let Some(budget) = args.memory_limit {
+            for warning in memory::enforce_budget(&mut plots, budget) {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+    });
+    // a stable sort keeps the channels of a file in order
+    let mut loaded: Vec<(usize, PlotData)> = positions
+        .into_iter()
+        .zip(plots.drain(first_loaded..))
+        .collect();
+    loaded.sort_by_key(|&(i, _)| i);
+    plots.extend(loaded.into_iter().map(|(_, plot_data)| plot_data));
+
+    // shown in the GUI's error list too, where GUI users actually see them
+    let grid_warnings = if args.keep_grids {
+        Vec::new()
+    } else {
+        reconcile_grids(&mut plots)
+    };
+    for warning in &grid_warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if let Some(path) = &args.log {
+        if let Err(e) = summary::append_run_log(path, &args.inputs, options, &plots, &failures) {
+            eprintln!(
+                "Warning: failed to append to the log {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    if args.timings {
+        summary::print_timings(&plots);
+    }
+    if let Some(format) = args.summary {
+        match output_dir {
+            Some(dir) => {
+                let path = dir.join(format!("summary.{}", format.extension()));
+                write_summaries(&path, &plots, format)?;
+                if args.report() {
+                    eprintln!("Wrote {}", path.display());
+                }
+            }
+            None => summary::print_summaries(&plots, format)?,
+        }
+        if args.report() {
+            eprintln!(
+                "Processed {} files, {} failed, in {:.1} s",
+                progress.done - failures.len(),
+                failures.len(),
+                started.elapsed().as_secs_f64()
+            );
+            for (file, e) in &failures {
+                eprintln!("  {}: {}", file, e);
+            }
+        }
+        if !failures.is_empty() {
+            return Err(format!("{} of {} files failed", failures.len(), progress.done).into());
+        }
+        return Ok(());
+    }
+
+    if args.verbose() {
+        eprintln!("Starting eframe with {} plots", plots.len());
+    }
+    // only taken once the window is up, so the spectra survive a failed start
+    let mut plots = Some(plots);
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_title(window_title(&args)),
+        ..Default::default()
+    };
+    let result = eframe::run_native(
+        APP_NAME,
+        options,
+        Box::new(|cc| {
+            let plots = plots.take().unwrap_or_default();
+            let load_errors = failures
+                .iter()
+                .map(|(file, e)| format!("Failed to load {}: {}", file, e));
+            let load_errors = load_errors.chain(grid_warnings.iter().cloned()).collect();
+            let app = MyApp::new(cc, plots, load_errors, &args);
+            Ok(Box::new(app))
+        }),
+    );
+    match (result, plots) {
+        (Ok(_), _) => Ok(()),
+        (Err(e), Some(plots)) => {
+            let dir = output_dir.unwrap_or(Path::new("."));
+            eprintln!(
+                "Couldn't start the GUI ({}), writing the results to {}",
+                e,
+                dir.display()
+            );
+            write_headless_results(&plots, dir)?;
+            Ok(())
+        }
+        (Err(e), None) => Err(e.into()),
+    }
+}
+
+/// The web build, started by index.html through trunk: draws into its canvas, with files dropped
+/// onto the page rather than named on a command line.
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use eframe::wasm_bindgen::JsCast as _;
+    wasm_bindgen_futures::spawn_local(async {
+        let document = eframe::web_sys::window()
+            .and_then(|window| window.document())
+            .expect("No document");
+        let canvas = document
+            .get_element_by_id("the_canvas_id")
+            .and_then(|element| {
+                element
+                    .dyn_into::<eframe::web_sys::HtmlCanvasElement>()
+                    .ok()
+            })
+            .expect("No canvas with the id the_canvas_id");
+        let started = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|cc| Ok(Box::new(MyApp::web(cc)))),
+            )
+            .await;
+        if let Err(e) = started {
+            eframe::web_sys::console::error_1(&e);
+        }
+    });
+}
+
+/// Fallback when no window can be opened, e.g. without a display or GPU: writes every spectrum
+/// as CSV to `dir` and prints the summary, so the analysis isn't lost.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_headless_results(plots: &[PlotData], dir: &Path) -> Result<(), String> {
+    create_output_dir(dir)?;
+    let mut taken = HashSet::new();
+    for plot_data in plots {
+        let path = dir.join(csv_file_name(&plot_data.label(), &mut taken));
+        // results of an earlier run with the same names are replaced, but not without a word
+        let verb = if path.exists() { "Replaced" } else { "Wrote" };
+        export::write_spectrum_csv(&path, &plot_data.freqs, &plot_data.amplitudes)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        eprintln!("{} {}", verb, path.display());
+    }
+    summary::print_summaries(plots, summary::SummaryFormat::Tsv)
+}
+
+/// `label.csv`, with path separators replaced, and numbered from 2 if a name in `taken` already
+/// has it, e.g. for `a/x.wav` and `b/x.wav`. Compared ignoring case, as some file systems do.
+#[cfg(not(target_arch = "wasm32"))]
+fn csv_file_name(label: &str, taken: &mut HashSet<String>) -> String {
+    let label: String = label
+        .chars()
+        .map(|c| {
+            if matches!(c, '/' | '\\' | ':') {
+                '_'
+            } else {
+                c
+            }
+        })
         .collect();
-    Ok((samples, sample_rate))
+    let base = if label.trim().is_empty() {
+        "spectrum"
+    } else {
+        label.trim()
+    };
+    let mut name = format!("{}.csv", base);
+    for n in 2.. {
+        if taken.insert(name.to_lowercase()) {
+            break;
+        }
+        name = format!("{} {}.csv", base, n);
+    }
+    name
+}
+
+/// Removes the files of unsupported formats from `files`, returning a failure for each one named
+/// outright. A folder or pattern may match anything, like notes or this tool's own exports, so
+/// the files found through one are only skipped with a warning.
+#[cfg(not(target_arch = "wasm32"))]
+fn drop_unsupported(files: &mut Vec<(PathBuf, &str)>) -> Vec<(String, String)> {
+    let mut failures = Vec::new();
+    files.retain(|(path, source)| {
+        if is_supported(path) {
+            return true;
+        }
+        if Path::new(source) == path {
+            failures.push((
+                path.display().to_string(),
+                "Unsupported file format".to_string(),
+            ));
+        } else {
+            eprintln!(
+                "Warning: skipping {}, not a supported format",
+                path.display()
+            );
+        }
+        false
+    });
+    failures
+}
+
+/// Files handled so far of all those a headless run loads or fails on, for the progress lines.
+#[cfg(not(target_arch = "wasm32"))]
+struct Progress {
+    done: usize,
+    total: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Progress {
+    /// Counts one more file, returning its `[done/total]` prefix.
+    fn next(&mut self) -> String {
+        self.done += 1;
+        format!("[{}/{}]", self.done, self.total)
+    }
+}
+
+/// Writes the summaries to `path` instead of stdout, creating its folder if needed.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_summaries(
+    path: &Path,
+    plots: &[PlotData],
+    format: summary::SummaryFormat,
+) -> Result<(), String> {
+    create_output_dir(path.parent().unwrap_or(Path::new(".")))?;
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    summary::write_summaries(&mut std::io::BufWriter::new(file), plots, format)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn create_output_dir(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))
+}
+
+/// `--verify-fft`: compares the FFT in both precisions with a direct DFT, on a tone plus noise at
+/// odd, prime and power of two lengths, which rustfft plans differently.
+#[cfg(not(target_arch = "wasm32"))]
+fn verify_fft() -> Result<(), String> {
+    const LENGTHS: [usize; 16] = [
+        1, 2, 3, 7, 8, 15, 16, 17, 64, 97, 100, 255, 256, 257, 1000, 1024,
+    ];
+    const RATE: u32 = 48_000;
+    // seeded noise, so failures reproduce
+    let tone_plus_noise = |len: usize, seed: u32| -> Vec<f32> {
+        let noise = testgen::white_noise(50.0, len, seed);
+        let tone = testgen::sine(2291.8, 1000.0, RATE, len);
+        tone.iter()
+            .zip(noise)
+            .map(|(tone, noise)| tone + noise)
+            .collect()
+    };
+    let mut failures = Vec::new();
+    for precision in Precision::ALL {
+        // relative to the largest bin; both results end up as f32, which limits f64 to about 1e-7
+        let tolerance = match precision {
+            Precision::F32 => 1e-4,
+            Precision::F64 => 1e-6,
+        };
+        let mut worst = 0.0_f64;
+        for len in LENGTHS {
+            let samples = tone_plus_noise(len, len as u32);
+            let error = fft::dft_error(&samples, precision);
+            if error.is_nan() || error > tolerance {
+                let name = precision.name();
+                failures.push(format!(
+                    "{} FFT of {} samples differs from the DFT by {:e}",
+                    name, len, error
+                ));
+            }
+            worst = worst.max(error);
+        }
+        let name = precision.name();
+        println!(
+            "{}: largest difference from the DFT {:e} (tolerance {:e})",
+            name, worst, tolerance
+        );
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("\n"))
+    }
+}
+
+/// `--bench`: times every window, with and without decimation, in `precision` or both, printing
+/// one JSON object per configuration.
+#[cfg(not(target_arch = "wasm32"))]
+fn bench(len: usize, runs: usize, precision: Option<Precision>) -> Result<(), String> {
+    const DECIMATION_FACTORS: [u32; 2] = [1, 4];
+    let precisions = precision.map_or(Precision::ALL.to_vec(), |precision| vec![precision]);
+    for precision in precisions {
+        for window in Window::ALL {
+            for factor in DECIMATION_FACTORS {
+                let options = AnalysisOptions::default()
+                    .window(window)
+                    .precision(precision)
+                    .decimation(Decimation {
+                        factor,
+                        ..Default::default()
+                    });
+                let result = ilena_analysis::bench::run(len, runs, options)?;
+                println!(
+                    "{}",
+                    serde_json::to_string(&result).map_err(|e| e.to_string())?
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `--device list`: prints the names `--device` takes, the default input first.
+#[cfg(all(feature = "live", not(target_arch = "wasm32")))]
+fn list_devices() -> Result<(), String> {
+    let names = ilena_analysis::capture::input_devices()?;
+    if names.is_empty() {
+        return Err("No input devices found".to_string());
+    }
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+#[cfg(all(not(feature = "live"), not(target_arch = "wasm32")))]
+fn list_devices() -> Result<(), String> {
+    Err(NO_CAPTURE.to_string())
+}
+
+/// `--generate`: writes the [`testgen::test_files`] signals to `dir` as float WAV files of
+/// `duration_ms` at `sample_rate`.
+#[cfg(not(target_arch = "wasm32"))]
+fn generate(dir: &Path, sample_rate: u32, duration_ms: f32) -> Result<(), String> {
+    create_output_dir(dir)?;
+    let len = (duration_ms / 1000.0 * sample_rate as f32).round() as usize;
+    for (name, samples) in testgen::test_files(sample_rate, len) {
+        let path = dir.join(format!("{}.wav", name));
+        wav::write_float_wav(&path, &samples, sample_rate)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Horizontal legend of `color_map` from -`range_db` to +`range_db`.
+fn color_bar(ui: &mut Ui, color_map: ColorMap, range_db: f32) {
+    const STEPS: usize = 64;
+    ui.horizontal(|ui| {
+        ui.label(format!("{:+.0} dB", -range_db));
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 14.0), egui::Sense::hover());
+        let step = rect.width() / STEPS as f32;
+        for i in 0..STEPS {
+            let x = rect.left() + i as f32 * step;
+            let cell = egui::Rect::from_min_size(
+                pos2(x, rect.top()),
+                egui::vec2(step + 0.5, rect.height()),
+            );
+            let t = (i as f32 + 0.5) / STEPS as f32;
+            ui.painter().rect_filled(cell, 0.0, color_map.color(t));
+        }
+        ui.label(format!("{:+.0} dB (A louder)", range_db));
+    });
+}
+
+/// White for dissimilar, dark blue for identical spectra.
+fn similarity_color(similarity: f32) -> Color32 {
+    let t = similarity.clamp(0.0, 1.0);
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t) as u8;
+    Color32::from_rgb(mix(255, 8), mix(255, 48), mix(255, 107))
+}
+
+/// The y of `points` at `x`, linearly interpolated between its neighbours, and the nearest point
+/// itself. `None` outside the points. `points` must be sorted by x.
+fn interpolate_at(points: &[PlotPoint], x: f64) -> Option<(f64, PlotPoint)> {
+    let i = points.partition_point(|point| point.x < x);
+    let (before, after) = (points.get(i.checked_sub(1)?)?, points.get(i)?);
+    let t = if after.x > before.x {
+        (x - before.x) / (after.x - before.x)
+    } else {
+        0.0
+    };
+    let nearest = if x - before.x <= after.x - x {
+        *before
+    } else {
+        *after
+    };
+    Some((before.y + t * (after.y - before.y), nearest))
+}
+
+/// A stretch of a spectrum above a threshold: the frequencies it crosses the threshold at,
+/// interpolated between the bins, and its highest point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Crossing {
+    start: f64,
+    end: f64,
+    highest: PlotPoint,
+}
+
+/// Stretches of `points` above `level`; one still above at either end of the points starts or
+/// ends there.
+fn crossings(points: &[PlotPoint], level: f64) -> Vec<Crossing> {
+    let cross = |a: &PlotPoint, b: &PlotPoint| a.x + (level - a.y) / (b.y - a.y) * (b.x - a.x);
+    let mut crossings: Vec<Crossing> = Vec::new();
+    let mut current: Option<Crossing> = None;
+    for (i, point) in points.iter().enumerate() {
+        let above = point.y > level;
+        if let Some(crossing) = current.as_mut() {
+            if above && point.y > crossing.highest.y {
+                crossing.highest = *point;
+            } else if !above {
+                crossing.end = cross(&points[i - 1], point);
+                crossings.extend(current.take());
+            }
+        } else if above {
+            let start = if i == 0 {
+                point.x
+            } else {
+                cross(&points[i - 1], point)
+            };
+            current = Some(Crossing {
+                start,
+                end: point.x,
+                highest: *point,
+            });
+        }
+    }
+    if let (Some(mut crossing), Some(last)) = (current, points.last()) {
+        crossing.end = last.x;
+        crossings.push(crossing);
+    }
+    crossings
+}
+
+/// Distance from `p` to the line segment from `a` to `b`.
+fn distance_to_segment(p: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_sq() > 0.0 {
+        ((p - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    p.distance(a + t * ab)
+}
+
+/// Translucent area between a spectrum line and zero amplitude.
+///
+/// `Line::fill` always uses egui_plot's fixed fill alpha, so the mesh is built here instead to
+/// make the opacity configurable and to allow tinting the area by frequency band.
+struct AreaFill {
+    /// Shared with the point cache rather than copied, see [`CachedPoints`].
+    series: Arc<[PlotPoint]>,
+    color: Color32,
+    opacity: f32,
+    band_edges: Option<[f32; 2]>,
+    /// Lower edge at each point of `series`; the fill goes down to 0 without one.
+    lower: Option<Vec<f64>>,
+}
+
+impl AreaFill {
+    fn new(points: impl Into<Arc<[PlotPoint]>>, color: Color32, opacity: f32) -> Self {
+        Self {
+            series: points.into(),
+            color,
+            opacity,
+            band_edges: None,
+            lower: None,
+        }
+    }
+
+    /// Fills between `upper` and `lower`, which must have the same x values.
+    fn between(upper: Vec<PlotPoint>, lower: Vec<PlotPoint>, color: Color32, opacity: f32) -> Self {
+        let lower = lower.iter().map(|point| point.y).collect();
+        Self {
+            lower: Some(lower),
+            ..Self::new(upper, color, opacity)
+        }
+    }
+
+    /// Shade bass / mid / treble in their own colors instead of the line color.
+    fn bands(mut self, band_edges: Option<[f32; 2]>) -> Self {
+        self.band_edges = band_edges;
+        self
+    }
+
+    fn color_at(&self, freq: f64) -> Color32 {
+        let color = match self.band_edges {
+            Some([low, _]) if freq < low as f64 => BASS_COLOR,
+            Some([_, high]) if freq < high as f64 => MID_COLOR,
+            Some(_) => TREBLE_COLOR,
+            None => self.color,
+        };
+        color.linear_multiply(self.opacity)
+    }
+}
+
+const BASS_COLOR: Color32 = Color32::from_rgb(220, 90, 60);
+const MID_COLOR: Color32 = Color32::from_rgb(90, 180, 90);
+const TREBLE_COLOR: Color32 = Color32::from_rgb(70, 130, 220);
+/// Opacity of the standard-deviation band around the average.
+const STD_BAND_OPACITY: f32 = 0.25;
+const REFERENCE_COLOR: Color32 = Color32::from_rgb(200, 60, 200);
+const PEAK_HOLD_COLOR: Color32 = Color32::from_rgb(230, 80, 80);
+const FROZEN_AVERAGE_COLOR: Color32 = Color32::from_rgb(130, 110, 230);
+const STREAM_COLOR: Color32 = Color32::from_rgb(240, 170, 40);
+const MASK_COLOR: Color32 = Color32::from_rgb(150, 150, 150);
+/// Stretches of a spectrum outside the tolerance mask, and a failing check.
+const VIOLATION_COLOR: Color32 = Color32::from_rgb(230, 40, 40);
+const PASS_COLOR: Color32 = Color32::from_rgb(60, 170, 60);
+const THRESHOLD_COLOR: Color32 = Color32::from_rgb(240, 110, 30);
+/// Tuning readout within 5 cents of the note, and further off.
+const IN_TUNE_COLOR: Color32 = Color32::from_rgb(60, 170, 60);
+const OUT_OF_TUNE_COLOR: Color32 = Color32::from_rgb(220, 140, 40);
+/// Fundamental confidence readout that's doubtful, and one not worth trusting.
+const UNSURE_COLOR: Color32 = Color32::from_rgb(210, 190, 40);
+const UNRELIABLE_COLOR: Color32 = Color32::from_rgb(210, 60, 50);
+
+impl PlotItem for AreaFill {
+    fn shapes(&self, _ui: &Ui, transform: &PlotTransform, shapes: &mut Vec<Shape>) {
+        let points = &self.series;
+        if points.len() < 2 {
+            return;
+        }
+        let base = |i: usize, x: f64| {
+            let y = self.lower.as_ref().map_or(0.0, |lower| lower[i]);
+            transform.position_from_point(&PlotPoint::new(x, y)).y
+        };
+        let mut mesh = Mesh::default();
+        mesh.reserve_triangles((points.len() - 1) * 2);
+        mesh.reserve_vertices((points.len() - 1) * 4);
+        for (j, w) in points.windows(2).enumerate() {
+            let color = self.color_at((w[0].x + w[1].x) / 2.0);
+            let a = transform.position_from_point(&w[0]);
+            let b = transform.position_from_point(&w[1]);
+            let i = mesh.vertices.len() as u32;
+            mesh.colored_vertex(a, color);
+            mesh.colored_vertex(pos2(a.x, base(j, w[0].x)), color);
+            mesh.colored_vertex(b, color);
+            mesh.colored_vertex(pos2(b.x, base(j + 1, w[1].x)), color);
+            mesh.add_triangle(i, i + 1, i + 2);
+            mesh.add_triangle(i + 1, i + 2, i + 3);
+        }
+        shapes.push(Shape::Mesh(mesh));
+    }
+
+    fn initialize(&mut self, _x_range: std::ops::RangeInclusive<f64>) {}
+
+    fn name(&self) -> &str {
+        ""
+    }
+
+    fn color(&self) -> Color32 {
+        self.color
+    }
+
+    fn highlight(&mut self) {}
+
+    fn highlighted(&self) -> bool {
+        false
+    }
+
+    fn allow_hover(&self) -> bool {
+        false
+    }
+
+    fn geometry(&self) -> PlotGeometry<'_> {
+        PlotGeometry::None
+    }
+
+    fn bounds(&self) -> PlotBounds {
+        let mut bounds = PlotBounds::NOTHING;
+        for point in self.series.iter() {
+            bounds.extend_with(point);
+        }
+        match &self.lower {
+            Some(lower) => lower.iter().for_each(|&y| bounds.extend_with_y(y)),
+            None => bounds.extend_with_y(0.0),
+        }
+        bounds
+    }
+
+    fn id(&self) -> Option<egui::Id> {
+        None
+    }
+}
+
+/// Same hue sequence egui_plot auto-assigns, so a line and its fill can share a color. Brighter
+/// on dark backgrounds, where egui_plot's darker shades are hard to make out.
+fn line_color(index: usize, background: Color32) -> Color32 {
+    let golden_ratio = (5.0_f32.sqrt() - 1.0) / 2.0;
+    let h = index as f32 * golden_ratio;
+    if egui::Rgba::from(background).intensity() < 0.5 {
+        egui::ecolor::Hsva::new(h, 0.7, 0.95, 1.0).into()
+    } else {
+        egui::ecolor::Hsva::new(h, 0.85, 0.5, 1.0).into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    fn visuals(self) -> egui::Visuals {
+        match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+/// A note pinned to a frequency, drawn as a labelled vertical marker.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Annotation {
+    frequency: f32,
+    label: String,
 }
 
-fn read_f(file_path: &str) -> Result<(Vec<f32>, Vec<f32>), String> {
-    let file = File::open(file_path).map_err(|e| e.to_string())?;
-    let plot_data: PlotData = bincode::deserialize_from(file).map_err(|e| e.to_string())?;
-    Ok((plot_data.freqs, plot_data.amplitudes))
-}
+const ANNOTATION_COLOR: Color32 = Color32::from_rgb(120, 200, 120);
+
+/// A measurement point dropped on the plot, in the units the plot showed at the time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Marker {
+    frequency: f64,
+    amplitude: f64,
+}
+
+const MARKER_COLOR: Color32 = Color32::from_rgb(255, 200, 60);
+const TONE_COLOR: Color32 = Color32::from_rgb(80, 190, 220);
+/// Mid gray at a third opacity, faint on light and dark plots so it doesn't hide the curves.
+const HARMONIC_COLOR: Color32 = Color32::from_rgba_premultiplied(45, 45, 45, 90);
+
+/// Vertical grid lines of the spectrum plot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum FrequencyGrid {
+    /// egui_plot's evenly spaced lines.
+    #[default]
+    Linear,
+    /// Octaves around 1 kHz, i.e. the ISO 266 octave band centers (31.5, 63, 125, … Hz).
+    Octave,
+    /// ISO third-octave band centers.
+    ThirdOctave,
+    None,
+}
+
+impl FrequencyGrid {
+    const ALL: [FrequencyGrid; 4] = [
+        FrequencyGrid::Linear,
+        FrequencyGrid::Octave,
+        FrequencyGrid::ThirdOctave,
+        FrequencyGrid::None,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            FrequencyGrid::Linear => "Linear",
+            FrequencyGrid::Octave => "Octaves",
+            FrequencyGrid::ThirdOctave => "Third octaves",
+            FrequencyGrid::None => "None",
+        }
+    }
+
+    /// Bands per octave for the fractional octave grids.
+    fn bands_per_octave(self) -> Option<f64> {
+        match self {
+            FrequencyGrid::Octave => Some(1.0),
+            FrequencyGrid::ThirdOctave => Some(3.0),
+            FrequencyGrid::Linear | FrequencyGrid::None => None,
+        }
+    }
+}
+
+/// Grid marks at `1000 * 2^(k / bands)` Hz within the visible range.
+fn fractional_octave_marks(input: GridInput, bands: f64) -> Vec<GridMark> {
+    let (low, high) = (input.bounds.0.max(1.0), input.bounds.1);
+    if high <= low {
+        return Vec::new();
+    }
+    let first = ((low / 1000.0).log2() * bands).floor() as i32;
+    let last = ((high / 1000.0).log2() * bands).ceil() as i32;
+    (first..=last)
+        .map(|k| 1000.0 * 2.0_f64.powf(k as f64 / bands))
+        .map(|value| GridMark {
+            value,
+            // the fade-out of egui_plot grid lines depends on the step, which grows with frequency
+            step_size: value * (1.0 - 2.0_f64.powf(-1.0 / bands)),
+        })
+        .collect()
+}
+
+/// Wavelength of `freq` Hz at `speed` m/s in a unit that keeps it readable; the wavelength of
+/// DC is infinite.
+fn format_wavelength(freq: f64, speed: f64) -> String {
+    let freq = freq.abs();
+    if freq <= 0.0 {
+        return "∞".to_string();
+    }
+    match speed / freq {
+        m if m >= 1.0 => format!("{:.3} m", m),
+        m if m >= 0.01 => format!("{:.2} cm", m * 100.0),
+        m if m >= 0.001 => format!("{:.2} mm", m * 1000.0),
+        m => format!("{:.1} µm", m * 1e6),
+    }
+}
+
+/// Value a second y axis puts at `value`, with `span` stretched over the visible `range`, e.g.
+/// −180° to 180° for the phase.
+fn overlay_value(value: f64, range: &std::ops::RangeInclusive<f64>, span: (f64, f64)) -> f64 {
+    let height = range.end() - range.start();
+    if height <= 0.0 {
+        return span.0;
+    }
+    span.0 + (value - range.start()) / height * (span.1 - span.0)
+}
+
+/// Correction of a plot's frequency axis for a known shift in the measurement chain, e.g. of a
+/// heterodyne setup: frequencies are shown as `scale · f + offset`. The spectrum itself keeps the
+/// analysed frequencies.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+struct FrequencyCalibration {
+    /// In Hz, added after the scale.
+    offset: f32,
+    scale: f32,
+}
+
+impl Default for FrequencyCalibration {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl FrequencyCalibration {
+    fn apply(self, freq: f32) -> f32 {
+        freq * self.scale + self.offset
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum LinePattern {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LinePattern {
+    const ALL: [LinePattern; 3] = [LinePattern::Solid, LinePattern::Dashed, LinePattern::Dotted];
+
+    fn name(self) -> &'static str {
+        match self {
+            LinePattern::Solid => "Solid",
+            LinePattern::Dashed => "Dashed",
+            LinePattern::Dotted => "Dotted",
+        }
+    }
+
+    fn line_style(self) -> LineStyle {
+        match self {
+            LinePattern::Solid => LineStyle::Solid,
+            LinePattern::Dashed => LineStyle::dashed_dense(),
+            LinePattern::Dotted => LineStyle::dotted_dense(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum Normalization {
+    None,
+    /// Every plot scaled so its own maximum is 1.0.
+    PerFile,
+    /// Every plot scaled so its strongest peak within the frequency range is 1.0, i.e. 0 dB,
+    /// ignoring DC and content outside the range, to compare harmonic rolloff.
+    PerPeak,
+    /// Every plot scaled by the same factor so the maximum across all plots is 1.0, preserving
+    /// the level differences between files.
+    Global,
+}
+
+/// What "Copy" puts on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum CopyContent {
+    #[default]
+    Spectrum,
+    Peaks,
+}
+
+/// What the y axis of the spectrum shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum AmplitudeMode {
+    /// FFT magnitudes as computed; white noise is flat.
+    #[default]
+    PerBin,
+    /// Magnitudes of the energy per octave, i.e. bin energy scaled by the number of bins in an
+    /// octave at that frequency, so pink noise is flat. Unchanged at 1 kHz.
+    PerOctave,
+}
+
+impl AmplitudeMode {
+    const ALL: [AmplitudeMode; 2] = [AmplitudeMode::PerBin, AmplitudeMode::PerOctave];
+
+    fn name(self) -> &'static str {
+        match self {
+            AmplitudeMode::PerBin => "Per bin",
+            AmplitudeMode::PerOctave => "Per octave (pink flat)",
+        }
+    }
+
+    /// Factor for the amplitude at `freq`. Energy scales with the octave's width, proportional
+    /// to `freq`, so amplitudes scale with its square root.
+    fn weight(self, freq: f32) -> f32 {
+        match self {
+            AmplitudeMode::PerBin => 1.0,
+            // negative frequencies of a two-sided spectrum weigh like their mirror image
+            AmplitudeMode::PerOctave => (freq.abs() / 1000.0).sqrt(),
+        }
+    }
+}
+
+/// How the files' amplitudes at a bin are combined into the average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum AverageDomain {
+    /// Mean of the amplitudes, which suits tones with a steady level.
+    #[default]
+    Amplitude,
+    /// Root of the mean power, i.e. the RMS across files. The amplitudes of a noise bin scatter
+    /// around its level, and their mean reads it low: for Gaussian noise by a factor of √(π/4),
+    /// about 1 dB, which averaging power avoids.
+    Power,
+}
+
+/// What is done with amplitudes below zero, which a magnitude spectrum can't have but e.g. a `.f`
+/// file from another tool or subtracting a noise floor can, and which have no level in dB. The
+/// plot, the average and exports all treat them the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum NegativeAmplitudes {
+    /// Raised to 0, i.e. silence; the default.
+    #[default]
+    Clamp,
+    /// Flipped to their magnitude.
+    Absolute,
+    /// Kept as they are, drawn below the axis.
+    Signed,
+}
+
+impl NegativeAmplitudes {
+    const ALL: [NegativeAmplitudes; 3] = [
+        NegativeAmplitudes::Clamp,
+        NegativeAmplitudes::Absolute,
+        NegativeAmplitudes::Signed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            NegativeAmplitudes::Clamp => "Clamp to 0",
+            NegativeAmplitudes::Absolute => "Absolute value",
+            NegativeAmplitudes::Signed => "Keep signed",
+        }
+    }
+
+    fn apply(self, amp: f32) -> f32 {
+        match self {
+            NegativeAmplitudes::Clamp => amp.max(0.0),
+            NegativeAmplitudes::Absolute => amp.abs(),
+            NegativeAmplitudes::Signed => amp,
+        }
+    }
+}
+
+/// Settings [`MyApp::plot_points`] depends on besides the plot, to tell whether cached points are
+/// still current. Changes to the plots themselves clear the cache in `update_average` instead.
+/// These are the display settings, which only redraw; the ones that change the spectra are in
+/// [`MyApp::analysis_options`] and re-run the FFT, see [`MyApp::reanalyze_if_changed`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PointsKey {
+    freq_range: [f32; 2],
+    normalization: Normalization,
+    global_max: f32,
+    amplitude_mode: AmplitudeMode,
+    negative_amplitudes: NegativeAmplitudes,
+    calibration: FrequencyCalibration,
+    difference: bool,
+    smoothing_bands: Option<u32>,
+    envelope_width: Option<f32>,
+    log_bins: Option<(u32, Pooling)>,
+}
+
+/// [`MyApp::plot_points`] of a plot, and while smoothing or showing the envelope the
+/// [`MyApp::raw_points`] drawn under them. Shared, so fills draw them without a copy; egui_plot
+/// 0.29 has no borrowed `PlotPoints`, so each line still copies its points once per frame.
+#[derive(Debug, Clone)]
+struct CachedPoints {
+    key: PointsKey,
+    points: Arc<[PlotPoint]>,
+    raw: Option<Arc<[PlotPoint]>>,
+}
+
+/// What the ranking view sorts the files by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum RankMetric {
+    #[default]
+    PeakFrequency,
+    Rms,
+    Centroid,
+    Flatness,
+    CrestFactor,
+}
+
+impl RankMetric {
+    const ALL: [RankMetric; 5] = [
+        RankMetric::PeakFrequency,
+        RankMetric::Rms,
+        RankMetric::Centroid,
+        RankMetric::Flatness,
+        RankMetric::CrestFactor,
+    ];
+
+    /// With the unit, as the column heading.
+    fn name(self) -> &'static str {
+        match self {
+            RankMetric::PeakFrequency => "Peak frequency (Hz)",
+            RankMetric::Rms => "RMS (dBFS)",
+            RankMetric::Centroid => "Centroid (Hz)",
+            RankMetric::Flatness => "Flatness",
+            RankMetric::CrestFactor => "Crest factor",
+        }
+    }
+
+    /// `None` where the file doesn't have it: the levels need samples, the features a spectrum
+    /// that isn't silent.
+    fn value(self, plot_data: &PlotData, features: Option<&SpectralFeatures>) -> Option<f32> {
+        match self {
+            RankMetric::PeakFrequency => {
+                metrics::peak_frequency(&plot_data.freqs, &plot_data.amplitudes)
+            }
+            RankMetric::Rms => plot_data
+                .level_stats
+                .map(|stats| 20.0 * stats.rms.max(1e-12).log10()),
+            RankMetric::Centroid => features.map(|features| features.centroid),
+            RankMetric::Flatness => features.map(|features| features.flatness),
+            RankMetric::CrestFactor => plot_data.level_stats.map(|stats| stats.crest_factor),
+        }
+    }
+
+    fn format(self, value: f32) -> String {
+        match self {
+            RankMetric::PeakFrequency | RankMetric::Rms | RankMetric::Centroid => {
+                format!("{:.1}", value)
+            }
+            RankMetric::Flatness => format!("{:.4}", value),
+            RankMetric::CrestFactor => format!("{:.2}", value),
+        }
+    }
+}
+
+/// Length every file is cut or padded to before the FFT, so their bins line up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+enum InputLength {
+    /// Each file as long as it is.
+    #[default]
+    Native,
+    Shortest,
+    Longest,
+    /// `fixed_length` samples.
+    Fixed,
+}
+
+impl InputLength {
+    const ALL: [InputLength; 4] = [
+        InputLength::Native,
+        InputLength::Shortest,
+        InputLength::Longest,
+        InputLength::Fixed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            InputLength::Native => "Native",
+            InputLength::Shortest => "Shortest file",
+            InputLength::Longest => "Longest file",
+            InputLength::Fixed => "Fixed",
+        }
+    }
+}
+
+fn max_amplitude(plot_data: &PlotData) -> f32 {
+    plot_data.amplitudes.iter().fold(0.0, |m, &a| m.max(a))
+}
+
+fn inverse_or_one(max: f32) -> f32 {
+    if max > 0.0 {
+        1.0 / max
+    } else {
+        1.0
+    }
+}
+
+/// Length of the stretch at the start of each file that auto-align cross-correlates.
+const ALIGN_SECONDS: f32 = 10.0;
+/// Largest shift auto-align considers.
+const MAX_ALIGN_SECONDS: f32 = 2.0;
+
+/// Largest side of the difference heatmap texture, in pixels.
+const MAX_HEATMAP_SIDE: usize = 2048;
+
+/// Name, color and frequency/value pairs of a line drawn against a y axis of its own.
+type Overlay = (String, Color32, Vec<[f64; 2]>);
+
+type FigureWriter = fn(&Path, &export::Figure) -> Result<(), String>;
+
+type SpectrogramWriter = fn(&Path, &Spectrogram) -> Result<(), String>;
+
+/// A spectrogram difference map and the texture it is currently drawn with.
+struct Heatmap {
+    /// Indices into `plots` of the A and B files it was computed from.
+    files: [usize; 2],
+    map: DifferenceMap,
+    texture: egui::TextureHandle,
+    /// `range_db` and `color_map` the texture was colored with.
+    range_db: f32,
+    color_map: ColorMap,
+}
+
+impl std::fmt::Debug for Heatmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Heatmap")
+            .field("map", &self.map)
+            .field("range_db", &self.range_db)
+            .field("color_map", &self.color_map)
+            .finish()
+    }
+}
+
+/// Turns displayed magnitudes into sound pressure levels once calibrated, see
+/// [`MyApp::spl_scale`].
+#[derive(Debug, Clone, Copy)]
+struct SplScale {
+    options: AnalysisOptions,
+    /// Real samples each FFT windowed, see [`AnalysisOptions::windowed_len`].
+    windowed_len: usize,
+    /// dB SPL a full-scale sine reads.
+    offset_db: f32,
+}
+
+impl SplScale {
+    /// Level in dB SPL of the sine producing a peak of `magnitude`.
+    fn level(self, magnitude: f32) -> f32 {
+        let amplitude = self.options.sine_amplitude(magnitude, self.windowed_len);
+        20.0 * (amplitude / 32_768.0).log10() + self.offset_db
+    }
+
+    fn tick_label(self, value: f64) -> String {
+        if value > 0.0 {
+            format!("{:.1}", self.level(value as f32))
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Layout `--live` reads stdin as without `--raw` when built without sound card capture: what
+/// e.g. `arecord -f S16_LE -r 48000` writes.
+#[cfg(all(not(feature = "live"), not(target_arch = "wasm32")))]
+const DEFAULT_LIVE_FORMAT: RawFormat = RawFormat {
+    sample_rate: 48_000,
+    sample_type: SampleType::I16,
+    channels: 1,
+    big_endian: false,
+};
+
+#[cfg(all(not(feature = "live"), not(target_arch = "wasm32")))]
+const NO_CAPTURE: &str = "Built without the live feature, so --live only reads PCM piped to stdin";
+
+/// Blocks captured from the input `device`, or the default one, with its name and sample rate.
+#[cfg(all(feature = "live", not(target_arch = "wasm32")))]
+fn capture_device(
+    device: Option<&str>,
+    sample_rate: Option<u32>,
+    channel: Channel,
+    block_frames: usize,
+) -> Result<(LiveBlocks, String, u32), String> {
+    let capture = ilena_analysis::capture::spawn(device, sample_rate, channel, block_frames)?;
+    Ok((capture.blocks, capture.name, capture.sample_rate))
+}
+
+/// Without sound card capture, stdin in [`DEFAULT_LIVE_FORMAT`] at `sample_rate`.
+#[cfg(all(not(feature = "live"), not(target_arch = "wasm32")))]
+fn capture_device(
+    device: Option<&str>,
+    sample_rate: Option<u32>,
+    channel: Channel,
+    block_frames: usize,
+) -> Result<(LiveBlocks, String, u32), String> {
+    if device.is_some() {
+        return Err(NO_CAPTURE.to_string());
+    }
+    let format = RawFormat {
+        sample_rate: sample_rate.unwrap_or(DEFAULT_LIVE_FORMAT.sample_rate),
+        ..DEFAULT_LIVE_FORMAT
+    };
+    let receiver = stream::spawn_raw_capture(std::io::stdin(), format, channel, block_frames);
+    Ok((receiver, "Live input".to_string(), format.sample_rate))
+}
+
+/// Blocks of a live input, or the error that ended it.
+type LiveBlocks = Receiver<Result<Vec<f32>, String>>;
+
+/// Where the samples of a [`SampleStream`] come from.
+enum StreamSource {
+    /// A file played as if it arrived live, with the samples fed so far.
+    File { samples: Vec<f32>, position: usize },
+    /// Blocks from a sound card or [`stream::spawn_raw_capture`]; `None` once the input ended.
+    /// Never in the browser, which has neither.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    Live(Option<LiveBlocks>),
+}
+
+/// Samples arriving over time and their rolling spectrum.
+struct SampleStream {
+    name: String,
+    source: StreamSource,
+    sample_rate: u32,
+    /// Stops feeding a file; a live stream keeps being read but its spectrum freezes.
+    paused: bool,
+    spectrum: StreamingSpectrum,
+    /// The latest spectrum, to draw it like the other plots.
+    plot: PlotData,
+}
+
+impl std::fmt::Debug for SampleStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SampleStream")
+            .field("name", &self.name)
+            .field("sample_rate", &self.sample_rate)
+            .field("frames", &self.spectrum.frames())
+            .finish()
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct MyApp {
+    #[serde(skip)]
+    plots: Vec<PlotData>,
+    #[serde(skip)]
+    avg_plot: PlotData,
+    /// Standard deviation across plots at each bin of `avg_plot`.
+    #[serde(skip)]
+    avg_std: Vec<f32>,
+    /// Copy of `avg_plot` taken on request, kept as it was while files are added and removed, to
+    /// see how far the average drifts from it.
+    #[serde(skip)]
+    frozen_average: Option<PlotData>,
+    /// Target curve from a `.f` file, drawn on top and excluded from the average.
+    #[serde(skip)]
+    reference: Option<PlotData>,
+    /// Path of the reference curve, reloaded on start so it stays pinned across sessions.
+    reference_path: String,
+    /// Keep the loudest amplitude seen at each frequency across every file loaded since the last
+    /// reset, like the max hold of a spectrum analyzer.
+    peak_hold: bool,
+    /// Envelope kept by [`Self::peak_hold`], on the common grid when it started.
+    #[serde(skip)]
+    held_peaks: PlotData,
+    /// FFT size and hop of the streamed spectrum.
+    stream_segments: Welch,
+    /// Multiple of real time a file is streamed at.
+    stream_speed: f32,
+    /// Share of the previous streamed spectrum each frame keeps, see [`StreamingSpectrum::new`].
+    stream_smoothing: f32,
+    #[serde(skip)]
+    stream: Option<SampleStream>,
+    /// CSV of the tolerance mask, reloaded on start like the reference.
+    mask_path: String,
+    /// CSV of expected frequencies drawn as reference markers, reloaded on start too.
+    tones_path: String,
+    #[serde(skip)]
+    tones: Vec<ExpectedTone>,
+    /// `.session` file the whole analysis is saved to and opened from.
+    session_path: String,
+    /// Label the y axis and readouts in dB SPL using `spl_offset_db`.
+    spl_calibrated: bool,
+    /// dB SPL of a full-scale sine, i.e. what to add to dBFS levels.
+    spl_offset_db: f32,
+    /// Level the calibrator's tone is specified at, usually 94 dB SPL (1 Pa).
+    calibrator_level_db: f32,
+    #[serde(skip)]
+    mask: Option<Mask>,
+    /// What the file statistics show, and what they showed for each plot at the last update.
+    #[serde(skip)]
+    analyzers: Registry,
+    #[serde(skip)]
+    file_stats: Vec<AnalysisResult>,
+    /// [`PlotData::overload_warning`]s of the plots that have one, shown under the analysis errors.
+    #[serde(skip)]
+    overloads: Vec<String>,
+    /// Draw a horizontal line at `threshold` and list where the spectra rise above it.
+    show_threshold: bool,
+    /// In the units of the y axis as plotted.
+    threshold: f32,
+    /// Whether the pointer is on the threshold line, so pressing there drags it instead of the
+    /// plot.
+    #[serde(skip)]
+    threshold_grabbed: bool,
+    /// Where each plot leaves the mask, recomputed whenever `plots` change.
+    #[serde(skip)]
+    mask_violations: Vec<Vec<Violation>>,
+    min_freq: f32,
+    max_freq: f32,
+    /// Keep `max_freq` at the highest analysed frequency as files come and go, so content above
+    /// 20 kHz isn't hidden; cleared once the range is set by hand or by a preset.
+    max_freq_to_nyquist: bool,
+    show_average: bool,
+    /// Shade the average ± one standard deviation.
+    show_std_band: bool,
+    average_color: Color32,
+    average_width: f32,
+    average_style: LinePattern,
+    /// Phase the average is given when exported as audio.
+    average_phase: Phase,
+    fill_under: bool,
+    fill_opacity: f32,
+    shade_bands: bool,
+    /// Plot the peak-hold envelope of each spectrum instead of every bin.
+    show_envelope: bool,
+    smoothing: bool,
+    /// Read out the active file at the exact hovered frequency rather than egui_plot's nearest
+    /// point.
+    interpolated_readout: bool,
+    /// Label the x axis with the wavelength of each frequency at `speed_of_sound` instead.
+    wavelength_axis: bool,
+    /// Overlay the active plot's phase, against a second y axis in degrees on the right.
+    show_phase: bool,
+    /// Phase of the plot at the index, recomputed whenever `plots` change.
+    #[serde(skip)]
+    phase: Option<(usize, Result<Vec<f32>, String>)>,
+    /// Overlay the active plot's group delay, against a y axis in ms of its own on the right.
+    show_group_delay: bool,
+    /// Group delay of the plot at the index in seconds, recomputed whenever `plots` change.
+    #[serde(skip)]
+    group_delay: Option<(usize, Result<Vec<f32>, String>)>,
+    /// Propagation speed in m/s for `wavelength_axis`, 343 for air at 20 °C.
+    speed_of_sound: f32,
+    /// Smoothing window of 1/`smoothing_bands` octave.
+    smoothing_bands: u32,
+    /// Width of the envelope's sliding maximum in Hz.
+    envelope_width: f32,
+    /// Draw the curves re-binned onto `log_bins_per_octave` bins per octave, see
+    /// [`envelope::log_bins`]. Exports keep every bin.
+    log_binning: bool,
+    log_bins_per_octave: u32,
+    log_pooling: Pooling,
+    /// Points of each plot with the settings they were built with, so that unchanged lines aren't
+    /// rebuilt every frame; see [`Self::refresh_points`].
+    #[serde(skip)]
+    point_cache: Vec<Option<CachedPoints>>,
+    /// [`Self::analysis_options`] the plots were last analysed with.
+    #[serde(skip)]
+    analysed: AnalysisOptions,
+    /// Opacity of the raw spectrum drawn under the envelope; 0 hides it.
+    raw_opacity: f32,
+    /// Bass/mid and mid/treble boundaries in Hz.
+    band_edges: [f32; 2],
+    window: Window,
+    use_welch: bool,
+    welch: Welch,
+    decimation: Decimation,
+    use_high_pass: bool,
+    /// High-pass cutoff in Hz, applied to the signal before analysis.
+    high_pass: f32,
+    use_low_pass: bool,
+    /// Low-pass cutoff in Hz, applied to the signal before analysis.
+    low_pass: f32,
+    use_detrend: bool,
+    /// Order of the polynomial trend removed from the signal before the filters, 1 for a ramp.
+    detrend_order: u32,
+    /// Skip the window's coherent gain correction.
+    raw_amplitudes: bool,
+    /// Show negative frequencies too, for complex or modulated signals.
+    two_sided: bool,
+    input_length: InputLength,
+    /// Samples for [`InputLength::Fixed`].
+    fixed_length: usize,
+    /// Zero-pads files whose FFT would be shorter than `min_fft_size`.
+    use_min_fft_size: bool,
+    min_fft_size: usize,
+    /// Longest single FFT, beyond which files are Welch-averaged or truncated.
+    sample_limit: SampleLimit,
+    /// Leading milliseconds of every file left out.
+    skip_ms: f32,
+    /// Analyses only `duration_ms` after the skipped part.
+    use_duration: bool,
+    duration_ms: f32,
+    precision: Precision,
+    show_window_shape: bool,
+    /// Problems from the last re-analysis, shown until the settings are changed again.
+    #[serde(skip)]
+    analysis_errors: Vec<String>,
+    /// Number of peaks reported per file.
+    peak_count: usize,
+    /// Minimum peak prominence as a fraction of the file's highest amplitude.
+    peak_prominence: f32,
+    /// Minimum distance between reported peaks in Hz.
+    peak_distance: f32,
+    /// Frequencies peaks and the fundamental are picked from.
+    peak_band: PeakBand,
+    export_format: ExportFormat,
+    export_edges: EdgeBins,
+    export_rounding: FrequencyRounding,
+    /// Bands and weighting of the band level table.
+    band_fraction: BandFraction,
+    band_weighting: Weighting,
+    /// Index into `plots` of the file keyboard navigation acts on.
+    #[serde(skip)]
+    active_plot: usize,
+    /// Peak selected with the `n`/`p` keys.
+    #[serde(skip)]
+    hopped_peak: Option<Peak>,
+    /// Peaks of the plot at the index with the peak count, prominence, distance and band they were
+    /// detected with, for the hover readout; recomputed when either changes.
+    #[serde(skip)]
+    hover_peaks: Option<(usize, usize, [f32; 4], Vec<Peak>)>,
+    /// Whether the view still has to be centered on `hopped_peak`.
+    #[serde(skip)]
+    center_on_peak: bool,
+    /// Set by the "Auto bounds" button to fit the plot to the visible data again.
+    #[serde(skip)]
+    reset_bounds: bool,
+    /// Set by the "Fit loudest" button to frame the range [`Self::fit_loudest_region`] picked on
+    /// the next frame.
+    #[serde(skip)]
+    fit_loudest: bool,
+    /// Horizontal or Shift+scroll over the plot pans `min_freq` and `max_freq`, and Ctrl+scroll
+    /// zooms them, instead of only the plot's view.
+    scroll_range: bool,
+    /// Keep the y axis fitted to the visible amplitudes, ignoring the bins around DC.
+    auto_y: bool,
+    /// Bins next to 0 Hz left out of the y range, 1 for only DC.
+    auto_y_skip_bins: usize,
+    /// Space above the highest amplitude in percent of it.
+    auto_y_headroom: f32,
+    /// Range the plot showed last frame, which PNG exports reproduce.
+    #[serde(skip)]
+    plot_bounds: Option<PlotBounds>,
+    /// Size of exported PNGs in pixels, independent of the window.
+    png_size: [u32; 2],
+    /// Resolution exported PNGs are drawn and tagged for; text and lines scale with it.
+    png_dpi: f32,
+    /// Heading above the spectrum plot, in PNG and matplotlib exports too; empty for none.
+    plot_title: String,
+    /// Axis labels of the spectrum plot; empty for ones describing what it shows.
+    x_label: String,
+    y_label: String,
+    /// Set from the command line; not persisted since it only applies to a specific dataset.
+    #[serde(skip)]
+    sample_type: Option<SampleType>,
+    normalization: Normalization,
+    amplitude_mode: AmplitudeMode,
+    negative_amplitudes: NegativeAmplitudes,
+    copy_content: CopyContent,
+    theme: Theme,
+    /// Overrides the theme's plot background.
+    plot_background: Option<Color32>,
+    /// Highest amplitude across all plots, for [`Normalization::Global`].
+    #[serde(skip)]
+    global_max: f32,
+    /// Plot each file as its deviation from the average in dB instead of its amplitude.
+    diff_from_average: bool,
+    /// Loaded from the config directory rather than persisted with the rest of the state.
+    #[serde(skip)]
+    presets: Vec<Preset>,
+    /// Name the current settings are saved under.
+    #[serde(skip)]
+    preset_name: String,
+    /// Indices into `plots` of the files compared as A − B, `None` once a picked file is removed.
+    #[serde(skip)]
+    heatmap_files: [Option<usize>; 2],
+    /// Differences beyond ±this many dB get the most saturated color.
+    heatmap_range_db: f32,
+    color_map: ColorMap,
+    #[serde(skip)]
+    heatmap: Option<Heatmap>,
+    /// Time in seconds of the STFT frame "Freeze frame" turns into a plot, picked by clicking
+    /// the heatmap.
+    #[serde(skip)]
+    frame_time: f32,
+    /// File names of plots hidden from the chart (they still count towards the average).
+    #[serde(skip)]
+    hidden: HashSet<String>,
+    /// File name of the only plot shown while soloing; overrides `hidden` and hides the average
+    /// without touching either, so turning it off restores the previous view.
+    #[serde(skip)]
+    solo: Option<String>,
+    /// Colors picked for individual files, by file name.
+    line_colors: HashMap<String, Color32>,
+    /// Weights of files in the average, by file name; 1 unless set. 0 leaves a file out of the
+    /// average while still drawing it.
+    average_weights: HashMap<String, f32>,
+    /// Frequency axis corrections of files, by file name; none unless set.
+    frequency_calibrations: HashMap<String, FrequencyCalibration>,
+    /// Write the calibrated frequencies in exports instead of the analysed ones.
+    calibrate_exports: bool,
+    average_domain: AverageDomain,
+    /// Only average and export the files that are shown, rather than hiding them from the plot
+    /// alone.
+    use_selection: bool,
+    /// Which plots the current `avg_plot` includes, to notice when the selection changes.
+    #[serde(skip)]
+    averaged: Vec<bool>,
+    /// Plot the open line context menu acts on.
+    #[serde(skip)]
+    context_plot: Option<usize>,
+    annotations: Vec<Annotation>,
+    /// Dropped with shift+click; they only last as long as the session.
+    #[serde(skip)]
+    markers: Vec<Marker>,
+    frequency_grid: FrequencyGrid,
+    /// Pitch of A4 in Hz the tuning readout measures against.
+    a4_reference: f32,
+    /// Estimated fundamental of the plot at the index, recomputed when either changes.
+    #[serde(skip)]
+    fundamental: Option<(usize, Option<Fundamental>)>,
+    /// Mark the multiples of the fundamental on the spectrum plot.
+    show_harmonics: bool,
+    /// Harmonics marked, counting the fundamental as the first.
+    harmonic_count: usize,
+    /// Mark the harmonics of `manual_fundamental` instead of the active plot's estimated one.
+    use_manual_fundamental: bool,
+    manual_fundamental: f32,
+    /// Scales every plot's frequencies so its fundamental lines up with the active plot's, to
+    /// compare timbre without tuning differences.
+    align_pitch: bool,
+    /// Plot the frequencies are currently aligned to with `align_pitch`.
+    #[serde(skip)]
+    pitch_reference: Option<usize>,
+    /// Pitch range in Hz the cepstrum peak is searched in.
+    cepstrum_range: [f32; 2],
+    /// Cepstrum of the plot at the index over frames of the length, recomputed when either changes.
+    #[serde(skip)]
+    cepstrum: Option<(usize, usize, Result<Cepstrum, String>)>,
+    /// Metadata chunks of each WAV file, read when first shown.
+    #[serde(skip)]
+    metadata: HashMap<PathBuf, Result<WavMetadata, String>>,
+    /// Reflection delays in ms a comb filter is searched for.
+    comb_delay_range: [f32; 2],
+    /// Comb filter of the plot at the index, with the delay range and frequency range searched.
+    #[serde(skip)]
+    comb: Option<(usize, [f32; 4], Option<Comb>)>,
+    /// From 0 to 1, see [`onsets::detect`].
+    onset_sensitivity: f32,
+    /// Onsets of the plot at the index with the sensitivity, recomputed when either changes.
+    #[serde(skip)]
+    onsets: Option<(usize, f32, Result<Onsets, String>)>,
+    /// Budget from `--memory-limit`, re-applied after every re-analysis.
+    #[serde(skip)]
+    memory_limit: Option<usize>,
+    /// Threads re-analysis runs on; `--threads` overrides the persisted value.
+    threads: usize,
+    /// Spectral features of each plot, recomputed whenever `plots` change.
+    #[serde(skip)]
+    features: Vec<Option<SpectralFeatures>>,
+    rank_metric: RankMetric,
+    /// Put the highest values of `rank_metric` first.
+    rank_descending: bool,
+    /// Pairwise spectral similarity of `plots`, cleared whenever they change.
+    #[serde(skip)]
+    similarity: Option<Vec<Vec<f32>>>,
+    /// Draws the files of two sources side by side instead of all in one plot.
+    split_view: bool,
+    /// Whether zooming or panning one side of the split view does the same to the other.
+    link_axes: bool,
+    /// Indices into [`Self::sources`] of the left and right side of the split view.
+    #[serde(skip)]
+    split_sources: [usize; 2],
+    /// Source the plot being drawn is limited to, while drawing one side of the split view.
+    #[serde(skip)]
+    split_panel: Option<String>,
+    /// Where the state is loaded from and saved to, see [`storage_key`].
+    #[serde(skip)]
+    storage_key: String,
+}
+
+impl Default for MyApp {
+    fn default() -> Self {
+        Self {
+            plots: Vec::new(),
+            avg_plot: PlotData::default(),
+            avg_std: Vec::new(),
+            frozen_average: None,
+            reference: None,
+            reference_path: String::new(),
+            peak_hold: false,
+            held_peaks: PlotData::default(),
+            stream_segments: Welch::default(),
+            stream_speed: 1.0,
+            stream_smoothing: 0.5,
+            stream: None,
+            mask_path: String::new(),
+            tones_path: String::new(),
+            tones: Vec::new(),
+            session_path: String::new(),
+            spl_calibrated: false,
+            spl_offset_db: 0.0,
+            calibrator_level_db: 94.0,
+            mask: None,
+            analyzers: Registry::default(),
+            file_stats: Vec::new(),
+            overloads: Vec::new(),
+            show_threshold: false,
+            threshold: 1.0,
+            threshold_grabbed: false,
+            mask_violations: Vec::new(),
+            min_freq: 0.0,
+            max_freq: 20_000.0,
+            max_freq_to_nyquist: true,
+            show_average: true,
+            show_std_band: true,
+            average_color: Color32::from_rgb(230, 160, 30),
+            average_width: 2.5,
+            average_style: LinePattern::Solid,
+            average_phase: Phase::default(),
+            fill_under: false,
+            fill_opacity: 0.2,
+            shade_bands: false,
+            show_envelope: false,
+            smoothing: false,
+            interpolated_readout: false,
+            wavelength_axis: false,
+            show_phase: false,
+            phase: None,
+            show_group_delay: false,
+            group_delay: None,
+            speed_of_sound: 343.0,
+            smoothing_bands: 6,
+            envelope_width: 100.0,
+            log_binning: false,
+            log_bins_per_octave: 48,
+            log_pooling: Pooling::default(),
+            point_cache: Vec::new(),
+            analysed: AnalysisOptions::default(),
+            raw_opacity: 0.3,
+            band_edges: [250.0, 4_000.0],
+            window: Window::default(),
+            use_welch: false,
+            welch: Welch::default(),
+            decimation: Decimation::default(),
+            use_high_pass: false,
+            high_pass: 20.0,
+            use_low_pass: false,
+            low_pass: 16_000.0,
+            use_detrend: false,
+            detrend_order: 1,
+            raw_amplitudes: false,
+            two_sided: false,
+            input_length: InputLength::default(),
+            fixed_length: 1 << 16,
+            use_min_fft_size: false,
+            min_fft_size: 4096,
+            sample_limit: SampleLimit::default(),
+            skip_ms: 0.0,
+            use_duration: false,
+            duration_ms: 1000.0,
+            precision: Precision::default(),
+            show_window_shape: false,
+            analysis_errors: Vec::new(),
+            peak_count: 10,
+            peak_prominence: 0.05,
+            peak_distance: 0.0,
+            peak_band: PeakBand::default(),
+            export_format: ExportFormat::default(),
+            export_edges: EdgeBins::default(),
+            export_rounding: FrequencyRounding::default(),
+            band_fraction: BandFraction::default(),
+            band_weighting: Weighting::default(),
+            active_plot: 0,
+            hopped_peak: None,
+            hover_peaks: None,
+            center_on_peak: false,
+            reset_bounds: false,
+            fit_loudest: false,
+            scroll_range: true,
+            auto_y: false,
+            auto_y_skip_bins: 3,
+            auto_y_headroom: 10.0,
+            plot_bounds: None,
+            png_size: [3000, 1800],
+            png_dpi: 300.0,
+            plot_title: String::new(),
+            x_label: String::new(),
+            y_label: String::new(),
+            sample_type: None,
+            diff_from_average: false,
+            normalization: Normalization::None,
+            amplitude_mode: AmplitudeMode::default(),
+            negative_amplitudes: NegativeAmplitudes::default(),
+            copy_content: CopyContent::default(),
+            theme: Theme::default(),
+            plot_background: None,
+            global_max: 0.0,
+            presets: Vec::new(),
+            preset_name: String::new(),
+            heatmap_files: [Some(0), Some(0)],
+            heatmap_range_db: 20.0,
+            frame_time: 0.0,
+            color_map: ColorMap::default(),
+            heatmap: None,
+            hidden: HashSet::new(),
+            solo: None,
+            line_colors: HashMap::new(),
+            average_weights: HashMap::new(),
+            frequency_calibrations: HashMap::new(),
+            calibrate_exports: false,
+            average_domain: AverageDomain::default(),
+            use_selection: false,
+            averaged: Vec::new(),
+            context_plot: None,
+            similarity: None,
+            annotations: Vec::new(),
+            markers: Vec::new(),
+            frequency_grid: FrequencyGrid::default(),
+            a4_reference: 440.0,
+            fundamental: None,
+            show_harmonics: false,
+            harmonic_count: 10,
+            use_manual_fundamental: false,
+            manual_fundamental: 100.0,
+            align_pitch: false,
+            pitch_reference: None,
+            cepstrum_range: [50.0, 2000.0],
+            cepstrum: None,
+            metadata: HashMap::new(),
+            comb_delay_range: [0.5, 50.0],
+            comb: None,
+            onset_sensitivity: 0.5,
+            onsets: None,
+            memory_limit: None,
+            threads: parallel::default_threads(),
+            features: Vec::new(),
+            rank_metric: RankMetric::default(),
+            rank_descending: true,
+            split_view: false,
+            link_axes: true,
+            split_sources: [0, 1],
+            split_panel: None,
+            storage_key: storage_key(None),
+        }
+    }
+}
+
+impl MyApp {
+    /// Called once before the first frame. `load_errors` are the files `main` couldn't load and
+    /// its warnings about the spectra it did.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        plots: Vec<PlotData>,
+        load_errors: Vec<String>,
+        args: &cli::Args,
+    ) -> Self {
+        let mut app = Self::restore(cc, args.profile.as_deref());
+        app.plots = plots;
+        app.sample_type = args.sample_type;
+        app.memory_limit = args.memory_limit;
+        if let Some(threads) = args.threads {
+            app.threads = threads;
+        }
+        // The config file and command line override the persisted settings, like `main` does.
+        let analysed = args.analysis_options();
+        if let Some(config) = &args.config {
+            app.set_analysis_options(analysed);
+            if let Some(size) = config.run.fft_len {
+                app.input_length = InputLength::Fixed;
+                app.fixed_length = size;
+            }
+            app.min_freq = config.run.min_freq.unwrap_or(app.min_freq);
+            if let Some(max_freq) = config.run.max_freq {
+                app.max_freq = max_freq;
+                app.max_freq_to_nyquist = false;
+            }
+            app.normalization = config.run.normalization.unwrap_or(app.normalization);
+        } else {
+            if let Some(precision) = args.precision {
+                app.precision = precision;
+            }
+            if let Some(skip_ms) = args.skip_ms {
+                app.skip_ms = skip_ms;
+            }
+            if let Some(duration_ms) = args.duration_ms {
+                app.use_duration = true;
+                app.duration_ms = duration_ms;
+            }
+        }
+        // `main` analyses with the default settings; redo it if different ones were persisted.
+        app.analysed = analysed;
+        app.reanalyze_if_changed();
+        app.update_average();
+        app.load_pinned();
+        match presets::load_presets() {
+            Ok(presets) => app.presets = presets,
+            Err(e) => app.analysis_errors.push(e),
+        }
+        app.analysis_errors.extend(load_errors);
+        if args.live {
+            let channel = args.channels.first().copied().unwrap_or_default();
+            app.start_live(args.raw_format, args.device.as_deref(), args.rate, channel);
+        }
+        if let Some(path) = &args.session {
+            app.session_path = path.display().to_string();
+            app.open_session(&cc.egui_ctx);
+        }
+        app
+    }
+
+    /// Called once before the first frame of the web app. There's no command line, files are
+    /// dropped onto the page instead, and no file system to load presets or pinned files from.
+    #[cfg(target_arch = "wasm32")]
+    pub fn web(cc: &eframe::CreationContext<'_>) -> Self {
+        Self::restore(cc, None)
+    }
+
+    /// The state persisted under `profile`'s [`storage_key`], or the defaults, with its theme set.
+    fn restore(cc: &eframe::CreationContext<'_>, profile: Option<&str>) -> Self {
+        // This is also where you can customize the look and feel of egui using
+        // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
+
+        // Load previous app state (if any).
+        // Note that you must enable the `persistence` feature for this to work.
+        let storage_key = storage_key(profile);
+        let mut app: Self = if let Some(storage) = cc.storage {
+            eframe::get_value(storage, &storage_key).unwrap_or_default()
+        } else {
+            Self::default()
+        };
+        app.storage_key = storage_key;
+
+        cc.egui_ctx.set_visuals(app.theme.visuals());
+        app
+    }
+
+    /// As a compressed [`Session`] with every spectrum, or as a [`RonSession`] listing the files
+    /// if `session_path` ends in `.ron`.
+    fn save_session(&mut self) {
+        if session::is_ron(Path::new(&self.session_path)) {
+            self.save_ron_session();
+            return;
+        }
+        let session = ron::to_string(&*self).map(|settings| Session {
+            settings,
+            sample_type: self.sample_type,
+            plots: self.plots.iter().map(SessionPlot::from).collect(),
+            hidden: self.hidden.clone(),
+            solo: self.solo.clone(),
+            active_plot: self.active_plot,
+        });
+        let path = Path::new(&self.session_path);
+        let written = session
+            .map_err(|e| e.to_string())
+            .and_then(|session| session::write(path, &session));
+        if let Err(e) = written {
+            self.analysis_errors.push(format!(
+                "Failed to save session {}: {}",
+                self.session_path, e
+            ));
+        }
+    }
+
+    /// Plots without a file to load again, like frozen frames, are left out with a warning.
+    fn save_ron_session(&mut self) {
+        let mut files = Vec::new();
+        let mut active_plot = 0;
+        for (i, plot_data) in self.plots.iter().enumerate() {
+            match SessionFile::new(plot_data) {
+                Some(file) => {
+                    if i == self.active_plot {
+                        active_plot = files.len();
+                    }
+                    files.push(file);
+                }
+                None => self.analysis_errors.push(format!(
+                    "Warning: {} has no file to load again, left out of {}",
+                    plot_data.file_name, self.session_path
+                )),
+            }
+        }
+        let session = RonSession {
+            settings: &*self,
+            sample_type: self.sample_type,
+            files,
+            hidden: self.hidden.clone(),
+            solo: self.solo.clone(),
+            active_plot,
+        };
+        if let Err(e) = session::write_ron(Path::new(&self.session_path), &session) {
+            self.analysis_errors.push(format!(
+                "Failed to save session {}: {}",
+                self.session_path, e
+            ));
+        }
+    }
+
+    /// Replaces the plots and settings with the ones saved in `session_path`. What belongs to
+    /// this window rather than the analysis, like the presets and the memory budget, is kept.
+    fn open_session(&mut self, ctx: &egui::Context) {
+        let path = Path::new(&self.session_path);
+        if session::is_ron(path) {
+            self.open_ron_session(ctx);
+            return;
+        }
+        let opened = session::read(path).and_then(|session| {
+            let app: Self = ron::from_str(&session.settings).map_err(|e| e.to_string())?;
+            Ok((session, app))
+        });
+        let (session, mut app) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.analysis_errors.push(format!(
+                    "Failed to open session {}: {}",
+                    self.session_path, e
+                ));
+                return;
+            }
+        };
+        app.plots = session
+            .plots
+            .into_iter()
+            .map(SessionPlot::into_plot_data)
+            .collect();
+        app.sample_type = session.sample_type;
+        app.hidden = session.hidden;
+        app.solo = session.solo;
+        app.active_plot = session.active_plot.min(app.plots.len().saturating_sub(1));
+        self.adopt_session(app, ctx);
+        // the session's plots come analysed with its settings
+        self.analysed = self.analysis_options();
+        self.enforce_memory_limit();
+        self.update_average();
+        self.load_pinned();
+    }
+
+    /// Loads and analyses the files a [`RonSession`] lists with its settings. Files that fail to
+    /// load are reported and left out.
+    fn open_ron_session(&mut self, ctx: &egui::Context) {
+        let session: RonSession<Self> = match session::read_ron(Path::new(&self.session_path)) {
+            Ok(session) => session,
+            Err(e) => {
+                self.analysis_errors.push(format!(
+                    "Failed to open session {}: {}",
+                    self.session_path, e
+                ));
+                return;
+            }
+        };
+        let mut app = session.settings;
+        let (options, sample_type) = (app.analysis_options(), session.sample_type);
+        let mut errors = Vec::new();
+        let mut active_plot = 0;
+        for (i, file) in session.files.iter().enumerate() {
+            let loaded = load_file(
+                &file.path,
+                options,
+                sample_type,
+                file.raw_format,
+                &[file.channel],
+            );
+            let mut plot_data = match loaded.map(|plots| plots.into_iter().next()) {
+                Ok(Some(plot_data)) => plot_data,
+                Ok(None) => continue,
+                Err(e) => {
+                    errors.push(format!("Failed to load {}: {}", file.path.display(), e));
+                    continue;
+                }
+            };
+            file.apply(&mut plot_data);
+            if file.changes_analysis() {
+                if let Err(e) = plot_data.reanalyze(options, sample_type) {
+                    errors.push(format!(
+                        "Failed to re-analyse {}: {}",
+                        plot_data.file_name, e
+                    ));
+                }
+            }
+            if i == session.active_plot {
+                active_plot = app.plots.len();
+            }
+            app.plots.push(plot_data);
+        }
+        app.sample_type = sample_type;
+        app.hidden = session.hidden;
+        app.solo = session.solo;
+        app.active_plot = active_plot;
+        self.adopt_session(app, ctx);
+        self.analysed = options;
+        self.analysis_errors = errors;
+        self.analysis_errors.extend(self.length_warnings());
+        self.enforce_memory_limit();
+        self.update_average();
+        self.load_pinned();
+    }
+
+    /// Replaces this app with one opened from a session, keeping what belongs to this window
+    /// rather than the analysis.
+    fn adopt_session(&mut self, mut app: Self, ctx: &egui::Context) {
+        app.session_path = std::mem::take(&mut self.session_path);
+        app.storage_key = std::mem::take(&mut self.storage_key);
+        app.presets = std::mem::take(&mut self.presets);
+        app.memory_limit = self.memory_limit;
+        app.threads = self.threads;
+        *self = app;
+        ctx.set_visuals(self.theme.visuals());
+    }
+
+    /// Loads the reference, mask and tones the settings point to, if any.
+    fn load_pinned(&mut self) {
+        if !self.reference_path.is_empty() {
+            self.load_reference();
+        }
+        if !self.mask_path.is_empty() {
+            self.load_mask();
+        }
+        if !self.tones_path.is_empty() {
+            self.load_tones();
+        }
+    }
+
+    fn current_preset(&self) -> Preset {
+        Preset {
+            name: self.preset_name.clone(),
+            analysis: self.analysis_options(),
+            min_freq: self.min_freq,
+            max_freq: self.max_freq,
+            normalization: self.normalization,
+        }
+    }
+
+    /// Saves the current settings, replacing a preset of the same name.
+    fn save_preset(&mut self) {
+        let preset = self.current_preset();
+        match self.presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+        if let Err(e) = presets::save_presets(&self.presets) {
+            self.analysis_errors
+                .push(format!("Failed to save presets: {}", e));
+        }
+    }
+
+    fn apply_preset(&mut self, preset: Preset) {
+        let previous = self.analysis_options();
+        self.set_analysis_options(preset.analysis);
+        let analysis_changed = self.analysis_options() != previous;
+        self.min_freq = preset.min_freq;
+        self.max_freq = preset.max_freq;
+        self.max_freq_to_nyquist = false;
+        self.normalization = preset.normalization;
+        self.preset_name = preset.name;
+        if analysis_changed {
+            self.reanalyze();
+        } else {
+            self.update_average();
+        }
+    }
+
+    fn load_reference(&mut self) {
+        match read_f(Path::new(&self.reference_path)) {
+            Ok((freqs, amplitudes)) => {
+                self.reference = Some(PlotData {
+                    freqs,
+                    amplitudes,
+                    file_name: self.reference_path.clone(),
+                    ..Default::default()
+                });
+                self.follow_nyquist();
+            }
+            Err(e) => {
+                self.reference = None;
+                self.analysis_errors.push(format!(
+                    "Failed to load reference {}: {}",
+                    self.reference_path, e
+                ));
+            }
+        }
+    }
+
+    fn load_mask(&mut self) {
+        match Mask::read_csv(Path::new(&self.mask_path)) {
+            Ok(mask) => self.mask = Some(mask),
+            Err(e) => {
+                self.mask = None;
+                self.analysis_errors
+                    .push(format!("Failed to load mask {}: {}", self.mask_path, e));
+            }
+        }
+        self.check_masks();
+    }
+
+    fn load_tones(&mut self) {
+        match tones::read_csv(Path::new(&self.tones_path)) {
+            Ok(tones) => self.tones = tones,
+            Err(e) => {
+                self.tones.clear();
+                self.analysis_errors
+                    .push(format!("Failed to load tones {}: {}", self.tones_path, e));
+            }
+        }
+    }
+
+    /// The expected tones, with the detected peak of the active file nearest to each.
+    fn tones_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Tones CSV:");
+            ui.text_edit_singleline(&mut self.tones_path)
+                .on_hover_text("frequency,label rows, the label being optional");
+            if ui.button("Load tones").clicked() {
+                self.load_tones();
+            }
+            if !self.tones.is_empty() && ui.button("Clear tones").clicked() {
+                self.tones.clear();
+                self.tones_path.clear();
+            }
+        });
+        if self.tones.is_empty() {
+            return;
+        }
+        let peaks = self
+            .plots
+            .get(self.active_plot)
+            .map(|plot_data| self.detect_peaks(plot_data));
+        egui::Grid::new("tones").striped(true).show(ui, |ui| {
+            ui.strong("Tone");
+            ui.strong("Frequency");
+            ui.strong("Nearest peak");
+            ui.end_row();
+            for tone in &self.tones {
+                ui.colored_label(TONE_COLOR, &tone.label);
+                ui.label(format!("{:.2} Hz", tone.frequency));
+                let nearest = peaks.iter().flatten().min_by(|a, b| {
+                    let distance = |peak: &&Peak| (peak.frequency - tone.frequency).abs();
+                    distance(a).total_cmp(&distance(b))
+                });
+                match nearest {
+                    Some(peak) => ui.label(format!(
+                        "{:.2} Hz ({:+.2} Hz)",
+                        peak.frequency,
+                        peak.frequency - tone.frequency
+                    )),
+                    None => ui.weak("no peaks"),
+                };
+                ui.end_row();
+            }
+        });
+    }
+
+    fn check_masks(&mut self) {
+        self.mask_violations = match &self.mask {
+            Some(mask) => self
+                .plots
+                .iter()
+                .map(|plot_data| mask::check_mask(plot_data, mask))
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    /// The threshold, and where each shown spectrum rises above it.
+    fn threshold_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.show_threshold, "Show threshold");
+            let drag = egui::DragValue::new(&mut self.threshold).speed(0.01);
+            ui.add_enabled(self.show_threshold, drag).on_hover_text(
+                "In the units of the y axis; the line can be dragged on the plot too",
+            );
+        });
+        if !self.show_threshold {
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                egui::Grid::new("threshold_crossings")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("File");
+                        ui.strong("Above from");
+                        ui.strong("to");
+                        ui.strong("Highest");
+                        ui.end_row();
+                        for (i, plot_data) in self.plots.iter().enumerate() {
+                            if !self.is_visible(plot_data) || !self.in_panel(plot_data) {
+                                continue;
+                            }
+                            let crossings =
+                                crossings(&self.cached_points(i), self.threshold as f64);
+                            if crossings.is_empty() {
+                                ui.label(plot_data.label());
+                                ui.weak("below everywhere");
+                                ui.end_row();
+                            }
+                            for crossing in crossings {
+                                ui.label(plot_data.label());
+                                ui.label(format!("{:.1} Hz", crossing.start));
+                                ui.label(format!("{:.1} Hz", crossing.end));
+                                let PlotPoint { x, y } = crossing.highest;
+                                ui.label(format!("{:.4} at {:.1} Hz", y, x));
+                                ui.end_row();
+                            }
+                        }
+                    });
+            });
+    }
+
+    /// The tolerance mask file and whether each plot stays inside it.
+    fn mask_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Mask CSV:");
+            ui.text_edit_singleline(&mut self.mask_path).on_hover_text(
+                "frequency,lower,upper rows in the units of Export CSV; empty for no limit",
+            );
+            if ui.button("Load mask").clicked() {
+                self.load_mask();
+            }
+            if self.mask.is_some() && ui.button("Clear mask").clicked() {
+                self.mask = None;
+                self.mask_path.clear();
+                self.check_masks();
+            }
+        });
+        if self.mask.is_none() {
+            return;
+        }
+        egui::Grid::new("mask_results")
+            .striped(true)
+            .show(ui, |ui| {
+                for (plot_data, violations) in self.plots.iter().zip(&self.mask_violations) {
+                    ui.label(plot_data.label());
+                    let Some(worst) = violations
+                        .iter()
+                        .max_by(|a, b| a.worst_db.total_cmp(&b.worst_db))
+                    else {
+                        ui.colored_label(PASS_COLOR, "PASS");
+                        ui.end_row();
+                        continue;
+                    };
+                    ui.colored_label(VIOLATION_COLOR, "FAIL");
+                    let side = match worst.side {
+                        Side::Above => "above",
+                        Side::Below => "below",
+                    };
+                    ui.label(format!(
+                        "{} regions, worst {:.1} dB {} at {:.1} Hz",
+                        violations.len(),
+                        worst.worst_db,
+                        side,
+                        worst.worst_frequency
+                    ));
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Limits of the mask as drawn, one line per stretch where that side is limited.
+    fn mask_lines(&self, mask: &Mask) -> Vec<Vec<PlotPoint>> {
+        let mut lines = Vec::new();
+        for limit in [
+            |point: &mask::MaskPoint| point.lower,
+            |point: &mask::MaskPoint| point.upper,
+        ] {
+            let mut line: Vec<PlotPoint> = Vec::new();
+            for point in &mask.points {
+                match limit(point) {
+                    Some(level) => {
+                        let y = level * self.amplitude_mode.weight(point.frequency);
+                        line.push(PlotPoint::new(point.frequency, y));
+                    }
+                    None => lines.push(std::mem::take(&mut line)),
+                }
+            }
+            lines.push(line);
+        }
+        lines.retain(|line| line.len() >= 2);
+        lines
+    }
+
+    fn detect_peaks(&self, plot_data: &PlotData) -> Vec<Peak> {
+        peaks::find_peaks(
+            &plot_data.freqs,
+            &plot_data.amplitudes,
+            self.peak_band,
+            self.peak_count,
+            self.peak_prominence * max_amplitude(plot_data),
+            self.peak_distance,
+        )
+    }
+
+    fn export_peaks(&self) -> Result<(), String> {
+        let labels: Vec<String> = self.plots.iter().map(PlotData::label).collect();
+        let peaks: Vec<_> = self
+            .plots
+            .iter()
+            .zip(&labels)
+            .map(|(plot_data, label)| (label.as_str(), self.exported_peaks(plot_data)))
+            .collect();
+        let path = format!("peaks.{}", self.export_format.extension());
+        export::write_peaks(Path::new(&path), self.export_format, &peaks)
+    }
+
+    /// Factor applied to the amplitudes of `plot_data` for the selected normalization, on top of
+    /// the [`AmplitudeMode`] weight.
+    fn display_scale(&self, plot_data: &PlotData) -> f32 {
+        match self.normalization {
+            Normalization::None => 1.0,
+            Normalization::PerFile => inverse_or_one(self.weighted_max(plot_data)),
+            Normalization::PerPeak => inverse_or_one(self.strongest_peak(plot_data)),
+            Normalization::Global => inverse_or_one(self.global_max),
+        }
+    }
+
+    /// Highest amplitude of `plot_data` as displayed in the selected [`AmplitudeMode`].
+    fn weighted_max(&self, plot_data: &PlotData) -> f32 {
+        plot_data
+            .freqs
+            .iter()
+            .zip(&plot_data.amplitudes)
+            .map(|(&freq, &amp)| {
+                self.negative_amplitudes.apply(amp) * self.amplitude_mode.weight(freq)
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Highest local maximum of `plot_data` between `min_freq` and `max_freq` as displayed in the
+    /// selected [`AmplitudeMode`], leaving out DC and bins still rising at the edges of the range.
+    fn strongest_peak(&self, plot_data: &PlotData) -> f32 {
+        let (freqs, amplitudes) = (&plot_data.freqs, &plot_data.amplitudes);
+        let weighted = |i: usize| {
+            self.negative_amplitudes.apply(amplitudes[i]) * self.amplitude_mode.weight(freqs[i])
+        };
+        let len = freqs.len().min(amplitudes.len());
+        (1..len.saturating_sub(1))
+            .filter(|&i| freqs[i] != 0.0 && freqs[i] >= self.min_freq && freqs[i] <= self.max_freq)
+            .filter(|&i| weighted(i) >= weighted(i - 1) && weighted(i) >= weighted(i + 1))
+            .map(weighted)
+            .fold(0.0, f32::max)
+    }
+
+    /// Finest and coarsest bin width in Hz across the plots, after decimation and pitch scaling.
+    fn resolution(&self) -> Option<(f32, f32)> {
+        self.plots
+            .iter()
+            .filter(|plot_data| plot_data.freqs.len() >= 2)
+            .map(|plot_data| {
+                let freqs = &plot_data.freqs;
+                (freqs[freqs.len() - 1] - freqs[0]) / (freqs.len() - 1) as f32
+            })
+            .fold(None, |range, width| match range {
+                Some((finest, coarsest)) => Some((width.min(finest), width.max(coarsest))),
+                None => Some((width, width)),
+            })
+    }
+
+    /// Moves to the next (`forward`) or previous prominent peak of the active plot, wrapping
+    /// around at the ends.
+    fn hop_peak(&mut self, forward: bool) {
+        let Some(plot_data) = self.plots.get(self.active_plot) else {
+            return;
+        };
+        let min_prominence = self.peak_prominence * max_amplitude(plot_data);
+        let (freqs, amplitudes) = (&plot_data.freqs, &plot_data.amplitudes);
+        let (band, distance) = (self.peak_band, self.peak_distance);
+        let mut peaks = peaks::find_peaks(
+            freqs,
+            amplitudes,
+            band,
+            usize::MAX,
+            min_prominence,
+            distance,
+        );
+        if peaks.is_empty() {
+            self.hopped_peak = None;
+            return;
+        }
+        peaks.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+        let current = self.hopped_peak.map(|peak| peak.frequency);
+        let next = match (current, forward) {
+            (Some(freq), true) => peaks
+                .iter()
+                .find(|p| p.frequency > freq)
+                .unwrap_or(&peaks[0]),
+            (Some(freq), false) => peaks
+                .iter()
+                .rev()
+                .find(|p| p.frequency < freq)
+                .unwrap_or(&peaks[peaks.len() - 1]),
+            (None, true) => &peaks[0],
+            (None, false) => &peaks[peaks.len() - 1],
+        };
+        self.hopped_peak = Some(*next);
+        self.center_on_peak = true;
+    }
+
+    fn plot_background(&self) -> Color32 {
+        self.plot_background
+            .unwrap_or(self.theme.visuals().extreme_bg_color)
+    }
+
+    fn plot_color(&self, index: usize) -> Color32 {
+        let file_name = &self.plots[index].file_name;
+        let background = self.plot_background();
+        self.line_colors
+            .get(file_name)
+            .copied()
+            .unwrap_or_else(|| line_color(index, background))
+    }
+
+    /// What the chart shows for `plot_data`: its amplitudes or its deviation from the average.
+    fn raw_points(&self, plot_data: &PlotData) -> Vec<PlotPoint> {
+        if self.showing_difference() {
+            self.difference_points(plot_data)
+        } else {
+            self.display_points(plot_data)
+        }
+    }
+
+    /// The main curve of `plot_data`: [`Self::raw_points`], smoothed and/or their envelope, and
+    /// log-binned.
+    fn plot_points(&self, plot_data: &PlotData) -> Vec<PlotPoint> {
+        self.shape_points(self.raw_points(plot_data))
+    }
+
+    /// Smooths, envelopes and log-bins raw `points` as set, see [`Self::plot_points`].
+    fn shape_points(&self, mut points: Vec<PlotPoint>) -> Vec<PlotPoint> {
+        if self.smoothing {
+            points = envelope::octave_smooth(&points, self.smoothing_bands as f64);
+        }
+        if self.show_envelope {
+            points = envelope::upper_envelope(&points, self.envelope_width as f64);
+        }
+        if self.log_binning {
+            points = envelope::log_bins(&points, self.log_bins_per_octave as f64, self.log_pooling);
+        }
+        points
+    }
+
+    fn points_key(&self, plot_data: &PlotData) -> PointsKey {
+        PointsKey {
+            freq_range: [self.min_freq, self.max_freq],
+            normalization: self.normalization,
+            global_max: self.global_max,
+            amplitude_mode: self.amplitude_mode,
+            negative_amplitudes: self.negative_amplitudes,
+            calibration: self.calibration(plot_data),
+            difference: self.showing_difference(),
+            smoothing_bands: self.smoothing.then_some(self.smoothing_bands),
+            envelope_width: self.show_envelope.then_some(self.envelope_width),
+            log_bins: self
+                .log_binning
+                .then_some((self.log_bins_per_octave, self.log_pooling)),
+        }
+    }
+
+    /// Rebuilds the cached points of the visible plots whose settings changed since, once per
+    /// frame before drawing.
+    fn refresh_points(&mut self) {
+        self.point_cache.resize(self.plots.len(), None);
+        for (i, plot_data) in self.plots.iter().enumerate() {
+            if !self.is_visible(plot_data) {
+                continue;
+            }
+            let key = self.points_key(plot_data);
+            if !matches!(&self.point_cache[i], Some(cached) if cached.key == key) {
+                self.point_cache[i] = Some(self.build_points(plot_data, key));
+            }
+        }
+    }
+
+    fn build_points(&self, plot_data: &PlotData, key: PointsKey) -> CachedPoints {
+        let raw = self.raw_points(plot_data);
+        let kept_raw = (self.smoothing || self.show_envelope).then(|| Arc::from(raw.as_slice()));
+        CachedPoints {
+            key,
+            points: self.shape_points(raw).into(),
+            raw: kept_raw,
+        }
+    }
+
+    /// [`CachedPoints`] of the plot at `index`, from the cache while they're current.
+    fn line_points(&self, index: usize) -> Cow<'_, CachedPoints> {
+        let plot_data = &self.plots[index];
+        let key = self.points_key(plot_data);
+        match self.point_cache.get(index) {
+            Some(Some(cached)) if cached.key == key => Cow::Borrowed(cached),
+            _ => Cow::Owned(self.build_points(plot_data, key)),
+        }
+    }
+
+    /// [`Self::plot_points`] of the plot at `index`, from the cache while they're current.
+    fn cached_points(&self, index: usize) -> Arc<[PlotPoint]> {
+        self.line_points(index).points.clone()
+    }
+
+    /// Index of the visible plot line closest to the screen position `pos`, if any is within a few
+    /// pixels.
+    fn line_near(&self, pos: egui::Pos2, transform: &PlotTransform) -> Option<usize> {
+        const MAX_DISTANCE: f32 = 8.0;
+        let mut nearest = None;
+        let mut nearest_distance = MAX_DISTANCE;
+        for (i, plot_data) in self.plots.iter().enumerate() {
+            if !self.is_visible(plot_data) || !self.in_panel(plot_data) {
+                continue;
+            }
+            let screen: Vec<_> = self
+                .cached_points(i)
+                .iter()
+                .map(|p| transform.position_from_point(p))
+                .collect();
+            for segment in screen.windows(2) {
+                let distance = egui::emath::Rect::from_two_pos(segment[0], segment[1])
+                    .expand(MAX_DISTANCE)
+                    .contains(pos)
+                    .then(|| distance_to_segment(pos, segment[0], segment[1]));
+                if let Some(distance) = distance.filter(|&d| d < nearest_distance) {
+                    nearest_distance = distance;
+                    nearest = Some(i);
+                }
+            }
+        }
+        nearest
+    }
+
+    fn is_visible(&self, plot_data: &PlotData) -> bool {
+        match &self.solo {
+            Some(solo) => *solo == plot_data.file_name,
+            None => !self.hidden.contains(&plot_data.file_name),
+        }
+    }
+
+    /// Whether the plot belongs on the side of the split view being drawn; always outside of it.
+    fn in_panel(&self, plot_data: &PlotData) -> bool {
+        self.split_panel
+            .as_ref()
+            .is_none_or(|source| *source == plot_data.source)
+    }
+
+    /// Shows only the plot at `index`, or everything again if it's already soloed.
+    fn toggle_solo(&mut self, index: usize) {
+        let Some(file_name) = self.plots.get(index).map(|p| &p.file_name) else {
+            return;
+        };
+        self.solo = if self.solo.as_ref() == Some(file_name) {
+            None
+        } else {
+            Some(file_name.clone())
+        };
+    }
+
+    /// Right-click menu of a plot line.
+    fn line_context_menu(&mut self, ui: &mut Ui, index: usize) {
+        let Some(file_name) = self.plots.get(index).map(|p| p.file_name.clone()) else {
+            ui.close_menu();
+            return;
+        };
+        ui.label(&file_name);
+        ui.horizontal(|ui| {
+            ui.label("Label:");
+            let plot_data = &mut self.plots[index];
+            let mut label = plot_data.label.clone().unwrap_or_default();
+            let hint = plot_data.label();
+            if ui
+                .add(egui::TextEdit::singleline(&mut label).hint_text(hint))
+                .changed()
+            {
+                plot_data.label = Some(label).filter(|label| !label.trim().is_empty());
+            }
+        });
+        ui.separator();
+        if ui.button("Hide").clicked() {
+            self.hidden.insert(file_name.clone());
+            ui.close_menu();
+        }
+        let soloed = self.solo.as_ref() == Some(&file_name);
+        if ui.button(if soloed { "Unsolo" } else { "Solo" }).clicked() {
+            self.toggle_solo(index);
+            ui.close_menu();
+        }
+        if !self.hidden.is_empty() && ui.button("Show all").clicked() {
+            self.hidden.clear();
+            ui.close_menu();
+        }
+        if ui.button("Export CSV").clicked() {
+            let stem = Path::new(&file_name)
+                .file_stem()
+                .map_or("spectrum".into(), |s| s.to_string_lossy());
+            let path = format!("{}.csv", stem);
+            let (freqs, amplitudes) = self.exported_spectrum(&self.plots[index]);
+            if let Err(e) = export::write_spectrum_csv(Path::new(&path), &freqs, &amplitudes) {
+                self.analysis_errors
+                    .push(format!("Failed to export {}: {}", path, e));
+            }
+            ui.close_menu();
+        }
+        if ui.button("Save spectrum (.f.gz)").clicked() {
+            let stem = Path::new(&file_name)
+                .file_stem()
+                .map_or("spectrum".into(), |s| s.to_string_lossy());
+            let path = format!("{}.f.gz", stem);
+            if let Err(e) = write_f(Path::new(&path), &self.plots[index]) {
+                self.analysis_errors
+                    .push(format!("Failed to save {}: {}", path, e));
+            }
+            ui.close_menu();
+        }
+        if self.plots[index].sample_rate.is_some() && ui.button("Export FFT input").clicked() {
+            let stem = Path::new(&file_name)
+                .file_stem()
+                .map_or("spectrum".into(), |s| s.to_string_lossy());
+            let path = format!("{}_fft_input.wav", stem);
+            let result = self.plots[index]
+                .fft_input(self.analysis_options(), self.sample_type)
+                .and_then(|(samples, rate)| wav::write_float_wav(Path::new(&path), &samples, rate));
+            if let Err(e) = result {
+                self.analysis_errors
+                    .push(format!("Failed to export {}: {}", path, e));
+            }
+            ui.close_menu();
+        }
+        if self.plots[index].sample_rate.is_some() {
+            ui.menu_button("Export spectrogram", |ui| {
+                let formats: [(&str, &str, SpectrogramWriter); 2] = [
+                    ("NumPy (.npy)", "npy", export::write_spectrogram_npy),
+                    ("CSV grid", "csv", export::write_spectrogram_csv),
+                ];
+                for (label, extension, write) in formats {
+                    if ui.button(label).clicked() {
+                        self.export_spectrogram(index, extension, write);
+                        ui.close_menu();
+                    }
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            let mut color = self.plot_color(index);
+            if ui.color_edit_button_srgba(&mut color).changed() {
+                self.line_colors.insert(file_name.clone(), color);
+            }
+            ui.label("Color");
+        });
+        let opacity = &mut self.plots[index].opacity;
+        ui.add(egui::Slider::new(opacity, 0.05..=1.0).text("Opacity"));
+        if ui.button("Remove").clicked() {
+            self.remove_plot(index);
+            ui.close_menu();
+        }
+    }
+
+    /// Writes the spectrogram of a plot, segmented like Welch averaging, to
+    /// `<stem>_spectrogram.<extension>`.
+    fn export_spectrogram(&mut self, index: usize, extension: &str, write: SpectrogramWriter) {
+        let plot_data = &self.plots[index];
+        let file_name = Path::new(&plot_data.file_name);
+        let stem = file_name
+            .file_stem()
+            .map_or("spectrum".into(), |s| s.to_string_lossy());
+        let path = format!("{}_spectrogram.{}", stem, extension);
+        let window = plot_data.window.unwrap_or(self.window);
+        let result = plot_data
+            .spectrogram(window, self.welch, self.precision, self.sample_type)
+            .and_then(|spectrogram| write(Path::new(&path), &spectrogram));
+        if let Err(e) = result {
+            self.analysis_errors
+                .push(format!("Failed to export {}: {}", path, e));
+        }
+    }
+
+    /// Adds a file dropped onto the window. Browsers only hand over its contents, desktops its
+    /// path.
+    fn add_dropped(&mut self, file: &egui::DroppedFile) -> Result<(), String> {
+        let (options, sample_type) = (self.analysis_options(), self.sample_type);
+        let plots = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => vec![load_wav_bytes(bytes, &file.name, options, sample_type)?],
+            (None, Some(path)) if is_supported(path) => {
+                load_file(path, options, sample_type, None, &[Channel::Interleaved])?
+            }
+            (None, Some(_)) => return Err("Unsupported file format".to_string()),
+            (None, None) => return Err("Neither a path nor contents were dropped".to_string()),
+        };
+        self.plots.extend(plots);
+        Ok(())
+    }
+
+    /// The bins of `plot_data` that spectrum exports write, see [`EdgeBins`].
+    fn export_bins(&self, plot_data: &PlotData) -> (Vec<f32>, Vec<f32>) {
+        let fft_size = plot_data.timings.fft_size;
+        let amplitudes: Vec<f32> = plot_data
+            .amplitudes
+            .iter()
+            .map(|&amp| self.negative_amplitudes.apply(amp))
+            .collect();
+        self.export_edges
+            .apply(&plot_data.freqs, &amplitudes, fft_size)
+    }
+
+    /// The bins spectrum exports write, with their frequencies rounded as selected.
+    fn exported_spectrum(&self, plot_data: &PlotData) -> (Vec<f32>, Vec<f32>) {
+        let (freqs, amplitudes) = self.export_bins(plot_data);
+        (
+            freqs
+                .into_iter()
+                .map(|freq| self.exported_frequency(plot_data, freq))
+                .collect(),
+            amplitudes,
+        )
+    }
+
+    fn exported_peaks(&self, plot_data: &PlotData) -> Vec<Peak> {
+        let mut peaks = self.detect_peaks(plot_data);
+        for peak in &mut peaks {
+            peak.frequency = self.exported_frequency(plot_data, peak.frequency);
+        }
+        peaks
+    }
+
+    /// `freq` of `plot_data` rounded for export, and calibrated with `calibrate_exports`.
+    fn exported_frequency(&self, plot_data: &PlotData, freq: f32) -> f32 {
+        let freq = if self.calibrate_exports {
+            self.calibration(plot_data).apply(freq)
+        } else {
+            freq
+        };
+        self.export_rounding.apply(freq)
+    }
+
+    fn remove_plot(&mut self, index: usize) {
+        let removed = self.plots.remove(index);
+        self.hidden.remove(&removed.file_name);
+        if self.solo.as_ref() == Some(&removed.file_name) {
+            self.solo = None;
+        }
+        let shift = |i: &mut usize| {
+            if *i > index {
+                *i -= 1;
+            }
+        };
+        if self.active_plot == index {
+            self.hopped_peak = None;
+        }
+        shift(&mut self.active_plot);
+        for slot in &mut self.heatmap_files {
+            match slot {
+                Some(i) if *i == index => *slot = None,
+                Some(i) => shift(i),
+                None => {}
+            }
+        }
+        if self
+            .heatmap
+            .as_ref()
+            .is_some_and(|heatmap| heatmap.files.contains(&index))
+        {
+            self.heatmap = None;
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.files.iter_mut().for_each(shift);
+        }
+        self.active_plot = self.active_plot.min(self.plots.len().saturating_sub(1));
+        self.update_average();
+    }
+
+    /// Every loaded file with its path and frequency calibration, to pick the active one or drop
+    /// files from the set.
+    fn files_view(&mut self, ui: &mut Ui) {
+        let mut removed = None;
+        egui::Grid::new("files").striped(true).show(ui, |ui| {
+            ui.label("");
+            ui.label("Offset")
+                .on_hover_text("Added to the frequencies shown, after the scale");
+            ui.label("Scale")
+                .on_hover_text("Factor the frequencies shown are multiplied by");
+            ui.end_row();
+            for (i, plot_data) in self.plots.iter().enumerate() {
+                let label = ui.selectable_label(i == self.active_plot, plot_data.label());
+                if label.on_hover_text(&plot_data.file_name).clicked() && i != self.active_plot {
+                    self.active_plot = i;
+                    self.hopped_peak = None;
+                }
+                let calibrations = &mut self.frequency_calibrations;
+                let mut calibration = calibrations
+                    .get(&plot_data.file_name)
+                    .copied()
+                    .unwrap_or_default();
+                let offset = egui::DragValue::new(&mut calibration.offset)
+                    .speed(1.0)
+                    .suffix(" Hz");
+                let scale = egui::DragValue::new(&mut calibration.scale)
+                    .range(0.001..=1000.0)
+                    .speed(0.0001);
+                if ui.add(offset).changed() | ui.add(scale.prefix("×")).changed() {
+                    if calibration == FrequencyCalibration::default() {
+                        calibrations.remove(&plot_data.file_name);
+                    } else {
+                        calibrations.insert(plot_data.file_name.clone(), calibration);
+                    }
+                }
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = removed {
+            self.remove_plot(i);
+        }
+        ui.checkbox(
+            &mut self.calibrate_exports,
+            "Calibrated frequencies in exports",
+        )
+        .on_hover_text("Export the frequencies as shown instead of as analysed");
+    }
+
+    fn calibration(&self, plot_data: &PlotData) -> FrequencyCalibration {
+        self.frequency_calibrations
+            .get(&plot_data.file_name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// `freq` of the plot at `index` as shown, after its [`FrequencyCalibration`].
+    fn shown_frequency(&self, index: usize, freq: f32) -> f32 {
+        self.plots
+            .get(index)
+            .map_or(freq, |plot_data| self.calibration(plot_data).apply(freq))
+    }
+
+    /// Points of `plot_data` inside the frequency range, normalized as selected, at its
+    /// calibrated frequencies.
+    fn display_points(&self, plot_data: &PlotData) -> Vec<PlotPoint> {
+        let scale = self.display_scale(plot_data);
+        let calibration = self.calibration(plot_data);
+        plot_data
+            .freqs
+            .iter()
+            .map(|&freq| calibration.apply(freq))
+            .zip(plot_data.amplitudes.iter())
+            .filter(|&(freq, _)| freq >= self.min_freq && freq <= self.max_freq)
+            .map(|(freq, &amp)| {
+                let amp = self.negative_amplitudes.apply(amp);
+                PlotPoint::new(freq, amp * scale * self.amplitude_mode.weight(freq))
+            })
+            .collect()
+    }
+
+    /// Upper and lower edge of the average ± one standard deviation, scaled like
+    /// [`Self::display_points`].
+    fn std_band_points(&self) -> (Vec<PlotPoint>, Vec<PlotPoint>) {
+        let scale = self.display_scale(&self.avg_plot);
+        let avg = &self.avg_plot;
+        avg.freqs
+            .iter()
+            .zip(&avg.amplitudes)
+            .zip(&self.avg_std)
+            .filter(|((&freq, _), _)| freq >= self.min_freq && freq <= self.max_freq)
+            .map(|((&freq, &amp), &std)| {
+                let factor = scale * self.amplitude_mode.weight(freq);
+                let upper = PlotPoint::new(freq, (amp + std) * factor);
+                let lower = PlotPoint::new(freq, (amp - std).max(0.0) * factor);
+                (upper, lower)
+            })
+            .unzip()
+    }
+
+    /// Points of `plot_data` as deviation from the average in dB. Normalization doesn't apply here
+    /// since it would only shift the whole curve.
+    fn difference_points(&self, plot_data: &PlotData) -> Vec<PlotPoint> {
+        let avg = self.avg_plot.resample(&plot_data.freqs);
+        let calibration = self.calibration(plot_data);
+        plot_data
+            .freqs
+            .iter()
+            .map(|&freq| calibration.apply(freq))
+            .zip(plot_data.amplitudes.iter())
+            .zip(avg)
+            .filter(|&((freq, _), _)| freq >= self.min_freq && freq <= self.max_freq)
+            .map(|((freq, &amp), avg)| PlotPoint::new(freq, db_difference(amp, avg.unwrap_or(0.0))))
+            .collect()
+    }
+
+    /// Small inset with the window function over one segment, and with Welch averaging its
+    /// neighbouring segments too, so the overlap is visible.
+    fn window_shape_plot(&self, ui: &mut Ui) {
+        const POINTS: usize = 256;
+        let coefficients = self.window.coefficients(POINTS);
+        // Offsets in units of the segment length.
+        let offsets: Vec<f64> = match self.use_welch.then_some(self.welch) {
+            Some(welch) if welch.hop > 0 && welch.segment_len > 0 => {
+                let step = welch.hop as f64 / welch.segment_len as f64;
+                (0..)
+                    .map(|i| i as f64 * step)
+                    .take_while(|&o| o < 2.0)
+                    .collect()
+            }
+            _ => vec![0.0],
+        };
+        Plot::new("window_shape")
+            .height(120.0)
+            .width(320.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .include_y(0.0)
+            .include_y(1.0)
+            .show(ui, |plot_ui| {
+                for (i, offset) in offsets.iter().enumerate() {
+                    let points: Vec<_> = coefficients
+                        .iter()
+                        .enumerate()
+                        .map(|(n, &c)| PlotPoint::new(offset + n as f64 / (POINTS - 1) as f64, c))
+                        .collect();
+                    let color = if i == 0 {
+                        Color32::LIGHT_BLUE
+                    } else {
+                        Color32::GRAY
+                    };
+                    plot_ui.line(Line::new(PlotPoints::Owned(points)).color(color));
+                }
+            });
+    }
+
+    fn compute_heatmap(&mut self, ctx: &egui::Context) {
+        let spectrogram = |i: usize| -> Result<Spectrogram, String> {
+            let plot_data = self.plots.get(i).ok_or("No file selected")?;
+            plot_data.spectrogram(self.window, self.welch, self.precision, self.sample_type)
+        };
+        let result = match self.heatmap_files {
+            [Some(a), Some(b)] => {
+                spectrogram(a).and_then(|a| DifferenceMap::new(&a, &spectrogram(b)?))
+            }
+            _ => Err("No file selected".to_string()),
+        };
+        match result {
+            Ok(map) => {
+                let image = map.to_image(self.heatmap_range_db, self.color_map, MAX_HEATMAP_SIDE);
+                self.heatmap = Some(Heatmap {
+                    files: self.heatmap_files.map(Option::unwrap_or_default),
+                    texture: ctx.load_texture(
+                        "difference_heatmap",
+                        image,
+                        egui::TextureOptions::NEAREST,
+                    ),
+                    map,
+                    range_db: self.heatmap_range_db,
+                    color_map: self.color_map,
+                });
+            }
+            Err(e) => {
+                self.heatmap = None;
+                self.analysis_errors
+                    .push(format!("Failed to compute the difference heatmap: {}", e));
+            }
+        }
+    }
+
+    /// A − B heatmap of the two files picked in `heatmap_files`.
+    fn heatmap_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            for (slot, label) in ["A", "B"].into_iter().enumerate() {
+                let selected = &mut self.heatmap_files[slot];
+                let selected_plot = selected.and_then(|i| self.plots.get(i));
+                egui::ComboBox::from_label(label)
+                    .selected_text(selected_plot.map_or(String::new(), PlotData::label))
+                    .show_ui(ui, |ui| {
+                        for (i, plot_data) in self.plots.iter().enumerate() {
+                            ui.selectable_value(selected, Some(i), plot_data.label());
+                        }
+                    });
+            }
+            ui.label("Color range (dB):");
+            ui.add(egui::DragValue::new(&mut self.heatmap_range_db).range(1.0..=120.0));
+            egui::ComboBox::from_label("Colors")
+                .selected_text(self.color_map.name())
+                .show_ui(ui, |ui| {
+                    for color_map in ColorMap::ALL {
+                        ui.selectable_value(&mut self.color_map, color_map, color_map.name());
+                    }
+                });
+            if ui.button("Compute").clicked() {
+                self.compute_heatmap(ui.ctx());
+            }
+        });
+        ui.label("Uses the Welch segment size and hop as STFT frame length and step.");
+        let Some(heatmap) = &mut self.heatmap else {
+            return;
+        };
+        let heatmap_files = heatmap.files;
+        if (heatmap.range_db, heatmap.color_map) != (self.heatmap_range_db, self.color_map) {
+            let image =
+                heatmap
+                    .map
+                    .to_image(self.heatmap_range_db, self.color_map, MAX_HEATMAP_SIDE);
+            heatmap.texture.set(image, egui::TextureOptions::NEAREST);
+            heatmap.range_db = self.heatmap_range_db;
+            heatmap.color_map = self.color_map;
+        }
+        let (duration, max_freq) = (heatmap.map.duration, heatmap.map.max_freq);
+        let image = egui_plot::PlotImage::new(
+            &heatmap.texture,
+            PlotPoint::new(duration / 2.0, max_freq / 2.0),
+            [duration, max_freq],
+        );
+        let frame_time = self.frame_time as f64;
+        let plot_response = Plot::new("difference_heatmap")
+            .height(250.0)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Frequency (Hz)")
+            .show(ui, |plot_ui| {
+                plot_ui.image(image);
+                plot_ui.vline(VLine::new(frame_time).color(MARKER_COLOR));
+            });
+        let response = &plot_response.response;
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        {
+            let time = plot_response.transform.value_from_position(pos).x as f32;
+            self.frame_time = time.clamp(0.0, duration);
+        }
+        color_bar(ui, self.color_map, self.heatmap_range_db);
+        ui.horizontal(|ui| {
+            ui.label("Frame at");
+            ui.add(
+                egui::DragValue::new(&mut self.frame_time)
+                    .range(0.0..=duration)
+                    .speed(0.01)
+                    .suffix(" s"),
+            );
+            for (slot, label) in ["A", "B"].into_iter().enumerate() {
+                let freeze = ui.button(format!("Freeze {} frame", label));
+                if freeze
+                    .on_hover_text("Add the spectrum of this STFT frame as a plot")
+                    .clicked()
+                {
+                    self.freeze_frame(heatmap_files[slot]);
+                }
+            }
+        });
+    }
+
+    /// Adds the STFT frame of plot `index` at `frame_time` as a plot of its own, to compare,
+    /// export or save like any spectrum.
+    fn freeze_frame(&mut self, index: usize) {
+        let Some(plot_data) = self.plots.get(index) else {
+            return;
+        };
+        let spectrogram =
+            plot_data.spectrogram(self.window, self.welch, self.precision, self.sample_type);
+        let result = spectrogram.and_then(|spectrogram| {
+            let last = spectrogram
+                .frames
+                .len()
+                .checked_sub(1)
+                .ok_or("No STFT frames")?;
+            let frame = ((self.frame_time / spectrogram.frame_seconds).round() as usize).min(last);
+            let stem = Path::new(&plot_data.file_name)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let millis = (frame as f32 * spectrogram.frame_seconds * 1000.0).round();
+            Ok(PlotData {
+                freqs: spectrogram.freqs(),
+                amplitudes: spectrogram.frames[frame].clone(),
+                // no dot, which would read as an extension in export file names
+                file_name: format!("{} @ {} ms", stem, millis),
+                source: plot_data.source.clone(),
+                ..Default::default()
+            })
+        });
+        match result {
+            Ok(frame) => {
+                self.plots.push(frame);
+                self.update_average();
+            }
+            Err(e) => self
+                .analysis_errors
+                .push(format!("Failed to freeze the frame: {}", e)),
+        }
+    }
+
+    /// Files with different sample rates or lengths have different bins, so they are compared on
+    /// the grid of the file reaching the highest frequency (the finest one if tied) by Hz.
+    fn common_grid(&self) -> Vec<f32> {
+        self.plots
+            .iter()
+            .max_by(|a, b| {
+                let top = |p: &PlotData| p.freqs.last().copied().unwrap_or(0.0);
+                top(a)
+                    .total_cmp(&top(b))
+                    .then(a.freqs.len().cmp(&b.freqs.len()))
+            })
+            .map(|plot_data| plot_data.freqs.clone())
+            .unwrap_or_default()
+    }
+
+    /// Pairwise cosine similarity of all spectra on the common grid.
+    fn compute_similarity(&mut self) {
+        let grid = self.common_grid();
+        let resampled: Vec<_> = self
+            .plots
+            .iter()
+            .map(|plot_data| plot_data.resample(&grid))
+            .collect();
+        let matrix = resampled
+            .iter()
+            .map(|a| {
+                resampled
+                    .iter()
+                    .map(|b| metrics::cosine_similarity(a, b))
+                    .collect()
+            })
+            .collect();
+        self.similarity = Some(matrix);
+    }
+
+    /// Similarity matrix as a grid of colored cells; clicking one shows only those two files.
+    fn similarity_view(&mut self, ui: &mut Ui) {
+        if ui.button("Compute").clicked() {
+            self.compute_similarity();
+        }
+        let Some(matrix) = &self.similarity else {
+            return;
+        };
+        let mut clicked = None;
+        egui::Grid::new("similarity")
+            .spacing([2.0, 2.0])
+            .show(ui, |ui| {
+                for (i, row) in matrix.iter().enumerate() {
+                    for (j, &similarity) in row.iter().enumerate() {
+                        let cell = egui::Button::new("")
+                            .fill(similarity_color(similarity))
+                            .min_size(egui::vec2(18.0, 18.0));
+                        let hover = format!(
+                            "{}\n{}\nsimilarity {:.3}",
+                            self.plots[i].label(),
+                            self.plots[j].label(),
+                            similarity
+                        );
+                        if ui.add(cell).on_hover_text(hover).clicked() {
+                            clicked = Some((i, j));
+                        }
+                    }
+                    ui.label(self.plots[i].label());
+                    ui.end_row();
+                }
+            });
+        if let Some((i, j)) = clicked {
+            self.hidden = self.plots.iter().map(|p| p.file_name.clone()).collect();
+            self.hidden.remove(&self.plots[i].file_name);
+            self.hidden.remove(&self.plots[j].file_name);
+        }
+    }
+
+    fn annotations_view(&mut self, ui: &mut Ui) {
+        let mut removed = None;
+        for (i, annotation) in self.annotations.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut annotation.frequency)
+                        .range(0.0..=100_000.0)
+                        .suffix(" Hz"),
+                );
+                ui.text_edit_singleline(&mut annotation.label);
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            self.annotations.remove(i);
+        }
+        if ui.button("Add annotation").clicked() {
+            // start at the selected peak if there is one, it's the usual thing to annotate
+            let frequency = self.hopped_peak.map_or(1_000.0, |peak| peak.frequency);
+            self.annotations.push(Annotation {
+                frequency,
+                label: String::new(),
+            });
+        }
+    }
+
+    /// Dropped markers with the distance of each to the one before.
+    fn markers_view(&mut self, ui: &mut Ui) {
+        if self.markers.is_empty() {
+            ui.weak("Shift+click the plot to drop a marker");
+            return;
+        }
+        let mut removed = None;
+        egui::Grid::new("markers").striped(true).show(ui, |ui| {
+            for header in ["", "Frequency", "Amplitude", "Δf", "Δamp", ""] {
+                ui.strong(header);
+            }
+            ui.end_row();
+            let mut previous: Option<Marker> = None;
+            for (i, marker) in self.markers.iter().enumerate() {
+                ui.colored_label(MARKER_COLOR, format!("M{}", i + 1));
+                ui.label(format!("{:.2} Hz", marker.frequency));
+                ui.label(format!("{:.4}", marker.amplitude));
+                match previous {
+                    Some(previous) => {
+                        ui.label(format!("{:+.2} Hz", marker.frequency - previous.frequency));
+                        ui.label(format!("{:+.4}", marker.amplitude - previous.amplitude));
+                    }
+                    None => {
+                        ui.label("");
+                        ui.label("");
+                    }
+                }
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
+                previous = Some(*marker);
+            }
+        });
+        if let Some(i) = removed {
+            self.markers.remove(i);
+        }
+        if ui.button("Clear markers").clicked() {
+            self.markers.clear();
+        }
+    }
+
+    /// The flux needs an STFT, which is only computed with Welch averaging (using its segments as
+    /// frames) and for files whose samples are cached.
+    fn update_features(&mut self) {
+        let welch = self.use_welch.then_some(self.welch);
+        self.features = self
+            .plots
+            .iter()
+            .map(|plot_data| {
+                let mut features = SpectralFeatures::new(&plot_data.freqs, &plot_data.amplitudes)?;
+                if let Some(welch) = welch.filter(|_| plot_data.samples.is_some()) {
+                    features.flux = plot_data
+                        .spectrogram(self.window, welch, self.precision, self.sample_type)
+                        .ok()
+                        .and_then(|spectrogram| metrics::spectral_flux(&spectrogram.frames));
+                }
+                Some(features)
+            })
+            .collect();
+    }
+
+    /// Top of the y axis for [`Self::auto_y`], `None` if nothing is visible in `x_range`.
+    fn auto_y_top(&self, x_range: std::ops::RangeInclusive<f64>) -> Option<f64> {
+        let highest = |plot_data: &PlotData, points: &[PlotPoint]| {
+            let bin_width = match plot_data.freqs[..] {
+                [first, second, ..] => (second - first) as f64,
+                _ => 0.0,
+            };
+            let dc_width = self.auto_y_skip_bins as f64 * bin_width;
+            points
+                .iter()
+                .filter(|point| point.x.abs() >= dc_width && x_range.contains(&point.x))
+                .map(|point| point.y)
+                .fold(0.0, f64::max)
+        };
+        // linked sides share the y axis, so it has to fit both
+        let max = self
+            .plots
+            .iter()
+            .enumerate()
+            .filter(|(_, plot_data)| self.is_visible(plot_data))
+            .filter(|(_, plot_data)| self.link_axes || self.in_panel(plot_data))
+            .map(|(i, plot_data)| highest(plot_data, &self.cached_points(i)))
+            .chain(
+                self.reference
+                    .iter()
+                    .map(|reference| highest(reference, &self.plot_points(reference))),
+            )
+            .fold(0.0, f64::max);
+        (max > 0.0).then(|| max * (1.0 + self.auto_y_headroom as f64 / 100.0))
+    }
+
+    /// Sets the frequency range to where the visible plots have 99 % of their energy, ignoring the
+    /// bins `auto_y_skip_bins` leaves out, and has the next frame fit the y axis to it like
+    /// [`Self::auto_y_top`] does.
+    fn fit_loudest_region(&mut self) {
+        const FRACTION: f64 = 0.99;
+        let ranges = self
+            .plots
+            .iter()
+            .filter(|plot_data| self.is_visible(plot_data))
+            .filter_map(|plot_data| {
+                let bin_width = match plot_data.freqs[..] {
+                    [first, second, ..] => second - first,
+                    _ => 0.0,
+                };
+                let dc_width = self.auto_y_skip_bins as f32 * bin_width;
+                let (freqs, amplitudes): (Vec<f32>, Vec<f32>) = plot_data
+                    .freqs
+                    .iter()
+                    .zip(&plot_data.amplitudes)
+                    .filter(|(freq, _)| freq.abs() >= dc_width)
+                    .unzip();
+                metrics::energy_range(&freqs, &amplitudes, FRACTION)
+            });
+        let Some((low, high)) = ranges.reduce(|(low, high), (l, h)| (low.min(l), high.max(h)))
+        else {
+            return;
+        };
+        let margin = ((high - low) * 0.05).max(1.0);
+        self.min_freq = if low >= 0.0 {
+            (low - margin).max(0.0)
+        } else {
+            low - margin
+        };
+        self.max_freq = (high + margin).min(self.data_max_freq());
+        self.max_freq_to_nyquist = false;
+        self.fit_loudest = true;
+    }
+
+    /// Pans `min_freq` and `max_freq` by this frame's horizontal scrolling, which Shift+scroll is
+    /// too, and zooms them around the pointer by Ctrl+scroll, keeping them within the sliders'
+    /// range. The plot's x axis is set to the new range, so the view and the sliders agree.
+    fn scroll_frequency_range(&mut self, plot_ui: &mut egui_plot::PlotUi) {
+        let (scroll, zoom, shift) = plot_ui.ctx().input(|input| {
+            (
+                input.smooth_scroll_delta,
+                input.zoom_delta(),
+                input.modifiers.shift,
+            )
+        });
+        let pan = if scroll.x == 0.0 && shift {
+            scroll.y
+        } else {
+            scroll.x
+        };
+        if pan == 0.0 && zoom == 1.0 {
+            return;
+        }
+        let top = self.data_max_freq();
+        let bottom = if self.two_sided { -top } else { 0.0 };
+        let (mut low, mut high) = (self.min_freq as f64, self.max_freq as f64);
+        let per_pixel =
+            plot_ui.plot_bounds().width() / plot_ui.response().rect.width().max(1.0) as f64;
+        // content follows the scrolling, like egui_plot's own
+        let offset = (-pan as f64 * per_pixel).clamp(bottom as f64 - low, top as f64 - high);
+        (low, high) = (low + offset, high + offset);
+        if zoom != 1.0 {
+            let center = plot_ui
+                .pointer_coordinate()
+                .map_or((low + high) / 2.0, |pointer| pointer.x);
+            let zoom = zoom as f64;
+            (low, high) = (
+                center - (center - low) / zoom,
+                center + (high - center) / zoom,
+            );
+        }
+        let (low, high) = (low.max(bottom as f64), high.min(top as f64));
+        if high - low < 1e-3 {
+            return;
+        }
+        self.min_freq = low as f32;
+        if high as f32 != self.max_freq {
+            self.max_freq = high as f32;
+            self.max_freq_to_nyquist = false;
+        }
+        let mut bounds = plot_ui.plot_bounds();
+        bounds.set_x(&PlotBounds::from_min_max([low, 0.0], [high, 0.0]));
+        plot_ui.set_plot_bounds(bounds);
+    }
+
+    /// Moves `max_freq` to [`Self::data_max_freq`] while `max_freq_to_nyquist` is set.
+    fn follow_nyquist(&mut self) {
+        if self.max_freq_to_nyquist && (!self.plots.is_empty() || self.reference.is_some()) {
+            self.max_freq = self.data_max_freq();
+        }
+    }
+
+    /// Highest analysed frequency across the plots and the reference, i.e. the highest Nyquist
+    /// frequency for audio files.
+    fn data_max_freq(&self) -> f32 {
+        const NO_DATA_MAX_FREQ: f32 = 100_000.0;
+        let top = self
+            .plots
+            .iter()
+            .chain(&self.reference)
+            .filter_map(|plot_data| plot_data.freqs.last().copied())
+            .fold(0.0, f32::max);
+        if top > 0.0 {
+            top
+        } else {
+            NO_DATA_MAX_FREQ
+        }
+    }
+
+    /// Distinct `source`s of the plots, in the order given on the command line.
+    fn sources(&self) -> Vec<&str> {
+        let mut sources: Vec<&str> = Vec::new();
+        for plot_data in &self.plots {
+            if !sources.contains(&plot_data.source.as_str()) {
+                sources.push(&plot_data.source);
+            }
+        }
+        sources
+    }
+
+    /// Shows or hides all files of a command line argument at once.
+    fn sources_view(&mut self, ui: &mut Ui) {
+        let sources: Vec<String> = self.sources().into_iter().map(str::to_string).collect();
+        for source in &sources {
+            let files: Vec<String> = self
+                .plots
+                .iter()
+                .filter(|p| p.source == *source)
+                .map(|p| p.file_name.clone())
+                .collect();
+            let mut visible = files.iter().any(|file| !self.hidden.contains(file));
+            let label = format!("{} ({} files)", source, files.len());
+            if ui.checkbox(&mut visible, label).changed() {
+                for file in files {
+                    if visible {
+                        self.hidden.remove(&file);
+                    } else {
+                        self.hidden.insert(file);
+                    }
+                }
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.split_view, "Split view")
+                .on_hover_text(
+                    "One plot per source, side by side; the average is only drawn unsplit",
+                );
+            for (side, label) in ["Left", "Right"].into_iter().enumerate() {
+                let selected = &mut self.split_sources[side];
+                egui::ComboBox::from_label(label)
+                    .selected_text(sources.get(*selected).map_or("", String::as_str))
+                    .show_ui(ui, |ui| {
+                        for (i, source) in sources.iter().enumerate() {
+                            ui.selectable_value(selected, i, source);
+                        }
+                    });
+            }
+            ui.add_enabled(
+                self.split_view,
+                egui::Checkbox::new(&mut self.link_axes, "Link axes"),
+            );
+        });
+    }
+
+    /// Sets every plot's [`PlotData::pitch_scale`] so its fundamental matches the active plot's
+    /// with `align_pitch`, or back to 1 without. Plots without a fundamental stay unscaled.
+    fn update_pitch_alignment(&mut self) {
+        let unscaled_fundamental = |plot_data: &PlotData| {
+            peaks::fundamental(&plot_data.freqs, &plot_data.amplitudes, self.peak_band)
+                .map(|fundamental| fundamental.frequency / plot_data.pitch_scale)
+        };
+        self.pitch_reference = self.align_pitch.then_some(self.active_plot);
+        let target = self
+            .pitch_reference
+            .and_then(|i| self.plots.get(i))
+            .and_then(unscaled_fundamental);
+        for plot_data in &mut self.plots {
+            let scale = match (target, unscaled_fundamental(plot_data)) {
+                (Some(target), Some(fundamental)) => target / fundamental,
+                _ => 1.0,
+            };
+            plot_data.set_pitch_scale(scale);
+        }
+        self.update_average();
+    }
+
+    /// Fundamental of the active plot and its deviation from the nearest note.
+    fn tuning_view(&mut self, ui: &mut Ui) {
+        self.pitch_alignment_view(ui);
+        ui.horizontal(|ui| {
+            ui.label("A4 =");
+            let reference = egui::DragValue::new(&mut self.a4_reference)
+                .range(400.0..=480.0)
+                .speed(0.1);
+            ui.add(reference.suffix(" Hz"));
+            if ui.button("Reset").clicked() {
+                self.a4_reference = 440.0;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.show_harmonics, "Harmonics");
+            ui.add_enabled_ui(self.show_harmonics, |ui| {
+                ui.add(egui::DragValue::new(&mut self.harmonic_count).range(1..=100))
+                    .on_hover_text("Multiples marked, counting the fundamental");
+                ui.checkbox(&mut self.use_manual_fundamental, "of");
+                let manual =
+                    egui::DragValue::new(&mut self.manual_fundamental).range(1.0..=20_000.0);
+                ui.add_enabled(self.use_manual_fundamental, manual.suffix(" Hz"))
+                    .on_hover_text("Instead of the estimated fundamental");
+            });
+        });
+        if self.plots.is_empty() {
+            return;
+        }
+        let a4 = self.a4_reference;
+        let Some((fundamental, tuning)) = self
+            .active_fundamental()
+            .and_then(|fundamental| Some((fundamental, peaks::tuning(fundamental.frequency, a4)?)))
+        else {
+            ui.weak("No fundamental found");
+            return;
+        };
+        let color = if tuning.cents.abs() <= 5.0 {
+            IN_TUNE_COLOR
+        } else {
+            OUT_OF_TUNE_COLOR
+        };
+        ui.label(
+            egui::RichText::new(format!("{} {:+.1} ¢", tuning.note, tuning.cents))
+                .size(40.0)
+                .strong()
+                .color(color),
+        );
+        ui.horizontal(|ui| {
+            ui.label(format!("Fundamental {:.2} Hz", fundamental.frequency));
+            let confidence = fundamental.confidence;
+            let color = match confidence {
+                c if c >= 0.7 => IN_TUNE_COLOR,
+                c if c >= 0.4 => UNSURE_COLOR,
+                _ => UNRELIABLE_COLOR,
+            };
+            ui.colored_label(color, format!("● {:.0} % confidence", confidence * 100.0))
+                .on_hover_text(
+                    "Share of the energy at the harmonics, beyond what noise would put there",
+                );
+        });
+    }
+
+    /// Estimated fundamental of the active plot, from the cache if it's for that plot.
+    fn active_fundamental(&mut self) -> Option<Fundamental> {
+        match self.fundamental {
+            Some((index, fundamental)) if index == self.active_plot => fundamental,
+            _ => {
+                let plot_data = self.plots.get(self.active_plot)?;
+                let fundamental =
+                    peaks::fundamental(&plot_data.freqs, &plot_data.amplitudes, self.peak_band);
+                self.fundamental = Some((self.active_plot, fundamental));
+                fundamental
+            }
+        }
+    }
+
+    /// Starts playing the active plot's samples through a [`StreamingSpectrum`].
+    fn start_stream(&mut self) -> Result<(), String> {
+        let plot_data = self
+            .plots
+            .get(self.active_plot)
+            .ok_or("No file to stream")?;
+        let samples = plot_data.decoded_samples(self.sample_type)?;
+        let (Some(samples), Some(sample_rate)) = (samples, plot_data.sample_rate) else {
+            return Err(format!("{} has no samples to stream", plot_data.file_name));
+        };
+        let source = StreamSource::File {
+            samples: samples.into_owned(),
+            position: 0,
+        };
+        self.stream = Some(self.sample_stream(plot_data.label(), source, sample_rate)?);
+        Ok(())
+    }
+
+    /// Shows the rolling spectrum of the input `device`, or the default one, captured at
+    /// `sample_rate` or the device's own rate. With `raw` the input is PCM in that layout read
+    /// from stdin instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_live(
+        &mut self,
+        raw: Option<RawFormat>,
+        device: Option<&str>,
+        sample_rate: Option<u32>,
+        channel: Channel,
+    ) {
+        let block_frames = self.stream_segments.hop;
+        let started = match raw {
+            Some(format) => {
+                let receiver =
+                    stream::spawn_raw_capture(std::io::stdin(), format, channel, block_frames);
+                Ok((receiver, "Live input".to_string(), format.sample_rate))
+            }
+            None => capture_device(device, sample_rate, channel, block_frames),
+        };
+        let started = started.and_then(|(receiver, name, sample_rate)| {
+            let stream =
+                self.sample_stream(name, StreamSource::Live(Some(receiver)), sample_rate)?;
+            Ok((stream, sample_rate))
+        });
+        match started {
+            Ok((stream, sample_rate)) => {
+                self.stream = Some(stream);
+                if self.max_freq_to_nyquist {
+                    self.max_freq = sample_rate as f32 / 2.0;
+                }
+            }
+            Err(e) => self
+                .analysis_errors
+                .push(format!("Failed to start live input: {}", e)),
+        }
+    }
+
+    /// A stream of `source` with a rolling spectrum in the current settings.
+    fn sample_stream(
+        &self,
+        name: String,
+        source: StreamSource,
+        sample_rate: u32,
+    ) -> Result<SampleStream, String> {
+        let spectrum = StreamingSpectrum::new(
+            sample_rate,
+            self.window,
+            self.stream_segments,
+            self.precision,
+            self.stream_smoothing,
+        )?;
+        Ok(SampleStream {
+            name,
+            source,
+            sample_rate,
+            paused: false,
+            plot: PlotData {
+                freqs: spectrum.freqs(),
+                ..Default::default()
+            },
+            spectrum,
+        })
+    }
+
+    /// Feeds the stream a file's samples that would have arrived since the last frame at
+    /// `stream_speed`, or the blocks captured meanwhile, and keeps repainting while it runs.
+    fn advance_stream(&mut self, ctx: &egui::Context) {
+        let speed = self.stream_speed;
+        let Some(stream) = self.stream.as_mut() else {
+            return;
+        };
+        let mut changed = false;
+        match &mut stream.source {
+            StreamSource::File { samples, position } => {
+                if stream.paused {
+                    return;
+                }
+                // a stalled frame shouldn't jump ahead by seconds
+                let dt = ctx.input(|i| i.stable_dt).min(0.1);
+                let count = ((dt * stream.sample_rate as f32 * speed) as usize).max(1);
+                let end = (*position + count).min(samples.len());
+                changed = stream.spectrum.push(&samples[*position..end]);
+                *position = end;
+                stream.paused = end == samples.len();
+            }
+            StreamSource::Live(receiver) => {
+                let Some(blocks) = receiver else {
+                    return;
+                };
+                loop {
+                    match blocks.try_recv() {
+                        Ok(Ok(block)) if !stream.paused => changed |= stream.spectrum.push(&block),
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => {
+                            self.analysis_errors
+                                .push(format!("Live input failed: {}", e));
+                            *receiver = None;
+                            break;
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            *receiver = None;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        if changed {
+            stream.plot.amplitudes = stream.spectrum.amplitudes().to_vec();
+        }
+        ctx.request_repaint();
+    }
+
+    /// Controls for streaming the active file, or the live input, through a rolling spectrum.
+    fn stream_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let segments = &mut self.stream_segments;
+            ui.label("FFT size");
+            ui.add(egui::DragValue::new(&mut segments.segment_len).range(16..=1 << 20));
+            ui.label("hop");
+            ui.add(egui::DragValue::new(&mut segments.hop).range(1..=segments.segment_len))
+                .on_hover_text("Samples between updates");
+            let speed = egui::Slider::new(&mut self.stream_speed, 0.1..=100.0).logarithmic(true);
+            ui.add(speed.text("× real time"));
+            ui.add(egui::Slider::new(&mut self.stream_smoothing, 0.0..=0.95).text("Smoothing"))
+                .on_hover_text("Share of the previous spectrum each frame keeps");
+        });
+        let mut restart = false;
+        ui.horizontal(|ui| {
+            if ui.button("Stream active file").clicked() {
+                if let Err(e) = self.start_stream() {
+                    self.analysis_errors
+                        .push(format!("Failed to stream: {}", e));
+                }
+            }
+            let Some(stream) = &mut self.stream else {
+                return;
+            };
+            let rate = stream.sample_rate as f32;
+            let (running, status) = match &stream.source {
+                StreamSource::File { samples, position } => (
+                    *position < samples.len(),
+                    format!(
+                        "{:.1} of {:.1} s",
+                        *position as f32 / rate,
+                        samples.len() as f32 / rate
+                    ),
+                ),
+                StreamSource::Live(receiver) => {
+                    let state = if receiver.is_some() {
+                        "live"
+                    } else {
+                        "input ended"
+                    };
+                    (
+                        receiver.is_some(),
+                        format!("{}, {} Hz", state, stream.sample_rate),
+                    )
+                }
+            };
+            if running {
+                let label = if stream.paused { "Resume" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    stream.paused = !stream.paused;
+                }
+            }
+            if matches!(stream.source, StreamSource::Live(_)) {
+                let apply = ui
+                    .button("Apply settings")
+                    .on_hover_text("Restart the spectrum with them");
+                restart = apply.clicked();
+            }
+            let stop = ui.button("Stop").clicked();
+            ui.label(format!(
+                "{} {}, {} frames",
+                stream.name,
+                status,
+                stream.spectrum.frames()
+            ));
+            if stop {
+                self.stream = None;
+            }
+        });
+        if restart {
+            self.restart_stream();
+        }
+    }
+
+    /// Rebuilds the rolling spectrum of the stream with the current settings, keeping its source.
+    fn restart_stream(&mut self) {
+        let Some(old) = self.stream.take() else {
+            return;
+        };
+        match self.sample_stream(old.name, old.source, old.sample_rate) {
+            Ok(stream) => self.stream = Some(stream),
+            Err(e) => self
+                .analysis_errors
+                .push(format!("Failed to restart the stream: {}", e)),
+        }
+    }
+
+    /// Conversion of the y axis to dB SPL, while calibrated and the plot shows unweighted,
+    /// unnormalized magnitudes. Levels depend on the samples windowed, so the active file's are used.
+    fn spl_scale(&self) -> Option<SplScale> {
+        let plain = self.normalization == Normalization::None
+            && self.amplitude_mode == AmplitudeMode::PerBin;
+        if !self.spl_calibrated || !plain || self.showing_difference() {
+            return None;
+        }
+        let options = self.analysis_options();
+        let windowed_len = self.plots.get(self.active_plot)?.windowed_len(options);
+        (windowed_len > 0).then_some(SplScale {
+            options,
+            windowed_len,
+            offset_db: self.spl_offset_db,
+        })
+    }
+
+    /// The calibration offset, and a helper setting it from a recording of a calibrator tone.
+    fn calibration_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.spl_calibrated, "Show dB SPL");
+            let offset = egui::DragValue::new(&mut self.spl_offset_db)
+                .range(-200.0..=300.0)
+                .speed(0.1);
+            ui.add(offset.suffix(" dB SPL at 0 dBFS"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Calibrator at");
+            let level = egui::DragValue::new(&mut self.calibrator_level_db).range(0.0..=200.0).speed(0.1);
+            ui.add(level.suffix(" dB SPL"));
+            let hover = "Set the offset so the active file, a recording of the calibrator, reads that level";
+            if ui.button("Calibrate from active file").on_hover_text(hover).clicked() {
+                let stats = self.plots.get(self.active_plot).and_then(|plot_data| plot_data.level_stats);
+                match stats {
+                    Some(stats) => {
+                        // dBFS of a sine with that RMS, so a full-scale sine is 0 dBFS
+                        let dbfs = 20.0 * (stats.rms * std::f32::consts::SQRT_2 / 32_768.0).log10();
+                        self.spl_offset_db = self.calibrator_level_db - dbfs;
+                        self.spl_calibrated = true;
+                    }
+                    None => self.analysis_errors.push("The active file has no level statistics".to_string()),
+                }
+            }
+        });
+        if self.spl_calibrated && self.spl_scale().is_none() {
+            ui.weak("The axis shows dB SPL without normalization and per-bin amplitudes");
+        }
+    }
+
+    /// Name, color and frequency/degree pairs of the active plot's phase while `show_phase` is
+    /// on, see [`Self::overlay_points`].
+    fn phase_points(&mut self) -> Option<Overlay> {
+        if !self.show_phase || self.showing_difference() {
+            return None;
+        }
+        let plot_data = self.plots.get(self.active_plot)?;
+        if !matches!(self.phase, Some((index, _)) if index == self.active_plot) {
+            let phases = plot_data.phases(self.analysis_options(), self.sample_type);
+            self.phase = Some((self.active_plot, phases));
+        }
+        let Some((_, Ok(phases))) = &self.phase else {
+            return None;
+        };
+        let name = format!("{} phase", plot_data.label());
+        Some((
+            name,
+            self.plot_color(self.active_plot),
+            self.overlay_points(phases),
+        ))
+    }
+
+    /// Like [`Self::phase_points`] for the group delay in ms while `show_group_delay` is on, with
+    /// the span its axis shows: what's between the 2nd and 98th percentile, as the delay spikes
+    /// wherever the phase jumps, e.g. at the notches of a filter.
+    fn group_delay_points(&mut self) -> Option<(Overlay, (f64, f64))> {
+        if !self.show_group_delay || self.showing_difference() {
+            return None;
+        }
+        let plot_data = self.plots.get(self.active_plot)?;
+        if !matches!(self.group_delay, Some((index, _)) if index == self.active_plot) {
+            let delays = plot_data.group_delays(self.analysis_options(), self.sample_type);
+            self.group_delay = Some((self.active_plot, delays));
+        }
+        let Some((_, Ok(delays))) = &self.group_delay else {
+            return None;
+        };
+        let ms: Vec<f32> = delays.iter().map(|delay| delay * 1000.0).collect();
+        let points = self.overlay_points(&ms);
+        let mut sorted: Vec<f64> = points.iter().map(|point| point[1]).collect();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            sorted
+                .get((p * (sorted.len() - 1) as f64).round() as usize)
+                .copied()
+        };
+        let (low, high) = (percentile(0.02)?, percentile(0.98)?);
+        // a flat delay, e.g. of a pure delay line, still gets an axis around it
+        let margin = ((high - low) * 0.1).max(0.01);
+        let name = format!("{} group delay", plot_data.label());
+        Some((
+            (name, self.plot_color(self.active_plot), points),
+            (low - margin, high + margin),
+        ))
+    }
+
+    /// Frequency/value pairs of `values` per bin of the active plot within the frequency range,
+    /// leaving out bins more than 60 dB below its peak, whose phase is just noise.
+    fn overlay_points(&self, values: &[f32]) -> Vec<[f64; 2]> {
+        const FLOOR: f32 = 1e-3;
+        let Some(plot_data) = self.plots.get(self.active_plot) else {
+            return Vec::new();
+        };
+        let floor = plot_data
+            .amplitudes
+            .iter()
+            .fold(0.0_f32, |max, &a| max.max(a))
+            * FLOOR;
+        plot_data
+            .freqs
+            .iter()
+            .zip(&plot_data.amplitudes)
+            .zip(values)
+            .filter(|((&freq, &amp), _)| {
+                freq >= self.min_freq && freq <= self.max_freq && amp >= floor
+            })
+            .map(|((&freq, _), &value)| [freq as f64, value as f64])
+            .collect()
+    }
+
+    /// Frequencies of the harmonics marked with `show_harmonics`, within the frequency range.
+    fn harmonic_frequencies(&mut self) -> Vec<f32> {
+        if !self.show_harmonics {
+            return Vec::new();
+        }
+        let fundamental = if self.use_manual_fundamental {
+            Some(self.manual_fundamental)
+        } else {
+            self.active_fundamental()
+                .map(|fundamental| fundamental.frequency)
+        };
+        let Some(fundamental) = fundamental.filter(|&f| f > 0.0) else {
+            return Vec::new();
+        };
+        (1..=self.harmonic_count)
+            .map(|k| k as f32 * fundamental)
+            .take_while(|&freq| freq <= self.max_freq)
+            .filter(|&freq| freq >= self.min_freq)
+            .collect()
+    }
+
+    /// The `align_pitch` toggle and the shift it applies to each plot.
+    fn pitch_alignment_view(&mut self, ui: &mut Ui) {
+        let hover =
+            "Scales each spectrum's frequencies so tuning differences don't hide timbral ones";
+        ui.checkbox(
+            &mut self.align_pitch,
+            "Align fundamentals to the active file",
+        )
+        .on_hover_text(hover);
+        if !self.align_pitch {
+            return;
+        }
+        egui::Grid::new("pitch_alignment").show(ui, |ui| {
+            for (i, plot_data) in self.plots.iter().enumerate() {
+                ui.label(plot_data.label());
+                if i == self.active_plot {
+                    ui.weak("reference");
+                } else {
+                    ui.label(format!("{:+.1} ¢", 1200.0 * plot_data.pitch_scale.log2()));
+                }
+                ui.end_row();
+            }
+        });
+        ui.separator();
+    }
+
+    /// Cepstrum of the active plot against quefrency, with the peak in the pitch range marked as
+    /// the pitch period.
+    fn cepstrum_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Pitch range:");
+            let [min, max] = &mut self.cepstrum_range;
+            ui.add(
+                egui::DragValue::new(min)
+                    .range(1.0..=*max)
+                    .speed(1.0)
+                    .suffix(" Hz"),
+            );
+            ui.label("to");
+            ui.add(
+                egui::DragValue::new(max)
+                    .range(*min..=100_000.0)
+                    .speed(10.0)
+                    .suffix(" Hz"),
+            );
+        });
+        let Some(plot_data) = self.plots.get(self.active_plot) else {
+            return;
+        };
+        let [min_hz, max_hz] = self.cepstrum_range;
+        let frame_len = plot_data
+            .sample_rate
+            .map_or(0, |rate| Cepstrum::frame_len(rate, min_hz));
+        let cepstrum = match &self.cepstrum {
+            Some((index, len, cepstrum)) if (*index, *len) == (self.active_plot, frame_len) => {
+                cepstrum
+            }
+            _ => {
+                let cepstrum = match (
+                    plot_data.sample_rate,
+                    plot_data.decoded_samples(self.sample_type),
+                ) {
+                    (Some(sample_rate), Ok(Some(samples))) => {
+                        Ok(Cepstrum::new(&samples, sample_rate, frame_len))
+                    }
+                    (_, Err(e)) => Err(e),
+                    _ => Err("The active file has no samples".to_string()),
+                };
+                &self
+                    .cepstrum
+                    .insert((self.active_plot, frame_len, cepstrum))
+                    .2
+            }
+        };
+        let cepstrum = match cepstrum {
+            Ok(cepstrum) => cepstrum,
+            Err(e) => {
+                ui.weak(e);
+                return;
+            }
+        };
+        let period = cepstrum.pitch_period(min_hz, max_hz);
+        match period {
+            Some(period) => ui.label(format!(
+                "Period {:.3} ms ({:.2} Hz)",
+                period * 1000.0,
+                1.0 / period
+            )),
+            None => ui.weak("No quefrencies in the pitch range"),
+        };
+        // quefrency 0 is the overall level and dwarfs everything else
+        let shown = ((1.5 / min_hz / cepstrum.quefrency_step) as usize).min(cepstrum.values.len());
+        let points: Vec<[f64; 2]> = (1..shown)
+            .map(|i| {
+                [
+                    (i as f32 * cepstrum.quefrency_step * 1000.0) as f64,
+                    cepstrum.values[i] as f64,
+                ]
+            })
+            .collect();
+        Plot::new("cepstrum")
+            .height(200.0)
+            .x_axis_label("Quefrency (ms)")
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(points)).color(Color32::LIGHT_BLUE));
+                if let Some(period) = period {
+                    plot_ui.vline(VLine::new(period * 1000.0).color(MARKER_COLOR));
+                }
+            });
+    }
+
+    /// Evenly spaced notches in the active plot within the frequency range, as a reflection
+    /// arriving after the direct sound causes, with their spacing and the delay it implies.
+    fn comb_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Delay range:");
+            let [min, max] = &mut self.comb_delay_range;
+            ui.add(
+                egui::DragValue::new(min)
+                    .range(0.01..=*max)
+                    .speed(0.1)
+                    .suffix(" ms"),
+            );
+            ui.label("to");
+            ui.add(
+                egui::DragValue::new(max)
+                    .range(*min..=1000.0)
+                    .speed(1.0)
+                    .suffix(" ms"),
+            );
+        });
+        let Some(plot_data) = self.plots.get(self.active_plot) else {
+            return;
+        };
+        let [min_delay, max_delay] = self.comb_delay_range;
+        let key = [min_delay, max_delay, self.min_freq, self.max_freq];
+        let comb = match self.comb {
+            Some((index, k, comb)) if (index, k) == (self.active_plot, key) => comb,
+            _ => {
+                let band = [self.min_freq, self.max_freq];
+                let (freqs, amplitudes) = (&plot_data.freqs, &plot_data.amplitudes);
+                let comb = comb::detect(freqs, amplitudes, band, self.comb_delay_range);
+                self.comb = Some((self.active_plot, key, comb));
+                comb
+            }
+        };
+        let Some(comb) = comb else {
+            ui.weak("Too few bins in the frequency range for the delays");
+            return;
+        };
+        ui.label(format!(
+            "Notches every {:.2} Hz, delay {:.3} ms",
+            comb.spacing_hz, comb.delay_ms
+        ));
+        let path = self.speed_of_sound * comb.delay_ms / 1000.0;
+        let hover = "How periodic the ripple is. A pitched sound's harmonics show up the same way";
+        ui.label(format!(
+            "Strength {:.0} %, {:.2} m extra path",
+            comb.strength * 100.0,
+            path
+        ))
+        .on_hover_text(hover);
+    }
+
+    /// Hits of the active plot over its envelope, with their count and spacing.
+    fn transients_view(&mut self, ui: &mut Ui) {
+        const MAX_POINTS: usize = 4000;
+        let sensitivity =
+            egui::Slider::new(&mut self.onset_sensitivity, 0.0..=1.0).text("Sensitivity");
+        ui.add(sensitivity)
+            .on_hover_text("Lower needs a hit to rise further above the recent level");
+        let Some(plot_data) = self.plots.get(self.active_plot) else {
+            return;
+        };
+        let sensitivity = self.onset_sensitivity;
+        let onsets = match &self.onsets {
+            Some((index, s, onsets)) if (*index, *s) == (self.active_plot, sensitivity) => onsets,
+            _ => {
+                let onsets = match (
+                    plot_data.sample_rate,
+                    plot_data.decoded_samples(self.sample_type),
+                ) {
+                    (Some(sample_rate), Ok(Some(samples))) => {
+                        Ok(onsets::detect(&samples, sample_rate, sensitivity))
+                    }
+                    (_, Err(e)) => Err(e),
+                    _ => Err("The active file has no samples".to_string()),
+                };
+                &self
+                    .onsets
+                    .insert((self.active_plot, sensitivity, onsets))
+                    .2
+            }
+        };
+        let onsets = match onsets {
+            Ok(onsets) => onsets,
+            Err(e) => {
+                ui.weak(e);
+                return;
+            }
+        };
+        let mut summary = format!("{} onsets", onsets.times.len());
+        if let (Some(interval), Some(tempo)) = (onsets.mean_interval(), onsets.tempo_bpm()) {
+            summary += &format!(
+                ", every {:.1} ms on average ({:.1} BPM)",
+                interval * 1000.0,
+                tempo
+            );
+        }
+        ui.label(summary);
+        // the loudest frame of each chunk, so hits aren't lost when thinning long files
+        let chunk = onsets.envelope_db.len().div_ceil(MAX_POINTS).max(1);
+        let points: Vec<[f64; 2]> = onsets
+            .envelope_db
+            .chunks(chunk)
+            .enumerate()
+            .map(|(i, frames)| {
+                let time = (i * chunk) as f32 * onsets.frame_seconds;
+                [
+                    time as f64,
+                    frames.iter().copied().fold(f32::MIN, f32::max) as f64,
+                ]
+            })
+            .collect();
+        Plot::new("transients")
+            .height(200.0)
+            .x_axis_label("Time (s)")
+            .y_axis_label("Envelope (dB)")
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(points)).color(Color32::LIGHT_BLUE));
+                for &time in &onsets.times {
+                    plot_ui.vline(VLine::new(time).color(MARKER_COLOR));
+                }
+            });
+    }
+
+    fn timings_view(&self, ui: &mut Ui) {
+        egui::Grid::new("timings").striped(true).show(ui, |ui| {
+            for heading in ["File", "Load", "FFT", "Samples", "FFT size"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for plot_data in &self.plots {
+                let timings = plot_data.timings;
+                ui.label(plot_data.label());
+                ui.label(format!("{:.1} ms", timings.load.as_secs_f64() * 1000.0));
+                if plot_data.sample_rate.is_some() {
+                    ui.label(format!("{:.1} ms", timings.fft.as_secs_f64() * 1000.0));
+                    ui.label(timings.sample_count.to_string());
+                    ui.label(timings.fft_size.to_string());
+                } else {
+                    for _ in 0..3 {
+                        ui.label("-");
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn features_view(&mut self, ui: &mut Ui) {
+        egui::Grid::new("spectral_features")
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in [
+                    "File",
+                    "Centroid",
+                    "Bandwidth",
+                    "Rolloff",
+                    "Flatness",
+                    "Flux",
+                ] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (plot_data, features) in self.plots.iter().zip(&self.features) {
+                    ui.label(plot_data.label());
+                    match features {
+                        Some(f) => {
+                            ui.label(format!("{:.1} Hz", f.centroid));
+                            ui.label(format!("{:.1} Hz", f.bandwidth));
+                            ui.label(format!("{:.1} Hz", f.rolloff));
+                            ui.label(format!("{:.4}", f.flatness));
+                            ui.label(
+                                f.flux
+                                    .map_or("-".to_string(), |flux| format!("{:.4}", flux)),
+                            );
+                        }
+                        None => {
+                            for _ in 0..5 {
+                                ui.label("-");
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        if ui.button("Export features").clicked() {
+            let labels: Vec<String> = self.plots.iter().map(PlotData::label).collect();
+            let rows: Vec<_> = labels
+                .iter()
+                .zip(&self.features)
+                .filter_map(|(label, features)| Some((label.as_str(), (*features)?)))
+                .collect();
+            let path = format!("features.{}", self.export_format.extension());
+            if let Err(e) = export::write_features(Path::new(&path), self.export_format, &rows) {
+                self.analysis_errors
+                    .push(format!("Failed to export features: {}", e));
+            }
+        }
+    }
+
+    /// Indices of the plots ordered by `rank_metric`, with its value; files without one come last.
+    fn ranking(&self) -> Vec<(usize, Option<f32>)> {
+        let mut ranking: Vec<_> = self
+            .plots
+            .iter()
+            .enumerate()
+            .map(|(i, plot_data)| {
+                let features = self.features.get(i).and_then(Option::as_ref);
+                (i, self.rank_metric.value(plot_data, features))
+            })
+            .collect();
+        ranking.sort_by(|(_, a), (_, b)| match (a, b) {
+            (Some(a), Some(b)) if self.rank_descending => b.total_cmp(a),
+            (Some(a), Some(b)) => a.total_cmp(b),
+            _ => b.is_some().cmp(&a.is_some()),
+        });
+        ranking
+    }
+
+    /// Every file ranked by a metric, to find the outliers of a batch. Clicking a file solos it
+    /// and fits the view to it, clicking it again shows everything.
+    fn ranking_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("rank_metric")
+                .selected_text(self.rank_metric.name())
+                .show_ui(ui, |ui| {
+                    for metric in RankMetric::ALL {
+                        ui.selectable_value(&mut self.rank_metric, metric, metric.name());
+                    }
+                });
+            ui.checkbox(&mut self.rank_descending, "Highest first");
+        });
+        let ranking = self.ranking();
+        let mut clicked = None;
+        egui::Grid::new("ranking").striped(true).show(ui, |ui| {
+            for heading in ["#", "File", self.rank_metric.name()] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for (rank, &(i, value)) in ranking.iter().enumerate() {
+                let plot_data = &self.plots[i];
+                ui.label((rank + 1).to_string());
+                let soloed = self.solo.as_ref() == Some(&plot_data.file_name);
+                let label = ui.selectable_label(soloed, plot_data.label());
+                if label.on_hover_text("Solo and zoom to it").clicked() {
+                    clicked = Some(i);
+                }
+                ui.label(value.map_or("-".to_string(), |value| self.rank_metric.format(value)));
+                ui.end_row();
+            }
+        });
+        if let Some(i) = clicked {
+            self.active_plot = i;
+            self.hopped_peak = None;
+            self.toggle_solo(i);
+            if self.solo.is_some() {
+                self.fit_loudest_region();
+            } else {
+                self.reset_bounds = true;
+            }
+        }
+        if ui.button("Export ranking").clicked() {
+            let labels: Vec<String> = self.plots.iter().map(PlotData::label).collect();
+            let rows: Vec<_> = ranking
+                .iter()
+                .map(|&(i, value)| (labels[i].as_str(), value))
+                .collect();
+            if let Err(e) =
+                export::write_ranking(Path::new("ranking.csv"), self.rank_metric.name(), &rows)
+            {
+                self.analysis_errors
+                    .push(format!("Failed to export ranking.csv: {}", e));
+            }
+        }
+    }
+
+    /// Octave or third-octave band levels of the active file, and an export of every file's.
+    fn bands_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            for fraction in BandFraction::ALL {
+                ui.radio_value(&mut self.band_fraction, fraction, fraction.name());
+            }
+            ui.separator();
+            ui.label("Weighting:");
+            for weighting in Weighting::ALL {
+                ui.radio_value(&mut self.band_weighting, weighting, weighting.name());
+            }
+        });
+        let levels = |app: &Self, plot_data: &PlotData| {
+            let (freqs, amplitudes) = app.export_bins(plot_data);
+            octave::band_levels(&freqs, &amplitudes, app.band_fraction, app.band_weighting)
+        };
+        if let Some(plot_data) = self.plots.get(self.active_plot) {
+            let levels = levels(self, plot_data);
+            ui.label(format!(
+                "{}, relative to an amplitude of 1:",
+                plot_data.label()
+            ));
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("band_levels").striped(true).show(ui, |ui| {
+                        ui.strong("Center");
+                        ui.strong("Level");
+                        ui.end_row();
+                        for band in &levels.bands {
+                            ui.label(format!("{:.1} Hz", band.center));
+                            ui.label(format!("{:.1} dB", band.level_db));
+                            ui.end_row();
+                        }
+                        ui.strong("Overall");
+                        ui.strong(format!("{:.1} dB", levels.overall_db));
+                        ui.end_row();
+                    });
+                });
+        }
+        if ui.button("Export band levels").clicked() {
+            let labels: Vec<String> = self.plots.iter().map(PlotData::label).collect();
+            let rows: Vec<_> = self
+                .plots
+                .iter()
+                .zip(&labels)
+                .map(|(plot_data, label)| {
+                    let mut levels = levels(self, plot_data);
+                    for band in &mut levels.bands {
+                        for freq in [&mut band.center, &mut band.lower, &mut band.upper] {
+                            *freq = self.export_rounding.apply(*freq);
+                        }
+                    }
+                    (label.as_str(), levels)
+                })
+                .collect();
+            let path = format!("bands.{}", self.export_format.extension());
+            let format = self.export_format;
+            if let Err(e) =
+                export::write_band_levels(Path::new(&path), format, self.band_weighting, &rows)
+            {
+                self.analysis_errors
+                    .push(format!("Failed to export band levels: {}", e));
+            }
+        }
+    }
+
+    /// Recomputes `avg_plot`; called whenever `plots` change rather than while drawing.
+    fn update_average(&mut self) {
+        self.follow_nyquist();
+        self.averaged = self
+            .plots
+            .iter()
+            .map(|plot_data| self.in_composite(plot_data))
+            .collect();
+        self.similarity = None;
+        self.fundamental = None;
+        self.onsets = None;
+        self.cepstrum = None;
+        self.comb = None;
+        self.phase = None;
+        self.group_delay = None;
+        self.hover_peaks = None;
+        self.point_cache.clear();
+        self.update_features();
+        self.file_stats = self
+            .plots
+            .iter()
+            .map(|plot_data| self.analyzers.analyze(plot_data))
+            .collect();
+        let options = self.analysis_options();
+        let overloads = self
+            .plots
+            .iter()
+            .filter_map(|plot_data| plot_data.overload_warning(options));
+        self.overloads = overloads.collect();
+        self.update_peak_hold();
+        self.check_masks();
+        self.global_max = self
+            .plots
+            .iter()
+            .map(|p| self.weighted_max(p))
+            .fold(0.0, f32::max);
+        if !self.has_average() {
+            self.avg_plot = PlotData::default();
+            self.avg_std.clear();
+            return;
+        }
+        let grid = self.common_grid();
+        let mut sums = vec![0.0; grid.len()];
+        // f64 since the variance is the difference of two large, similar numbers
+        let mut squares = vec![0.0_f64; grid.len()];
+        // dividing by the total weight at each bin normalizes the weights to sum to 1
+        let mut weights = vec![0.0_f32; grid.len()];
+        for plot_data in &self.plots {
+            let weight = self.composite_weight(plot_data);
+            if weight <= 0.0 {
+                continue;
+            }
+            for (i, amp) in plot_data.resample(&grid).into_iter().enumerate() {
+                // bins above a file's Nyquist frequency only average the files that reach them
+                if let Some(amp) = amp.map(|amp| self.negative_amplitudes.apply(amp)) {
+                    sums[i] += weight * amp;
+                    squares[i] += weight as f64 * (amp as f64).powi(2);
+                    weights[i] += weight;
+                }
+            }
+        }
+        let means: Vec<f32> = sums
+            .iter()
+            .zip(&weights)
+            .map(|(&sum, &weight)| if weight > 0.0 { sum / weight } else { 0.0 })
+            .collect();
+        // the band shows how far the amplitudes spread, whichever average it's drawn around
+        self.avg_std = squares
+            .iter()
+            .zip(&weights)
+            .zip(&means)
+            .map(|((&square, &weight), &mean)| {
+                if weight <= 0.0 {
+                    return 0.0;
+                }
+                let variance = square / weight as f64 - (mean as f64).powi(2);
+                variance.max(0.0).sqrt() as f32
+            })
+            .collect();
+        let avg_amplitudes = match self.average_domain {
+            AverageDomain::Amplitude => means,
+            AverageDomain::Power => squares
+                .iter()
+                .zip(&weights)
+                .map(|(&square, &weight)| {
+                    if weight > 0.0 {
+                        (square / weight as f64).sqrt() as f32
+                    } else {
+                        0.0
+                    }
+                })
+                .collect(),
+        };
+        self.avg_plot = PlotData {
+            freqs: grid,
+            amplitudes: avg_amplitudes,
+            file_name: "average".to_string(),
+            ..Default::default()
+        };
+    }
+
+    /// Raises [`Self::held_peaks`] to the loudest of the loaded plots at each bin. Plots removed
+    /// since stay in it until it's reset.
+    fn update_peak_hold(&mut self) {
+        if !self.peak_hold {
+            return;
+        }
+        if self.held_peaks.freqs.is_empty() {
+            let grid = self.common_grid();
+            self.held_peaks = PlotData {
+                amplitudes: vec![0.0; grid.len()],
+                freqs: grid,
+                file_name: "peak hold".to_string(),
+                ..Default::default()
+            };
+        }
+        for plot_data in &self.plots {
+            let resampled = plot_data.resample(&self.held_peaks.freqs);
+            for (held, amp) in self.held_peaks.amplitudes.iter_mut().zip(resampled) {
+                if let Some(amp) = amp {
+                    *held = held.max(amp);
+                }
+            }
+        }
+    }
+
+    /// A single file is its own average, so there's only one with at least two files.
+    fn has_average(&self) -> bool {
+        self.plots
+            .iter()
+            .filter(|plot_data| self.in_composite(plot_data))
+            .count()
+            >= 2
+    }
+
+    /// Whether `plot_data` goes into the average and the combined export: every file, or with
+    /// `use_selection` only those shown.
+    fn in_composite(&self, plot_data: &PlotData) -> bool {
+        !self.use_selection || self.is_visible(plot_data)
+    }
+
+    /// Weight of `plot_data` in the average, 0 if it's left out.
+    fn composite_weight(&self, plot_data: &PlotData) -> f32 {
+        if self.in_composite(plot_data) {
+            self.average_weight(plot_data)
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the chart shows deviations from the average rather than amplitudes.
+    fn showing_difference(&self) -> bool {
+        self.diff_from_average && self.has_average()
+    }
+
+    /// Writes every file going into the average and the average itself on the common grid.
+    fn export_combined(&self, path: &Path) -> Result<(), String> {
+        let grid = self.common_grid();
+        let labels: Vec<String> = self.plots.iter().map(PlotData::label).collect();
+        let mut columns: Vec<(&str, Vec<Option<f32>>)> = self
+            .plots
+            .iter()
+            .zip(&labels)
+            .filter(|(plot_data, _)| self.in_composite(plot_data))
+            .map(|(plot_data, label)| (label.as_str(), plot_data.resample(&grid)))
+            .collect();
+        if self.has_average() {
+            columns.push(("average", self.avg_plot.resample(&grid)));
+        }
+        let grid: Vec<f32> = grid
+            .into_iter()
+            .map(|freq| self.export_rounding.apply(freq))
+            .collect();
+        export::write_combined_csv(path, &grid, &columns)
+    }
+
+    fn average_weight(&self, plot_data: &PlotData) -> f32 {
+        self.average_weights
+            .get(&plot_data.file_name)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Writes the visible curves with the view's range and labels through `write`, i.e.
+    /// [`export::write_figure`] or [`export::write_html`].
+    fn export_figure(&self, path: &Path, write: FigureWriter) -> Result<(), String> {
+        let to_xy = |points: Vec<PlotPoint>| points.into_iter().map(|p| [p.x, p.y]).collect();
+        let labels: Vec<String> = self.plots.iter().map(PlotData::label).collect();
+        let mut curves: Vec<export::Curve> = self
+            .plots
+            .iter()
+            .zip(&labels)
+            .enumerate()
+            .filter(|(_, (plot_data, _))| self.is_visible(plot_data))
+            .map(|(i, (plot_data, label))| export::Curve {
+                name: label,
+                color: self.plot_color(i),
+                points: to_xy(self.plot_points(plot_data)),
+            })
+            .collect();
+        if let Some(reference) = self
+            .reference
+            .as_ref()
+            .filter(|_| !self.showing_difference())
+        {
+            curves.push(export::Curve {
+                name: &reference.file_name,
+                color: REFERENCE_COLOR,
+                points: to_xy(self.display_points(reference)),
+            });
+        }
+        if self.has_average() && self.show_average && self.solo.is_none() {
+            let points = if self.showing_difference() {
+                vec![[self.min_freq as f64, 0.0], [self.max_freq as f64, 0.0]]
+            } else {
+                to_xy(self.display_points(&self.avg_plot))
+            };
+            curves.push(export::Curve {
+                name: "Average",
+                color: self.average_color,
+                points,
+            });
+        }
+        let (x_range, y_range) = match self.plot_bounds {
+            Some(bounds) => (
+                [bounds.min()[0], bounds.max()[0]],
+                Some([bounds.min()[1], bounds.max()[1]]),
+            ),
+            None => ([self.min_freq as f64, self.max_freq as f64], None),
+        };
+        let [mut x_label, y_label] = self.axis_labels();
+        if self.wavelength_axis && self.x_label.is_empty() {
+            // the script plots against frequency, without the converted tick labels
+            x_label = "Frequency (Hz)".to_string();
+        }
+        let figure = export::Figure {
+            curves,
+            x_range,
+            y_range,
+            title: &self.plot_title,
+            x_label: &x_label,
+            y_label: &y_label,
+        };
+        write(path, &figure)
+    }
+
+    /// Labels of the spectrum plot's axes: the custom ones, or what it shows.
+    fn axis_labels(&self) -> [String; 2] {
+        let x_label = match self.x_label.as_str() {
+            "" if self.wavelength_axis => format!("Wavelength at {} m/s", self.speed_of_sound),
+            "" => "Frequency (Hz)".to_string(),
+            label => label.to_string(),
+        };
+        let y_label = match self.y_label.as_str() {
+            "" if self.showing_difference() => "Difference from average (dB)".to_string(),
+            "" if self.spl_scale().is_some() => "Sine level (dB SPL)".to_string(),
+            "" => {
+                let amplitude = match self.amplitude_mode {
+                    AmplitudeMode::PerBin => "Amplitude",
+                    AmplitudeMode::PerOctave => "Amplitude per octave",
+                };
+                let normalized = if self.normalization == Normalization::None {
+                    ""
+                } else {
+                    " (normalized)"
+                };
+                format!("{}{}", amplitude, normalized)
+            }
+            label => label.to_string(),
+        };
+        [x_label, y_label]
+    }
+
+    /// Writes the average spectrum turned back into a signal with `average_phase`, at the sample
+    /// rate whose FFT would have had its bins.
+    fn export_average_wav(&self, path: &Path) -> Result<(), String> {
+        let freqs = &self.avg_plot.freqs;
+        let bin_hz = match freqs[..] {
+            [first, second, ..] => second - first,
+            _ => return Err("The average has too few bins".to_string()),
+        };
+        let samples = resynthesis::resynthesize(&self.avg_plot.amplitudes, self.average_phase);
+        let sample_rate = (samples.len() as f32 * bin_hz).round() as u32;
+        wav::write_float_wav(path, &samples, sample_rate)
+    }
+
+    /// Draws the plot as currently shown into a PNG of `png_size` pixels. Layout happens in
+    /// points of 1/96 inch, so at higher DPI text and lines stay the same size on paper.
+    fn export_png(&mut self, path: &Path) -> Result<(), String> {
+        const POINTS_PER_INCH: f32 = 96.0;
+        let [width, height] = self.png_size.map(|side| side as usize);
+        let pixels_per_point = self.png_dpi / POINTS_PER_INCH;
+        let ctx = egui::Context::default();
+        ctx.set_visuals(self.theme.visuals());
+        let screen = egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(width as f32, height as f32) / pixels_per_point,
+        );
+        let bounds = self.plot_bounds;
+        let mut rasterizer = Rasterizer::default();
+        let mut shapes = Vec::new();
+        // the first pass lays out the text, filling the font atlas the second one draws with
+        for _ in 0..2 {
+            let mut input = egui::RawInput {
+                screen_rect: Some(screen),
+                ..Default::default()
+            };
+            input
+                .viewports
+                .entry(egui::ViewportId::ROOT)
+                .or_default()
+                .native_pixels_per_point = Some(pixels_per_point);
+            let output = ctx.run(input, |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    self.spectrum_plot(ui, bounds);
+                });
+            });
+            rasterizer.update_textures(&output.textures_delta);
+            shapes = output.shapes;
+        }
+        let primitives = ctx.tessellate(shapes, pixels_per_point);
+        let background = ctx.style().visuals.panel_fill;
+        let image =
+            rasterizer.render(&primitives, pixels_per_point, [width, height], background)?;
+        export::write_png(path, &image, self.png_dpi)
+    }
+
+    /// The spectrum plot with clicks selecting, soloing and marking, or on one side of the split
+    /// view only the files of [`Self::split_panel`].
+    fn plot_view(&mut self, ui: &mut Ui) {
+        let plot_response = self.spectrum_plot(ui, None);
+        self.plot_bounds = Some(*plot_response.transform.bounds());
+        let response = &plot_response.response;
+        let shift = ui.input(|i| i.modifiers.shift);
+        if response.clicked() && shift {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let point = plot_response.transform.value_from_position(pos);
+                self.markers.push(Marker {
+                    frequency: point.x,
+                    amplitude: point.y,
+                });
+            }
+        } else if response.clicked() {
+            let clicked = response
+                .interact_pointer_pos()
+                .and_then(|pos| self.line_near(pos, &plot_response.transform));
+            if let Some(index) = clicked {
+                self.active_plot = index;
+                self.hopped_peak = None;
+                self.toggle_solo(index);
+            }
+        }
+        if response.secondary_clicked() {
+            self.context_plot = response
+                .interact_pointer_pos()
+                .and_then(|pos| self.line_near(pos, &plot_response.transform));
+        }
+        // the other side of the split view would close the menu again
+        let context_plot = self
+            .context_plot
+            .filter(|&i| self.plots.get(i).is_some_and(|p| self.in_panel(p)));
+        if let Some(index) = context_plot {
+            let menu = response.context_menu(|ui| self.line_context_menu(ui, index));
+            if menu.is_none() {
+                self.context_plot = None;
+            }
+        }
+    }
+
+    /// The spectra and everything drawn over them. `bounds` fixes the visible range instead of
+    /// the interactive zoom, for drawing the same view off screen.
+    fn spectrum_plot(&mut self, ui: &mut Ui, bounds: Option<PlotBounds>) -> PlotResponse<()> {
+        self.refresh_points();
+        let plot = match &self.split_panel {
+            Some(source) => Plot::new(("my_plot", source)),
+            None => Plot::new("my_plot"),
+        };
+        if !self.plot_title.is_empty() {
+            ui.vertical_centered(|ui| ui.heading(&self.plot_title));
+        }
+        let [x_label, y_label] = self.axis_labels();
+        let mut plot = plot
+            .legend(Legend::default())
+            .x_axis_label(x_label)
+            .y_axis_label(y_label)
+            .show_grid([self.frequency_grid != FrequencyGrid::None, true]);
+        if let Some(bands) = self.frequency_grid.bands_per_octave() {
+            plot = plot.x_grid_spacer(move |input| fractional_octave_marks(input, bands));
+        }
+        if self.split_panel.is_some() && self.link_axes {
+            plot = plot
+                .link_axis("split_view", true, true)
+                .link_cursor("split_view", true, true);
+        }
+        if bounds.is_none() && std::mem::take(&mut self.reset_bounds) {
+            plot = plot.reset();
+        }
+        if self.scroll_range {
+            // the x axis follows the range instead, see `scroll_frequency_range`
+            plot = plot.allow_scroll([false, true]).allow_zoom(false);
+        }
+        if self.wavelength_axis {
+            // the data stays in Hz, only the labels are converted
+            let speed = self.speed_of_sound as f64;
+            plot = plot.x_axis_formatter(move |mark, _| format_wavelength(mark.value, speed));
+            plot = plot.label_formatter(move |name, point| {
+                let wavelength = format_wavelength(point.x, speed);
+                let name = if name.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}\n", name)
+                };
+                format!(
+                    "{}λ = {} ({:.2} Hz)\n{:.4}",
+                    name, wavelength, point.x, point.y
+                )
+            });
+        }
+        if self.interpolated_readout {
+            // drawn below instead
+            plot = plot.label_formatter(|_, _| String::new());
+        }
+        let drag_threshold = self.show_threshold && bounds.is_none();
+        if drag_threshold && self.threshold_grabbed {
+            plot = plot.allow_drag(false);
+        }
+        let spl = self.spl_scale();
+        if let Some(spl) = spl {
+            // the magnitudes stay linear, only the labels are converted
+            plot = plot.y_axis_formatter(move |mark, _| spl.tick_label(mark.value));
+        }
+        let phase = self.phase_points();
+        let group_delay = self.group_delay_points();
+        if phase.is_some() || group_delay.is_some() {
+            // egui_plot has one transform for all axes, so the others relabel the same range:
+            // −180° at the bottom of the view and 180° at the top for the phase
+            let mut amplitude_axis = AxisHints::new_y().label(self.axis_labels()[1].clone());
+            if let Some(spl) = spl {
+                amplitude_axis =
+                    amplitude_axis.formatter(move |mark, _| spl.tick_label(mark.value));
+            }
+            let mut axes = vec![amplitude_axis];
+            if phase.is_some() {
+                axes.push(
+                    AxisHints::new_y()
+                        .label("Phase (°)")
+                        .placement(egui_plot::HPlacement::Right)
+                        .formatter(|mark, range| {
+                            let degrees = overlay_value(mark.value, range, (-180.0, 180.0));
+                            let shown = (-180.0..=180.0).contains(&degrees);
+                            if shown {
+                                format!("{:.0}°", degrees)
+                            } else {
+                                String::new()
+                            }
+                        }),
+                );
+            }
+            if let Some((_, span)) = group_delay {
+                axes.push(
+                    AxisHints::new_y()
+                        .label("Group delay (ms)")
+                        .placement(egui_plot::HPlacement::Right)
+                        .formatter(move |mark, range| {
+                            let ms = overlay_value(mark.value, range, span);
+                            // enough decimals to tell the ticks apart
+                            let decimals =
+                                (2.0 - (span.1 - span.0).log10().floor()).clamp(0.0, 6.0) as usize;
+                            format!("{:.*}", decimals, ms)
+                        }),
+                );
+            }
+            plot = plot.custom_y_axes(axes);
+        }
+        // egui_plot draws its background in the extreme background color
+        let plot_background = self.plot_background();
+        let background = std::mem::replace(&mut ui.visuals_mut().extreme_bg_color, plot_background);
+        let harmonics = self.harmonic_frequencies();
+        let plot_response = plot
+            // .view_aspect(2.0)
+            .show(ui, |plot_ui| {
+                if let Some(bounds) = bounds {
+                    plot_ui.set_plot_bounds(bounds);
+                }
+                if bounds.is_none() && self.auto_y && !self.showing_difference() {
+                    let current = plot_ui.plot_bounds();
+                    let x_range = if current.is_valid_x() {
+                        current.range_x()
+                    } else {
+                        self.min_freq as f64..=self.max_freq as f64
+                    };
+                    if let Some(top) = self.auto_y_top(x_range) {
+                        let fitted = PlotBounds::from_min_max([0.0, 0.0], [0.0, top]);
+                        let mut bounds = current;
+                        bounds.set_y(&fitted);
+                        plot_ui.set_plot_bounds(bounds);
+                    }
+                }
+                if bounds.is_none() && self.scroll_range && plot_ui.response().hovered() {
+                    self.scroll_frequency_range(plot_ui);
+                }
+                if bounds.is_none() && std::mem::take(&mut self.fit_loudest) {
+                    let (left, right) = (self.min_freq as f64, self.max_freq as f64);
+                    let top = self
+                        .auto_y_top(left..=right)
+                        .unwrap_or(plot_ui.plot_bounds().max()[1]);
+                    plot_ui.set_plot_bounds(PlotBounds::from_min_max([left, 0.0], [right, top]));
+                }
+                if let Some(peak) = self.hopped_peak {
+                    let frequency = self.shown_frequency(self.active_plot, peak.frequency);
+                    if bounds.is_none() && std::mem::take(&mut self.center_on_peak) {
+                        let mut bounds = plot_ui.plot_bounds();
+                        bounds.set_x_center_width(frequency as f64, bounds.width());
+                        plot_ui.set_plot_bounds(bounds);
+                    }
+                    let active = self.plots.get(self.active_plot);
+                    if let Some(plot_data) = active.filter(|_| !self.showing_difference()) {
+                        let weight = self.amplitude_mode.weight(frequency);
+                        let y = (peak.amplitude * self.display_scale(plot_data) * weight) as f64;
+                        let position = PlotPoint::new(frequency, y);
+                        let marker = Points::new(vec![[position.x, position.y]]);
+                        plot_ui.points(marker.radius(5.0).color(Color32::RED));
+                        let label = Text::new(position, format!("{:.2} Hz", frequency));
+                        plot_ui.text(label.anchor(egui::Align2::LEFT_BOTTOM));
+                    }
+                }
+                let band_edges = self.shade_bands.then_some(self.band_edges);
+                for (i, plot_data) in self.plots.iter().enumerate() {
+                    if !self.is_visible(plot_data) || !self.in_panel(plot_data) {
+                        continue;
+                    }
+                    let cached = self.line_points(i);
+                    let color = self.plot_color(i).gamma_multiply(plot_data.opacity);
+                    if let Some(raw) = cached.raw.as_ref().filter(|_| self.raw_opacity > 0.0) {
+                        let raw = PlotPoints::Owned(raw.to_vec());
+                        plot_ui.line(Line::new(raw).color(color.gamma_multiply(self.raw_opacity)));
+                    }
+                    if self.fill_under {
+                        let fill = AreaFill::new(cached.points.clone(), color, self.fill_opacity);
+                        plot_ui.add(fill.bands(band_edges));
+                    }
+                    plot_ui.line(
+                        Line::new(PlotPoints::Owned(cached.points.to_vec()))
+                            .color(color)
+                            .name(plot_data.label()),
+                    );
+                }
+                if let Some([low, high]) = band_edges.filter(|_| self.fill_under) {
+                    for edge in [low, high] {
+                        let vline = VLine::new(edge).color(Color32::GRAY);
+                        plot_ui.vline(vline.style(LineStyle::dashed_loose()));
+                    }
+                }
+                for &freq in &harmonics {
+                    plot_ui.vline(VLine::new(freq).color(HARMONIC_COLOR).name("Harmonics"));
+                }
+                if let Some((name, color, points)) = &phase {
+                    let bounds = plot_ui.plot_bounds();
+                    let (bottom, height) = (bounds.min()[1], bounds.height());
+                    let points: Vec<[f64; 2]> = points
+                        .iter()
+                        .map(|&[freq, degrees]| [freq, bottom + (degrees + 180.0) / 360.0 * height])
+                        .collect();
+                    let line = Line::new(PlotPoints::from(points)).color(*color).name(name);
+                    plot_ui.line(line.style(LineStyle::dashed_dense()));
+                }
+                if let Some(((name, color, points), (low, high))) = &group_delay {
+                    let bounds = plot_ui.plot_bounds();
+                    let (bottom, height) = (bounds.min()[1], bounds.height());
+                    let points: Vec<[f64; 2]> = points
+                        .iter()
+                        .map(|&[freq, ms]| [freq, bottom + (ms - low) / (high - low) * height])
+                        .collect();
+                    let line = Line::new(PlotPoints::from(points)).color(*color).name(name);
+                    plot_ui.line(line.style(LineStyle::dotted_dense()));
+                }
+                let top = plot_ui.plot_bounds().max()[1];
+                for annotation in &self.annotations {
+                    let vline = VLine::new(annotation.frequency).color(ANNOTATION_COLOR);
+                    plot_ui.vline(vline.style(LineStyle::dashed_dense()));
+                    let position = PlotPoint::new(annotation.frequency, top);
+                    let label = Text::new(position, &annotation.label).color(ANNOTATION_COLOR);
+                    plot_ui.text(label.anchor(egui::Align2::LEFT_TOP));
+                }
+                let active = self.plots.get(self.active_plot).is_some_and(|plot_data| {
+                    self.is_visible(plot_data) && self.in_panel(plot_data)
+                });
+                let readout = self.interpolated_readout && bounds.is_none() && active;
+                if let Some(pointer) = plot_ui.pointer_coordinate().filter(|_| readout) {
+                    let points = self.cached_points(self.active_plot);
+                    if let Some((y, nearest)) = interpolate_at(&points, pointer.x) {
+                        let color = self.plot_color(self.active_plot);
+                        plot_ui.points(Points::new(vec![[pointer.x, y]]).radius(3.0).color(color));
+                        let text = format!(
+                            "{:.2} Hz: {:.4}\nnearest bin {:.2} Hz: {:.4}",
+                            pointer.x, y, nearest.x, nearest.y
+                        );
+                        let label = Text::new(PlotPoint::new(pointer.x, y), text).color(color);
+                        plot_ui.text(label.anchor(egui::Align2::LEFT_BOTTOM));
+                    }
+                }
+                let bottom = plot_ui.plot_bounds().min()[1];
+                for tone in &self.tones {
+                    let vline = VLine::new(tone.frequency)
+                        .color(TONE_COLOR)
+                        .name("Expected tones");
+                    plot_ui.vline(vline.style(LineStyle::dotted_dense()));
+                    let position = PlotPoint::new(tone.frequency, bottom);
+                    let label = Text::new(position, &tone.label).color(TONE_COLOR);
+                    plot_ui.text(label.anchor(egui::Align2::LEFT_BOTTOM));
+                }
+                for (i, marker) in self.markers.iter().enumerate() {
+                    let points = Points::new(vec![[marker.frequency, marker.amplitude]]);
+                    plot_ui.points(points.radius(4.0).color(MARKER_COLOR));
+                    let position = PlotPoint::new(marker.frequency, marker.amplitude);
+                    let label = Text::new(position, format!("M{}", i + 1)).color(MARKER_COLOR);
+                    plot_ui.text(label.anchor(egui::Align2::LEFT_BOTTOM));
+                }
+                // The reference is an absolute curve, it has no meaning in the difference view.
+                if let Some(reference) = self
+                    .reference
+                    .as_ref()
+                    .filter(|_| !self.showing_difference())
+                {
+                    plot_ui.line(
+                        Line::new(PlotPoints::Owned(self.display_points(reference)))
+                            .color(REFERENCE_COLOR)
+                            .width(3.0)
+                            .style(LineStyle::dashed_loose())
+                            .name(format!("Reference: {}", reference.file_name)),
+                    );
+                }
+                // the mask is in absolute levels, which normalization and the difference view rescale
+                let mask = self
+                    .mask
+                    .as_ref()
+                    .filter(|_| self.normalization == Normalization::None);
+                if let Some(mask) = mask.filter(|_| !self.showing_difference()) {
+                    for line in self.mask_lines(mask) {
+                        let line = Line::new(PlotPoints::Owned(line))
+                            .color(MASK_COLOR)
+                            .width(2.0);
+                        plot_ui.line(line.style(LineStyle::dashed_loose()).name("Mask"));
+                    }
+                }
+                if self.show_threshold {
+                    let line = HLine::new(self.threshold).color(THRESHOLD_COLOR).width(2.0);
+                    plot_ui.hline(line.name("Threshold"));
+                }
+                if drag_threshold {
+                    const GRAB_DISTANCE: f32 = 6.0;
+                    let dragged = plot_ui.response().dragged();
+                    if dragged && self.threshold_grabbed {
+                        if let Some(pointer) = plot_ui.pointer_coordinate() {
+                            self.threshold = pointer.y as f32;
+                        }
+                    } else if !dragged {
+                        let line = plot_ui
+                            .screen_from_plot(PlotPoint::new(0.0, self.threshold))
+                            .y;
+                        let pointer = plot_ui.response().hover_pos();
+                        let near = |pos: egui::Pos2| (pos.y - line).abs() <= GRAB_DISTANCE;
+                        self.threshold_grabbed = pointer.is_some_and(near);
+                    }
+                }
+                for (i, (plot_data, violations)) in
+                    self.plots.iter().zip(&self.mask_violations).enumerate()
+                {
+                    if !self.is_visible(plot_data)
+                        || !self.in_panel(plot_data)
+                        || violations.is_empty()
+                    {
+                        continue;
+                    }
+                    let points = self.cached_points(i);
+                    for violation in violations {
+                        let range =
+                            violation.start_frequency as f64..=violation.end_frequency as f64;
+                        let outside: Vec<PlotPoint> = points
+                            .iter()
+                            .filter(|point| range.contains(&point.x))
+                            .copied()
+                            .collect();
+                        // a single bin has no line to draw
+                        if let [point] = outside[..] {
+                            let marker = Points::new(vec![[point.x, point.y]]).radius(3.0);
+                            plot_ui.points(marker.color(VIOLATION_COLOR));
+                        } else if !outside.is_empty() {
+                            let line = Line::new(PlotPoints::Owned(outside)).color(VIOLATION_COLOR);
+                            plot_ui.line(line.width(3.0));
+                        }
+                    }
+                }
+                if let Some(stream) = self.stream.as_ref().filter(|_| !self.showing_difference()) {
+                    let name = format!("{} (streaming)", stream.name);
+                    let points = PlotPoints::Owned(self.display_points(&stream.plot));
+                    plot_ui.line(Line::new(points).color(STREAM_COLOR).width(2.0).name(name));
+                }
+                if self.peak_hold && !self.held_peaks.freqs.is_empty() && !self.showing_difference()
+                {
+                    plot_ui.line(
+                        Line::new(PlotPoints::Owned(self.display_points(&self.held_peaks)))
+                            .color(PEAK_HOLD_COLOR)
+                            .style(LineStyle::dashed_dense())
+                            .name("Peak hold"),
+                    );
+                }
+                // in the difference view it's the drift of the live average from it, mirrored
+                if let Some(frozen) = &self.frozen_average {
+                    let points = if self.showing_difference() {
+                        self.difference_points(frozen)
+                    } else {
+                        self.display_points(frozen)
+                    };
+                    plot_ui.line(
+                        Line::new(PlotPoints::Owned(points))
+                            .color(FROZEN_AVERAGE_COLOR)
+                            .width(2.0)
+                            .style(LineStyle::dashed_loose())
+                            .name(&frozen.file_name),
+                    );
+                }
+                let split = self.split_panel.is_some();
+                if !self.has_average() || !self.show_average || self.solo.is_some() || split {
+                    return;
+                }
+                let style = self.average_style.line_style();
+                if self.diff_from_average {
+                    // Deviation of the average from itself is 0 dB everywhere.
+                    plot_ui.hline(
+                        HLine::new(0.0)
+                            .color(self.average_color)
+                            .width(self.average_width)
+                            .style(style)
+                            .name("Average"),
+                    );
+                } else {
+                    if self.show_std_band {
+                        let (upper, lower) = self.std_band_points();
+                        let band =
+                            AreaFill::between(upper, lower, self.average_color, STD_BAND_OPACITY);
+                        plot_ui.add(band);
+                    }
+                    plot_ui.line(
+                        Line::new(PlotPoints::Owned(self.display_points(&self.avg_plot)))
+                            .color(self.average_color)
+                            .width(self.average_width)
+                            .style(style)
+                            .name("Average"),
+                    );
+                }
+            });
+        ui.visuals_mut().extreme_bg_color = background;
+        if bounds.is_none() {
+            self.peak_tooltip(&plot_response);
+        }
+        plot_response
+    }
+
+    /// Exact readings of the active plot's detected peak under the pointer, if it's within a few
+    /// pixels of one.
+    fn peak_tooltip(&mut self, plot_response: &PlotResponse<()>) {
+        const MAX_DISTANCE: f32 = 10.0;
+        let Some(pointer) = plot_response.response.hover_pos() else {
+            return;
+        };
+        let index = self.active_plot;
+        let shown = self
+            .plots
+            .get(index)
+            .is_some_and(|p| self.is_visible(p) && self.in_panel(p));
+        if !shown || self.showing_difference() {
+            return;
+        }
+        let band = [self.peak_band.min_freq, self.peak_band.max_freq];
+        let (count, limits) = (
+            self.peak_count,
+            [self.peak_prominence, self.peak_distance, band[0], band[1]],
+        );
+        if !matches!(&self.hover_peaks, Some((i, c, l, _)) if (*i, *c, *l) == (index, count, limits))
+        {
+            self.hover_peaks = Some((index, count, limits, self.detect_peaks(&self.plots[index])));
+        }
+        let plot_data = &self.plots[index];
+        let Some((_, _, _, peaks)) = &self.hover_peaks else {
+            return;
+        };
+        let scale = self.display_scale(plot_data);
+        let calibration = self.calibration(plot_data);
+        let peak = peaks
+            .iter()
+            .map(|peak| {
+                let frequency = calibration.apply(peak.frequency);
+                let y = peak.amplitude * scale * self.amplitude_mode.weight(frequency);
+                let position = PlotPoint::new(frequency, y);
+                (
+                    plot_response
+                        .transform
+                        .position_from_point(&position)
+                        .distance(pointer),
+                    peak,
+                )
+            })
+            .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, peak)| *peak);
+        let Some(peak) = peak else {
+            return;
+        };
+        let bin = plot_data
+            .freqs
+            .partition_point(|&freq| freq < peak.frequency);
+        let (frequency, amplitude) =
+            peaks::interpolate(&plot_data.freqs, &plot_data.amplitudes, bin);
+        let frequency = calibration.apply(frequency);
+        let mut lines = vec![
+            format!("Bin: {:.2} Hz", calibration.apply(peak.frequency)),
+            format!("Interpolated: {:.3} Hz", frequency),
+            format!(
+                "Amplitude: {:.4} ({:.1} dB)",
+                amplitude,
+                20.0 * amplitude.max(1e-12).log10()
+            ),
+        ];
+        if let Some(spl) = self.spl_scale() {
+            lines.push(format!("Level: {:.1} dB SPL", spl.level(amplitude)));
+        }
+        if let Some(tuning) = peaks::tuning(frequency, self.a4_reference) {
+            lines.push(format!("Note: {} {:+.1} ¢", tuning.note, tuning.cents));
+        }
+        plot_response.response.clone().on_hover_ui_at_pointer(|ui| {
+            for line in lines {
+                ui.label(line);
+            }
+        });
+    }
 
-fn fourier_analysis(samples: &[f32], sample_rate: u32) -> (Vec<f32>, Vec<f32>) {
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(samples.len());
-    let mut buffer: Vec<Complex<f32>> = samples.iter().map(|&s| Complex::new(s, 0.0)).collect();
-    fft.process(&mut buffer);
+    /// Per-file weights in the average, with each file's normalized share.
+    fn weights_view(&mut self, ui: &mut Ui) {
+        let total: f32 = self
+            .plots
+            .iter()
+            .map(|plot_data| self.composite_weight(plot_data))
+            .sum();
+        let previous_domain = self.average_domain;
+        ui.horizontal(|ui| {
+            ui.label("Average:");
+            ui.radio_value(
+                &mut self.average_domain,
+                AverageDomain::Amplitude,
+                "Amplitude",
+            )
+            .on_hover_text("Mean of the amplitudes, for steady tones");
+            ui.radio_value(
+                &mut self.average_domain,
+                AverageDomain::Power,
+                "Power (RMS)",
+            )
+            .on_hover_text("Root of the mean power; about 1 dB higher, and unbiased, for noise");
+        });
+        let mut changed = self.average_domain != previous_domain;
+        egui::Grid::new("average_weights").show(ui, |ui| {
+            for plot_data in &self.plots {
+                ui.label(plot_data.label());
+                let mut weight = self.average_weight(plot_data);
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut weight)
+                            .range(0.0..=100.0)
+                            .speed(0.01),
+                    )
+                    .changed()
+                {
+                    self.average_weights
+                        .insert(plot_data.file_name.clone(), weight);
+                    changed = true;
+                }
+                if self.in_composite(plot_data) {
+                    let share = if total > 0.0 { weight / total } else { 0.0 };
+                    ui.weak(format!("{:.1} %", share * 100.0));
+                } else {
+                    ui.weak("not selected");
+                }
+                ui.end_row();
+            }
+        });
+        if ui.button("Equal weights").clicked() {
+            self.average_weights.clear();
+            changed = true;
+        }
+        if changed {
+            self.update_average();
+        }
+    }
 
-    let freqs: Vec<f32> = (0..buffer.len() / 2)
-        .map(|i| i as f32 * sample_rate as f32 / samples.len() as f32)
-        .collect();
-    let amplitudes: Vec<f32> = buffer.iter().take(buffer.len() / 2).map(|c| c.norm()).collect();
-    (freqs, amplitudes)
-}
+    /// Per-file start offsets in milliseconds.
+    fn offsets_view(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        egui::Grid::new("offsets").show(ui, |ui| {
+            for plot_data in &mut self.plots {
+                let Some(sample_rate) = plot_data.sample_rate else {
+                    continue;
+                };
+                ui.label(plot_data.label());
+                let mut ms = plot_data.offset as f32 * 1000.0 / sample_rate as f32;
+                let drag = egui::DragValue::new(&mut ms)
+                    .range(0.0..=f32::MAX)
+                    .speed(1.0)
+                    .suffix(" ms");
+                if ui.add(drag).changed() {
+                    plot_data.offset = (ms / 1000.0 * sample_rate as f32).round() as usize;
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+        let mut align_errors = Vec::new();
+        if ui.button("Auto-align to active file").clicked() {
+            align_errors = self.auto_align();
+            changed = true;
+        }
+        if changed {
+            self.reanalyze();
+            self.analysis_errors.extend(align_errors);
+        }
+    }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <folder>", args[0]);
-        return Ok(());
+    /// Per-file window overrides; changing one re-analyses only that file.
+    fn windows_view(&mut self, ui: &mut Ui) {
+        let (options, sample_type) = (self.analysis_options(), self.sample_type);
+        let global = format!("Global ({})", self.window.name());
+        let mut changed = None;
+        egui::Grid::new("windows").show(ui, |ui| {
+            for (i, plot_data) in self.plots.iter_mut().enumerate() {
+                if plot_data.sample_rate.is_none() {
+                    continue;
+                }
+                ui.label(plot_data.label());
+                let previous = plot_data.window;
+                let selected = previous.map_or(global.clone(), |window| window.name().to_string());
+                egui::ComboBox::from_id_salt(("window", i))
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut plot_data.window, None, global.as_str());
+                        for window in Window::ALL {
+                            ui.selectable_value(&mut plot_data.window, Some(window), window.name());
+                        }
+                    });
+                if plot_data.window != previous {
+                    changed = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = changed {
+            let plot_data = &mut self.plots[i];
+            if let Err(e) = plot_data.reanalyze(options, sample_type) {
+                self.analysis_errors.push(format!(
+                    "Failed to re-analyse {}: {}",
+                    plot_data.file_name, e
+                ));
+            }
+            self.update_average();
+        }
     }
-    let folder_path = &args[1];
-    let all_files = std::fs::read_dir(folder_path)?;
-    let mut plots = Vec::new();
-    for file in all_files {
-        let file_path = file?.path().display().to_string();
-        let (freqs, amplitudes) = if file_path.ends_with(".wav") {
-            let t0 = std::time::Instant::now();
-            let (samples, sample_rate) = read_wav(&file_path)?;
-            let (freqs, amplitudes) = fourier_analysis(&samples, sample_rate);
-            println!("Time taken for reading wav: {:?}", t0.elapsed());
-            (freqs, amplitudes)
-            // } else if file_path.ends_with(".mp3") {
-            //     read_mp3(file_path)?
-        } else if file_path.ends_with(".f") {
-            let t0 = std::time::Instant::now();
-            let res = read_f(&file_path)?;
-            println!("Time taken for reading f: {:?}", t0.elapsed());
-            res
-        } else {
-            eprintln!("Unsupported file format");
-            return Ok(());
+
+    /// Resolution and time span of each power-of-two FFT size for the active file, from its length
+    /// after the start offset, trimming and decimation. Picking one sets the Welch segment size,
+    /// or without Welch averaging a fixed input length.
+    fn fft_size_view(&mut self, ui: &mut Ui) {
+        let Some(plot_data) = self.plots.get(self.active_plot) else {
+            return;
         };
+        let Some(sample_rate) = plot_data.sample_rate else {
+            ui.label(format!("{} has no samples", plot_data.label()));
+            return;
+        };
+        let options = self.analysis_options();
+        let factor = self.decimation.factor.max(1);
+        let rate = (sample_rate / factor) as f32;
+        let trimmed = options
+            .trimmed_range(plot_data.analysed_len(), sample_rate)
+            .len();
+        let len = trimmed / factor as usize;
+        ui.label(format!(
+            "{}: {} samples at {} Hz, {:.3} s",
+            plot_data.label(),
+            len,
+            rate,
+            len as f32 / rate
+        ));
+        let current = options.fft_size(trimmed);
+        ui.label(format!(
+            "Current FFT size: {} ({:.3} Hz/bin)",
+            current,
+            rate / current as f32
+        ));
+        let largest = len
+            .next_power_of_two()
+            .min(self.sample_limit.max_fft_size)
+            .max(256);
+        let mut picked = None;
+        egui::Grid::new("fft_sizes").striped(true).show(ui, |ui| {
+            ui.strong("FFT size");
+            ui.strong("Resolution");
+            ui.strong("Span");
+            ui.strong("Segments")
+                .on_hover_text("Welch segments with 50 % overlap");
+            ui.end_row();
+            for size in (8..usize::BITS)
+                .map(|bits| 1 << bits)
+                .take_while(|&size| size <= largest)
+            {
+                let text = |text: String| {
+                    if size == current {
+                        egui::RichText::new(text).strong()
+                    } else {
+                        egui::RichText::new(text)
+                    }
+                };
+                ui.label(text(size.to_string()));
+                ui.label(text(format!("{:.3} Hz/bin", rate / size as f32)));
+                ui.label(text(format!("{:.1} ms", size as f32 / rate * 1000.0)));
+                if size <= len {
+                    ui.label(text(((len - size) / (size / 2) + 1).to_string()));
+                } else {
+                    ui.label(text("zero-padded".to_string()));
+                }
+                if ui.button("Use").clicked() {
+                    picked = Some(size);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(size) = picked {
+            if self.use_welch {
+                self.welch = Welch {
+                    segment_len: size,
+                    hop: size / 2,
+                };
+            } else {
+                self.input_length = InputLength::Fixed;
+                self.fixed_length = size * factor as usize;
+            }
+            self.reanalyze();
+        }
+    }
 
-        plots.push(PlotData {
-            freqs,
-            amplitudes,
-            file_name: file_path.to_string(),
+    /// Recorder, timecode and notes embedded in each WAV file, with every field and the cue
+    /// markers on hover.
+    fn metadata_view(&mut self, ui: &mut Ui) {
+        egui::Grid::new("metadata").striped(true).show(ui, |ui| {
+            ui.strong("File");
+            ui.strong("Recorder");
+            ui.strong("Timecode");
+            ui.strong("Notes");
+            ui.end_row();
+            for plot_data in &self.plots {
+                let Some(path) = plot_data
+                    .path
+                    .as_ref()
+                    .filter(|_| plot_data.raw_format.is_none())
+                else {
+                    continue;
+                };
+                let metadata = self
+                    .metadata
+                    .entry(path.clone())
+                    .or_insert_with(|| WavMetadata::read(path));
+                let metadata = match metadata {
+                    Ok(metadata) if !metadata.is_empty() => metadata,
+                    Ok(_) => {
+                        ui.label(plot_data.label());
+                        ui.weak("no metadata");
+                        ui.end_row();
+                        continue;
+                    }
+                    Err(e) => {
+                        ui.label(plot_data.label());
+                        ui.weak(e.as_str());
+                        ui.end_row();
+                        continue;
+                    }
+                };
+                // positions count samples at the rate the file was recorded with
+                let rate = plot_data
+                    .header_sample_rate
+                    .or(plot_data.sample_rate)
+                    .unwrap_or(1);
+                let mut details: Vec<String> = metadata
+                    .tags
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                for marker in &metadata.markers {
+                    let seconds = marker.position as f32 / rate as f32;
+                    let label = marker.label.as_deref().unwrap_or("");
+                    details.push(format!("Marker at {:.3} s: {}", seconds, label));
+                }
+                ui.label(plot_data.label())
+                    .on_hover_text(details.join("\n"));
+                ui.label(metadata.recorder.as_deref().unwrap_or("-"));
+                ui.label(metadata.timecode(rate).as_deref().unwrap_or("-"));
+                ui.label(metadata.notes.as_deref().unwrap_or("-"));
+                ui.end_row();
+            }
         });
     }
 
-    println!("Starting eframe with {} plots", plots.len());
-    match eframe::run_native(
-        "Frequency Spectrum",
-        eframe::NativeOptions::default(),
-        Box::new(|cc| Ok(Box::new(MyApp::new(cc, plots)))),
-    ) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+    /// Per-file sample rates, to correct the frequency axis of files whose header is wrong.
+    fn sample_rates_view(&mut self, ui: &mut Ui) {
+        let mut changed = Vec::new();
+        egui::Grid::new("sample_rates").show(ui, |ui| {
+            for (i, plot_data) in self.plots.iter_mut().enumerate() {
+                let Some(sample_rate) = plot_data.sample_rate else {
+                    continue;
+                };
+                ui.label(plot_data.label());
+                ui.label(format!(
+                    "{} Hz in header",
+                    plot_data.header_sample_rate.unwrap_or(sample_rate)
+                ));
+                let current = plot_data.header_sample_rate.map(|_| sample_rate);
+                let (mut overridden, mut rate) = (current.is_some(), sample_rate);
+                ui.checkbox(&mut overridden, "Override");
+                let drag = egui::DragValue::new(&mut rate)
+                    .range(1..=10_000_000)
+                    .speed(10.0)
+                    .suffix(" Hz");
+                ui.add_enabled(overridden, drag);
+                let wanted = overridden.then_some(rate);
+                if wanted != current {
+                    plot_data.override_sample_rate(wanted);
+                    changed.push(i);
+                }
+                ui.end_row();
+            }
+        });
+        if changed.is_empty() {
+            return;
+        }
+        let options = self.analysis_options();
+        // the cutoffs of a pre-filter are in Hz, only the FFT itself doesn't depend on the rate
+        if options.pre_filter != PreFilter::default() {
+            for i in changed {
+                let plot_data = &mut self.plots[i];
+                if let Err(e) = plot_data.reanalyze(options, self.sample_type) {
+                    self.analysis_errors.push(format!(
+                        "Failed to re-analyse {}: {}",
+                        plot_data.file_name, e
+                    ));
+                }
+            }
+        }
+        self.update_average();
     }
-}
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct PlotData {
-    freqs: Vec<f32>,
-    amplitudes: Vec<f32>,
-    file_name: String,
-}
+    /// Sets the offsets so the start of every file lines up with the active one, by cross-
+    /// correlation. Offsets can't be negative, so the file whose content comes earliest keeps
+    /// offset 0. Returns the files that couldn't be aligned.
+    fn auto_align(&mut self) -> Vec<String> {
+        let Some(reference) = self.plots.get(self.active_plot) else {
+            return Vec::new();
+        };
+        let (Some(sample_rate), Ok(Some(reference_samples))) = (
+            reference.sample_rate,
+            reference.decoded_samples(self.sample_type),
+        ) else {
+            return vec!["The active file has no samples to align to".to_string()];
+        };
+        let head_len = (ALIGN_SECONDS * sample_rate as f32) as usize;
+        let max_lag = (MAX_ALIGN_SECONDS * sample_rate as f32) as usize;
+        let head = |samples: &[f32]| samples[..head_len.min(samples.len())].to_vec();
+        let reference_head = head(&reference_samples);
+        let mut lags = Vec::with_capacity(self.plots.len());
+        let mut errors = Vec::new();
+        for plot_data in &self.plots {
+            let lag = match plot_data.decoded_samples(self.sample_type) {
+                Ok(Some(_)) if plot_data.sample_rate != Some(sample_rate) => {
+                    errors.push(format!(
+                        "Not aligning {}: different sample rate",
+                        plot_data.file_name
+                    ));
+                    None
+                }
+                Ok(Some(samples)) => Some(align::lag(&head(&samples), &reference_head, max_lag)),
+                Ok(None) => None,
+                Err(e) => {
+                    errors.push(format!("Failed to align {}: {}", plot_data.file_name, e));
+                    None
+                }
+            };
+            lags.push(lag);
+        }
+        let earliest = lags.iter().flatten().copied().min().unwrap_or(0);
+        for (plot_data, lag) in self.plots.iter_mut().zip(lags) {
+            if let Some(lag) = lag {
+                plot_data.offset = (lag - earliest) as usize;
+            }
+        }
+        errors
+    }
 
-impl Default for PlotData {
-    fn default() -> Self {
-        Self {
-            freqs: vec![],
-            amplitudes: vec![],
-            file_name: "".to_string(),
+    /// The inverse of [`Self::analysis_options`], except for the length, which follows the input
+    /// length setting. Disabled Welch averaging and filters keep their last values.
+    fn set_analysis_options(&mut self, options: AnalysisOptions) {
+        self.window = options.window;
+        self.use_welch = options.welch.is_some();
+        if let Some(welch) = options.welch {
+            self.welch = welch;
+        }
+        self.decimation = options.decimation;
+        self.use_high_pass = options.pre_filter.high_pass.is_some();
+        if let Some(cutoff) = options.pre_filter.high_pass {
+            self.high_pass = cutoff;
         }
+        self.use_low_pass = options.pre_filter.low_pass.is_some();
+        if let Some(cutoff) = options.pre_filter.low_pass {
+            self.low_pass = cutoff;
+        }
+        self.use_detrend = options.detrend.is_some();
+        if let Some(order) = options.detrend {
+            self.detrend_order = order;
+        }
+        self.precision = options.precision;
+        self.raw_amplitudes = options.raw_amplitudes;
+        self.two_sided = options.two_sided;
+        self.use_min_fft_size = options.min_fft_size.is_some();
+        if let Some(size) = options.min_fft_size {
+            self.min_fft_size = size;
+        }
+        self.skip_ms = options.skip_ms;
+        self.use_duration = options.duration_ms.is_some();
+        if let Some(duration) = options.duration_ms {
+            self.duration_ms = duration;
+        }
+        self.sample_limit = options.sample_limit;
     }
-}
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct MyApp {
-    #[serde(skip)]
-    plots: Vec<PlotData>,
-    #[serde(skip)]
-    avg_plot: PlotData,
-    min_freq: f32,
-    max_freq: f32,
-}
+    fn analysis_options(&self) -> AnalysisOptions {
+        AnalysisOptions {
+            window: self.window,
+            welch: self.use_welch.then_some(self.welch),
+            decimation: self.decimation,
+            pre_filter: PreFilter {
+                high_pass: self.use_high_pass.then_some(self.high_pass),
+                low_pass: self.use_low_pass.then_some(self.low_pass),
+            },
+            detrend: self.use_detrend.then_some(self.detrend_order),
+            precision: self.precision,
+            raw_amplitudes: self.raw_amplitudes,
+            two_sided: self.two_sided,
+            length: self.analysis_length(),
+            min_fft_size: self.use_min_fft_size.then_some(self.min_fft_size),
+            skip_ms: self.skip_ms,
+            duration_ms: self.use_duration.then_some(self.duration_ms),
+            sample_limit: self.sample_limit,
+        }
+    }
 
-impl Default for MyApp {
-    fn default() -> Self {
-        Self {
-            plots: Vec::new(),
-            avg_plot: PlotData::default(),
-            min_freq: 0.0,
-            max_freq: 20_000.0,
+    /// Samples every file is fitted to, from the lengths after the start offsets and trimming.
+    /// Spectra from `.f` files have no samples and don't count.
+    fn analysis_length(&self) -> Option<usize> {
+        let trim = AnalysisOptions::default().skip_ms(self.skip_ms);
+        let trim = trim.duration_ms(self.use_duration.then_some(self.duration_ms));
+        let lengths = self.plots.iter().filter_map(|plot_data| {
+            let sample_rate = plot_data.sample_rate?;
+            Some(
+                trim.trimmed_range(plot_data.analysed_len(), sample_rate)
+                    .len(),
+            )
+        });
+        match self.input_length {
+            InputLength::Native => None,
+            InputLength::Shortest => lengths.filter(|&len| len > 0).min(),
+            InputLength::Longest => lengths.max(),
+            InputLength::Fixed => Some(self.fixed_length),
         }
     }
-}
 
-impl MyApp {
-    /// Called once before the first frame.
-    pub fn new(cc: &eframe::CreationContext<'_>, plots: Vec<PlotData>) -> Self {
-        // This is also where you can customize the look and feel of egui using
-        // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
+    fn enforce_memory_limit(&mut self) {
+        if let Some(budget) = self.memory_limit {
+            self.analysis_errors
+                .extend(memory::enforce_budget(&mut self.plots, budget));
+        }
+    }
 
-        // Load previous app state (if any).
-        // Note that you must enable the `persistence` feature for this to work.
-        let mut app:Self = if let Some(storage) = cc.storage {
-            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
-        } else {
-            Self::default()
-        };
+    /// Re-runs the FFT of every plot after an analysis setting changed.
+    fn reanalyze(&mut self) {
+        let (options, sample_type) = (self.analysis_options(), self.sample_type);
+        self.analysed = options;
+        self.analysis_errors = parallel::map(self.plots.iter_mut(), self.threads, |plot_data| {
+            let result = plot_data.reanalyze(options, sample_type);
+            result
+                .err()
+                .map(|e| format!("Failed to re-analyse {}: {}", plot_data.file_name, e))
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+        self.analysis_errors.extend(self.length_warnings());
+        self.enforce_memory_limit();
+        self.update_average();
+    }
 
-        app.plots = plots;
-        app
+    /// Files shorter than the minimum FFT size, which are zero-padded up to it, files too short
+    /// to skip `skip_ms` of, which are analysed whole, and files too long for the sample limit.
+    fn length_warnings(&self) -> Vec<String> {
+        let options = self.analysis_options();
+        let mut warnings = Vec::new();
+        for plot_data in &self.plots {
+            let Some(sample_rate) = plot_data.sample_rate else {
+                continue;
+            };
+            let trimmed = options.trimmed_range(plot_data.analysed_len(), sample_rate);
+            if options.skip_ms > 0.0 && trimmed.start == 0 {
+                let duration_ms = plot_data.analysed_len() as f32 / sample_rate as f32 * 1000.0;
+                warnings.push(format!(
+                    "Warning: {} is only {:.0} ms long, analysed whole instead of skipping {} ms",
+                    plot_data.file_name, duration_ms, options.skip_ms
+                ));
+            }
+            let len = options.length.unwrap_or(trimmed.len());
+            if let Some(min_fft_size) = options
+                .min_fft_size
+                .filter(|_| len < options.min_input_len())
+            {
+                warnings.push(format!(
+                    "Warning: {} has only {} samples, zero-padded to the minimum FFT size of {}",
+                    plot_data.file_name, len, min_fft_size
+                ));
+            }
+            warnings.extend(
+                plot_data
+                    .limit_warning(options)
+                    .map(|warning| format!("Warning: {}", warning)),
+            );
+        }
+        warnings
+    }
+
+    /// Re-analyses only if the analysis options differ from the ones the plots were analysed
+    /// with, e.g. after the analysis settings were edited or a file changing the common input
+    /// length was removed. Display settings never get here, they only rebuild the points.
+    fn reanalyze_if_changed(&mut self) {
+        if self.analysis_options() != self.analysed {
+            self.reanalyze();
+        }
+    }
+
+    fn reload(&mut self) {
+        let (options, sample_type) = (self.analysis_options(), self.sample_type);
+        self.analysed = options;
+        self.analysis_errors = parallel::map(self.plots.iter_mut(), self.threads, |plot_data| {
+            let result = plot_data.reload(options, sample_type);
+            result
+                .err()
+                .map(|e| format!("Failed to reload {}: {}", plot_data.file_name, e))
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+        self.enforce_memory_limit();
+        self.update_average();
     }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if ui.button("Save average plot").clicked() {
-                let encoded: Vec<u8> = bincode::serialize(&self.avg_plot).unwrap();
-                let path = Path::new("average_plot.f");
-                let mut file = File::create(path).unwrap();
-                file.write_all(&encoded).unwrap();
+        if !ctx.wants_keyboard_input() {
+            if ctx.input(|i| i.key_pressed(egui::Key::N)) {
+                self.hop_peak(true);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::P)) {
+                self.hop_peak(false);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::S)) {
+                self.toggle_solo(self.active_plot);
+            }
+            if ctx.input(|i| i.key_pressed(egui::Key::Delete))
+                && self.active_plot < self.plots.len()
+            {
+                self.remove_plot(self.active_plot);
+            }
+        }
+        if self.align_pitch.then_some(self.active_plot) != self.pitch_reference {
+            self.update_pitch_alignment();
+        }
+        self.advance_stream(ctx);
+        if self.use_selection {
+            let selected: Vec<bool> = self.plots.iter().map(|p| self.in_composite(p)).collect();
+            if selected != self.averaged {
+                self.update_average();
+            }
+        }
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped.is_empty() {
+            let errors: Vec<String> = dropped
+                .iter()
+                .filter_map(|file| {
+                    let name = file
+                        .path
+                        .as_ref()
+                        .map_or(file.name.clone(), |p| p.display().to_string());
+                    self.add_dropped(file)
+                        .err()
+                        .map(|e| format!("Failed to open {}: {}", name, e))
+                })
+                .collect();
+            if self.input_length == InputLength::Native {
+                self.enforce_memory_limit();
+                self.update_average();
+            } else {
+                // the new files change the common length
+                self.reanalyze();
             }
+            self.analysis_errors.extend(errors);
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let mut selected = None;
+                egui::ComboBox::from_label("Preset")
+                    .selected_text(self.preset_name.as_str())
+                    .show_ui(ui, |ui| {
+                        for preset in &self.presets {
+                            if ui.selectable_label(preset.name == self.preset_name, &preset.name).clicked() {
+                                selected = Some(preset.clone());
+                            }
+                        }
+                    });
+                if let Some(preset) = selected {
+                    self.apply_preset(preset);
+                }
+                ui.text_edit_singleline(&mut self.preset_name);
+                let can_save = !self.preset_name.trim().is_empty();
+                if ui.add_enabled(can_save, egui::Button::new("Save preset")).clicked() {
+                    self.save_preset();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Theme:");
+                let previous_theme = self.theme;
+                ui.radio_value(&mut self.theme, Theme::Dark, "Dark");
+                ui.radio_value(&mut self.theme, Theme::Light, "Light");
+                if self.theme != previous_theme {
+                    ui.ctx().set_visuals(self.theme.visuals());
+                }
+                ui.separator();
+                ui.label("Plot background:");
+                let mut background = self.plot_background();
+                if ui.color_edit_button_srgba(&mut background).changed() {
+                    self.plot_background = Some(background);
+                }
+                if self.plot_background.is_some() && ui.button("Theme default").clicked() {
+                    self.plot_background = None;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Title:");
+                ui.text_edit_singleline(&mut self.plot_title);
+                ui.label("x label:");
+                ui.add(egui::TextEdit::singleline(&mut self.x_label).hint_text("Frequency (Hz)"));
+                ui.label("y label:");
+                let y_label = egui::TextEdit::singleline(&mut self.y_label).hint_text("automatic");
+                ui.add(y_label).on_hover_text("Empty to describe the amplitudes shown");
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Export PNG").clicked() {
+                    if let Err(e) = self.export_png(Path::new("spectrum.png")) {
+                        self.analysis_errors.push(format!("Failed to export spectrum.png: {}", e));
+                    }
+                }
+                // together at most raster::MAX_PIXELS, which exporting checks
+                ui.add(egui::DragValue::new(&mut self.png_size[0]).range(100..=16_384).suffix(" px"));
+                ui.label("×");
+                ui.add(egui::DragValue::new(&mut self.png_size[1]).range(100..=16_384).suffix(" px"));
+                ui.label("at");
+                ui.add(egui::DragValue::new(&mut self.png_dpi).range(72.0..=1200.0).suffix(" DPI"));
+                ui.separator();
+                ui.label("Spectrum exports keep")
+                    .on_hover_text("Bins written by Export CSV and Copy; some tools expect N/2 bins");
+                ui.checkbox(&mut self.export_edges.dc, "DC bin");
+                ui.checkbox(&mut self.export_edges.nyquist, "Nyquist bin");
+                ui.separator();
+                ui.label("Frequencies:").on_hover_text("Rounding of exported frequencies, for report tables");
+                let rounding = &mut self.export_rounding;
+                ui.radio_value(rounding, FrequencyRounding::Exact, "Exact");
+                ui.radio_value(rounding, FrequencyRounding::WholeHz, "Whole Hz");
+                let significant = matches!(rounding, FrequencyRounding::Significant(_));
+                if ui.radio(significant, "Significant figures").clicked() && !significant {
+                    *rounding = FrequencyRounding::Significant(4);
+                }
+                if let FrequencyRounding::Significant(figures) = rounding {
+                    ui.add(egui::DragValue::new(figures).range(1..=9));
+                }
+                ui.separator();
+                let export_figure = ui.button("Export matplotlib script");
+                let hover = "figure.py plotting the visible curves from figure.csv";
+                if export_figure.on_hover_text(hover).clicked() {
+                    if let Err(e) = self.export_figure(Path::new("figure.py"), export::write_figure) {
+                        self.analysis_errors.push(format!("Failed to export figure.py: {}", e));
+                    }
+                }
+                let export_html = ui.button("Export HTML plot");
+                let hover = "figure.html, a page to zoom and pan the visible curves in any browser";
+                if export_html.on_hover_text(hover).clicked() {
+                    if let Err(e) = self.export_figure(Path::new("figure.html"), export::write_html) {
+                        self.analysis_errors.push(format!("Failed to export figure.html: {}", e));
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                let use_selection = ui.checkbox(&mut self.use_selection, "Use selection");
+                let hover = "Leave hidden files out of the average and the combined CSV too";
+                if use_selection.on_hover_text(hover).changed() {
+                    self.update_average();
+                }
+                if ui.button("Export combined CSV").clicked() {
+                    if let Err(e) = self.export_combined(Path::new("combined.csv")) {
+                        self.analysis_errors.push(format!("Failed to export combined.csv: {}", e));
+                    }
+                }
+                let save = ui.add_enabled(self.has_average(), egui::Button::new("Save average plot"));
+                if save.on_disabled_hover_text("Needs at least two files").clicked() {
+                    if let Err(e) = write_f(Path::new("average_plot.f.gz"), &self.avg_plot) {
+                        self.analysis_errors.push(format!("Failed to save average_plot.f.gz: {}", e));
+                    }
+                }
+                let freeze = ui.add_enabled(self.has_average(), egui::Button::new("Freeze average"));
+                let hover = "Keep the average as it is now as a fixed line, to watch it drift as files are \
+                             added and removed";
+                if freeze.on_hover_text(hover).on_disabled_hover_text("Needs at least two files").clicked() {
+                    let files = self.averaged.iter().filter(|&&averaged| averaged).count();
+                    self.frozen_average = Some(PlotData {
+                        freqs: self.avg_plot.freqs.clone(),
+                        amplitudes: self.avg_plot.amplitudes.clone(),
+                        file_name: format!("Frozen average of {} files", files),
+                        ..Default::default()
+                    });
+                }
+                if self.frozen_average.is_some() && ui.button("Clear frozen average").clicked() {
+                    self.frozen_average = None;
+                }
+                let can_export = self.has_average() && !self.two_sided;
+                let export = ui.add_enabled(can_export, egui::Button::new("Export average as WAV"));
+                let hover = "The spectrum has no phase, so it's synthesized: the WAV is an impulse response \
+                             with the average's magnitudes";
+                let export = export.on_hover_text(hover);
+                if export.on_disabled_hover_text("Needs two files and a single-sided spectrum").clicked() {
+                    if let Err(e) = self.export_average_wav(Path::new("average.wav")) {
+                        self.analysis_errors.push(format!("Failed to export average.wav: {}", e));
+                    }
+                }
+                egui::ComboBox::from_id_salt("average_phase")
+                    .selected_text(self.average_phase.name())
+                    .show_ui(ui, |ui| {
+                        for phase in Phase::ALL {
+                            ui.selectable_value(&mut self.average_phase, phase, phase.name());
+                        }
+                    });
+                ui.checkbox(&mut self.show_average, "Show average");
+                ui.add_enabled_ui(self.show_average, |ui| {
+                    ui.color_edit_button_srgba(&mut self.average_color);
+                    ui.add(egui::Slider::new(&mut self.average_width, 0.5..=8.0).text("Width"));
+                    egui::ComboBox::from_id_salt("average_style")
+                        .selected_text(self.average_style.name())
+                        .show_ui(ui, |ui| {
+                            for pattern in LinePattern::ALL {
+                                ui.selectable_value(&mut self.average_style, pattern, pattern.name());
+                            }
+                        });
+                    ui.checkbox(&mut self.show_std_band, "± σ band");
+                });
+            });
             ui.horizontal(|ui| {
                 ui.label("Min freq:");
-                ui.add(egui::Slider::new(&mut self.min_freq, 0.0..=100_000.0).text("Min freq"));
+                // sliders clamp, so a persisted range beyond the data shrinks to fit
+                let top = self.data_max_freq();
+                let bottom = if self.two_sided { -top } else { 0.0 };
+                ui.add(egui::Slider::new(&mut self.min_freq, bottom..=top).text("Min freq"));
                 ui.label("Max freq:");
-                ui.add(egui::Slider::new(&mut self.max_freq, 0.0..=100_000.0).text("Max freq"));
+                let max_slider = ui.add(egui::Slider::new(&mut self.max_freq, bottom..=top).text("Max freq"));
+                if max_slider.changed() {
+                    self.max_freq_to_nyquist = false;
+                }
+                let to_nyquist = ui.selectable_label(self.max_freq_to_nyquist, "To Nyquist").on_hover_text(
+                    "Show up to the highest analysed frequency and keep following it as files change",
+                );
+                if to_nyquist.clicked() {
+                    self.max_freq_to_nyquist = true;
+                    self.follow_nyquist();
+                    self.reset_bounds = true;
+                }
+                if ui.button("Auto bounds").on_hover_text("Fit the plot to the visible data").clicked() {
+                    self.reset_bounds = true;
+                }
+                ui.checkbox(&mut self.scroll_range, "Scroll sets range").on_hover_text(
+                    "Horizontal or Shift+scroll over the plot pans the frequency range, Ctrl+scroll zooms it",
+                );
+                let fit_loudest = ui.button("Fit loudest").on_hover_text(
+                    "Show the frequencies holding 99 % of the visible files' energy, with the y axis fitted \
+                     to their peak plus the headroom",
+                );
+                if fit_loudest.clicked() {
+                    self.fit_loudest_region();
+                }
+                ui.separator();
+                ui.checkbox(&mut self.auto_y, "Auto y")
+                    .on_hover_text("Fit the y axis to the visible amplitudes, ignoring a DC spike");
+                ui.add_enabled_ui(self.auto_y, |ui| {
+                    ui.label("skip");
+                    ui.add(egui::DragValue::new(&mut self.auto_y_skip_bins).range(0..=64).suffix(" bins"))
+                        .on_hover_text("Bins next to 0 Hz left out, 1 for only DC");
+                    ui.label("headroom");
+                    ui.add(egui::DragValue::new(&mut self.auto_y_headroom).range(0.0..=200.0).suffix(" %"));
+                });
             });
-
-            Plot::new("my_plot")
-                .legend(Legend::default())
-                // .view_aspect(2.0)
-                .show(ui, |plot_ui| {
-                    for plot_data in &self.plots {
-                        let points: Vec<_> = plot_data
-                            .freqs
-                            .iter()
-                            .zip(plot_data.amplitudes.iter())
-                            .filter(|(&freq, _)| freq >= self.min_freq && freq <= self.max_freq)
-                            .map(|(&freq, &amp)| PlotPoint::new(freq, amp))
-                            .collect();
-                        plot_ui.line(Line::new(PlotPoints::Owned(points)).name(&plot_data.file_name));
-                    }
-                    if self.plots.len() > 0 {
-                        // create average plot
-                        let mut avg_amplitudes = vec![0.0; self.plots[0].amplitudes.len()];
-                        for plot_data in &self.plots {
-                            for (i, &amp) in plot_data.amplitudes.iter().enumerate() {
-                                // add the amp or if it doesn't exist, insert it
-                                if let Some(avg_amp) = avg_amplitudes.get_mut(i) {
-                                    avg_amp.add_assign(amp);
-                                } else {
-                                    avg_amplitudes.push(amp);
-                                }
-                            }
+            ui.horizontal(|ui| {
+                ui.label("Reference .f:");
+                ui.text_edit_singleline(&mut self.reference_path);
+                if ui.button("Load reference curve").clicked() {
+                    self.load_reference();
+                }
+                if self.reference.is_some() && ui.button("Clear reference").clicked() {
+                    self.reference = None;
+                    self.reference_path.clear();
+                }
+                ui.separator();
+                let peak_hold = ui.checkbox(&mut self.peak_hold, "Peak hold");
+                let hover = "Keep the loudest amplitude at each frequency across every file loaded since \
+                             the last reset";
+                if peak_hold.on_hover_text(hover).changed() {
+                    self.update_peak_hold();
+                }
+                if ui.button("Reset peak hold").clicked() {
+                    self.held_peaks = PlotData::default();
+                    self.update_peak_hold();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Session:");
+                ui.text_edit_singleline(&mut self.session_path);
+                let has_path = !self.session_path.trim().is_empty();
+                let save = ui.add_enabled(has_path, egui::Button::new("Save session"));
+                let hover = "Every plot with the current settings, in one file. A .ron session is \
+                             editable text that lists the files instead, to analyse them again on open";
+                if save.on_hover_text(hover).clicked() {
+                    self.save_session();
+                }
+                if ui.add_enabled(has_path, egui::Button::new("Open session")).clicked() {
+                    self.open_session(ui.ctx());
+                }
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Window")
+                    .selected_text(self.window.name())
+                    .show_ui(ui, |ui| {
+                        for window in Window::ALL {
+                            ui.selectable_value(&mut self.window, window, window.name());
                         }
-                        avg_amplitudes.iter_mut().for_each(|amp| *amp /= self.plots.len() as f32);
-                        let points: Vec<_> = self
-                            .plots[0]
-                            .freqs
-                            .iter()
-                            .zip(avg_amplitudes.iter())
-                            .filter(|(&freq, _)| freq >= self.min_freq && freq <= self.max_freq)
-                            .map(|(&freq, &amp)| PlotPoint::new(freq, amp))
-                            .collect();
-                        plot_ui.line(Line::new(PlotPoints::Owned(points)).name("Average"));
-                        self.avg_plot = PlotData {
-                            freqs: self.plots[0].freqs.clone(),
-                            amplitudes: avg_amplitudes,
-                            file_name: "average".to_string(),
+                    });
+                let options = self.analysis_options();
+                let windowed_len = self.plots.get(self.active_plot).map_or(0, |p| p.windowed_len(options));
+                ui.weak(format!("coherent gain {:.3}", self.window.coherent_gain(windowed_len)));
+                ui.checkbox(&mut self.raw_amplitudes, "Raw amplitudes")
+                    .on_hover_text("Don't correct for the window's coherent gain");
+                ui.checkbox(&mut self.two_sided, "Two-sided")
+                    .on_hover_text("Full spectrum from -Fs/2 to +Fs/2 instead of 0 Hz to Nyquist");
+                let previous_sample_type = self.sample_type;
+                egui::ComboBox::from_label("Sample type")
+                    .selected_text(self.sample_type.map_or("Auto", SampleType::name))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.sample_type, None, "Auto");
+                        for sample_type in SampleType::ALL {
+                            ui.selectable_value(&mut self.sample_type, Some(sample_type), sample_type.name());
+                        }
+                    });
+                if self.sample_type != previous_sample_type {
+                    self.reload();
+                }
+                egui::ComboBox::from_label("FFT precision")
+                    .selected_text(self.precision.name())
+                    .show_ui(ui, |ui| {
+                        for precision in Precision::ALL {
+                            ui.selectable_value(&mut self.precision, precision, precision.name());
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.use_welch, "Welch averaging");
+                ui.add_enabled_ui(self.use_welch, |ui| {
+                    ui.label("Segment size:");
+                    ui.add(egui::DragValue::new(&mut self.welch.segment_len));
+                    ui.label("Hop:");
+                    ui.add(egui::DragValue::new(&mut self.welch.hop));
+                });
+                ui.separator();
+                egui::ComboBox::from_label("Input length")
+                    .selected_text(self.input_length.name())
+                    .show_ui(ui, |ui| {
+                        for length in InputLength::ALL {
+                            ui.selectable_value(&mut self.input_length, length, length.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Cut or zero-pad every file to the same length so their bins line up");
+                if self.input_length == InputLength::Fixed {
+                    let drag = egui::DragValue::new(&mut self.fixed_length).range(16..=1 << 30);
+                    ui.add(drag.speed(64.0).suffix(" samples"));
+                }
+                let hover = "Zero-pad short files so they aren't plotted at a much coarser resolution";
+                ui.checkbox(&mut self.use_min_fft_size, "Min. FFT size").on_hover_text(hover);
+                let drag = egui::DragValue::new(&mut self.min_fft_size).range(16..=1 << 24).speed(64.0);
+                ui.add_enabled(self.use_min_fft_size && !self.use_welch, drag);
+                let limit = &mut self.sample_limit;
+                let hover = "Longest single FFT; longer files are Welch-averaged or truncated instead of \
+                             running out of memory";
+                ui.label("Max. FFT size:").on_hover_text(hover);
+                ui.add(egui::DragValue::new(&mut limit.max_fft_size).range(16..=1 << 28).speed(1024.0));
+                egui::ComboBox::from_id_salt("limit_fallback")
+                    .selected_text(limit.fallback.name())
+                    .show_ui(ui, |ui| {
+                        for fallback in LimitFallback::ALL {
+                            ui.selectable_value(&mut limit.fallback, fallback, fallback.name());
+                        }
+                    });
+                ui.checkbox(&mut self.show_window_shape, "Show window shape");
+                if let Some((finest, coarsest)) = self.resolution() {
+                    ui.separator();
+                    let text = if coarsest - finest <= 1e-3 * coarsest {
+                        format!("Resolution: {:.3} Hz/bin", coarsest)
+                    } else {
+                        format!("Resolution: {:.3}-{:.3} Hz/bin", finest, coarsest)
+                    };
+                    let hover = "Bin width, i.e. sample rate / FFT size; features narrower than this \
+                                 aren't resolved";
+                    ui.strong(text).on_hover_text(hover);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Skip first");
+                let skip = egui::DragValue::new(&mut self.skip_ms).range(0.0..=3_600_000.0).suffix(" ms");
+                ui.add(skip).on_hover_text("Leading silence or count-in left out of every file");
+                ui.checkbox(&mut self.use_duration, "Analyse only");
+                let duration = egui::DragValue::new(&mut self.duration_ms).range(1.0..=3_600_000.0);
+                ui.add_enabled(self.use_duration, duration.suffix(" ms"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Decimate by:");
+                ui.add(egui::DragValue::new(&mut self.decimation.factor).range(1..=64));
+                ui.add_enabled_ui(self.decimation.factor > 1, |ui| {
+                    ui.label("Anti-aliasing filter order:");
+                    let order = egui::DragValue::new(&mut self.decimation.filter_order).range(2..=16);
+                    ui.add(order).on_hover_text("Even, one biquad section per two orders");
+                    // odd orders are run as the next even one, so show that
+                    self.decimation.filter_order += self.decimation.filter_order % 2;
+                });
+                ui.separator();
+                ui.checkbox(&mut self.use_high_pass, "High-pass");
+                ui.add_enabled(
+                    self.use_high_pass,
+                    egui::DragValue::new(&mut self.high_pass).range(1.0..=100_000.0).suffix(" Hz"),
+                );
+                ui.checkbox(&mut self.use_low_pass, "Low-pass");
+                ui.add_enabled(
+                    self.use_low_pass,
+                    egui::DragValue::new(&mut self.low_pass).range(1.0..=100_000.0).suffix(" Hz"),
+                );
+                ui.separator();
+                ui.checkbox(&mut self.use_detrend, "Detrend").on_hover_text(
+                    "Subtract the least-squares polynomial of this order before the filters, against drift \
+                     or a ramp: 0 for DC, 1 for linear",
+                );
+                let order = egui::DragValue::new(&mut self.detrend_order).prefix("order ");
+                ui.add_enabled(self.use_detrend, order.range(0..=filter::MAX_DETREND_ORDER));
+                ui.separator();
+                ui.label("Threads:");
+                ui.add(egui::DragValue::new(&mut self.threads).range(1..=256))
+                    .on_hover_text("Files re-analysed in parallel; fewer use less memory");
+            });
+            // once for all the analysis settings above, however many of them changed
+            self.reanalyze_if_changed();
+            if self.show_window_shape {
+                self.window_shape_plot(ui);
+            }
+            for error in &self.analysis_errors {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            for warning in &self.overloads {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("Warning: {}", warning));
+            }
+            ui.horizontal(|ui| {
+                let previous_active = self.active_plot;
+                egui::ComboBox::from_label("Active file")
+                    .selected_text(self.plots.get(self.active_plot).map_or(String::new(), PlotData::label))
+                    .show_ui(ui, |ui| {
+                        for (i, plot_data) in self.plots.iter().enumerate() {
+                            ui.selectable_value(&mut self.active_plot, i, plot_data.label());
+                        }
+                    });
+                if self.active_plot != previous_active {
+                    self.hopped_peak = None;
+                }
+                let remove = egui::Button::new("Remove");
+                let enabled = self.active_plot < self.plots.len();
+                if ui.add_enabled(enabled, remove).on_hover_text("Drop the active file (Delete)").clicked() {
+                    self.remove_plot(self.active_plot);
+                }
+                ui.separator();
+                ui.radio_value(&mut self.copy_content, CopyContent::Spectrum, "Spectrum");
+                ui.radio_value(&mut self.copy_content, CopyContent::Peaks, "Peaks");
+                if let Some(plot_data) = self.plots.get(self.active_plot) {
+                    if ui.button("Copy").on_hover_text("Copy the active file as TSV").clicked() {
+                        let text = match self.copy_content {
+                            CopyContent::Spectrum => {
+                                let (freqs, amplitudes) = self.exported_spectrum(plot_data);
+                                export::spectrum_tsv(&freqs, &amplitudes)
+                            }
+                            CopyContent::Peaks => export::peaks_tsv(&self.exported_peaks(plot_data)),
                         };
+                        ui.ctx().copy_text(text);
+                    }
+                }
+                ui.separator();
+                let mut soloed = self.solo.is_some();
+                if ui.checkbox(&mut soloed, "Solo").on_hover_text("Press s, or click a line").changed() {
+                    self.solo = None;
+                    if soloed {
+                        self.toggle_solo(self.active_plot);
+                    }
+                }
+                match self.hopped_peak {
+                    Some(peak) => {
+                        ui.label(format!(
+                            "Peak: {:.2} Hz ({}), amplitude {:.3}",
+                            peak.frequency,
+                            peaks::note_name(peak.frequency),
+                            peak.amplitude
+                        ));
+                        // only audio files know how many samples the magnitude came from
+                        let options = self.analysis_options();
+                        let active = self.plots.get(self.active_plot);
+                        let windowed_len = active.map_or(0, |p| p.windowed_len(options));
+                        if windowed_len > 0 {
+                            let amplitude = options.sine_amplitude(peak.amplitude, windowed_len);
+                            let dbfs = 20.0 * (amplitude / 32_768.0).log10();
+                            let spl = if self.spl_calibrated {
+                                format!(", {:.1} dB SPL", dbfs + self.spl_offset_db)
+                            } else {
+                                String::new()
+                            };
+                            ui.label(format!("sine amplitude {:.1} ({:.2} dBFS{})", amplitude, dbfs, spl))
+                                .on_hover_text("Use the flat top window for accurate amplitudes");
+                        }
+                    }
+                    None => {
+                        ui.weak("Press n / p to hop between peaks");
+                    }
+                };
+            });
+            ui.horizontal(|ui| {
+                ui.label("Peaks per file:");
+                ui.add(egui::DragValue::new(&mut self.peak_count).range(1..=1000));
+                ui.add(
+                    egui::Slider::new(&mut self.peak_prominence, 0.0..=1.0)
+                        .text("Min prominence (of max)"),
+                );
+                ui.label("Min distance:");
+                ui.add(egui::DragValue::new(&mut self.peak_distance).range(0.0..=10_000.0).suffix(" Hz"));
+                let previous_band = self.peak_band;
+                let band = &mut self.peak_band;
+                ui.label("Between").on_hover_text(
+                    "Peaks and the fundamental are only picked here, so DC drift and ultrasonic noise don't \
+                     outrank what's audible",
+                );
+                ui.add(egui::DragValue::new(&mut band.min_freq).range(0.0..=band.max_freq).suffix(" Hz"));
+                ui.label("and");
+                ui.add(egui::DragValue::new(&mut band.max_freq).range(band.min_freq..=1e6).suffix(" Hz"));
+                if ui.button("Audible").on_hover_text("20 Hz to 20 kHz").clicked() {
+                    self.peak_band = PeakBand::default();
+                }
+                if self.peak_band != previous_band {
+                    self.fundamental = None;
+                    if self.align_pitch {
+                        self.update_pitch_alignment();
+                    }
+                }
+                egui::ComboBox::from_id_salt("export_format")
+                    .selected_text(self.export_format.name())
+                    .show_ui(ui, |ui| {
+                        for format in ExportFormat::ALL {
+                            ui.selectable_value(&mut self.export_format, format, format.name());
+                        }
+                    });
+                if ui.button("Export peaks").clicked() {
+                    if let Err(e) = self.export_peaks() {
+                        self.analysis_errors.push(format!("Failed to export peaks: {}", e));
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Normalize:");
+                ui.radio_value(&mut self.normalization, Normalization::None, "None");
+                ui.radio_value(&mut self.normalization, Normalization::PerFile, "Per-file");
+                ui.radio_value(&mut self.normalization, Normalization::PerPeak, "Per-file peak")
+                    .on_hover_text("Each file's strongest peak in the frequency range at 0 dB, ignoring DC");
+                ui.radio_value(&mut self.normalization, Normalization::Global, "Global");
+                ui.separator();
+                let previous_mode = self.amplitude_mode;
+                egui::ComboBox::from_label("Amplitude")
+                    .selected_text(self.amplitude_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in AmplitudeMode::ALL {
+                            ui.selectable_value(&mut self.amplitude_mode, mode, mode.name());
+                        }
+                    });
+                let previous_negative = self.negative_amplitudes;
+                egui::ComboBox::from_label("Negative")
+                    .selected_text(self.negative_amplitudes.name())
+                    .show_ui(ui, |ui| {
+                        for treatment in NegativeAmplitudes::ALL {
+                            ui.selectable_value(&mut self.negative_amplitudes, treatment, treatment.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Amplitudes below 0, e.g. in .f files of other tools; clamped by default");
+                // the global maximum depends on the weighting and on negative amplitudes
+                if self.amplitude_mode != previous_mode || self.negative_amplitudes != previous_negative {
+                    self.update_average();
+                }
+                ui.separator();
+                ui.add_enabled(
+                    self.has_average(),
+                    egui::Checkbox::new(&mut self.diff_from_average, "Difference from average (dB)"),
+                )
+                .on_disabled_hover_text("Needs at least two files");
+                ui.checkbox(&mut self.fill_under, "Fill under curves");
+                ui.add_enabled(
+                    self.fill_under,
+                    egui::Slider::new(&mut self.fill_opacity, 0.0..=1.0).text("Opacity"),
+                );
+                ui.add_enabled(self.fill_under, egui::Checkbox::new(&mut self.shade_bands, "Shade bands"));
+                if self.fill_under && self.shade_bands {
+                    let [low, high] = self.band_edges;
+                    ui.label("Bass/mid:");
+                    ui.add(egui::DragValue::new(&mut self.band_edges[0]).range(0.0..=high).suffix(" Hz"));
+                    ui.label("Mid/treble:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.band_edges[1])
+                            .range(low..=100_000.0)
+                            .suffix(" Hz"),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.show_envelope, "Peak-hold envelope");
+                ui.add_enabled_ui(self.show_envelope, |ui| {
+                    ui.label("Width:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.envelope_width)
+                            .range(1.0..=10_000.0)
+                            .suffix(" Hz"),
+                    );
+                });
+                ui.checkbox(&mut self.smoothing, "Smoothing");
+                ui.add_enabled(
+                    self.smoothing,
+                    egui::Slider::new(&mut self.smoothing_bands, 1..=48)
+                        .logarithmic(true)
+                        .custom_formatter(|bands, _| format!("1/{}", bands))
+                        .custom_parser(|text| text.trim().trim_start_matches("1/").parse().ok())
+                        .text("octave"),
+                );
+                ui.add_enabled(
+                    self.show_envelope || self.smoothing,
+                    egui::Slider::new(&mut self.raw_opacity, 0.0..=1.0).text("Raw spectrum opacity"),
+                );
+                ui.separator();
+                ui.checkbox(&mut self.log_binning, "Log bins")
+                    .on_hover_text("Draw fewer points at high frequencies, pooled per fraction of an octave");
+                ui.add_enabled_ui(self.log_binning, |ui| {
+                    let bands = egui::DragValue::new(&mut self.log_bins_per_octave).range(1..=1000);
+                    ui.add(bands.suffix("/oct"));
+                    for pooling in Pooling::ALL {
+                        ui.radio_value(&mut self.log_pooling, pooling, pooling.name());
                     }
                 });
+                ui.separator();
+                egui::ComboBox::from_label("Frequency grid")
+                    .selected_text(self.frequency_grid.name())
+                    .show_ui(ui, |ui| {
+                        for grid in FrequencyGrid::ALL {
+                            ui.selectable_value(&mut self.frequency_grid, grid, grid.name());
+                        }
+                    });
+                ui.checkbox(&mut self.interpolated_readout, "Interpolated readout")
+                    .on_hover_text("Show the active file at the exact hovered frequency, between bins");
+                ui.checkbox(&mut self.wavelength_axis, "Wavelength axis")
+                    .on_hover_text("Label frequencies with their wavelength, speed / frequency");
+                let speed = egui::DragValue::new(&mut self.speed_of_sound).range(1.0..=100_000.0);
+                ui.add_enabled(self.wavelength_axis, speed.suffix(" m/s"));
+                ui.checkbox(&mut self.show_phase, "Phase")
+                    .on_hover_text("Overlay the active file's phase, in degrees on the right axis");
+                if let Some((_, Err(e))) = self.phase.as_ref().filter(|_| self.show_phase) {
+                    ui.weak(e.as_str());
+                }
+                ui.checkbox(&mut self.show_group_delay, "Group delay").on_hover_text(
+                    "Overlay the active file's group delay, −dφ/dω of its unwrapped phase, in ms on an \
+                     axis of its own on the right. Best with a rectangular window on an impulse response",
+                );
+                if let Some((_, Err(e))) = self.group_delay.as_ref().filter(|_| self.show_group_delay) {
+                    ui.weak(e.as_str());
+                }
+            });
+
+            egui::CollapsingHeader::new("Files").show(ui, |ui| self.files_view(ui));
+            egui::CollapsingHeader::new("File statistics").show(ui, |ui| {
+                egui::Grid::new("file_stats").striped(true).show(ui, |ui| {
+                    ui.strong("File");
+                    for column in self.analyzers.columns() {
+                        ui.strong(column);
+                    }
+                    ui.end_row();
+                    for (plot_data, stats) in self.plots.iter().zip(&self.file_stats) {
+                        ui.label(plot_data.label());
+                        for value in stats {
+                            ui.label(value.as_deref().unwrap_or("-"));
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+            if self.sources().len() > 1 {
+                egui::CollapsingHeader::new("Sources").show(ui, |ui| self.sources_view(ui));
+            }
+            egui::CollapsingHeader::new("Tuning").show(ui, |ui| self.tuning_view(ui));
+            egui::CollapsingHeader::new("Spectral features").show(ui, |ui| self.features_view(ui));
+            egui::CollapsingHeader::new("Ranking").show(ui, |ui| self.ranking_view(ui));
+            egui::CollapsingHeader::new("Octave bands").show(ui, |ui| self.bands_view(ui));
+            egui::CollapsingHeader::new("Cepstrum").show(ui, |ui| self.cepstrum_view(ui));
+            egui::CollapsingHeader::new("Comb filter").show(ui, |ui| self.comb_view(ui));
+            egui::CollapsingHeader::new("Streaming").show(ui, |ui| self.stream_view(ui));
+            egui::CollapsingHeader::new("Transients").show(ui, |ui| self.transients_view(ui));
+            egui::CollapsingHeader::new("Timings").show(ui, |ui| self.timings_view(ui));
+            egui::CollapsingHeader::new("Start offsets").show(ui, |ui| self.offsets_view(ui));
+            egui::CollapsingHeader::new("Windows").show(ui, |ui| self.windows_view(ui));
+            egui::CollapsingHeader::new("FFT size").show(ui, |ui| self.fft_size_view(ui));
+            egui::CollapsingHeader::new("Sample rates").show(ui, |ui| self.sample_rates_view(ui));
+            egui::CollapsingHeader::new("Metadata").show(ui, |ui| self.metadata_view(ui));
+            egui::CollapsingHeader::new("Average weights").show(ui, |ui| self.weights_view(ui));
+            egui::CollapsingHeader::new("Annotations").show(ui, |ui| self.annotations_view(ui));
+            egui::CollapsingHeader::new("Markers").show(ui, |ui| self.markers_view(ui));
+            egui::CollapsingHeader::new("Expected tones").show(ui, |ui| self.tones_view(ui));
+            egui::CollapsingHeader::new("Tolerance mask").show(ui, |ui| self.mask_view(ui));
+            egui::CollapsingHeader::new("Threshold").show(ui, |ui| self.threshold_view(ui));
+            egui::CollapsingHeader::new("SPL calibration").show(ui, |ui| self.calibration_view(ui));
+            egui::CollapsingHeader::new("Similarity matrix").show(ui, |ui| self.similarity_view(ui));
+            egui::CollapsingHeader::new("Spectrogram difference").show(ui, |ui| self.heatmap_view(ui));
+
+            let sources: Vec<String> = self.sources().into_iter().map(str::to_string).collect();
+            let split = self.split_sources.map(|i| sources.get(i).cloned());
+            match split {
+                [Some(left), Some(right)] if self.split_view => {
+                    ui.columns(2, |columns| {
+                        for (ui, source) in columns.iter_mut().zip([left, right]) {
+                            ui.strong(&source);
+                            self.split_panel = Some(source);
+                            self.plot_view(ui);
+                        }
+                    });
+                    self.split_panel = None;
+                }
+                _ => self.plot_view(ui),
+            }
         });
     }
 
     /// Called by the framework to save state before shutdown.
     /// On Windows its saved here: C:\Users\UserName\AppData\Roaming\Phoenix\data\app.ron
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+        eframe::set_value(storage, &self.storage_key, self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` plots of a second of white noise each, with their samples, named "noise 1" and on.
+    fn noise_plots(n: u32) -> Vec<PlotData> {
+        (1..=n)
+            .map(|seed| {
+                let noise = testgen::white_noise(8192.0, 48_000, seed);
+                let mut plot_data =
+                    analyze_samples(&noise, 48_000, AnalysisOptions::default()).unwrap();
+                plot_data.file_name = format!("noise {}", seed);
+                (plot_data.samples, plot_data.sample_rate) = (Some(noise), Some(48_000));
+                plot_data
+            })
+            .collect()
+    }
+
+    #[test]
+    fn power_average_of_noise_sits_above_the_amplitude_mean() {
+        let mut app = MyApp {
+            plots: noise_plots(32),
+            ..Default::default()
+        };
+        let mut averages = Vec::new();
+        for domain in [AverageDomain::Amplitude, AverageDomain::Power] {
+            app.average_domain = domain;
+            app.update_average();
+            // without DC and Nyquist, whose bins are real and don't scatter the same way
+            let bins = &app.avg_plot.amplitudes[1..app.avg_plot.amplitudes.len() - 1];
+            averages.push(bins.iter().map(|&amp| amp as f64).sum::<f64>() / bins.len() as f64);
+        }
+        // the bins' magnitudes are Rayleigh distributed: the RMS is 2 / √π times the mean
+        let ratio = averages[1] / averages[0];
+        assert!(
+            (ratio - 2.0 / std::f64::consts::PI.sqrt()).abs() < 0.01,
+            "{}",
+            ratio
+        );
+    }
+
+    #[test]
+    fn csv_names_of_plots_with_the_same_label_are_numbered() {
+        let mut taken = HashSet::new();
+        let names: Vec<String> = ["x", "x (mid)", "x (side)", "x", "X", "a/b", ""]
+            .iter()
+            .map(|label| csv_file_name(label, &mut taken))
+            .collect();
+        let expected = [
+            "x.csv",
+            "x (mid).csv",
+            "x (side).csv",
+            "x 2.csv",
+            "X 3.csv",
+            "a_b.csv",
+            "spectrum.csv",
+        ];
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn removing_a_heatmap_file_resets_its_slot_and_the_heatmap() {
+        let mut app = MyApp {
+            plots: noise_plots(3),
+            heatmap_files: [Some(1), Some(2)],
+            ..Default::default()
+        };
+        let ctx = egui::Context::default();
+        app.compute_heatmap(&ctx);
+        assert!(app.analysis_errors.is_empty(), "{:?}", app.analysis_errors);
+        app.remove_plot(0);
+        assert_eq!(app.heatmap_files, [Some(0), Some(1)]);
+        assert_eq!(
+            app.heatmap.as_ref().map(|heatmap| heatmap.files),
+            Some([0, 1])
+        );
+        app.remove_plot(1);
+        assert_eq!(app.heatmap_files, [Some(0), None]);
+        assert!(app.heatmap.is_none());
+    }
+
+    #[test]
+    fn progress_counts_named_unsupported_files_but_not_skipped_ones() {
+        let named = [
+            ("gen/chirp.wav", "gen/chirp.wav"),
+            ("bad.txt", "bad.txt"),
+            ("gen/notes.txt", "gen"),
+        ];
+        let mut files: Vec<(PathBuf, &str)> = named
+            .iter()
+            .map(|&(path, source)| (path.into(), source))
+            .collect();
+        let failures = drop_unsupported(&mut files);
+        assert_eq!(
+            failures,
+            [("bad.txt".to_string(), "Unsupported file format".to_string())]
+        );
+        assert_eq!(files, [(PathBuf::from("gen/chirp.wav"), "gen/chirp.wav")]);
+        let mut progress = Progress {
+            done: failures.len(),
+            total: failures.len() + files.len(),
+        };
+        assert_eq!(progress.next(), "[2/2]");
+    }
+
+    #[test]
+    fn cached_points_keep_the_raw_curve_while_smoothing() {
+        let mut app = MyApp {
+            plots: noise_plots(1),
+            ..Default::default()
+        };
+        app.refresh_points();
+        let plain = app.point_cache[0].clone().unwrap();
+        assert!(plain.raw.is_none());
+        app.smoothing = true;
+        app.refresh_points();
+        let smoothed = app.point_cache[0].clone().unwrap();
+        assert_eq!(smoothed.raw.as_deref(), Some(&*plain.points));
+        assert_ne!(smoothed.points, plain.points);
+        // unchanged settings reuse the cached points rather than building them again
+        app.refresh_points();
+        assert!(Arc::ptr_eq(
+            &app.point_cache[0].as_ref().unwrap().points,
+            &smoothed.points
+        ));
     }
 }
@@ -0,0 +1,223 @@
+use crate::{db_difference, PlotData};
+use std::path::Path;
+
+/// One corner of a [`Mask`]; between corners the limits are interpolated linearly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaskPoint {
+    pub frequency: f32,
+    /// `None` where the mask doesn't limit that side.
+    pub lower: Option<f32>,
+    pub upper: Option<f32>,
+}
+
+/// Tolerance band a spectrum has to stay inside, in the units of its amplitudes as Export CSV
+/// writes them, so a mask can be made from the spectrum of a known good unit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mask {
+    /// Ascending in frequency.
+    pub points: Vec<MaskPoint>,
+}
+
+impl Mask {
+    /// Reads `frequency,lower,upper` rows, ascending in frequency, with an empty field for no
+    /// limit. A header row and lines starting with `#` are skipped.
+    pub fn read_csv(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let limit = |field: Option<&str>| match field.map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid level '{}'", value)),
+        };
+        let mut points: Vec<MaskPoint> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(',');
+            let frequency = fields.next().unwrap_or_default().trim();
+            let Ok(frequency) = frequency.parse::<f32>() else {
+                if points.is_empty() {
+                    continue;
+                }
+                return Err(format!("Line {}: invalid frequency '{}'", i + 1, frequency));
+            };
+            let (lower, upper) = (limit(fields.next()), limit(fields.next()));
+            let point = MaskPoint {
+                frequency,
+                lower: lower.map_err(|e| format!("Line {}: {}", i + 1, e))?,
+                upper: upper.map_err(|e| format!("Line {}: {}", i + 1, e))?,
+            };
+            if points
+                .last()
+                .is_some_and(|last| last.frequency >= frequency)
+            {
+                return Err(format!("Line {}: frequencies must ascend", i + 1));
+            }
+            points.push(point);
+        }
+        if points.is_empty() {
+            return Err("No mask points".to_string());
+        }
+        Ok(Self { points })
+    }
+
+    /// Lower and upper limit at `freq`; `None` outside the mask, or for a side one of the
+    /// surrounding corners doesn't limit.
+    pub fn limits(&self, freq: f32) -> Option<(Option<f32>, Option<f32>)> {
+        let (first, last) = (self.points.first()?, self.points.last()?);
+        if freq < first.frequency || freq > last.frequency {
+            return None;
+        }
+        let j = self.points.partition_point(|point| point.frequency < freq);
+        let b = self.points[j];
+        if j == 0 || b.frequency == freq {
+            return Some((b.lower, b.upper));
+        }
+        let a = self.points[j - 1];
+        let t = (freq - a.frequency) / (b.frequency - a.frequency);
+        let lerp = |a: Option<f32>, b: Option<f32>| Some(a? + t * (b? - a?));
+        Some((lerp(a.lower, b.lower), lerp(a.upper, b.upper)))
+    }
+}
+
+/// Which limit of the mask a spectrum crossed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Above,
+    Below,
+}
+
+/// A stretch of consecutive bins outside the mask on the same side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub side: Side,
+    pub start_frequency: f32,
+    pub end_frequency: f32,
+    /// The bin furthest outside and by how many dB it crosses the limit.
+    pub worst_frequency: f32,
+    pub worst_db: f32,
+}
+
+/// Stretches of `plot_data` outside `mask`; empty if the spectrum passes. Bins beyond the
+/// frequency range of the mask aren't checked.
+pub fn check_mask(plot_data: &PlotData, mask: &Mask) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = Vec::new();
+    // whether the previous bin was outside, so violations only merge across adjacent bins
+    let mut outside = false;
+    for (&freq, &amp) in plot_data.freqs.iter().zip(&plot_data.amplitudes) {
+        let crossing = mask
+            .limits(freq)
+            .and_then(|(lower, upper)| match (lower, upper) {
+                (_, Some(upper)) if amp > upper => Some((Side::Above, db_difference(amp, upper))),
+                (Some(lower), _) if amp < lower => Some((Side::Below, db_difference(lower, amp))),
+                _ => None,
+            });
+        let Some((side, excess_db)) = crossing else {
+            outside = false;
+            continue;
+        };
+        match violations
+            .last_mut()
+            .filter(|last| outside && last.side == side)
+        {
+            Some(last) => {
+                last.end_frequency = freq;
+                if excess_db > last.worst_db {
+                    last.worst_frequency = freq;
+                    last.worst_db = excess_db;
+                }
+            }
+            None => violations.push(Violation {
+                side,
+                start_frequency: freq,
+                end_frequency: freq,
+                worst_frequency: freq,
+                worst_db: excess_db,
+            }),
+        }
+        outside = true;
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Upper limit falling from 20 to 10 over 0-100 Hz, lower limit of 2 from 50 Hz on.
+    fn mask() -> Mask {
+        let point = |frequency, lower, upper| MaskPoint {
+            frequency,
+            lower,
+            upper,
+        };
+        Mask {
+            points: vec![
+                point(0.0, None, Some(20.0)),
+                point(50.0, Some(2.0), Some(15.0)),
+                point(100.0, Some(2.0), Some(10.0)),
+            ],
+        }
+    }
+
+    fn spectrum(amplitudes: &[f32]) -> PlotData {
+        let freqs = (0..amplitudes.len()).map(|i| i as f32 * 10.0).collect();
+        PlotData {
+            freqs,
+            amplitudes: amplitudes.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spectrum_inside_the_mask_passes() {
+        // 0 Hz sits below the missing lower limit, 110 Hz beyond the mask
+        let plot_data = spectrum(&[0.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 100.0]);
+        assert_eq!(check_mask(&plot_data, &mask()), vec![]);
+    }
+
+    #[test]
+    fn violations_above_the_upper_limit() {
+        // the limit at 70 and 80 Hz is interpolated to 13 and 12
+        let plot_data = spectrum(&[5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 13.0, 24.0, 5.0, 11.0]);
+        let violations = check_mask(&plot_data, &mask());
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].side, Side::Above);
+        assert_eq!(
+            (violations[0].start_frequency, violations[0].end_frequency),
+            (80.0, 80.0)
+        );
+        assert_eq!(violations[0].worst_frequency, 80.0);
+        assert!(
+            (violations[0].worst_db - 6.0206).abs() < 1e-3,
+            "{}",
+            violations[0].worst_db
+        );
+        assert_eq!(
+            (violations[1].start_frequency, violations[1].end_frequency),
+            (100.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn violations_below_the_lower_limit() {
+        let plot_data = spectrum(&[5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 1.0, 0.5, 1.5, 5.0, 5.0]);
+        let violations = check_mask(&plot_data, &mask());
+        assert_eq!(violations.len(), 1);
+        let violation = violations[0];
+        assert_eq!(violation.side, Side::Below);
+        assert_eq!(
+            (violation.start_frequency, violation.end_frequency),
+            (60.0, 80.0)
+        );
+        assert_eq!(violation.worst_frequency, 70.0);
+        assert!(
+            (violation.worst_db - 12.0412).abs() < 1e-3,
+            "{}",
+            violation.worst_db
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::PlotData;
+
+/// Approximate heap usage of the spectra and cached samples of `plots`, in bytes.
+pub fn usage(plots: &[PlotData]) -> usize {
+    plots.iter().map(PlotData::memory_bytes).sum()
+}
+
+/// Shrinks `plots` until they fit in `budget` bytes, returning a warning for each step taken.
+///
+/// Cached samples go first, largest first, since those files can simply be read again from disk.
+/// Plots without a file, like stdin or `--concat` ones, keep theirs: they couldn't be re-analysed
+/// without them. If the spectra alone are still too large, they're reduced to an equal share of the budget
+/// each, keeping the peak of every group of bins.
+pub fn enforce_budget(plots: &mut [PlotData], budget: usize) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut total = usage(plots);
+    if total <= budget {
+        return warnings;
+    }
+
+    let mut cached: Vec<_> = (0..plots.len())
+        .filter(|&i| plots[i].samples.is_some() && plots[i].path.is_some())
+        .collect();
+    cached.sort_by_key(|&i| std::cmp::Reverse(plots[i].samples.as_ref().map_or(0, Vec::len)));
+    let mut dropped = 0;
+    for i in cached {
+        if total <= budget {
+            break;
+        }
+        let before = plots[i].memory_bytes();
+        plots[i].samples = None;
+        total -= before - plots[i].memory_bytes();
+        dropped += 1;
+    }
+    if dropped > 0 {
+        warnings.push(format!(
+            "Memory budget of {} MiB exceeded: dropped the cached samples of {} files, they'll be \
+             read from disk again when re-analysing",
+            budget >> 20,
+            dropped
+        ));
+    }
+
+    if total > budget && !plots.is_empty() {
+        let max_bins = (budget / plots.len() / PlotData::BYTES_PER_BIN).max(1);
+        let reduced = plots
+            .iter_mut()
+            .map(|plot_data| plot_data.downsample(max_bins))
+            .filter(|&reduced| reduced)
+            .count();
+        if reduced > 0 {
+            warnings.push(format!(
+                "Memory budget of {} MiB exceeded: reduced {} spectra to {} bins",
+                budget >> 20,
+                reduced,
+                max_bins
+            ));
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn samples_of_plots_without_a_file_are_kept() {
+        let plot = |path: Option<&str>| PlotData {
+            samples: Some(vec![0.0; 1 << 16]),
+            path: path.map(PathBuf::from),
+            ..Default::default()
+        };
+        let mut plots = [plot(None), plot(Some("a.wav")), plot(Some("b.wav"))];
+        let warnings = enforce_budget(&mut plots, 1 << 18);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        let cached: Vec<bool> = plots
+            .iter()
+            .map(|plot_data| plot_data.samples.is_some())
+            .collect();
+        assert_eq!(cached, [true, false, false]);
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Tags of the `iXML` chunk that are shown, in that order.
+const IXML_TAGS: [&str; 5] = ["PROJECT", "SCENE", "TAKE", "TAPE", "NOTE"];
+
+/// `LIST/INFO` fields that are shown, with their names.
+const INFO_TAGS: [(&[u8; 4], &str); 6] = [
+    (b"INAM", "Title"),
+    (b"IART", "Artist"),
+    (b"ICMT", "Comment"),
+    (b"ICRD", "Created"),
+    (b"ISFT", "Software"),
+    (b"IENG", "Engineer"),
+];
+
+/// A `cue ` point, labelled from the `LIST/adtl` chunk if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueMarker {
+    /// In sample frames from the start of the data.
+    pub position: u32,
+    pub label: Option<String>,
+}
+
+/// Recording details field recorders and DAWs embed in WAV files, from the Broadcast Wave `bext`
+/// chunk, `iXML`, `LIST/INFO` and `cue ` points. hound skips these chunks, so the RIFF chunks
+/// are walked here directly, seeking past the sample data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WavMetadata {
+    /// The bext originator, else the INFO software.
+    pub recorder: Option<String>,
+    /// The bext description, else the iXML note or INFO comment.
+    pub notes: Option<String>,
+    /// bext origination date and time as written, `yyyy-mm-dd hh:mm:ss`.
+    pub origination: Option<String>,
+    /// Sample frames since midnight at the first sample, the bext time reference.
+    pub time_reference: Option<u64>,
+    pub markers: Vec<CueMarker>,
+    /// Every field found, as name and value, for the details.
+    pub tags: Vec<(String, String)>,
+}
+
+impl WavMetadata {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+        let mut riff = [0; 12];
+        reader.read_exact(&mut riff).map_err(|e| e.to_string())?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err("Not a RIFF/WAVE file".to_string());
+        }
+        let mut metadata = Self::default();
+        // cue point ids with their positions and labels, matched up once all chunks are read
+        let mut cues: Vec<(u32, u32)> = Vec::new();
+        let mut labels: Vec<(u32, String)> = Vec::new();
+        let mut header = [0; 8];
+        // a file cut short ends the walk rather than failing, the chunks before it still count
+        while reader.read_exact(&mut header).is_ok() {
+            let len = u32_at(&header, 4) as usize;
+            // chunks are padded to an even length
+            let padded = len + len % 2;
+            let id: [u8; 4] = [header[0], header[1], header[2], header[3]];
+            if !matches!(&id, b"bext" | b"iXML" | b"LIST" | b"cue ") {
+                reader
+                    .seek_relative(padded as i64)
+                    .map_err(|e| e.to_string())?;
+                continue;
+            }
+            let mut body = vec![0; padded];
+            if reader.read_exact(&mut body).is_err() {
+                break;
+            }
+            body.truncate(len);
+            match &id {
+                b"bext" => metadata.parse_bext(&body),
+                b"iXML" => metadata.parse_ixml(&String::from_utf8_lossy(&body)),
+                b"cue " => cues.extend(parse_cue(&body)),
+                _ => metadata.parse_list(&body, &mut labels),
+            }
+        }
+        metadata.markers = cues
+            .iter()
+            .map(|&(id, position)| CueMarker {
+                position,
+                label: labels
+                    .iter()
+                    .find(|(label_id, _)| *label_id == id)
+                    .map(|(_, label)| label.clone()),
+            })
+            .collect();
+        metadata.markers.sort_by_key(|marker| marker.position);
+        let tag = |metadata: &Self, name: &str| {
+            metadata
+                .tags
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+        };
+        metadata.recorder = tag(&metadata, "Originator").or_else(|| tag(&metadata, "Software"));
+        metadata.notes = tag(&metadata, "Description")
+            .or_else(|| tag(&metadata, "NOTE"))
+            .or_else(|| tag(&metadata, "Comment"));
+        Ok(metadata)
+    }
+
+    /// Whether the file carries none of the chunks.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.markers.is_empty()
+    }
+
+    /// Time of day of the first sample as `hh:mm:ss.mmm`, from the bext time reference.
+    pub fn timecode(&self, sample_rate: u32) -> Option<String> {
+        let ms = self.time_reference? * 1000 / sample_rate.max(1) as u64;
+        let (hours, minutes, seconds) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60);
+        Some(format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            hours,
+            minutes,
+            seconds,
+            ms % 1000
+        ))
+    }
+
+    fn push_tag(&mut self, name: &str, value: &str) {
+        let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        if !value.is_empty() {
+            self.tags.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    /// Fixed-size ASCII fields padded with zeros, see EBU Tech 3285.
+    fn parse_bext(&mut self, bext: &[u8]) {
+        if bext.len() < 346 {
+            return;
+        }
+        let text = |range: std::ops::Range<usize>| {
+            let field = &bext[range];
+            let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+        self.push_tag("Description", &text(0..256));
+        self.push_tag("Originator", &text(256..288));
+        self.push_tag("Originator reference", &text(288..320));
+        let origination = format!(
+            "{} {}",
+            text(320..330),
+            text(330..338).replace(['-', '.'], ":")
+        );
+        if !origination.trim().is_empty() {
+            self.push_tag("Origination", &origination);
+            self.origination = Some(origination.trim().to_string());
+        }
+        self.time_reference = Some(u32_at(bext, 338) as u64 | (u32_at(bext, 342) as u64) << 32);
+    }
+
+    /// Only picks out the first occurrence of each of [`IXML_TAGS`], which is enough for the
+    /// flat layout recorders write.
+    fn parse_ixml(&mut self, xml: &str) {
+        for tag in IXML_TAGS {
+            let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+            let Some(start) = xml.find(&open).map(|i| i + open.len()) else {
+                continue;
+            };
+            if let Some(end) = xml[start..].find(&close) {
+                self.push_tag(tag, &xml[start..start + end]);
+            }
+        }
+    }
+
+    /// `INFO` fields or `adtl` labels of cue points; other list types are ignored.
+    fn parse_list(&mut self, list: &[u8], labels: &mut Vec<(u32, String)>) {
+        if list.len() < 4 {
+            return;
+        }
+        let kind = &list[0..4];
+        let mut rest = &list[4..];
+        while rest.len() >= 8 {
+            let len = u32_at(rest, 4) as usize;
+            let Some(body) = rest.get(8..8 + len) else {
+                break;
+            };
+            let text = |bytes: &[u8]| {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+            };
+            let id = &rest[0..4];
+            if kind == b"INFO" {
+                if let Some((_, name)) = INFO_TAGS.iter().find(|(tag, _)| id == *tag) {
+                    self.push_tag(name, &text(body));
+                }
+            } else if kind == b"adtl" && id == b"labl" && body.len() >= 4 {
+                labels.push((u32_at(body, 0), text(&body[4..])));
+            }
+            rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+        }
+    }
+}
+
+/// Id and sample offset of each point of a `cue ` chunk.
+fn parse_cue(cue: &[u8]) -> Vec<(u32, u32)> {
+    if cue.len() < 4 {
+        return Vec::new();
+    }
+    cue[4..]
+        .chunks_exact(24)
+        .take(u32_at(cue, 0) as usize)
+        .map(|point| (u32_at(point, 0), u32_at(point, 20)))
+        .collect()
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
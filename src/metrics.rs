@@ -0,0 +1,356 @@
+use crate::filter;
+
+/// Time-domain level descriptors computed from the decoded samples of a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelStats {
+    pub peak: f32,
+    pub rms: f32,
+    /// Peak / RMS. A sine is ≈ 1.41, a square wave 1.0; heavily compressed material sits close to 1.
+    pub crest_factor: f32,
+    /// Spread in dB between loud and quiet passages, see [`dynamic_range_db`].
+    pub dynamic_range_db: f32,
+    /// Integrated loudness, see [`integrated_loudness`]. `None` if everything is gated away.
+    pub loudness_lufs: Option<f32>,
+}
+
+/// Length of the blocks used for the short-term RMS in [`dynamic_range_db`].
+const BLOCK_SECONDS: f32 = 0.05;
+
+/// Returns `None` for empty or fully silent input, where the ratios are undefined.
+pub fn level_stats(samples: &[f32], sample_rate: u32) -> Option<LevelStats> {
+    let peak = samples.iter().fold(0.0_f32, |max, s| max.max(s.abs()));
+    let rms = rms(samples);
+    if peak == 0.0 || rms == 0.0 {
+        return None;
+    }
+    Some(LevelStats {
+        peak,
+        rms,
+        crest_factor: peak / rms,
+        dynamic_range_db: dynamic_range_db(samples, sample_rate),
+        loudness_lufs: integrated_loudness(samples, sample_rate),
+    })
+}
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+    (sum / samples.len() as f64).sqrt() as f32
+}
+
+/// Simple dynamic range: the ratio in dB between the 95th and 10th percentile of the short-term
+/// RMS over 50 ms blocks. Silent blocks are ignored so leading/trailing silence doesn't make
+/// every recording look infinitely dynamic.
+pub fn dynamic_range_db(samples: &[f32], sample_rate: u32) -> f32 {
+    let block_len = ((sample_rate as f32 * BLOCK_SECONDS) as usize).max(1);
+    let mut blocks: Vec<f32> = samples
+        .chunks(block_len)
+        .map(rms)
+        .filter(|&r| r > 0.0)
+        .collect();
+    if blocks.is_empty() {
+        return 0.0;
+    }
+    blocks.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: f32| blocks[((blocks.len() - 1) as f32 * p).round() as usize];
+    20.0 * (percentile(0.95) / percentile(0.10)).log10()
+}
+
+/// Approximate ITU-R BS.1770 integrated loudness in LUFS.
+///
+/// An approximation: the signal is treated as a single channel and the filters run in `f32`.
+/// K-weighting and gating follow the standard (400 ms blocks with 75% overlap, -70 LUFS absolute
+/// and -10 LU relative gate); a full-scale 997 Hz sine reads -3.05 LUFS, against the nominal
+/// -3.01 of the standard's calibration tone.
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    const ABSOLUTE_GATE: f64 = -70.0;
+    const RELATIVE_GATE: f64 = -10.0;
+    let rate = sample_rate as f32;
+    let [mut shelf, mut high_pass] = filter::k_weighting(rate);
+    // back to full scale, since samples are in the i16 range
+    let weighted: Vec<f32> = samples
+        .iter()
+        .map(|&s| high_pass.process(shelf.process(s / 32_768.0)))
+        .collect();
+
+    let block_len = (rate * 0.4) as usize;
+    let step = (block_len / 4).max(1);
+    if block_len == 0 || weighted.len() < block_len {
+        return None;
+    }
+    let powers: Vec<f64> = (0..=weighted.len() - block_len)
+        .step_by(step)
+        .map(|start| {
+            let block = &weighted[start..start + block_len];
+            block.iter().map(|&s| s as f64 * s as f64).sum::<f64>() / block_len as f64
+        })
+        .collect();
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let gated_mean = |threshold: f64| {
+        let gated: Vec<f64> = powers
+            .iter()
+            .copied()
+            .filter(|&p| loudness(p) > threshold)
+            .collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+    let relative_threshold = loudness(gated_mean(ABSOLUTE_GATE)?) + RELATIVE_GATE;
+    Some(loudness(gated_mean(relative_threshold)?) as f32)
+}
+
+/// Frequency of the strongest bin, ignoring the DC bin.
+pub fn peak_frequency(freqs: &[f32], amplitudes: &[f32]) -> Option<f32> {
+    freqs
+        .iter()
+        .zip(amplitudes)
+        .skip(1)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(&freq, _)| freq)
+}
+
+/// Narrowest range of bins from the first to the last frequency that holds `fraction` of the energy
+/// of the spectrum, leaving out equal shares at both ends. `None` for a silent spectrum.
+pub fn energy_range(freqs: &[f32], amplitudes: &[f32], fraction: f64) -> Option<(f32, f32)> {
+    let energy: f64 = amplitudes.iter().map(|&a| a as f64 * a as f64).sum();
+    if energy <= 0.0 {
+        return None;
+    }
+    let tail = (1.0 - fraction.clamp(0.0, 1.0)) / 2.0 * energy;
+    let mut cumulative = 0.0;
+    let mut low = None;
+    let mut high = None;
+    for (&f, &a) in freqs.iter().zip(amplitudes) {
+        cumulative += a as f64 * a as f64;
+        if low.is_none() && cumulative > tail {
+            low = Some(f);
+        }
+        if cumulative >= energy - tail {
+            high = Some(f);
+            break;
+        }
+    }
+    Some((low?, high.or(freqs.last().copied())?))
+}
+
+/// Fraction of the spectral energy below [`SpectralFeatures::rolloff`].
+pub const ROLLOFF_FRACTION: f64 = 0.85;
+
+/// Spectral shape descriptors, computed together since they share most of their sums.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct SpectralFeatures {
+    /// Amplitude-weighted mean frequency.
+    pub centroid: f32,
+    /// Amplitude-weighted standard deviation of the frequency around the centroid.
+    pub bandwidth: f32,
+    /// Frequency below which [`ROLLOFF_FRACTION`] of the energy lies.
+    pub rolloff: f32,
+    /// Geometric over arithmetic mean of the power spectrum: 1 for white noise, near 0 for tones.
+    pub flatness: f32,
+    /// Mean change between consecutive STFT frames, see [`spectral_flux`]. `None` without an STFT.
+    pub flux: Option<f32>,
+}
+
+impl SpectralFeatures {
+    /// `None` for silent spectra, where the descriptors are undefined.
+    pub fn new(freqs: &[f32], amplitudes: &[f32]) -> Option<Self> {
+        // power floor keeping the log in the geometric mean finite for empty bins
+        const FLOOR: f64 = 1e-20;
+        let (mut total, mut weighted, mut energy, mut log_power) =
+            (0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64);
+        for (&f, &a) in freqs.iter().zip(amplitudes) {
+            let (f, a) = (f as f64, a as f64);
+            total += a;
+            weighted += f * a;
+            energy += a * a;
+            log_power += (a * a).max(FLOOR).ln();
+        }
+        if total <= 0.0 || energy <= 0.0 {
+            return None;
+        }
+        let centroid = weighted / total;
+        let spread: f64 = freqs
+            .iter()
+            .zip(amplitudes)
+            .map(|(&f, &a)| (f as f64 - centroid).powi(2) * a as f64)
+            .sum();
+        let mut cumulative = 0.0;
+        let rolloff = freqs
+            .iter()
+            .zip(amplitudes)
+            .find(|(_, &a)| {
+                cumulative += a as f64 * a as f64;
+                cumulative >= ROLLOFF_FRACTION * energy
+            })
+            .map_or(0.0, |(&f, _)| f);
+        let n = amplitudes.len() as f64;
+        Some(Self {
+            centroid: centroid as f32,
+            bandwidth: (spread / total).sqrt() as f32,
+            rolloff,
+            flatness: ((log_power / n).exp() / (energy / n)) as f32,
+            flux: None,
+        })
+    }
+}
+
+/// Mean spectral flux: the Euclidean distance between consecutive frames, each normalized to unit
+/// length so the level of the recording doesn't matter. `None` for fewer than two frames.
+pub fn spectral_flux(frames: &[Vec<f32>]) -> Option<f32> {
+    let normalized: Vec<Vec<f32>> = frames
+        .iter()
+        .map(|frame| {
+            let norm = frame.iter().map(|&a| a * a).sum::<f32>().sqrt();
+            frame
+                .iter()
+                .map(|&a| if norm > 0.0 { a / norm } else { 0.0 })
+                .collect()
+        })
+        .collect();
+    if normalized.len() < 2 {
+        return None;
+    }
+    let total: f32 = normalized
+        .windows(2)
+        .map(|pair| {
+            pair[0]
+                .iter()
+                .zip(&pair[1])
+                .map(|(a, b)| (b - a) * (b - a))
+                .sum::<f32>()
+                .sqrt()
+        })
+        .sum();
+    Some(total / (normalized.len() - 1) as f32)
+}
+
+/// Cosine similarity of two spectra resampled onto the same grid, over the bins both cover.
+/// 1.0 means identical shape regardless of level; 0.0 if either is silent there.
+pub fn cosine_similarity(a: &[Option<f32>], b: &[Option<f32>]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0_f64, 0.0_f64, 0.0_f64);
+    for (&a, &b) in a.iter().zip(b) {
+        if let (Some(a), Some(b)) = (a, b) {
+            dot += a as f64 * b as f64;
+            norm_a += a as f64 * a as f64;
+            norm_b += b as f64 * b as f64;
+        }
+    }
+    if norm_a <= 0.0 || norm_b <= 0.0 {
+        return 0.0;
+    }
+    (dot / (norm_a.sqrt() * norm_b.sqrt())) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window::Window;
+    use crate::{testgen, AnalysisOptions};
+
+    #[test]
+    fn crest_factor_of_a_sine_and_a_square() {
+        let sine = testgen::sine(1000.0, 8192.0, 48_000, 48_000);
+        let sine = level_stats(&sine, 48_000).unwrap();
+        assert!(
+            (sine.crest_factor - std::f32::consts::SQRT_2).abs() < 1e-3,
+            "{}",
+            sine.crest_factor
+        );
+        // a naive square, since the band-limited one overshoots
+        let square: Vec<f32> = (0..48_000)
+            .map(|i| if i % 48 < 24 { 8192.0 } else { -8192.0 })
+            .collect();
+        let square = level_stats(&square, 48_000).unwrap();
+        assert!(
+            (square.crest_factor - 1.0).abs() < 1e-6,
+            "{}",
+            square.crest_factor
+        );
+    }
+
+    #[test]
+    fn loudness_of_a_full_scale_997_hz_sine() {
+        for sample_rate in [44_100, 48_000] {
+            let tone = testgen::sine(997.0, 32_768.0, sample_rate, 2 * sample_rate as usize);
+            let loudness = integrated_loudness(&tone, sample_rate).unwrap();
+            assert!(
+                (loudness + 3.05).abs() < 0.01,
+                "{} LUFS at {} Hz",
+                loudness,
+                sample_rate
+            );
+        }
+        assert_eq!(integrated_loudness(&[0.0; 48_000], 48_000), None);
+    }
+
+    /// Features of a second of `samples` at 48 kHz, on 1 Hz bins. In double precision, so it's
+    /// mostly the rounding of the `f32` samples that leaves a floor in the empty bins.
+    fn features(samples: &[f32], options: AnalysisOptions) -> SpectralFeatures {
+        let options = options.precision(crate::fft::Precision::F64);
+        let plot_data = crate::analyze_samples(samples, 48_000, options).unwrap();
+        SpectralFeatures::new(&plot_data.freqs, &plot_data.amplitudes).unwrap()
+    }
+
+    fn two_tones(second: f32) -> Vec<f32> {
+        testgen::sines(&[(440.0, 8192.0), (1000.0, second)], 48_000, 48_000)
+    }
+
+    #[test]
+    fn centroid_and_bandwidth() {
+        let tone = features(
+            &testgen::sine(1000.0, 8192.0, 48_000, 48_000),
+            AnalysisOptions::default(),
+        );
+        assert!((tone.centroid - 1000.0).abs() < 0.1, "{}", tone.centroid);
+        // the amplitude-weighted spread feels that floor over the 24000 bins
+        assert!(tone.bandwidth < 5.0, "{}", tone.bandwidth);
+        // halfway between equal tones, each half the spread away
+        let pair = features(&two_tones(8192.0), AnalysisOptions::default());
+        assert!((pair.centroid - 720.0).abs() < 0.1, "{}", pair.centroid);
+        assert!((pair.bandwidth - 280.0).abs() < 1.0, "{}", pair.bandwidth);
+    }
+
+    #[test]
+    fn rolloff() {
+        // equal tones hold half the energy each, so 85 % is only reached at the upper one
+        assert_eq!(
+            features(&two_tones(8192.0), AnalysisOptions::default()).rolloff,
+            1000.0
+        );
+        // at a quarter the amplitude the upper one has 6 % of the energy
+        assert_eq!(
+            features(&two_tones(2048.0), AnalysisOptions::default()).rolloff,
+            440.0
+        );
+    }
+
+    #[test]
+    fn flatness_of_noise_and_a_tone() {
+        // averaged, so the bins of the noise scatter little around its flat spectrum
+        let welch = crate::welch::Welch {
+            segment_len: 512,
+            hop: 256,
+        };
+        let options = AnalysisOptions::default()
+            .window(Window::Hann)
+            .welch(Some(welch));
+        let noise = features(&testgen::white_noise(8192.0, 48_000, 1), options);
+        assert!(noise.flatness > 0.9, "{}", noise.flatness);
+        let tone = features(
+            &testgen::sine(1000.0, 8192.0, 48_000, 48_000),
+            AnalysisOptions::default(),
+        );
+        assert!(tone.flatness < 1e-3, "{}", tone.flatness);
+    }
+
+    #[test]
+    fn flux_between_frames() {
+        let (low, high) = (vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 2.0]);
+        assert_eq!(spectral_flux(&[low.clone(), low.clone()]), Some(0.0));
+        // unit length frames at right angles are √2 apart, whatever their level
+        let flux = spectral_flux(&[low.clone(), high, low.clone()]).unwrap();
+        assert!((flux - std::f32::consts::SQRT_2).abs() < 1e-6, "{}", flux);
+        assert_eq!(spectral_flux(&[low]), None);
+    }
+}
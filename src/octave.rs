@@ -0,0 +1,218 @@
+/// Frequency weighting of IEC 61672 applied to band levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Weighting {
+    /// Flat, i.e. unweighted.
+    #[default]
+    Z,
+    /// Approximates the ear at low levels, the usual choice for noise limits.
+    A,
+    /// Nearly flat down to about 30 Hz, for loud and peak levels.
+    C,
+}
+
+impl Weighting {
+    pub const ALL: [Weighting; 3] = [Weighting::Z, Weighting::A, Weighting::C];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weighting::Z => "Z (flat)",
+            Weighting::A => "A",
+            Weighting::C => "C",
+        }
+    }
+
+    /// Gain in dB at `freq`, 0 at 1 kHz. The pole frequencies and the normalization constants are
+    /// the ones the standard gives.
+    pub fn gain_db(self, freq: f32) -> f32 {
+        let f2 = (freq as f64).powi(2);
+        let (low, high) = (20.6_f64.powi(2), 12_194.0_f64.powi(2));
+        let gain = match self {
+            Weighting::Z => return 0.0,
+            Weighting::A => {
+                let mid = ((f2 + 107.7_f64.powi(2)) * (f2 + 737.9_f64.powi(2))).sqrt();
+                20.0 * (high * f2 * f2 / ((f2 + low) * mid * (f2 + high))).log10() + 2.0
+            }
+            Weighting::C => 20.0 * (high * f2 / ((f2 + low) * (f2 + high))).log10() + 0.06,
+        };
+        // −∞ at DC
+        gain.max(-200.0) as f32
+    }
+}
+
+/// Width of the bands, see IEC 61260.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum BandFraction {
+    #[default]
+    Octave,
+    Third,
+}
+
+impl BandFraction {
+    pub const ALL: [BandFraction; 2] = [BandFraction::Octave, BandFraction::Third];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BandFraction::Octave => "1/1 octave",
+            BandFraction::Third => "1/3 octave",
+        }
+    }
+
+    pub fn bands_per_octave(self) -> u32 {
+        match self {
+            BandFraction::Octave => 1,
+            BandFraction::Third => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct BandLevel {
+    /// Exact midband frequency, e.g. 1258.9 Hz for the band nominally called 1250 Hz.
+    pub center: f32,
+    pub lower: f32,
+    pub upper: f32,
+    pub level_db: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BandLevels {
+    pub bands: Vec<BandLevel>,
+    /// Level of all the bands together, i.e. broadband over their range.
+    pub overall_db: f32,
+}
+
+/// Level of each band from 16 Hz to 20 kHz that the spectrum covers entirely and has bins in, as
+/// the weighted energy of those bins: 10 log10 of the sum of their squared amplitudes. Levels are
+/// relative to an amplitude of 1 like the plot's, so they compare between files and bands but
+/// aren't absolute.
+pub fn band_levels(
+    freqs: &[f32],
+    amplitudes: &[f32],
+    fraction: BandFraction,
+    weighting: Weighting,
+) -> BandLevels {
+    // base-10 band ratio of IEC 61260, within 0.1 % of 2
+    let ratio = 10.0_f64.powf(0.3);
+    let per_octave = fraction.bands_per_octave() as f64;
+    // band indices relative to the one centered on 1 kHz
+    let index = |freq: f64| (per_octave * (freq / 1000.0).log10() / 0.3).round() as i32;
+    let top = freqs.iter().fold(0.0_f32, |max, &freq| max.max(freq.abs())) as f64;
+    let to_db = |energy: f64| (10.0 * energy.max(1e-30).log10()) as f32;
+    let mut bands = Vec::new();
+    let mut overall = 0.0_f64;
+    for i in index(16.0)..=index(20_000.0) {
+        let center = 1000.0 * ratio.powf(i as f64 / per_octave);
+        let half = ratio.powf(0.5 / per_octave);
+        let (lower, upper) = (center / half, center * half);
+        if upper > top {
+            break;
+        }
+        let mut energy = 0.0_f64;
+        let mut bins = 0;
+        for (&freq, &amp) in freqs.iter().zip(amplitudes) {
+            let freq = freq.abs();
+            if (freq as f64) >= lower && (freq as f64) < upper {
+                let gain = 10.0_f64.powf(weighting.gain_db(freq) as f64 / 20.0);
+                energy += (amp as f64 * gain).powi(2);
+                bins += 1;
+            }
+        }
+        if bins == 0 {
+            continue;
+        }
+        overall += energy;
+        bands.push(BandLevel {
+            center: center as f32,
+            lower: lower as f32,
+            upper: upper as f32,
+            level_db: to_db(energy),
+        });
+    }
+    BandLevels {
+        bands,
+        overall_db: to_db(overall),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A spectrum with 1 Hz bins up to 24 kHz and a tone of amplitude 1 at `freq`.
+    fn tone_spectrum(freq: usize) -> (Vec<f32>, Vec<f32>) {
+        let freqs: Vec<f32> = (0..=24_000).map(|f| f as f32).collect();
+        let mut amplitudes = vec![0.0; freqs.len()];
+        amplitudes[freq] = 1.0;
+        (freqs, amplitudes)
+    }
+
+    #[test]
+    fn bands_meet_at_their_edges_around_1_khz() {
+        let (freqs, amplitudes) = tone_spectrum(1000);
+        let close = |a: f32, b: f32| (a - b).abs() < 0.1;
+        for (fraction, lower, upper) in [
+            (BandFraction::Octave, 707.9, 1412.5),
+            (BandFraction::Third, 891.3, 1122.0),
+        ] {
+            let levels = band_levels(&freqs, &amplitudes, fraction, Weighting::Z);
+            let band = levels
+                .bands
+                .iter()
+                .find(|band| close(band.center, 1000.0))
+                .unwrap();
+            assert!(
+                close(band.lower, lower) && close(band.upper, upper),
+                "{:?}",
+                band
+            );
+            assert!(levels
+                .bands
+                .windows(2)
+                .all(|pair| (pair[0].upper - pair[1].lower).abs() < 1e-3));
+            // up to the band below 20 kHz that fits under 24 kHz
+            assert!(levels.bands.first().unwrap().center < 20.0);
+            assert!(levels.bands.last().unwrap().upper <= 24_000.0);
+        }
+    }
+
+    #[test]
+    fn a_tone_puts_its_level_in_its_own_band() {
+        let (freqs, amplitudes) = tone_spectrum(1000);
+        let levels = band_levels(&freqs, &amplitudes, BandFraction::Third, Weighting::A);
+        let loudest = levels
+            .bands
+            .iter()
+            .max_by(|a, b| a.level_db.total_cmp(&b.level_db))
+            .unwrap();
+        assert!((loudest.center - 1000.0).abs() < 0.1);
+        // A-weighting is 0 dB at 1 kHz
+        assert!(
+            loudest.level_db.abs() < 0.01 && levels.overall_db.abs() < 0.01,
+            "{:?}",
+            loudest
+        );
+    }
+
+    #[test]
+    fn weightings_match_the_standard_table() {
+        // the table's 31.5 Hz is the exact midband 31.62 Hz
+        for (freq, a, c) in [
+            (31.62, -39.4, -3.0),
+            (100.0, -19.1, -0.3),
+            (1000.0, 0.0, 0.0),
+            (8000.0, -1.1, -3.0),
+        ] {
+            assert!(
+                (Weighting::A.gain_db(freq) - a).abs() < 0.1,
+                "A at {} Hz",
+                freq
+            );
+            assert!(
+                (Weighting::C.gain_db(freq) - c).abs() < 0.1,
+                "C at {} Hz",
+                freq
+            );
+            assert_eq!(Weighting::Z.gain_db(freq), 0.0);
+        }
+    }
+}
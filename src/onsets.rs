@@ -0,0 +1,120 @@
+/// Step of the envelope in seconds, fine enough to resolve drum flams.
+const FRAME_SECONDS: f32 = 0.001;
+/// Release of the fast follower that tracks the hits themselves.
+const FAST_RELEASE_SECONDS: f32 = 0.01;
+/// Time constant of the slow follower a hit has to rise above.
+const SLOW_SECONDS: f32 = 0.1;
+/// Onsets closer together than this are taken as one hit ringing on.
+const MIN_GAP_SECONDS: f32 = 0.05;
+/// Hits more than this far below the loudest frame are ignored as noise.
+const FLOOR_DB: f32 = -50.0;
+
+/// Envelope of a signal and the onsets found in it by [`detect`].
+#[derive(Debug, Clone, Default)]
+pub struct Onsets {
+    /// Fast envelope in dB relative to full scale, one value every [`Self::frame_seconds`].
+    pub envelope_db: Vec<f32>,
+    pub frame_seconds: f32,
+    /// Start of each hit in seconds.
+    pub times: Vec<f32>,
+}
+
+impl Onsets {
+    /// Mean time between consecutive onsets, `None` with fewer than two.
+    pub fn mean_interval(&self) -> Option<f32> {
+        let (first, last) = (self.times.first()?, self.times.last()?);
+        (self.times.len() >= 2).then(|| (last - first) / (self.times.len() - 1) as f32)
+    }
+
+    /// Tempo in beats per minute if every onset is a beat.
+    pub fn tempo_bpm(&self) -> Option<f32> {
+        self.mean_interval()
+            .filter(|&interval| interval > 0.0)
+            .map(|interval| 60.0 / interval)
+    }
+}
+
+/// Finds the hits of a percussive signal with two envelope followers: a fast one with instant
+/// attack, and a slow one it has to rise above by a margin that `sensitivity` (0 to 1) shrinks
+/// from 18 dB down to 3 dB.
+pub fn detect(samples: &[f32], sample_rate: u32, sensitivity: f32) -> Onsets {
+    let frame_len = ((sample_rate as f32 * FRAME_SECONDS) as usize).max(1);
+    let frame_seconds = frame_len as f32 / sample_rate as f32;
+    let coefficient = |seconds: f32| (-frame_seconds / seconds).exp();
+    let (fast_release, slow) = (coefficient(FAST_RELEASE_SECONDS), coefficient(SLOW_SECONDS));
+    let threshold_db = 3.0 + (1.0 - sensitivity.clamp(0.0, 1.0)) * 15.0;
+    let to_db = |level: f32| 20.0 * level.max(1e-6).log10();
+
+    let peaks: Vec<f32> = samples
+        .chunks(frame_len)
+        .map(|frame| frame.iter().fold(0.0_f32, |max, s| max.max(s.abs())))
+        .collect();
+    let floor_db = to_db(peaks.iter().copied().fold(0.0, f32::max)) + FLOOR_DB;
+    let (mut fast_level, mut slow_level) = (0.0_f32, 0.0_f32);
+    let mut above = false;
+    let mut envelope_db = Vec::with_capacity(peaks.len());
+    let mut times: Vec<f32> = Vec::new();
+    for (i, &peak) in peaks.iter().enumerate() {
+        fast_level = peak.max(fast_level * fast_release);
+        let fast_db = to_db(fast_level);
+        let rise = fast_db - to_db(slow_level);
+        slow_level = slow * slow_level + (1.0 - slow) * fast_level;
+        envelope_db.push(fast_db);
+        let time = i as f32 * frame_seconds;
+        let onset = rise > threshold_db && fast_db > floor_db;
+        if onset
+            && !above
+            && times
+                .last()
+                .is_none_or(|&last| time - last >= MIN_GAP_SECONDS)
+        {
+            times.push(time);
+        }
+        above = onset;
+    }
+    Onsets {
+        envelope_db,
+        frame_seconds,
+        times,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_are_found_where_they_start() {
+        let rate = 48_000;
+        let starts = [0.25, 0.75, 1.25, 1.75];
+        let mut samples = vec![0.0; 2 * rate as usize];
+        for start in starts {
+            // 20 ms clicks decaying from full scale
+            let first = (start * rate as f32) as usize;
+            for (i, sample) in samples[first..first + 960].iter_mut().enumerate() {
+                *sample = (-(i as f32) / 100.0).exp() * if i % 2 == 0 { 1.0 } else { -1.0 };
+            }
+        }
+        let onsets = detect(&samples, rate, 0.5);
+        assert_eq!(onsets.times.len(), starts.len(), "{:?}", onsets.times);
+        for (found, start) in onsets.times.iter().zip(starts) {
+            assert!(
+                (found - start).abs() <= onsets.frame_seconds,
+                "{} for {}",
+                found,
+                start
+            );
+        }
+        assert!((onsets.tempo_bpm().unwrap() - 120.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        let onsets = detect(&[0.0; 48_000], 48_000, 1.0);
+        assert!(onsets.times.is_empty());
+        assert_eq!(
+            (onsets.envelope_db.len(), onsets.mean_interval()),
+            (1000, None)
+        );
+    }
+}
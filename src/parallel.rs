@@ -0,0 +1,95 @@
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+/// One thread per core, or a single one if that can't be determined. Browsers can't start
+/// threads, so on wasm everything runs on the calling one whatever the count.
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Calls `f` on every item on up to `threads` scoped threads, returning the results in the order
+/// of `items`. Threads take the next item as soon as they're done, so a few large files don't
+/// hold up the rest.
+pub fn map<I, R>(items: I, threads: usize, f: impl Fn(I::Item) -> R + Sync) -> Vec<R>
+where
+    I: Iterator + Send,
+    I::Item: Send,
+    R: Send,
+{
+    let mut results: Vec<(usize, R)> = Vec::new();
+    for_each(items, threads, f, |i, result| results.push((i, result)));
+    results.sort_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Like [`map`], but hands each result with the index of its item to `each` on the calling thread
+/// as soon as it's done, in the order they finish, so they can be used up while the rest are
+/// still being worked on.
+pub fn for_each<I, R>(
+    items: I,
+    threads: usize,
+    f: impl Fn(I::Item) -> R + Sync,
+    mut each: impl FnMut(usize, R),
+) where
+    I: Iterator + Send,
+    I::Item: Send,
+    R: Send,
+{
+    if cfg!(target_arch = "wasm32") {
+        for (i, item) in items.enumerate() {
+            each(i, f(item));
+        }
+        return;
+    }
+    let items = Mutex::new(items.enumerate());
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let sender = sender.clone();
+            let (items, f) = (&items, &f);
+            scope.spawn(move || loop {
+                // release the lock before working on the item
+                let next = items.lock().unwrap().next();
+                let Some((i, item)) = next else {
+                    break;
+                };
+                if sender.send((i, f(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        // only the workers' senders are left, the loop ends once they all are done
+        drop(sender);
+        for (i, result) in receiver {
+            each(i, result);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_keep_the_order_of_the_items() {
+        for threads in [1, 3, 16] {
+            let squares = map(0..100_u64, threads, |n| n * n);
+            assert_eq!(squares, (0..100).map(|n| n * n).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn each_result_arrives_once_with_its_index() {
+        let mut seen = [0; 50];
+        for_each(
+            0..50_usize,
+            4,
+            |n| n + 1,
+            |i, result| {
+                assert_eq!(result, i + 1);
+                seen[i] += 1;
+            },
+        );
+        assert!(seen.iter().all(|&count| count == 1));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::window::Window;
+
+/// A local maximum of a spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Peak {
+    pub frequency: f32,
+    pub amplitude: f32,
+    /// Height above the higher of the two valleys separating this peak from any taller one.
+    pub prominence: f32,
+}
+
+/// Frequencies [`find_peaks`] and [`fundamental`] look at; outside it, DC drift and ultrasonic
+/// noise would outrank the peaks a listener hears. Negative frequencies of two-sided spectra
+/// count by their magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PeakBand {
+    pub min_freq: f32,
+    pub max_freq: f32,
+}
+
+impl Default for PeakBand {
+    /// The audible range.
+    fn default() -> Self {
+        Self {
+            min_freq: 20.0,
+            max_freq: 20_000.0,
+        }
+    }
+}
+
+impl PeakBand {
+    /// Every bin, DC included.
+    pub const ALL: PeakBand = PeakBand {
+        min_freq: 0.0,
+        max_freq: f32::INFINITY,
+    };
+
+    pub fn contains(self, freq: f32) -> bool {
+        (self.min_freq..=self.max_freq).contains(&freq.abs())
+    }
+}
+
+/// Returns the `max_peaks` highest local maxima within `band` whose prominence is at least
+/// `min_prominence`, sorted by descending amplitude. Maxima closer than `min_distance_hz` to a
+/// higher one that is kept are dropped, so a single broad peak isn't reported as several adjacent
+/// bins. Prominence is still measured against the whole spectrum.
+pub fn find_peaks(
+    freqs: &[f32],
+    amplitudes: &[f32],
+    band: PeakBand,
+    max_peaks: usize,
+    min_prominence: f32,
+    min_distance_hz: f32,
+) -> Vec<Peak> {
+    let mut peaks = Vec::new();
+    for i in 1..amplitudes.len().saturating_sub(1) {
+        let amp = amplitudes[i];
+        if amp <= amplitudes[i - 1] || amp < amplitudes[i + 1] || !band.contains(freqs[i]) {
+            continue;
+        }
+        let prominence = prominence(amplitudes, i);
+        if prominence >= min_prominence {
+            peaks.push(Peak {
+                frequency: freqs[i],
+                amplitude: amp,
+                prominence,
+            });
+        }
+    }
+    if min_distance_hz > 0.0 {
+        peaks = suppress_close(peaks, min_distance_hz);
+    }
+    peaks.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+    peaks.truncate(max_peaks);
+    peaks
+}
+
+/// Keeps the highest of `peaks` (sorted by frequency) and drops the ones within `min_distance_hz`
+/// of it, then the highest remaining one and so on.
+fn suppress_close(peaks: Vec<Peak>, min_distance_hz: f32) -> Vec<Peak> {
+    let mut by_amplitude: Vec<usize> = (0..peaks.len()).collect();
+    by_amplitude.sort_by(|&a, &b| peaks[b].amplitude.total_cmp(&peaks[a].amplitude));
+    let mut keep = vec![true; peaks.len()];
+    for i in by_amplitude {
+        if !keep[i] {
+            continue;
+        }
+        let near = |j: &usize| (peaks[*j].frequency - peaks[i].frequency).abs() < min_distance_hz;
+        for j in (0..i)
+            .rev()
+            .take_while(near)
+            .chain((i + 1..peaks.len()).take_while(near))
+        {
+            keep[j] = false;
+        }
+    }
+    peaks
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(peak, keep)| keep.then_some(peak))
+        .collect()
+}
+
+fn prominence(amplitudes: &[f32], i: usize) -> f32 {
+    let amp = amplitudes[i];
+    let left = valley(amplitudes[..i].iter().rev(), amp);
+    let right = valley(amplitudes[i + 1..].iter(), amp);
+    amp - left.max(right)
+}
+
+/// Lowest point walking away from a peak of height `amp` until something taller is reached.
+fn valley<'a>(side: impl Iterator<Item = &'a f32>, amp: f32) -> f32 {
+    side.take_while(|&&a| a <= amp).fold(amp, |m, &a| m.min(a))
+}
+
+/// Position and height of the peak at bin `i` refined by fitting a parabola through the log
+/// magnitudes of it and its neighbours. Accurate to a small fraction of a bin for windowed
+/// spectra, where the raw bin frequency can be off by half a bin.
+pub fn interpolate(freqs: &[f32], amplitudes: &[f32], i: usize) -> (f32, f32) {
+    if i == 0 || i + 1 >= amplitudes.len() {
+        return (freqs[i], amplitudes[i]);
+    }
+    let ln = |a: f32| a.max(f32::MIN_POSITIVE).ln();
+    let (left, center, right) = (
+        ln(amplitudes[i - 1]),
+        ln(amplitudes[i]),
+        ln(amplitudes[i + 1]),
+    );
+    let curvature = left - 2.0 * center + right;
+    if curvature >= 0.0 {
+        return (freqs[i], amplitudes[i]);
+    }
+    // vertex of the parabola, in bins relative to `i`
+    let offset = 0.5 * (left - right) / curvature;
+    let bin_width = if offset > 0.0 {
+        freqs[i + 1] - freqs[i]
+    } else {
+        freqs[i] - freqs[i - 1]
+    };
+    let amplitude = (center - 0.25 * (left - right) * offset).exp();
+    (freqs[i] + offset * bin_width, amplitude)
+}
+
+/// Frequency and amplitude of the strongest sinusoid in a spectrum analysed with `window`,
+/// accurate wherever it falls between bins. The parabola of [`interpolate`] tells which
+/// neighbour the tone lies towards; the offset from the bin is then the one at which the
+/// window's [`Window::response`] gives the ratio of the two bins, and the amplitude is the bin's
+/// corrected for the response there. The amplitude is a bin height like the spectrum's, see
+/// [`Window::sine_amplitude`] for the sinusoid's amplitude.
+pub fn measure_tone(freqs: &[f32], amplitudes: &[f32], window: Window) -> (f32, f32) {
+    let len = freqs.len().min(amplitudes.len());
+    let Some(i) = (0..len).max_by(|&a, &b| amplitudes[a].total_cmp(&amplitudes[b])) else {
+        return (0.0, 0.0);
+    };
+    if i == 0 || i + 1 >= len || amplitudes[i] <= 0.0 {
+        return (freqs[i], amplitudes[i]);
+    }
+    let neighbour = if interpolate(freqs, amplitudes, i).0 >= freqs[i] {
+        i + 1
+    } else {
+        i - 1
+    };
+    let ratio = amplitudes[neighbour] / amplitudes[i];
+    // the ratio rises monotonically from the bin to halfway to its neighbour
+    let (mut low, mut high) = (0.0_f32, 0.5_f32);
+    for _ in 0..24 {
+        let offset = 0.5 * (low + high);
+        if window.response(1.0 - offset) < ratio * window.response(offset) {
+            low = offset;
+        } else {
+            high = offset;
+        }
+    }
+    let offset = 0.5 * (low + high);
+    let freq = freqs[i] + offset * (freqs[neighbour] - freqs[i]);
+    (freq, amplitudes[i] / window.response(offset))
+}
+
+/// Estimated fundamental of a harmonic sound, see [`fundamental`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fundamental {
+    pub frequency: f32,
+    /// How much of the spectrum's energy lies at the harmonics, beyond the share they'd get by
+    /// chance from their width alone: 1 for a clean harmonic tone, near 0 for noise.
+    pub confidence: f32,
+}
+
+/// Fundamental frequency of a harmonic sound, e.g. a played note.
+///
+/// The loudest peaks within `band` are candidates; each is scored by the energy at its first
+/// harmonics, so a strong overtone doesn't win over the note it belongs to. Bins outside `band`
+/// count neither as candidates nor towards the confidence.
+pub fn fundamental(freqs: &[f32], amplitudes: &[f32], band: PeakBand) -> Option<Fundamental> {
+    const CANDIDATES: usize = 10;
+    const HARMONICS: usize = 8;
+    // half a semitone either side of each harmonic
+    const TOLERANCE: f32 = 0.03;
+    let len = freqs.len().min(amplitudes.len());
+    let in_band = |i: &usize| band.contains(freqs[*i]);
+    let max = (0..len)
+        .filter(in_band)
+        .map(|i| amplitudes[i])
+        .fold(0.0, f32::max);
+    let mut candidates: Vec<usize> = (1..len.saturating_sub(1))
+        .filter(|&i| freqs[i] > 0.0 && in_band(&i) && amplitudes[i] >= 0.1 * max)
+        .filter(|&i| amplitudes[i] > amplitudes[i - 1] && amplitudes[i] >= amplitudes[i + 1])
+        .collect();
+    candidates.sort_by(|&a, &b| amplitudes[b].total_cmp(&amplitudes[a]));
+    candidates.truncate(CANDIDATES);
+    let near = |freq: f32| {
+        let start = freqs[..len].partition_point(|&f| f < freq * (1.0 - TOLERANCE));
+        let end = freqs[..len].partition_point(|&f| f <= freq * (1.0 + TOLERANCE));
+        start..end
+    };
+    let loudest_near = |freq: f32| amplitudes[near(freq)].iter().copied().fold(0.0, f32::max);
+    let f0 = candidates
+        .into_iter()
+        .map(|i| interpolate(freqs, amplitudes, i).0)
+        .map(|f0| {
+            (
+                f0,
+                (1..=HARMONICS)
+                    .map(|h| loudest_near(h as f32 * f0))
+                    .sum::<f32>(),
+            )
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(f0, _)| f0)?;
+
+    let mut harmonic = vec![false; len];
+    for h in 1..=HARMONICS {
+        harmonic[near(h as f32 * f0)]
+            .iter_mut()
+            .for_each(|bin| *bin = true);
+    }
+    let (mut total, mut explained, mut covered, mut bins) = (0.0_f64, 0.0_f64, 0, 0);
+    for i in (0..len).filter(|&i| freqs[i] > 0.0).filter(in_band) {
+        bins += 1;
+        let energy = (amplitudes[i] as f64).powi(2);
+        total += energy;
+        if harmonic[i] {
+            explained += energy;
+            covered += 1;
+        }
+    }
+    // noise puts as much energy at the harmonics as their share of the bins
+    let chance = covered as f64 / bins.max(1) as f64;
+    let confidence = if total > 0.0 && chance < 1.0 {
+        ((explained / total - chance) / (1.0 - chance)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    Some(Fundamental {
+        frequency: f0,
+        confidence: confidence as f32,
+    })
+}
+
+/// A frequency relative to the equal-tempered scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    /// Nearest note, e.g. `"A4"`.
+    pub note: String,
+    /// Deviation from that note, between -50 and +50 cents.
+    pub cents: f32,
+}
+
+/// Nearest equal-tempered note to `freq` with A4 tuned to `a4` Hz, `None` for non-positive
+/// frequencies.
+pub fn tuning(freq: f32, a4: f32) -> Option<Tuning> {
+    if freq <= 0.0 || a4 <= 0.0 {
+        return None;
+    }
+    let semitones = 12.0 * (freq / a4).log2() + 69.0;
+    let midi = semitones.round() as i32;
+    Some(Tuning {
+        note: format!(
+            "{}{}",
+            NOTE_NAMES[midi.rem_euclid(12) as usize],
+            midi.div_euclid(12) - 1
+        ),
+        cents: 100.0 * (semitones - midi as f32),
+    })
+}
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Nearest equal-tempered note (A4 = 440 Hz), e.g. `"A4"`. Empty for non-positive frequencies.
+pub fn note_name(freq: f32) -> String {
+    tuning(freq, 440.0)
+        .map(|tuning| tuning.note)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analyze_samples, testgen, AnalysisOptions};
+
+    #[test]
+    fn measure_tone_between_bins() {
+        // 4800 samples make bins 10 Hz wide, 1003.7 Hz is 0.37 bins off
+        let tone = testgen::sine(1003.7, 1000.0, 48_000, 4800);
+        for window in Window::ALL {
+            let options = AnalysisOptions::default().window(window);
+            let plot_data = analyze_samples(&tone, 48_000, options).unwrap();
+            let (freq, amp) = measure_tone(&plot_data.freqs, &plot_data.amplitudes, window);
+            let amp = options.sine_amplitude(amp, tone.len());
+            assert!(
+                (freq - 1003.7).abs() < 0.1,
+                "{}: {} Hz",
+                window.name(),
+                freq
+            );
+            assert!(
+                (amp / 1000.0 - 1.0).abs() < 1e-3,
+                "{}: amplitude {}",
+                window.name(),
+                amp
+            );
+        }
+    }
+}
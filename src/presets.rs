@@ -0,0 +1,51 @@
+use crate::Normalization;
+use ilena_analysis::AnalysisOptions;
+use std::fs::File;
+use std::path::PathBuf;
+
+/// A named set of analysis and display settings.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Preset {
+    pub name: String,
+    /// Flattened, so presets saved when only the window, Welch averaging and decimation were
+    /// stored still load.
+    #[serde(flatten)]
+    pub analysis: AnalysisOptions,
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub normalization: Normalization,
+}
+
+/// `presets.json` next to eframe's persisted state.
+#[cfg(not(target_arch = "wasm32"))]
+fn presets_path() -> Result<PathBuf, String> {
+    eframe::storage_dir(crate::APP_NAME)
+        .map(|dir| dir.join("presets.json"))
+        .ok_or_else(|| "No config directory on this platform".to_string())
+}
+
+/// Browsers keep eframe's state in local storage instead, with no directory to save files in.
+#[cfg(target_arch = "wasm32")]
+fn presets_path() -> Result<PathBuf, String> {
+    Err("Presets can't be saved in the browser, they last until the page is closed".to_string())
+}
+
+/// All saved presets; none if the file doesn't exist yet.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_presets() -> Result<Vec<Preset>, String> {
+    let path = presets_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = File::open(&path).map_err(|e| e.to_string())?;
+    serde_json::from_reader(file).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+pub fn save_presets(presets: &[Preset]) -> Result<(), String> {
+    let path = presets_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let file = File::create(&path).map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(file, presets).map_err(|e| e.to_string())
+}
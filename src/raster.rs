@@ -0,0 +1,265 @@
+use egui::epaint::{ClippedPrimitive, Primitive, Vertex};
+use egui::{Color32, ColorImage, ImageData, Pos2, TextureId, TexturesDelta};
+use std::collections::HashMap;
+
+/// Most pixels [`Rasterizer::render`] draws, 64 megapixels: the canvas, the image and the PNG
+/// encoder's buffers each hold a copy, so several hundred MB at this size already.
+pub const MAX_PIXELS: usize = 8192 * 8192;
+
+/// Software renderer for egui's tessellated output, to draw the UI into an image of any size
+/// without a window or GPU, e.g. for exporting figures.
+#[derive(Default)]
+pub struct Rasterizer {
+    textures: HashMap<TextureId, ColorImage>,
+}
+
+impl Rasterizer {
+    /// Applies the texture changes of one pass, such as the font atlas growing.
+    pub fn update_textures(&mut self, delta: &TexturesDelta) {
+        for (id, delta) in &delta.set {
+            let image = match &delta.image {
+                ImageData::Color(image) => (**image).clone(),
+                ImageData::Font(font) => ColorImage {
+                    size: font.size,
+                    pixels: font.srgba_pixels(None).collect(),
+                },
+            };
+            match (delta.pos, self.textures.get_mut(id)) {
+                (Some([x, y]), Some(texture)) => {
+                    let [width, height] = image.size;
+                    for row in 0..height {
+                        let start = (y + row) * texture.size[0] + x;
+                        texture.pixels[start..start + width]
+                            .copy_from_slice(&image.pixels[row * width..(row + 1) * width]);
+                    }
+                }
+                _ => {
+                    self.textures.insert(*id, image);
+                }
+            }
+        }
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    /// Draws `primitives`, tessellated at `pixels_per_point`, over `background`. Fails for
+    /// images of more than [`MAX_PIXELS`].
+    pub fn render(
+        &self,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+        size: [usize; 2],
+        background: Color32,
+    ) -> Result<ColorImage, String> {
+        let pixels = size[0]
+            .checked_mul(size[1])
+            .filter(|&pixels| pixels <= MAX_PIXELS);
+        let Some(pixels) = pixels else {
+            return Err(format!(
+                "{} × {} pixels is more than the {} megapixels that can be drawn",
+                size[0],
+                size[1],
+                MAX_PIXELS >> 20
+            ));
+        };
+        let mut canvas = Canvas {
+            size,
+            pixels: vec![background.to_array(); pixels],
+        };
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let texture = self.textures.get(&mesh.texture_id);
+            let clip = primitive.clip_rect;
+            let clip = [
+                clip.min.x * pixels_per_point,
+                clip.min.y * pixels_per_point,
+                clip.max.x * pixels_per_point,
+                clip.max.y * pixels_per_point,
+            ];
+            for triangle in mesh.indices.chunks_exact(3) {
+                let vertices = [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]);
+                canvas.fill_triangle(vertices, pixels_per_point, clip, texture);
+            }
+        }
+        Ok(ColorImage {
+            size,
+            pixels: canvas
+                .pixels
+                .iter()
+                .map(|&[r, g, b, a]| Color32::from_rgba_premultiplied(r, g, b, a))
+                .collect(),
+        })
+    }
+}
+
+/// Premultiplied RGBA bytes, blended in gamma space like egui's own renderers into an 8-bit
+/// framebuffer.
+struct Canvas {
+    size: [usize; 2],
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Canvas {
+    fn fill_triangle(
+        &mut self,
+        vertices: [&Vertex; 3],
+        pixels_per_point: f32,
+        clip: [f32; 4],
+        texture: Option<&ColorImage>,
+    ) {
+        let [a, b, c] =
+            vertices.map(|v| Pos2::new(v.pos.x * pixels_per_point, v.pos.y * pixels_per_point));
+        let area = edge(a, b, c);
+        if area.abs() < 1e-6 {
+            return;
+        }
+        let [width, height] = self.size;
+        let x0 = a.x.min(b.x).min(c.x).max(clip[0]).max(0.0).floor() as usize;
+        let y0 = a.y.min(b.y).min(c.y).max(clip[1]).max(0.0).floor() as usize;
+        let x1 = (a.x.max(b.x).max(c.x).min(clip[2]).ceil().max(0.0) as usize).min(width);
+        let y1 = (a.y.max(b.y).max(c.y).min(clip[3]).ceil().max(0.0) as usize).min(height);
+        let colors = vertices.map(|v| rgba(v.color));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                // barycentric weights, all positive inside the triangle for either winding
+                let wa = edge(b, c, p) / area;
+                let wb = edge(c, a, p) / area;
+                let wc = 1.0 - wa - wb;
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                let mut src = [0.0; 4];
+                for (channel, value) in src.iter_mut().enumerate() {
+                    *value =
+                        wa * colors[0][channel] + wb * colors[1][channel] + wc * colors[2][channel];
+                }
+                if let Some(texture) = texture {
+                    let u = wa * vertices[0].uv.x + wb * vertices[1].uv.x + wc * vertices[2].uv.x;
+                    let v = wa * vertices[0].uv.y + wb * vertices[1].uv.y + wc * vertices[2].uv.y;
+                    let texel = sample(texture, u, v);
+                    for (value, texel) in src.iter_mut().zip(texel) {
+                        *value *= texel;
+                    }
+                }
+                let dst = &mut self.pixels[y * width + x];
+                for (dst, src_value) in dst.iter_mut().zip(src) {
+                    *dst = byte(src_value + *dst as f32 / 255.0 * (1.0 - src[3]));
+                }
+            }
+        }
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`.
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Bilinear sample of `texture` at normalized coordinates.
+fn sample(texture: &ColorImage, u: f32, v: f32) -> [f32; 4] {
+    let [width, height] = texture.size;
+    if width == 0 || height == 0 {
+        return [1.0; 4];
+    }
+    let x = (u * width as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+    let at = |x: usize, y: usize| rgba(texture.pixels[y * width + x]);
+    let (p00, p10, p01, p11) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+    let mut texel = [0.0; 4];
+    for (channel, value) in texel.iter_mut().enumerate() {
+        let top = p00[channel] + tx * (p10[channel] - p00[channel]);
+        let bottom = p01[channel] + tx * (p11[channel] - p01[channel]);
+        *value = top + ty * (bottom - top);
+    }
+    texel
+}
+
+fn rgba(color: Color32) -> [f32; 4] {
+    color.to_array().map(|c| c as f32 / 255.0)
+}
+
+/// Channel value from 0 to 1 as a byte.
+fn byte(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{Mesh, Rect};
+
+    /// `rects` filled with their colors, in order, on a 10 × 8 black image, clipped to `clip`.
+    fn draw(rects: &[(Rect, Color32)], clip: Rect, pixels_per_point: f32) -> ColorImage {
+        let mut mesh = Mesh::default();
+        for &(rect, color) in rects {
+            mesh.add_colored_rect(rect, color);
+        }
+        let primitive = ClippedPrimitive {
+            clip_rect: clip,
+            primitive: Primitive::Mesh(mesh),
+        };
+        Rasterizer::default()
+            .render(&[primitive], pixels_per_point, [10, 8], Color32::BLACK)
+            .unwrap()
+    }
+
+    fn pixel(image: &ColorImage, x: usize, y: usize) -> Color32 {
+        image.pixels[y * image.size[0] + x]
+    }
+
+    #[test]
+    fn rectangles_cover_exactly_their_pixels() {
+        let rect = Rect::from_min_max(Pos2::new(2.0, 1.0), Pos2::new(6.0, 4.0));
+        let image = draw(&[(rect, Color32::RED)], Rect::EVERYTHING, 1.0);
+        assert_eq!(image.size, [10, 8]);
+        for (x, y) in [(2, 1), (5, 1), (2, 3), (5, 3)] {
+            assert_eq!(pixel(&image, x, y), Color32::RED, "({}, {})", x, y);
+        }
+        for (x, y) in [(1, 1), (6, 1), (2, 0), (2, 4), (9, 7)] {
+            assert_eq!(pixel(&image, x, y), Color32::BLACK, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn pixels_per_point_scale_and_clipping_cuts() {
+        let rect = Rect::from_min_max(Pos2::new(1.0, 1.0), Pos2::new(3.0, 3.0));
+        let clip = Rect::from_min_max(Pos2::ZERO, Pos2::new(2.0, 10.0));
+        let image = draw(&[(rect, Color32::GREEN)], clip, 2.0);
+        // 2 to 6 pixels after scaling, cut off at 4 by the clip rectangle
+        assert_eq!(pixel(&image, 2, 2), Color32::GREEN);
+        assert_eq!(pixel(&image, 3, 5), Color32::GREEN);
+        assert_eq!(pixel(&image, 4, 2), Color32::BLACK);
+        assert_eq!(pixel(&image, 1, 1), Color32::BLACK);
+    }
+
+    #[test]
+    fn translucent_colors_blend_over_what_is_below() {
+        let rect = Rect::from_min_max(Pos2::ZERO, Pos2::new(10.0, 8.0));
+        let half_white = Color32::from_rgba_premultiplied(128, 128, 128, 128);
+        let image = draw(
+            &[(rect, Color32::BLUE), (rect, half_white)],
+            Rect::EVERYTHING,
+            1.0,
+        );
+        assert_eq!(
+            pixel(&image, 4, 4),
+            Color32::from_rgba_premultiplied(128, 128, 255, 255)
+        );
+    }
+
+    #[test]
+    fn images_beyond_the_pixel_limit_are_refused() {
+        let result = Rasterizer::default().render(&[], 1.0, [MAX_PIXELS / 2, 3], Color32::BLACK);
+        assert!(result.unwrap_err().contains("megapixels"));
+        assert!(Rasterizer::default()
+            .render(&[], 1.0, [usize::MAX, 2], Color32::BLACK)
+            .is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::fft::mirrored;
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Peak of a resynthesized signal in the i16 range, -1 dBFS.
+const OUTPUT_PEAK: f32 = 0.891 * 32_768.0;
+
+/// Phase given to a magnitude spectrum turned back into a signal by [`resynthesize`]. The
+/// spectrum only says how strong each frequency is, so the phase has to be made up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Phase {
+    /// Every bin in phase, a pulse symmetric around the middle of the output.
+    Zero,
+    /// The causal signal with the least delay for the magnitudes, built from the cepstrum, so
+    /// the energy comes at the start like in a real impulse response.
+    #[default]
+    Minimum,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 2] = [Phase::Zero, Phase::Minimum];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Zero => "Zero phase",
+            Phase::Minimum => "Minimum phase",
+        }
+    }
+}
+
+/// Signal with the single-sided `magnitudes`, as if from an even-length FFT, and a synthesized
+/// `phase`. As long as that FFT and scaled to peak at -1 dBFS of the i16 range.
+pub fn resynthesize(magnitudes: &[f32], phase: Phase) -> Vec<f32> {
+    // below that the log of the minimum phase construction would dominate
+    const FLOOR: f64 = 1e-6;
+    let magnitudes: Vec<f64> = magnitudes.iter().map(|&m| m as f64).collect();
+    let n = 2 * magnitudes.len().saturating_sub(1);
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut planner = FftPlanner::new();
+    let (forward, inverse) = (planner.plan_fft_forward(n), planner.plan_fft_inverse(n));
+    let mut samples: Vec<f64> = match phase {
+        Phase::Zero => {
+            let mut buffer = mirrored(&magnitudes);
+            inverse.process(&mut buffer);
+            let mut samples: Vec<f64> = buffer.iter().map(|c| c.re).collect();
+            samples.rotate_right(n / 2);
+            samples
+        }
+        Phase::Minimum => {
+            let floor = magnitudes
+                .iter()
+                .fold(0.0_f64, |max, &m| max.max(m))
+                .max(f64::MIN_POSITIVE)
+                * FLOOR;
+            let log: Vec<f64> = magnitudes.iter().map(|m| m.max(floor).ln()).collect();
+            let mut cepstrum = mirrored(&log);
+            inverse.process(&mut cepstrum);
+            // folding the anti-causal half onto the causal one gives the minimum phase cepstrum
+            for (i, c) in cepstrum.iter_mut().enumerate() {
+                let fold = match i {
+                    0 => 1.0,
+                    i if i < n / 2 => 2.0,
+                    i if i == n / 2 => 1.0,
+                    _ => 0.0,
+                };
+                *c *= fold / n as f64;
+            }
+            forward.process(&mut cepstrum);
+            let mut spectrum: Vec<Complex<f64>> = cepstrum.iter().map(|c| c.exp()).collect();
+            inverse.process(&mut spectrum);
+            spectrum.iter().map(|c| c.re).collect()
+        }
+    };
+    let peak = samples.iter().fold(0.0_f64, |max, s| max.max(s.abs()));
+    if peak > 0.0 {
+        samples
+            .iter_mut()
+            .for_each(|s| *s *= OUTPUT_PEAK as f64 / peak);
+    }
+    samples.into_iter().map(|s| s as f32).collect()
+}
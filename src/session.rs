@@ -0,0 +1,278 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ilena_analysis::wav::{self, Channel, RawFormat, SampleType};
+use ilena_analysis::window::Window;
+use ilena_analysis::PlotData;
+use ron::extensions::Extensions;
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A loaded file as it was analysed. The samples aren't saved, so a session opens without the
+/// audio; files still found where they were can be re-analysed as well.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SessionPlot {
+    pub file_name: String,
+    pub source: String,
+    pub freqs: Vec<f32>,
+    pub amplitudes: Vec<f32>,
+    pub path: Option<PathBuf>,
+    pub sample_rate: Option<u32>,
+    pub header_sample_rate: Option<u32>,
+    pub channel: Channel,
+    pub raw_format: Option<RawFormat>,
+    pub pitch_scale: f32,
+    pub opacity: f32,
+    pub decoded_len: usize,
+    pub offset: usize,
+    pub fft_size: usize,
+    pub window: Option<Window>,
+    pub label: Option<String>,
+}
+
+impl From<&PlotData> for SessionPlot {
+    fn from(plot_data: &PlotData) -> Self {
+        Self {
+            file_name: plot_data.file_name.clone(),
+            source: plot_data.source.clone(),
+            freqs: plot_data.freqs.clone(),
+            amplitudes: plot_data.amplitudes.clone(),
+            path: plot_data.path.clone(),
+            sample_rate: plot_data.sample_rate,
+            header_sample_rate: plot_data.header_sample_rate,
+            channel: plot_data.channel,
+            raw_format: plot_data.raw_format,
+            pitch_scale: plot_data.pitch_scale,
+            opacity: plot_data.opacity,
+            decoded_len: plot_data.decoded_len,
+            offset: plot_data.offset,
+            fft_size: plot_data.timings.fft_size,
+            window: plot_data.window,
+            label: plot_data.label.clone(),
+        }
+    }
+}
+
+impl SessionPlot {
+    /// Without a file to read the samples from, e.g. one piped to stdin or moved since, the plot
+    /// is a fixed spectrum like one loaded from a `.f` file.
+    pub fn into_plot_data(self) -> PlotData {
+        let readable = self.path.as_deref().is_some_and(Path::exists);
+        let mut plot_data = PlotData {
+            freqs: self.freqs,
+            amplitudes: self.amplitudes,
+            file_name: self.file_name,
+            sample_rate: self.sample_rate.filter(|_| readable),
+            header_sample_rate: self.header_sample_rate,
+            pitch_scale: self.pitch_scale,
+            opacity: self.opacity,
+            decoded_len: self.decoded_len,
+            offset: self.offset,
+            source: self.source,
+            path: self.path,
+            channel: self.channel,
+            raw_format: self.raw_format,
+            window: self.window,
+            label: self.label,
+            ..Default::default()
+        };
+        plot_data.timings.fft_size = self.fft_size;
+        plot_data
+    }
+}
+
+/// Everything needed to pick an analysis up again as one file: the GUI's settings, what only the
+/// command line sets, and every plot with how it's shown.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    /// The persisted state of the GUI as RON, like eframe stores it, so that sessions saved before
+    /// a setting was added still open with its default.
+    pub settings: String,
+    pub sample_type: Option<SampleType>,
+    pub plots: Vec<SessionPlot>,
+    /// File names of the plots hidden from the chart and of the soloed one.
+    pub hidden: HashSet<String>,
+    pub solo: Option<String>,
+    pub active_plot: usize,
+}
+
+/// Compressed like a `.f.gz` spectrum, since the plots make up almost all of it.
+pub fn write(path: &Path, session: &Session) -> Result<(), String> {
+    let file = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    bincode::serialize_into(&mut encoder, session)
+        .and_then(|_| Ok(encoder.finish()?.flush()?))
+        .map_err(|e| e.to_string())
+}
+
+pub fn read(path: &Path) -> Result<Session, String> {
+    let file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    bincode::deserialize_from(GzDecoder::new(file))
+        .map_err(|e| format!("Not a session file: {}", e))
+}
+
+/// A file of a [`RonSession`] and how its plot is shown, without the spectrum, which is computed
+/// again when the session is opened. Anything but the path may be left out.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SessionFile {
+    pub path: PathBuf,
+    pub channel: Channel,
+    pub raw_format: Option<RawFormat>,
+    /// Rate the samples are treated as recorded at instead of the header's.
+    pub sample_rate: Option<u32>,
+    pub offset: usize,
+    pub window: Option<Window>,
+    pub label: Option<String>,
+    pub pitch_scale: f32,
+    pub opacity: f32,
+}
+
+impl Default for SessionFile {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            channel: Channel::default(),
+            raw_format: None,
+            sample_rate: None,
+            offset: 0,
+            window: None,
+            label: None,
+            pitch_scale: 1.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl SessionFile {
+    /// `None` for plots without a file to load again, like one piped to stdin or a frozen frame.
+    pub fn new(plot_data: &PlotData) -> Option<Self> {
+        Some(Self {
+            path: plot_data.path.clone()?,
+            channel: plot_data.channel,
+            raw_format: plot_data.raw_format,
+            sample_rate: plot_data.header_sample_rate.and(plot_data.sample_rate),
+            offset: plot_data.offset,
+            window: plot_data.window,
+            label: plot_data.label.clone(),
+            pitch_scale: plot_data.pitch_scale,
+            opacity: plot_data.opacity,
+        })
+    }
+
+    /// Sets up `plot_data`, loaded from [`Self::path`], the way it was shown. The offset, window
+    /// and sample rate only take effect with the next [`PlotData::reanalyze`].
+    pub fn apply(&self, plot_data: &mut PlotData) {
+        plot_data.override_sample_rate(self.sample_rate);
+        plot_data.set_pitch_scale(self.pitch_scale);
+        plot_data.offset = self.offset;
+        plot_data.window = self.window;
+        plot_data.label = self.label.clone();
+        plot_data.opacity = self.opacity;
+    }
+
+    /// Whether the plot has to be analysed again after [`Self::apply`].
+    pub fn changes_analysis(&self) -> bool {
+        self.offset > 0 || self.window.is_some() || self.sample_rate.is_some()
+    }
+}
+
+/// A session as readable RON, to diff, version and edit by hand: the GUI's settings as such
+/// rather than a string, and the files instead of their spectra. `S` is the GUI's state.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RonSession<S> {
+    pub settings: S,
+    pub sample_type: Option<SampleType>,
+    pub files: Vec<SessionFile>,
+    pub hidden: HashSet<String>,
+    pub solo: Option<String>,
+    pub active_plot: usize,
+}
+
+/// Whether `path` is a [`RonSession`] rather than a compressed [`Session`].
+pub fn is_ron(path: &Path) -> bool {
+    wav::has_extension(path, "ron")
+}
+
+/// Pretty-printed, with `Some(..)` around optional values left out like in `--config` files.
+pub fn write_ron<S: Serialize>(path: &Path, session: &RonSession<S>) -> Result<(), String> {
+    let pretty = PrettyConfig::new().extensions(Extensions::IMPLICIT_SOME);
+    let text = ron::ser::to_string_pretty(session, pretty).map_err(|e| e.to_string())?;
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+pub fn read_ron<S: DeserializeOwned + Default>(path: &Path) -> Result<RonSession<S>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    ron::Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .from_str(&text)
+        .map_err(|e| format!("Not a session file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_read_back_as_written() {
+        let plot_data = PlotData {
+            freqs: vec![0.0, 10.0, 20.0],
+            amplitudes: vec![1.0, 0.5, 0.25],
+            file_name: "moved.wav".to_string(),
+            path: Some(PathBuf::from("/nonexistent/moved.wav")),
+            sample_rate: Some(48_000),
+            label: Some("take 2".to_string()),
+            opacity: 0.5,
+            offset: 120,
+            ..Default::default()
+        };
+        let session = Session {
+            settings: "(min_freq: 20.0)".to_string(),
+            sample_type: None,
+            plots: vec![SessionPlot::from(&plot_data)],
+            hidden: HashSet::from(["moved.wav".to_string()]),
+            solo: None,
+            active_plot: 0,
+        };
+        let path = std::env::temp_dir().join(format!(
+            "ilena_analysis_session_{}.session",
+            std::process::id()
+        ));
+        write(&path, &session).unwrap();
+        let read_back = read(&path);
+        std::fs::remove_file(&path).unwrap();
+        let read_back = read_back.unwrap();
+        assert_eq!(read_back.settings, session.settings);
+        assert_eq!(read_back.hidden, session.hidden);
+        let plot = read_back.plots.into_iter().next().unwrap().into_plot_data();
+        assert_eq!(
+            (plot.freqs, plot.amplitudes),
+            (plot_data.freqs, plot_data.amplitudes)
+        );
+        assert_eq!(
+            (plot.file_name, plot.label),
+            (plot_data.file_name, plot_data.label)
+        );
+        assert_eq!((plot.opacity, plot.offset), (0.5, 120));
+        // the file is gone, so the plot is a fixed spectrum that can't be analysed again
+        assert_eq!(plot.sample_rate, None);
+    }
+
+    #[test]
+    fn other_files_are_not_sessions() {
+        let path = std::env::temp_dir().join(format!(
+            "ilena_analysis_not_session_{}.session",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"RIFF0000WAVE").unwrap();
+        let result = read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.unwrap_err().starts_with("Not a session file"));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::colormap::ColorMap;
+use crate::db_difference;
+use crate::fft::{Fft, Precision};
+use crate::welch::Welch;
+use crate::window::Window;
+use egui::ColorImage;
+
+/// Short-time Fourier transform magnitudes, one frame per segment.
+#[derive(Debug, Clone)]
+pub struct Spectrogram {
+    /// Time between the starts of consecutive frames.
+    pub frame_seconds: f32,
+    /// Width of a frequency bin.
+    pub bin_hz: f32,
+    pub frames: Vec<Vec<f32>>,
+}
+
+impl Spectrogram {
+    /// Segments the signal the same way Welch averaging does, keeping each segment's spectrum.
+    pub fn new(
+        samples: &[f32],
+        sample_rate: u32,
+        window: Window,
+        segments: Welch,
+        precision: Precision,
+    ) -> Result<Self, String> {
+        let n = segments.segment_len;
+        let fft = Fft::new(n, precision);
+        let frames = segments
+            .segments(samples.len())?
+            .into_iter()
+            .map(|segment| fft.magnitudes(&window.apply(&samples[segment])))
+            .collect();
+        Ok(Self {
+            frame_seconds: segments.hop as f32 / sample_rate as f32,
+            bin_hz: sample_rate as f32 / n as f32,
+            frames,
+        })
+    }
+
+    fn bins(&self) -> usize {
+        self.frames.first().map_or(0, Vec::len)
+    }
+
+    /// Start time of each frame in seconds.
+    pub fn times(&self) -> Vec<f32> {
+        (0..self.frames.len())
+            .map(|t| t as f32 * self.frame_seconds)
+            .collect()
+    }
+
+    /// Frequency of each bin of a frame in Hz.
+    pub fn freqs(&self) -> Vec<f32> {
+        (0..self.bins()).map(|i| i as f32 * self.bin_hz).collect()
+    }
+
+    /// Magnitude at `time` (nearest frame) and `freq` (linearly interpolated between bins).
+    fn at(&self, time: f32, freq: f32) -> f32 {
+        let frame =
+            &self.frames[((time / self.frame_seconds).round() as usize).min(self.frames.len() - 1)];
+        let pos = freq / self.bin_hz;
+        let i = (pos.floor() as usize).min(frame.len() - 1);
+        let j = (i + 1).min(frame.len() - 1);
+        let t = pos - i as f32;
+        frame[i] + t * (frame[j] - frame[i])
+    }
+}
+
+/// `a − b` in dB on the time-frequency grid of `a`, limited to the span both cover.
+#[derive(Debug, Clone)]
+pub struct DifferenceMap {
+    pub duration: f32,
+    pub max_freq: f32,
+    /// Time columns of frequency rows, lowest frequency first.
+    pub columns: Vec<Vec<f32>>,
+}
+
+impl DifferenceMap {
+    pub fn new(a: &Spectrogram, b: &Spectrogram) -> Result<Self, String> {
+        if a.frames.is_empty() || b.frames.is_empty() {
+            return Err("Both files need at least one complete segment".to_string());
+        }
+        let duration =
+            (a.frames.len() as f32 * a.frame_seconds).min(b.frames.len() as f32 * b.frame_seconds);
+        let max_freq = (a.bins() as f32 * a.bin_hz).min(b.bins() as f32 * b.bin_hz);
+        let rows = ((max_freq / a.bin_hz) as usize).min(a.bins());
+        let columns = a
+            .frames
+            .iter()
+            .enumerate()
+            .take_while(|&(t, _)| (t as f32) * a.frame_seconds < duration)
+            .map(|(t, frame)| {
+                let time = t as f32 * a.frame_seconds;
+                (0..rows)
+                    .map(|i| db_difference(frame[i], b.at(time, i as f32 * a.bin_hz)))
+                    .collect()
+            })
+            .collect();
+        Ok(Self {
+            duration,
+            max_freq: rows as f32 * a.bin_hz,
+            columns,
+        })
+    }
+
+    /// Colors the map from -`range_db` to +`range_db`, 0 dB at the center of `color_map`. Cells
+    /// are subsampled so neither side exceeds `max_side` pixels.
+    pub fn to_image(&self, range_db: f32, color_map: ColorMap, max_side: usize) -> ColorImage {
+        let rows = self.columns.first().map_or(0, Vec::len);
+        let column_step = self.columns.len().div_ceil(max_side).max(1);
+        let row_step = rows.div_ceil(max_side).max(1);
+        let width = self.columns.len().div_ceil(column_step);
+        let height = rows.div_ceil(row_step);
+        let mut pixels = Vec::with_capacity(width * height);
+        // image rows go top-down, so start at the highest frequency
+        for row in (0..height).rev() {
+            for column in self.columns.iter().step_by(column_step) {
+                let t = (column[row * row_step] / range_db + 1.0) / 2.0;
+                pixels.push(color_map.color(t));
+            }
+        }
+        ColorImage {
+            size: [width, height],
+            pixels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testgen;
+
+    /// 50 Hz bins and 10 ms hops at 48 kHz.
+    const SEGMENTS: Welch = Welch {
+        segment_len: 960,
+        hop: 480,
+    };
+
+    fn spectrogram(samples: &[f32]) -> Spectrogram {
+        Spectrogram::new(samples, 48_000, Window::Hann, SEGMENTS, Precision::F32).unwrap()
+    }
+
+    #[test]
+    fn frames_follow_a_tone_that_changes_pitch() {
+        let mut samples = testgen::sine(1000.0, 8192.0, 48_000, 24_000);
+        samples.extend(testgen::sine(3000.0, 8192.0, 48_000, 24_000));
+        let spectrogram = spectrogram(&samples);
+        assert_eq!(
+            (
+                spectrogram.frames.len(),
+                spectrogram.bin_hz,
+                spectrogram.frame_seconds
+            ),
+            (99, 50.0, 0.01)
+        );
+        let loudest = |frame: &Vec<f32>| {
+            let bin = (0..frame.len())
+                .max_by(|&a, &b| frame[a].total_cmp(&frame[b]))
+                .unwrap();
+            spectrogram.freqs()[bin]
+        };
+        assert_eq!(loudest(&spectrogram.frames[10]), 1000.0);
+        assert_eq!(loudest(&spectrogram.frames[80]), 3000.0);
+        assert!((spectrogram.times()[80] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn difference_of_a_signal_and_its_double_is_minus_6_db_everywhere() {
+        let noise = testgen::white_noise(8192.0, 9600, 1);
+        let doubled: Vec<f32> = noise.iter().map(|s| 2.0 * s).collect();
+        let map = DifferenceMap::new(&spectrogram(&noise), &spectrogram(&doubled)).unwrap();
+        assert_eq!((map.columns.len(), map.columns[0].len()), (19, 481));
+        let expected = -20.0 * 2.0_f32.log10();
+        assert!(map
+            .columns
+            .iter()
+            .flatten()
+            .all(|&db| (db - expected).abs() < 1e-3));
+        let empty = Spectrogram {
+            frames: Vec::new(),
+            ..spectrogram(&noise)
+        };
+        assert!(DifferenceMap::new(&spectrogram(&noise), &empty).is_err());
+    }
+
+    #[test]
+    fn images_are_subsampled_to_fit_and_put_high_frequencies_on_top() {
+        // 10 columns of 6 rows, each row as many dB as it's high
+        let map = DifferenceMap {
+            duration: 1.0,
+            max_freq: 600.0,
+            columns: vec![(0..6).map(|row| row as f32).collect(); 10],
+        };
+        let image = map.to_image(4.0, ColorMap::Grayscale, 4);
+        // every third column and second row
+        assert_eq!(image.size, [4, 3]);
+        assert_eq!(
+            image.pixels[0],
+            ColorMap::Grayscale.color((4.0 / 4.0 + 1.0) / 2.0)
+        );
+        assert_eq!(image.pixels[11], ColorMap::Grayscale.color(0.5));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::fft::{self, Fft, Precision};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::wav::{Channel, RawFormat};
+use crate::welch::Welch;
+use crate::window::Window;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{self, Receiver};
+
+/// Rolling spectrum of a stream of samples arriving in blocks of any size, e.g. from a sound
+/// card or a file read piece by piece. Each frame is the last `segment_len` samples: the
+/// `segment_len - hop` of them already seen are saved from the previous frame, so every `hop`
+/// new samples cost one FFT and nothing is read twice.
+pub struct StreamingSpectrum {
+    fft: Fft,
+    window: Window,
+    segments: Welch,
+    sample_rate: u32,
+    /// The frame being filled, oldest sample first.
+    frame: Vec<f32>,
+    /// Samples arrived since the last transform.
+    pending: usize,
+    /// How much of the previous spectrum each new frame keeps, from 0 for none to below 1.
+    smoothing: f32,
+    amplitudes: Vec<f32>,
+    frames: usize,
+}
+
+impl StreamingSpectrum {
+    pub fn new(
+        sample_rate: u32,
+        window: Window,
+        segments: Welch,
+        precision: Precision,
+        smoothing: f32,
+    ) -> Result<Self, String> {
+        // only the segment length has to fit, the stream itself is unbounded
+        segments.validate(segments.segment_len)?;
+        Ok(Self {
+            fft: Fft::new(segments.segment_len, precision),
+            window,
+            segments,
+            sample_rate,
+            frame: Vec::with_capacity(segments.segment_len),
+            pending: 0,
+            smoothing: smoothing.clamp(0.0, 0.99),
+            amplitudes: Vec::new(),
+            frames: 0,
+        })
+    }
+
+    /// Appends `samples` to the stream, transforming once for each `hop` completed. Returns
+    /// whether the spectrum changed.
+    pub fn push(&mut self, samples: &[f32]) -> bool {
+        let (len, hop) = (self.segments.segment_len, self.segments.hop);
+        let frames = self.frames;
+        let mut rest = samples;
+        while !rest.is_empty() {
+            // until the first frame is full every sample is new
+            let needed = if self.frame.len() < len {
+                len - self.frame.len()
+            } else {
+                hop - self.pending
+            };
+            let (now, later) = rest.split_at(needed.min(rest.len()));
+            self.frame.extend_from_slice(now);
+            self.pending += now.len();
+            rest = later;
+            if self.frame.len() > len {
+                self.frame.drain(..self.frame.len() - len);
+            }
+            if self.frame.len() == len && self.pending >= hop {
+                self.transform();
+            }
+        }
+        self.frames != frames
+    }
+
+    fn transform(&mut self) {
+        let gain = self.window.coherent_gain(self.segments.segment_len);
+        let magnitudes = self.fft.magnitudes(&self.window.apply(&self.frame));
+        if self.amplitudes.len() != magnitudes.len() {
+            self.amplitudes = magnitudes.iter().map(|m| m / gain).collect();
+        } else {
+            let keep = self.smoothing;
+            for (amp, m) in self.amplitudes.iter_mut().zip(magnitudes) {
+                *amp = keep * *amp + (1.0 - keep) * m / gain;
+            }
+        }
+        self.pending = 0;
+        self.frames += 1;
+    }
+
+    /// Frequencies of the bins of [`Self::amplitudes`].
+    pub fn freqs(&self) -> Vec<f32> {
+        fft::bin_freqs(self.segments.segment_len, self.sample_rate, false)
+    }
+
+    /// Spectrum of the latest frame, with earlier ones mixed in by the smoothing and corrected
+    /// for the window's coherent gain; empty until `segment_len` samples have arrived.
+    pub fn amplitudes(&self) -> &[f32] {
+        &self.amplitudes
+    }
+
+    /// Frames transformed so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Samples from the end of the latest frame to the end of the stream so far.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+/// Reads headerless PCM laid out as `format` from `reader` on a background thread, e.g. piped
+/// from a capture tool like `arecord`, and sends `channel` of it in blocks of `block_frames`
+/// frames. Sends an error and stops if reading fails; stops quietly at the end of the input or
+/// once the receiver is dropped.
+///
+/// `--live --raw` captures this way, from e.g. `arecord -D hw:1 -f S16_LE -r 48000` piped in, for
+/// inputs cpal can't open or builds without the `live` feature; otherwise `--live` opens the sound
+/// card itself, see `capture::spawn`. Not on wasm, where there's neither stdin nor threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn_raw_capture(
+    mut reader: impl Read + Send + 'static,
+    format: RawFormat,
+    channel: Channel,
+    block_frames: usize,
+) -> Receiver<Result<Vec<f32>, String>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut block = vec![0; block_frames.max(1) * format.frame_bytes()];
+        loop {
+            // pipes deliver in pieces of any size, a block is only complete once full
+            let samples = match reader.read_exact(&mut block) {
+                Ok(()) => channel.extract(&format.decode(&block), format.channels),
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return,
+                Err(e) => Err(e.to_string()),
+            };
+            let failed = samples.is_err();
+            if sender.send(samples).is_err() || failed {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testgen;
+
+    #[test]
+    fn blocks_of_uneven_sizes_give_the_spectrum_of_the_last_frame() {
+        let segments = Welch {
+            segment_len: 1024,
+            hop: 256,
+        };
+        let signal = testgen::sines(&[(1000.0, 0.5), (3000.0, 0.1)], 48_000, 1024 + 20 * 256);
+        let mut stream =
+            StreamingSpectrum::new(48_000, Window::Hann, segments, Precision::F64, 0.0).unwrap();
+        let mut rest = signal.as_slice();
+        for size in [1, 700, 3, 255, 256, 1500].into_iter().cycle() {
+            let (block, later) = rest.split_at(size.min(rest.len()));
+            stream.push(block);
+            rest = later;
+            if rest.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(stream.frames(), 21);
+        assert_eq!(stream.pending(), 0);
+
+        let frame = &signal[signal.len() - 1024..];
+        let gain = Window::Hann.coherent_gain(1024);
+        let magnitudes = Fft::new(1024, Precision::F64).magnitudes(&Window::Hann.apply(frame));
+        assert_eq!(stream.amplitudes().len(), magnitudes.len());
+        for (amp, magnitude) in stream.amplitudes().iter().zip(magnitudes) {
+            let expected = magnitude / gain;
+            assert!(
+                (amp - expected).abs() <= 1e-6 * (1.0 + expected),
+                "{} instead of {}",
+                amp,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn push_reports_each_new_frame() {
+        let segments = Welch {
+            segment_len: 8,
+            hop: 4,
+        };
+        let mut stream =
+            StreamingSpectrum::new(8000, Window::Rectangular, segments, Precision::F32, 0.5)
+                .unwrap();
+        assert!(!stream.push(&[0.0; 7]));
+        assert!(stream.amplitudes().is_empty());
+        assert!(stream.push(&[0.0]));
+        assert!(!stream.push(&[0.0; 3]));
+        assert!(stream.push(&[0.0; 9]));
+        assert_eq!((stream.frames(), stream.pending()), (4, 0));
+        assert_eq!(stream.amplitudes().len(), stream.freqs().len());
+    }
+}
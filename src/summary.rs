@@ -0,0 +1,175 @@
+use crate::wav::Channel;
+use crate::{metrics, wav, AnalysisOptions, PlotData};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum SummaryFormat {
+    /// Tab separated, one header row followed by one row per file.
+    Tsv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl SummaryFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SummaryFormat::Tsv => "tsv",
+            SummaryFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// One-line description of an analysed file. Fields that can't be known for `.f` spectra are
+/// `None` (empty in TSV, `null` in JSON).
+#[derive(Debug, serde::Serialize)]
+pub struct Summary<'a> {
+    pub file: &'a str,
+    pub duration_s: Option<f32>,
+    pub sample_rate: Option<u32>,
+    pub peak_frequency_hz: Option<f32>,
+    pub rms: Option<f32>,
+    pub centroid_hz: Option<f32>,
+}
+
+impl<'a> Summary<'a> {
+    /// The duration is read from the header, at the overridden sample rate if there is one.
+    /// Without a file, like from stdin, it's counted from the cached samples if they're a single
+    /// channel; interleaved ones hold every channel.
+    pub fn new(plot_data: &'a PlotData) -> Self {
+        let header_duration = match plot_data.raw_format {
+            Some(format) => format.duration(plot_data.audio_path()).ok(),
+            None => wav::duration(plot_data.audio_path()).ok(),
+        };
+        let duration_s = plot_data.sample_rate.and_then(|sample_rate| {
+            let header_rate = plot_data.header_sample_rate.unwrap_or(sample_rate);
+            let scale = header_rate as f32 / sample_rate as f32;
+            let counted = match (&plot_data.samples, plot_data.channel) {
+                (Some(_), Channel::Interleaved) | (None, _) => None,
+                (Some(samples), _) => Some(samples.len() as f32 / sample_rate as f32),
+            };
+            header_duration.map(|duration| duration * scale).or(counted)
+        });
+        Self {
+            file: &plot_data.file_name,
+            duration_s,
+            sample_rate: plot_data.sample_rate,
+            peak_frequency_hz: metrics::peak_frequency(&plot_data.freqs, &plot_data.amplitudes),
+            rms: plot_data.level_stats.map(|stats| stats.rms),
+            centroid_hz: metrics::SpectralFeatures::new(&plot_data.freqs, &plot_data.amplitudes)
+                .map(|features| features.centroid),
+        }
+    }
+}
+
+pub const TSV_HEADER: &str = "file\tduration_s\tsample_rate\tpeak_frequency_hz\trms\tcentroid_hz";
+
+/// Prints the summary of every plot to stdout.
+pub fn print_summaries(plots: &[PlotData], format: SummaryFormat) -> Result<(), String> {
+    write_summaries(&mut std::io::stdout().lock(), plots, format)
+}
+
+/// Writes the summary of every plot to `writer`, as [`print_summaries`] prints it.
+pub fn write_summaries(
+    writer: &mut impl Write,
+    plots: &[PlotData],
+    format: SummaryFormat,
+) -> Result<(), String> {
+    if format == SummaryFormat::Tsv {
+        writeln!(writer, "{}", TSV_HEADER).map_err(|e| e.to_string())?;
+    }
+    for plot_data in plots {
+        let summary = Summary::new(plot_data);
+        match format {
+            SummaryFormat::Tsv => writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                summary.file,
+                field(summary.duration_s),
+                field(summary.sample_rate),
+                field(summary.peak_frequency_hz),
+                field(summary.rms),
+                field(summary.centroid_hz)
+            ),
+            SummaryFormat::JsonLines => {
+                let line = serde_json::to_string(&summary).map_err(|e| e.to_string())?;
+                writeln!(writer, "{}", line)
+            }
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Prints load and FFT timings of every plot to stdout as TSV.
+pub fn print_timings(plots: &[PlotData]) {
+    println!("file\tload_ms\tfft_ms\tsamples\tfft_size");
+    for plot_data in plots {
+        let timings = plot_data.timings;
+        println!(
+            "{}\t{:.3}\t{:.3}\t{}\t{}",
+            plot_data.file_name,
+            timings.load.as_secs_f64() * 1000.0,
+            timings.fft.as_secs_f64() * 1000.0,
+            timings.sample_count,
+            timings.fft_size
+        );
+    }
+}
+
+/// A file that couldn't be loaded, in a [`RunLog`].
+#[derive(Debug, serde::Serialize)]
+pub struct Failure<'a> {
+    pub file: &'a str,
+    pub error: &'a str,
+}
+
+/// One line of the `--log` file: what a run analysed, how, and what came out.
+#[derive(Debug, serde::Serialize)]
+pub struct RunLog<'a> {
+    /// Seconds since the Unix epoch.
+    pub timestamp: f64,
+    pub inputs: &'a [String],
+    pub options: AnalysisOptions,
+    pub files: Vec<Summary<'a>>,
+    pub failures: Vec<Failure<'a>>,
+}
+
+/// Appends a [`RunLog`] line for `plots` and `failures` (file, error) to the JSON lines file at
+/// `path`, creating it if needed, so measurement sessions leave an audit trail.
+pub fn append_run_log(
+    path: &Path,
+    inputs: &[String],
+    options: AnalysisOptions,
+    plots: &[PlotData],
+    failures: &[(String, String)],
+) -> Result<(), String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64());
+    let log = RunLog {
+        timestamp,
+        inputs,
+        options,
+        files: plots.iter().map(Summary::new).collect(),
+        failures: failures
+            .iter()
+            .map(|(file, error)| Failure { file, error })
+            .collect(),
+    };
+    let line = serde_json::to_string(&log).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    // one write, so runs appending at the same time don't interleave within a line
+    file.write_all(format!("{}\n", line).as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn field<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
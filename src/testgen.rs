@@ -0,0 +1,315 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::f64::consts::PI;
+
+/// How a [`chirp`]'s frequency moves from start to end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sweep {
+    /// The same number of Hz every second.
+    Linear,
+    /// The same number of octaves every second, so each octave gets equal time and energy.
+    Exponential,
+}
+
+/// Uniform white noise from a fixed LCG, so every run with the same `seed` gets the same samples.
+struct Lcg(u32);
+
+impl Lcg {
+    /// Between -0.5 and 0.5.
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        self.0 as f64 / u32::MAX as f64 - 0.5
+    }
+}
+
+/// `len` samples at `sample_rate` of a sine of `freq` peaking at `amplitude`, starting at phase 0.
+pub fn sine(freq: f32, amplitude: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+    sines(&[(freq, amplitude)], sample_rate, len)
+}
+
+/// Sum of sines given as frequency and peak amplitude, all starting at phase 0. A negative
+/// amplitude is a sine in antiphase.
+pub fn sines(tones: &[(f32, f32)], sample_rate: u32, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            let phase = |freq: f32| 2.0 * PI * freq as f64 * t;
+            tones
+                .iter()
+                .map(|&(freq, amp)| amp as f64 * phase(freq).sin())
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+/// Harmonics of a square wave of `freq` between ±`amplitude`: the odd multiples k of `freq`
+/// below Nyquist, each at 4 `amplitude` / (π k).
+pub fn square_harmonics(freq: f32, amplitude: f32, sample_rate: u32) -> Vec<(f32, f32)> {
+    harmonics(freq, sample_rate)
+        .filter(|k| k % 2 == 1)
+        .map(|k| {
+            (
+                k as f32 * freq,
+                (4.0 * amplitude as f64 / (PI * k as f64)) as f32,
+            )
+        })
+        .collect()
+}
+
+/// Harmonics of a rising sawtooth of `freq` between ±`amplitude`: every multiple k of `freq` below
+/// Nyquist at 2 `amplitude` / (π k), the even ones in antiphase.
+pub fn sawtooth_harmonics(freq: f32, amplitude: f32, sample_rate: u32) -> Vec<(f32, f32)> {
+    harmonics(freq, sample_rate)
+        .map(|k| {
+            let sign = if k % 2 == 1 { 1.0 } else { -1.0 };
+            (
+                k as f32 * freq,
+                (sign * 2.0 * amplitude as f64 / (PI * k as f64)) as f32,
+            )
+        })
+        .collect()
+}
+
+/// Multiples of `freq` below Nyquist, from 1.
+fn harmonics(freq: f32, sample_rate: u32) -> impl Iterator<Item = u32> {
+    let below = if freq > 0.0 {
+        (sample_rate as f64 / 2.0 / freq as f64).ceil() as u32
+    } else {
+        1
+    };
+    1..below
+}
+
+/// Square wave built from [`square_harmonics`], so its spectrum is only those and nothing aliases.
+/// Like any band-limited square it overshoots `amplitude` by about 9 % at the edges.
+pub fn square(freq: f32, amplitude: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+    sines(
+        &square_harmonics(freq, amplitude, sample_rate),
+        sample_rate,
+        len,
+    )
+}
+
+/// Sawtooth built from [`sawtooth_harmonics`], band-limited like [`square`].
+pub fn sawtooth(freq: f32, amplitude: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+    sines(
+        &sawtooth_harmonics(freq, amplitude, sample_rate),
+        sample_rate,
+        len,
+    )
+}
+
+/// Noise spread evenly between ±`amplitude`, with an RMS of `amplitude` / √3 and, on average, a
+/// flat spectrum.
+pub fn white_noise(amplitude: f32, len: usize, seed: u32) -> Vec<f32> {
+    let mut lcg = Lcg(seed);
+    (0..len)
+        .map(|_| (lcg.next() * 2.0 * amplitude as f64) as f32)
+        .collect()
+}
+
+/// Noise whose power falls by 3 dB per octave, scaled to an RMS of `rms`. Made in the frequency
+/// domain from exactly that slope with random phases, so a rectangular-windowed FFT of all `len`
+/// samples shows it without leakage or ripple; there's no DC.
+pub fn pink_noise(rms: f32, len: usize, seed: u32) -> Vec<f32> {
+    if len < 2 {
+        return vec![0.0; len];
+    }
+    let mut lcg = Lcg(seed);
+    let mut spectrum = vec![Complex::new(0.0, 0.0); len];
+    for k in 1..=len / 2 {
+        let bin = Complex::from_polar(1.0 / (k as f64).sqrt(), 2.0 * PI * lcg.next());
+        // the Nyquist bin of an even length has to be real
+        spectrum[k] = if 2 * k == len {
+            Complex::new(bin.norm(), 0.0)
+        } else {
+            bin
+        };
+        spectrum[len - k] = spectrum[k].conj();
+    }
+    FftPlanner::new()
+        .plan_fft_inverse(len)
+        .process(&mut spectrum);
+    let power = spectrum.iter().map(|c| c.re * c.re).sum::<f64>() / len as f64;
+    let scale = rms as f64 / power.sqrt().max(f64::MIN_POSITIVE);
+    spectrum.iter().map(|c| (c.re * scale) as f32).collect()
+}
+
+/// Sine of `amplitude` sweeping from `from` to `to` Hz over the `len` samples. An exponential
+/// sweep raises frequencies below 0.01 Hz to that.
+pub fn chirp(
+    from: f32,
+    to: f32,
+    amplitude: f32,
+    sample_rate: u32,
+    len: usize,
+    sweep: Sweep,
+) -> Vec<f32> {
+    let duration = len as f64 / sample_rate as f64;
+    let (from, to) = (from as f64, to as f64);
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            // the integral of the instantaneous frequency
+            let cycles = match sweep {
+                Sweep::Linear => from * t + (to - from) * t * t / (2.0 * duration),
+                Sweep::Exponential => {
+                    let (from, to) = (from.max(0.01), to.max(0.01));
+                    let rate = (to / from).ln() / duration;
+                    if rate.abs() < 1e-12 {
+                        from * t
+                    } else {
+                        from * ((rate * t).exp() - 1.0) / rate
+                    }
+                }
+            };
+            (amplitude as f64 * (2.0 * PI * cycles).sin()) as f32
+        })
+        .collect()
+}
+
+/// The signals `--generate` writes, named for their files: tones and waveforms with known
+/// harmonics at -12 dBFS of the i16 range, noise and sweeps over the audio band, `len` samples
+/// each.
+pub fn test_files(sample_rate: u32, len: usize) -> Vec<(&'static str, Vec<f32>)> {
+    const AMPLITUDE: f32 = 8192.0;
+    let top = 20_000.0_f32.min(sample_rate as f32 * 0.45);
+    vec![
+        ("sine_1k", sine(1000.0, AMPLITUDE, sample_rate, len)),
+        (
+            "two_tones",
+            sines(
+                &[(440.0, AMPLITUDE / 2.0), (1000.0, AMPLITUDE / 2.0)],
+                sample_rate,
+                len,
+            ),
+        ),
+        ("square_440", square(440.0, AMPLITUDE, sample_rate, len)),
+        ("sawtooth_440", sawtooth(440.0, AMPLITUDE, sample_rate, len)),
+        ("white_noise", white_noise(AMPLITUDE, len, 1)),
+        ("pink_noise", pink_noise(AMPLITUDE / 2.0, len, 1)),
+        (
+            "chirp_linear",
+            chirp(20.0, top, AMPLITUDE, sample_rate, len, Sweep::Linear),
+        ),
+        (
+            "chirp_exponential",
+            chirp(20.0, top, AMPLITUDE, sample_rate, len, Sweep::Exponential),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft::{Fft, Precision};
+    use crate::metrics::rms;
+
+    /// Peak amplitude of the sinusoid in each bin of a rectangular FFT of all of `samples`.
+    fn amplitudes(samples: &[f32]) -> Vec<f32> {
+        let magnitudes = Fft::new(samples.len(), Precision::F64).magnitudes(samples);
+        magnitudes
+            .iter()
+            .map(|m| 2.0 * m / samples.len() as f32)
+            .collect()
+    }
+
+    /// Frequency from the zero crossings of `samples` at `sample_rate`.
+    fn crossing_freq(samples: &[f32], sample_rate: u32) -> f32 {
+        let crossings = samples
+            .windows(2)
+            .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+            .count();
+        crossings as f32 / 2.0 / (samples.len() as f32 / sample_rate as f32)
+    }
+
+    #[test]
+    fn square_has_only_its_odd_harmonics() {
+        // 10 Hz bins, so every harmonic lands on one
+        let amps = amplitudes(&square(480.0, 1000.0, 48_000, 4800));
+        for (bin, amp) in amps.iter().enumerate() {
+            let (k, odd_harmonic) = (bin / 48, bin % 48 == 0 && (bin / 48) % 2 == 1);
+            let expected = if odd_harmonic {
+                4000.0 / (PI as f32 * k as f32)
+            } else {
+                0.0
+            };
+            assert!(
+                (amp - expected).abs() < 1e-2,
+                "bin {}: {} instead of {}",
+                bin,
+                amp,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sawtooth_has_every_harmonic() {
+        let amps = amplitudes(&sawtooth(480.0, 1000.0, 48_000, 4800));
+        for (freq, amp) in sawtooth_harmonics(480.0, 1000.0, 48_000) {
+            let bin = (freq / 10.0) as usize;
+            assert!(
+                (amps[bin] - amp.abs()).abs() < 1e-2,
+                "{} Hz: {} instead of {}",
+                freq,
+                amps[bin],
+                amp
+            );
+        }
+        assert_eq!(sawtooth_harmonics(480.0, 1000.0, 48_000).len(), 49);
+    }
+
+    #[test]
+    fn white_noise_is_uniform_and_repeatable() {
+        let noise = white_noise(1000.0, 100_000, 7);
+        assert!(noise.iter().all(|s| s.abs() <= 1000.0));
+        let expected = 1000.0 / 3.0_f32.sqrt();
+        assert!(
+            (rms(&noise) / expected - 1.0).abs() < 0.01,
+            "RMS {} instead of {}",
+            rms(&noise),
+            expected
+        );
+        assert_eq!(noise, white_noise(1000.0, 100_000, 7));
+        assert_ne!(noise, white_noise(1000.0, 100_000, 8));
+    }
+
+    #[test]
+    fn pink_noise_falls_3_db_per_octave() {
+        let noise = pink_noise(100.0, 8192, 3);
+        assert!((rms(&noise) - 100.0).abs() < 1e-3, "RMS {}", rms(&noise));
+        let amps = amplitudes(&noise);
+        assert!(amps[0] < 1e-3, "DC {}", amps[0]);
+        // 1/f power is 1/√f amplitude, the same in every bin once that is taken out
+        let flattened: Vec<f32> = (1..4096).map(|k| amps[k] * (k as f32).sqrt()).collect();
+        for level in &flattened {
+            assert!(
+                (level / flattened[0] - 1.0).abs() < 1e-3,
+                "{} vs {}",
+                level,
+                flattened[0]
+            );
+        }
+    }
+
+    #[test]
+    fn chirps_sweep_linearly_or_exponentially() {
+        let tenth = 4800;
+        let linear = chirp(100.0, 6400.0, 1.0, 48_000, 10 * tenth, Sweep::Linear);
+        let exponential = chirp(100.0, 6400.0, 1.0, 48_000, 10 * tenth, Sweep::Exponential);
+        // around the middle: halfway in Hz for the linear sweep, halfway in octaves for the other
+        let middle = 5 * tenth - tenth / 20..5 * tenth + tenth / 20;
+        for (chirp, mid) in [(&linear, 3250.0), (&exponential, 800.0)] {
+            let freq = crossing_freq(&chirp[middle.clone()], 48_000);
+            assert!(
+                (freq / mid - 1.0).abs() < 0.02,
+                "{} Hz instead of {} Hz",
+                freq,
+                mid
+            );
+            assert!(chirp.iter().all(|s| s.abs() <= 1.0));
+        }
+        assert!(crossing_freq(&linear[..tenth / 10], 48_000) < 150.0);
+        assert!(crossing_freq(&linear[linear.len() - tenth / 10..], 48_000) > 6300.0);
+    }
+}
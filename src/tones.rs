@@ -0,0 +1,47 @@
+use std::path::Path;
+
+/// A frequency a recording should contain, e.g. from a datasheet, shown as a reference marker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedTone {
+    pub frequency: f32,
+    /// Empty if the file gives none.
+    pub label: String,
+}
+
+/// Reads `frequency,label` rows, the label being optional. Everything after the first comma is
+/// the label, so it may contain commas; quotes around it are dropped. A header row and lines
+/// starting with `#` are skipped.
+pub fn read_csv(path: &Path) -> Result<Vec<ExpectedTone>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut tones = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (frequency, label) = line.split_once(',').unwrap_or((line, ""));
+        let Ok(frequency) = frequency.trim().parse::<f32>() else {
+            if tones.is_empty() {
+                continue;
+            }
+            return Err(format!(
+                "Line {}: invalid frequency '{}'",
+                i + 1,
+                frequency.trim()
+            ));
+        };
+        let label = label.trim();
+        let label = label
+            .strip_prefix('"')
+            .and_then(|l| l.strip_suffix('"'))
+            .unwrap_or(label);
+        tones.push(ExpectedTone {
+            frequency,
+            label: label.to_string(),
+        });
+    }
+    if tones.is_empty() {
+        return Err("No frequencies".to_string());
+    }
+    Ok(tones)
+}
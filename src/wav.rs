@@ -0,0 +1,681 @@
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+/// How the sample data of a WAV file is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SampleType {
+    /// 8-bit PCM, which WAV stores unsigned with silence at 128.
+    U8,
+    I16,
+    I24,
+    I32,
+    F32,
+}
+
+impl SampleType {
+    pub const ALL: [SampleType; 5] = [
+        SampleType::U8,
+        SampleType::I16,
+        SampleType::I24,
+        SampleType::I32,
+        SampleType::F32,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SampleType::U8 => "u8",
+            SampleType::I16 => "i16",
+            SampleType::I24 => "i24",
+            SampleType::I32 => "i32",
+            SampleType::F32 => "f32",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown sample type '{}', expected one of u8, i16, i24, i32, f32",
+                    name
+                )
+            })
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            SampleType::U8 => 1,
+            SampleType::I16 => 2,
+            SampleType::I24 => 3,
+            SampleType::I32 | SampleType::F32 => 4,
+        }
+    }
+
+    /// Factor bringing a sample into the i16 range. Spectra have always been computed on raw i16
+    /// values, so keeping that scale makes other bit depths comparable with existing `.f` files.
+    fn scale(self) -> f32 {
+        match self {
+            SampleType::U8 => 256.0,
+            SampleType::I16 => 1.0,
+            SampleType::I24 => 1.0 / 256.0,
+            SampleType::I32 => 1.0 / 65_536.0,
+            SampleType::F32 => 32_768.0,
+        }
+    }
+
+    /// Like [`SampleType::decode`] for samples stored most significant byte first.
+    fn decode_big_endian(self, bytes: &[u8]) -> f32 {
+        let mut little_endian = [0; 4];
+        for (to, from) in little_endian.iter_mut().zip(bytes.iter().rev()) {
+            *to = *from;
+        }
+        self.decode(&little_endian[..bytes.len()])
+    }
+
+    fn decode(self, bytes: &[u8]) -> f32 {
+        let raw = match self {
+            SampleType::U8 => bytes[0] as f32 - 128.0,
+            SampleType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            // Shift into the top of an i32 and back to sign-extend the 24-bit value.
+            SampleType::I24 => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32,
+            SampleType::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            SampleType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        };
+        raw * self.scale()
+    }
+}
+
+/// Which part of a multi-channel file is analysed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Channel {
+    /// All channels' samples in file order, as the analysis has always read them.
+    #[default]
+    Interleaved,
+    /// Average of all channels.
+    Mono,
+    Left,
+    Right,
+    /// (L + R) / 2, what stereo channels have in common.
+    Mid,
+    /// (L − R) / 2, what differs between them.
+    Side,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 6] = [
+        Channel::Interleaved,
+        Channel::Mono,
+        Channel::Left,
+        Channel::Right,
+        Channel::Mid,
+        Channel::Side,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Interleaved => "interleaved",
+            Channel::Mono => "mono",
+            Channel::Left => "left",
+            Channel::Right => "right",
+            Channel::Mid => "mid",
+            Channel::Side => "side",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.name() == name)
+            .ok_or_else(|| {
+                format!(
+                "Unknown channel '{}', expected one of interleaved, mono, left, right, mid, side",
+                name
+            )
+            })
+    }
+
+    /// This channel of `samples` interleaved from `channels` channels.
+    pub fn extract(self, samples: &[f32], channels: u16) -> Result<Vec<f32>, String> {
+        let channels = channels.max(1) as usize;
+        let frames = samples.chunks_exact(channels);
+        let stereo = || -> Result<_, String> {
+            if channels < 2 {
+                return Err(format!("The {} channel needs a stereo file", self.name()));
+            }
+            Ok(samples
+                .chunks_exact(channels)
+                .map(|frame| (frame[0], frame[1])))
+        };
+        Ok(match self {
+            Channel::Interleaved => samples.to_vec(),
+            Channel::Mono => frames
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                .collect(),
+            Channel::Left => frames.map(|frame| frame[0]).collect(),
+            Channel::Right => stereo()?.map(|(_, right)| right).collect(),
+            Channel::Mid => stereo()?
+                .map(|(left, right)| (left + right) / 2.0)
+                .collect(),
+            Channel::Side => stereo()?
+                .map(|(left, right)| (left - right) / 2.0)
+                .collect(),
+        })
+    }
+}
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_IEEE_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// Longest `fmt ` chunk read; WAVE_FORMAT_EXTENSIBLE needs 40 bytes. Checked before anything is
+/// allocated, so a corrupt length can't ask for gigabytes.
+const MAX_FMT_LEN: usize = 64;
+
+/// Sample layout declared by the `fmt ` chunk.
+///
+/// hound reports the *valid* bits of WAVE_FORMAT_EXTENSIBLE files (e.g. 24 for 24-in-32 files
+/// written by field recorders) and hides the container size, which is what the data is actually
+/// laid out in, so the chunk is parsed here directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavFormat {
+    /// `FORMAT_PCM` or `FORMAT_IEEE_FLOAT`; for extensible files the sub-format.
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    /// Bits each sample occupies in the data chunk.
+    pub container_bits: u16,
+    /// Bits carrying signal, at most `container_bits`. Samples are left-justified in the container.
+    pub valid_bits: u16,
+    pub extensible: bool,
+}
+
+impl WavFormat {
+    pub fn read(mut reader: impl Read) -> Result<Self, String> {
+        let mut riff = [0; 12];
+        reader.read_exact(&mut riff).map_err(|e| e.to_string())?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err("Not a RIFF/WAVE file".to_string());
+        }
+        loop {
+            let mut header = [0; 8];
+            reader
+                .read_exact(&mut header)
+                .map_err(|_| "No fmt chunk found".to_string())?;
+            let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+            // chunks are padded to an even length
+            let padded = len + len % 2;
+            if &header[0..4] != b"fmt " {
+                let skipped = io::copy(&mut reader.by_ref().take(padded as u64), &mut io::sink());
+                if skipped.map_err(|e| e.to_string())? < padded as u64 {
+                    return Err("No fmt chunk found".to_string());
+                }
+                continue;
+            }
+            if len > MAX_FMT_LEN {
+                return Err(format!("fmt chunk of {} bytes is too long", len));
+            }
+            let mut body = vec![0; padded];
+            reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+            return Self::parse_fmt(&body[..len]);
+        }
+    }
+
+    fn parse_fmt(fmt: &[u8]) -> Result<Self, String> {
+        if fmt.len() < 16 {
+            return Err("fmt chunk too short".to_string());
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+        let mut format = WavFormat {
+            format_tag: u16_at(0),
+            channels: u16_at(2),
+            sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+            container_bits: u16_at(14),
+            valid_bits: u16_at(14),
+            extensible: false,
+        };
+        let block_align = u16_at(12);
+        if format.channels > 0 && block_align > 0 {
+            // more reliable than bits_per_sample, which some writers set to the valid bits
+            format.container_bits = block_align / format.channels * 8;
+        }
+        if format.format_tag == FORMAT_EXTENSIBLE {
+            if fmt.len() < 40 {
+                return Err("WAVE_FORMAT_EXTENSIBLE fmt chunk too short".to_string());
+            }
+            format.extensible = true;
+            if u16_at(18) > 0 {
+                format.valid_bits = u16_at(18);
+            }
+            // the first two bytes of the sub-format GUID are the regular format tag
+            format.format_tag = u16_at(24);
+        }
+        Ok(format)
+    }
+
+    /// How the data should be decoded, `None` for layouts we can't read.
+    pub fn sample_type(&self) -> Option<SampleType> {
+        match (self.format_tag, self.container_bits) {
+            (FORMAT_PCM, 8) => Some(SampleType::U8),
+            (FORMAT_PCM, 16) => Some(SampleType::I16),
+            (FORMAT_PCM, 24) => Some(SampleType::I24),
+            (FORMAT_PCM, 32) => Some(SampleType::I32),
+            (FORMAT_IEEE_FLOAT, 32) => Some(SampleType::F32),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let kind = match self.format_tag {
+            FORMAT_PCM => "PCM",
+            FORMAT_IEEE_FLOAT => "float",
+            _ => "unknown format",
+        };
+        let mut description = if self.valid_bits != self.container_bits {
+            format!(
+                "{}-bit {} in {}-bit containers",
+                self.valid_bits, kind, self.container_bits
+            )
+        } else {
+            format!("{}-bit {}", self.container_bits, kind)
+        };
+        if self.extensible {
+            description.push_str(" (WAVE_FORMAT_EXTENSIBLE)");
+        }
+        description
+    }
+}
+
+/// Reads all samples of a WAV file, scaled to the i16 range.
+///
+/// The sample type is inferred from the header unless `sample_type` forces a different
+/// interpretation of the data, for files whose header lies about the format.
+pub fn read_wav(
+    file_path: &Path,
+    sample_type: Option<SampleType>,
+) -> Result<(Vec<f32>, u32), String> {
+    let (samples, sample_rate, _) = read_wav_channels(file_path, sample_type)?;
+    Ok((samples, sample_rate))
+}
+
+/// Like [`read_wav`], also returning the number of channels the samples are interleaved from.
+pub fn read_wav_channels(
+    file_path: &Path,
+    sample_type: Option<SampleType>,
+) -> Result<(Vec<f32>, u32, u16), String> {
+    let open = || {
+        File::open(file_path)
+            .map(BufReader::new)
+            .map_err(|e| e.to_string())
+    };
+    read_samples(open, &file_path.display().to_string(), sample_type)
+}
+
+/// Like [`read_wav`] with only `channel` of the samples, for a whole WAV file already in memory,
+/// e.g. read from stdin, fetched over the network or embedded with `include_bytes!`. `name` is
+/// only used in warnings.
+pub fn read_wav_bytes(
+    bytes: &[u8],
+    name: &str,
+    channel: Channel,
+    sample_type: Option<SampleType>,
+) -> Result<(Vec<f32>, u32), String> {
+    let (samples, sample_rate, channels) =
+        read_samples(|| Ok(Cursor::new(bytes)), name, sample_type)?;
+    Ok((channel.extract(&samples, channels)?, sample_rate))
+}
+
+/// Decodes the WAV stream returned by `open`, which is called twice: once for the `fmt ` chunk
+/// and once for hound to find the data.
+fn read_samples<R: Read>(
+    open: impl Fn() -> Result<R, String>,
+    name: &str,
+    sample_type: Option<SampleType>,
+) -> Result<(Vec<f32>, u32, u16), String> {
+    let format = WavFormat::read(open()?)?;
+    let header_type = format.sample_type();
+    let sample_type = match sample_type {
+        Some(forced) => {
+            if header_type != Some(forced) {
+                eprintln!(
+                    "Warning: {} declares {} samples, reading as {} instead",
+                    name,
+                    format.describe(),
+                    forced.name()
+                );
+            }
+            forced
+        }
+        None => header_type
+            .ok_or_else(|| format!("Unsupported sample format: {}", format.describe()))?,
+    };
+
+    // hound locates the data chunk and leaves the underlying reader at its start.
+    let reader = hound::WavReader::new(open()?).map_err(|e| e.to_string())?;
+    let data_len = reader.len() as usize * (format.container_bits / 8) as usize;
+    let mut data = Vec::with_capacity(data_len);
+    reader
+        .into_inner()
+        .take(data_len as u64)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    let samples = data
+        .chunks_exact(sample_type.bytes())
+        .map(|b| sample_type.decode(b))
+        .collect();
+    Ok((samples, format.sample_rate, format.channels))
+}
+
+/// Whether `path` ends in `.extension`, in any case so e.g. `TAKE1.WAV` counts too. Compares the
+/// extension itself rather than the display string, which mangles paths that aren't UTF-8.
+pub fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+pub fn is_wav(path: &Path) -> bool {
+    has_extension(path, "wav")
+}
+
+/// Whether `path` is headerless PCM, which needs a [`RawFormat`] to be read.
+pub fn is_raw(path: &Path) -> bool {
+    has_extension(path, "raw") || has_extension(path, "pcm")
+}
+
+/// Layout of a headerless PCM file, e.g. from an embedded capture tool, which has to be given
+/// since nothing in the file describes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RawFormat {
+    pub sample_rate: u32,
+    /// [`SampleType::U8`] is taken to be unsigned with silence at 128, as in WAV files.
+    pub sample_type: SampleType,
+    pub channels: u16,
+    pub big_endian: bool,
+}
+
+impl RawFormat {
+    /// Parses `rate,type,channels` with an optional `,le` or `,be` for the byte order, little
+    /// endian by default, e.g. `48000,i16,2` or `16000,i24,1,be`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = value.split(',').collect();
+        let (rate, sample_type, channels, endianness) = match parts[..] {
+            [rate, sample_type, channels] => (rate, sample_type, channels, "le"),
+            [rate, sample_type, channels, endianness] => (rate, sample_type, channels, endianness),
+            _ => {
+                return Err(format!(
+                    "Invalid raw format '{}', expected rate,type,channels[,le|be]",
+                    value
+                ))
+            }
+        };
+        let sample_rate = match rate.parse() {
+            Ok(rate) if rate > 0 => rate,
+            _ => return Err(format!("Invalid sample rate '{}'", rate)),
+        };
+        let channels = match channels.parse() {
+            Ok(channels) if channels > 0 => channels,
+            _ => return Err(format!("Invalid channel count '{}'", channels)),
+        };
+        let big_endian = match endianness {
+            "le" => false,
+            "be" => true,
+            _ => {
+                return Err(format!(
+                    "Unknown byte order '{}', expected le or be",
+                    endianness
+                ))
+            }
+        };
+        Ok(Self {
+            sample_rate,
+            sample_type: SampleType::parse(sample_type)?,
+            channels,
+            big_endian,
+        })
+    }
+
+    pub fn frame_bytes(self) -> usize {
+        self.sample_type.bytes() * self.channels.max(1) as usize
+    }
+
+    /// Samples of the whole frames in `data`, e.g. a block read from a stream.
+    pub fn decode(self, data: &[u8]) -> Vec<f32> {
+        let whole_frames = data.len() / self.frame_bytes() * self.frame_bytes();
+        let sample_type = self.sample_type;
+        data[..whole_frames]
+            .chunks_exact(sample_type.bytes())
+            .map(|b| {
+                if self.big_endian {
+                    sample_type.decode_big_endian(b)
+                } else {
+                    sample_type.decode(b)
+                }
+            })
+            .collect()
+    }
+
+    /// Length of the file in seconds, from its size.
+    pub fn duration(self, file_path: &Path) -> Result<f32, String> {
+        let len = std::fs::metadata(file_path)
+            .map_err(|e| e.to_string())?
+            .len();
+        Ok((len / self.frame_bytes() as u64) as f32 / self.sample_rate as f32)
+    }
+}
+
+/// Reads a headerless PCM file laid out as `format`, like [`read_wav_channels`]. A trailing
+/// partial frame is ignored.
+pub fn read_raw(file_path: &Path, format: RawFormat) -> Result<(Vec<f32>, u32, u16), String> {
+    let data = std::fs::read(file_path).map_err(|e| e.to_string())?;
+    let whole_frames = data.len() / format.frame_bytes() * format.frame_bytes();
+    if whole_frames < data.len() {
+        eprintln!(
+            "Warning: {} ends with a partial frame of {} bytes, ignoring it",
+            file_path.display(),
+            data.len() - whole_frames
+        );
+    }
+    Ok((format.decode(&data), format.sample_rate, format.channels))
+}
+
+/// Samples of all `file_paths` joined in the given order, for recordings split into chunks.
+///
+/// All files must share the sample rate, channel count and sample layout, since joining them
+/// otherwise would silently produce garbage.
+pub fn read_concatenated(
+    file_paths: &[PathBuf],
+    sample_type: Option<SampleType>,
+) -> Result<(Vec<f32>, u32), String> {
+    let (first, rest) = file_paths
+        .split_first()
+        .ok_or("No WAV files to concatenate")?;
+    let read_format = |file_path: &Path| -> Result<WavFormat, String> {
+        WavFormat::read(BufReader::new(
+            File::open(file_path).map_err(|e| e.to_string())?,
+        ))
+        .map_err(|e| format!("{}: {}", file_path.display(), e))
+    };
+    let expected = read_format(first)?;
+    for file_path in rest {
+        let format = read_format(file_path)?;
+        if (format.sample_rate, format.channels) != (expected.sample_rate, expected.channels)
+            || format.sample_type() != expected.sample_type()
+        {
+            return Err(format!(
+                "Can't concatenate {} ({} Hz, {} channels, {}) with {} ({} Hz, {} channels, {})",
+                file_path.display(),
+                format.sample_rate,
+                format.channels,
+                format.describe(),
+                first.display(),
+                expected.sample_rate,
+                expected.channels,
+                expected.describe()
+            ));
+        }
+    }
+    let mut samples = Vec::new();
+    for file_path in file_paths {
+        samples.extend(read_wav(file_path, sample_type)?.0);
+    }
+    Ok((samples, expected.sample_rate))
+}
+
+/// Writes mono samples in the i16 range as a 32-bit float WAV file, which [`read_wav`] reads
+/// back to the same values without requantizing.
+pub fn write_float_wav(file_path: &Path, samples: &[f32], sample_rate: u32) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(file_path, spec).map_err(|e| e.to_string())?;
+    for &sample in samples {
+        writer
+            .write_sample(sample / SampleType::F32.scale())
+            .map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+/// Length of the file in seconds, read from the header only.
+pub fn duration(file_path: &Path) -> Result<f32, String> {
+    let reader = hound::WavReader::open(file_path).map_err(|e| e.to_string())?;
+    Ok(reader.duration() as f32 / reader.spec().sample_rate as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A WAV file of `data` in memory, with a WAVE_FORMAT_EXTENSIBLE `fmt ` chunk if
+    /// `valid_bits` is given.
+    fn wav_bytes(
+        format_tag: u16,
+        channels: u16,
+        container_bits: u16,
+        valid_bits: Option<u16>,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let block_align = channels * container_bits / 8;
+        let mut fmt = Vec::new();
+        let declared_tag = if valid_bits.is_some() {
+            FORMAT_EXTENSIBLE
+        } else {
+            format_tag
+        };
+        fmt.extend(declared_tag.to_le_bytes());
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(48_000_u32.to_le_bytes());
+        fmt.extend((48_000 * block_align as u32).to_le_bytes());
+        fmt.extend(block_align.to_le_bytes());
+        fmt.extend(container_bits.to_le_bytes());
+        if let Some(valid_bits) = valid_bits {
+            fmt.extend(22_u16.to_le_bytes());
+            fmt.extend(valid_bits.to_le_bytes());
+            // front left and right
+            fmt.extend(3_u32.to_le_bytes());
+            fmt.extend(format_tag.to_le_bytes());
+            fmt.extend([
+                0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+            ]);
+        }
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((4 + 8 + fmt.len() as u32 + 8 + data.len() as u32).to_le_bytes());
+        bytes.extend(b"WAVEfmt ");
+        bytes.extend((fmt.len() as u32).to_le_bytes());
+        bytes.extend(fmt);
+        bytes.extend(b"data");
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn extensible_24_in_32_bit_pcm() {
+        // left-justified 24-bit samples: half scale, minus half scale, a quarter, silence
+        let data: Vec<u8> = [0x40_0000_i32, -0x40_0000, 0x20_0000, 0]
+            .into_iter()
+            .flat_map(|sample| (sample << 8).to_le_bytes())
+            .collect();
+        let bytes = wav_bytes(FORMAT_PCM, 2, 32, Some(24), &data);
+        let format = WavFormat::read(bytes.as_slice()).unwrap();
+        assert!(format.extensible);
+        assert_eq!(
+            (format.format_tag, format.container_bits, format.valid_bits),
+            (FORMAT_PCM, 32, 24)
+        );
+        assert_eq!(format.sample_type(), Some(SampleType::I32));
+        let (samples, sample_rate) =
+            read_wav_bytes(&bytes, "test", Channel::Interleaved, None).unwrap();
+        assert_eq!(sample_rate, 48_000);
+        assert_eq!(samples, [16_384.0, -16_384.0, 8192.0, 0.0]);
+    }
+
+    #[test]
+    fn unsigned_8_bit_is_centred_at_128_and_scaled_to_i16() {
+        let bytes = wav_bytes(FORMAT_PCM, 1, 8, None, &[128, 255, 0, 192, 64]);
+        assert_eq!(
+            WavFormat::read(bytes.as_slice()).unwrap().sample_type(),
+            Some(SampleType::U8)
+        );
+        let (samples, _) = read_wav_bytes(&bytes, "test", Channel::Interleaved, None).unwrap();
+        assert_eq!(samples, [0.0, 32_512.0, -32_768.0, 16_384.0, -16_384.0]);
+    }
+
+    /// 16-bit stereo at 44.1 kHz: two frames of (1000, -1000) and (-32768, 32767).
+    const STEREO_WAV: [u8; 52] = [
+        b'R', b'I', b'F', b'F', 44, 0, 0, 0, b'W', b'A', b'V', b'E', // RIFF header
+        b'f', b'm', b't', b' ', 16, 0, 0, 0, // fmt chunk
+        1, 0, 2, 0, 0x44, 0xAC, 0, 0, 0x10, 0xB1, 2, 0, 4, 0, 16,
+        0, // PCM, 2 channels, 44100 Hz
+        b'd', b'a', b't', b'a', 8, 0, 0, 0, // data chunk
+        0xE8, 0x03, 0x18, 0xFC, 0x00, 0x80, 0xFF, 0x7F,
+    ];
+
+    #[test]
+    fn read_wav_bytes_of_an_embedded_file() {
+        let read = |channel| read_wav_bytes(&STEREO_WAV, "embedded", channel, None).unwrap();
+        assert_eq!(
+            read(Channel::Interleaved),
+            (vec![1000.0, -1000.0, -32_768.0, 32_767.0], 44_100)
+        );
+        assert_eq!(read(Channel::Left).0, [1000.0, -32_768.0]);
+        assert_eq!(read(Channel::Right).0, [-1000.0, 32_767.0]);
+        assert!(read_wav_bytes(&STEREO_WAV[..20], "truncated", Channel::Left, None).is_err());
+    }
+
+    #[test]
+    fn chunks_before_fmt_are_skipped_without_reading_them_whole() {
+        let wav = wav_bytes(FORMAT_PCM, 1, 16, None, &[0, 0]);
+        // a LIST chunk claiming 4 GB, of which only a few bytes follow
+        let mut bytes = wav[..12].to_vec();
+        bytes.extend(b"LIST");
+        bytes.extend(u32::MAX.to_le_bytes());
+        bytes.extend(b"INFO");
+        assert_eq!(
+            WavFormat::read(bytes.as_slice()).unwrap_err(),
+            "No fmt chunk found"
+        );
+        // and a short one, padded to an even length, before a regular fmt chunk
+        let mut bytes = wav[..12].to_vec();
+        bytes.extend(b"junk");
+        bytes.extend(3_u32.to_le_bytes());
+        bytes.extend([1, 2, 3, 0]);
+        bytes.extend(&wav[12..]);
+        assert_eq!(
+            WavFormat::read(bytes.as_slice()).unwrap().sample_type(),
+            Some(SampleType::I16)
+        );
+    }
+
+    #[test]
+    fn oversized_fmt_chunks_are_rejected() {
+        let mut bytes = wav_bytes(FORMAT_PCM, 1, 16, None, &[0, 0]);
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(WavFormat::read(bytes.as_slice())
+            .unwrap_err()
+            .contains("too long"));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::fft::{self, Fft, Precision};
+use crate::window::Window;
+use std::ops::Range;
+
+/// Segmenting for Welch's method: the spectrum is the mean over overlapping windowed segments,
+/// trading frequency resolution for a much less noisy estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Welch {
+    /// Samples per segment, i.e. the FFT size.
+    pub segment_len: usize,
+    /// Distance between the starts of consecutive segments.
+    pub hop: usize,
+}
+
+impl Default for Welch {
+    fn default() -> Self {
+        Self {
+            segment_len: 4096,
+            hop: 2048,
+        }
+    }
+}
+
+impl Welch {
+    /// Checks the parameters against a signal of `len` samples.
+    pub fn validate(self, len: usize) -> Result<(), String> {
+        if self.segment_len == 0 {
+            return Err("Segment size must be greater than zero".to_string());
+        }
+        if self.hop == 0 {
+            return Err("Hop size must be greater than zero".to_string());
+        }
+        if self.hop > self.segment_len {
+            return Err(format!(
+                "Hop size ({}) is larger than the segment size ({}), samples in between would be skipped",
+                self.hop, self.segment_len
+            ));
+        }
+        if self.segment_len > len {
+            return Err(format!(
+                "Segment size ({}) is larger than the signal ({} samples)",
+                self.segment_len, len
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sample ranges of all complete segments of a signal of `len` samples.
+    pub fn segments(self, len: usize) -> Result<Vec<Range<usize>>, String> {
+        self.validate(len)?;
+        Ok((0..=len - self.segment_len)
+            .step_by(self.hop)
+            .map(|start| start..start + self.segment_len)
+            .collect())
+    }
+}
+
+/// Averaged amplitude spectrum over the segments described by `welch`, see
+/// [`crate::fourier_analysis`] for `two_sided`.
+pub fn welch_analysis(
+    samples: &[f32],
+    sample_rate: u32,
+    window: Window,
+    welch: Welch,
+    precision: Precision,
+    two_sided: bool,
+) -> Result<(Vec<f32>, Vec<f32>), String> {
+    let segments = welch.segments(samples.len())?;
+    let n = welch.segment_len;
+    let fft = Fft::new(n, precision);
+    let freqs = fft::bin_freqs(n, sample_rate, two_sided);
+    let mut amplitudes = vec![0.0; freqs.len()];
+    for segment in &segments {
+        let windowed = window.apply(&samples[segment.clone()]);
+        let magnitudes = if two_sided {
+            fft.two_sided_magnitudes(&windowed)
+        } else {
+            fft.magnitudes(&windowed)
+        };
+        for (amp, magnitude) in amplitudes.iter_mut().zip(magnitudes) {
+            *amp += magnitude;
+        }
+    }
+    amplitudes
+        .iter_mut()
+        .for_each(|amp| *amp /= segments.len() as f32);
+    Ok((freqs, amplitudes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_invalid_segments_and_hops() {
+        let welch = |segment_len, hop| Welch { segment_len, hop };
+        assert!(welch(1024, 512).validate(4096).is_ok());
+        assert!(welch(1024, 1024).validate(1024).is_ok());
+        assert!(welch(1024, 0).validate(4096).is_err(), "zero hop");
+        assert!(
+            welch(1024, 1025).validate(4096).is_err(),
+            "hop larger than the segment"
+        );
+        assert!(
+            welch(1024, 512).validate(1023).is_err(),
+            "segment larger than the signal"
+        );
+        assert!(welch(0, 0).validate(4096).is_err(), "zero segment");
+    }
+}